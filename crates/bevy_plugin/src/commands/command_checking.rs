@@ -0,0 +1,57 @@
+use crate::events::CommandDiagnosticsEvent;
+use crate::prelude::*;
+use crate::project::check_commands;
+use bevy::prelude::*;
+use yarnspinner::compiler::Diagnostic;
+use yarnspinner::core::CommandLibrary;
+
+pub(crate) fn command_checking_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        check_commands_of_dialogue_runners
+            .run_if(resource_exists::<YarnProject>)
+            .in_set(YarnSpinnerSystemSet),
+    );
+}
+
+/// Checks the command statements of the project against the [`YarnCommands`] of every new [`DialogueRunner`],
+/// and of all [`DialogueRunner`]s whenever the project was recompiled, so that typos and wrong parameters are found
+/// without having to play through the dialogue. Problems are sent as [`CommandDiagnosticsEvent`]s.
+///
+/// The project is only compiled once for each distinct set of commands, since most games register the same commands on all of their dialogue runners.
+fn check_commands_of_dialogue_runners(
+    dialogue_runners: Query<(Entity, Ref<DialogueRunner>)>,
+    project: Res<YarnProject>,
+    yarn_files: Res<Assets<YarnFile>>,
+    mut checked_command_libraries: Local<Vec<(CommandLibrary, Vec<Diagnostic>)>>,
+    mut events: EventWriter<CommandDiagnosticsEvent>,
+) {
+    if project.is_changed() {
+        checked_command_libraries.clear();
+    }
+    for (source, dialogue_runner) in dialogue_runners.iter() {
+        if !(dialogue_runner.is_added() || project.is_changed()) {
+            continue;
+        }
+        let command_library = dialogue_runner.commands().command_library();
+        let diagnostics = if let Some((_, diagnostics)) = checked_command_libraries
+            .iter()
+            .find(|(checked_library, _)| checked_library == &command_library)
+        {
+            diagnostics.clone()
+        } else {
+            let diagnostics = check_commands(&project, &yarn_files, command_library.clone());
+            for diagnostic in &diagnostics {
+                error!("{diagnostic}");
+            }
+            checked_command_libraries.push((command_library, diagnostics.clone()));
+            diagnostics
+        };
+        if !diagnostics.is_empty() {
+            events.send(CommandDiagnosticsEvent {
+                diagnostics,
+                source,
+            });
+        }
+    }
+}
@@ -5,6 +5,7 @@ use bevy::prelude::*;
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::time::Duration;
+use yarnspinner::core::{CommandDeclaration, CommandLibrary};
 
 pub(crate) mod wait;
 
@@ -105,6 +106,16 @@ impl YarnCommands {
         self.0.is_empty()
     }
 
+    /// Declares all registered commands for the compiler, which can then report calls to unknown commands
+    /// or calls with the wrong parameters via [`Compiler::extend_command_library`](yarnspinner::compiler::Compiler::extend_command_library).
+    pub fn command_library(&self) -> CommandLibrary {
+        self.iter()
+            .map(|(name, command)| {
+                CommandDeclaration::new(name).with_parameters(command.parameters())
+            })
+            .collect()
+    }
+
     /// Constructs an instance of [`YarnCommands`] with the builtin commands `wait` and `stop`.
     /// - `stop`: Stops the execution of the dialogue.
     /// - `wait`: Waits for the given amount of seconds before continuing the dialogue. Note that this does not block and that Bevy will continue updating as normal in the meantime.
@@ -196,6 +207,24 @@ mod tests {
         method.call(to_method_params([1.0]), app.world_mut());
     }
 
+    #[test]
+    fn declares_registered_commands() {
+        let mut methods = YarnCommands::default();
+        methods
+            .add_command("no_params", |_: In<()>| {})
+            .add_command("params", |_: In<(String, Option<f32>)>| {});
+        let library = methods.command_library();
+
+        assert_eq!(
+            "<<no_params>>",
+            library.get("no_params").unwrap().to_string()
+        );
+        assert_eq!(
+            "<<params String [Number]>>",
+            library.get("params").unwrap().to_string()
+        );
+    }
+
     #[test]
     fn can_add_multiple_fns() {
         let mut methods = YarnCommands::default();
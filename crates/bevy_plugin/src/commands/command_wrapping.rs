@@ -7,7 +7,7 @@ use std::fmt::{Debug, Display, Formatter};
 use std::marker::PhantomData;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
-use yarnspinner::core::{YarnFnParam, YarnFnParamItem, YarnValueWrapper};
+use yarnspinner::core::{CommandParameter, YarnFnParam, YarnFnParamItem, YarnValueWrapper};
 
 pub(crate) fn command_wrapping_plugin(_app: &mut App) {}
 
@@ -161,6 +161,8 @@ pub trait UntypedYarnCommand: Debug + Send + Sync + 'static {
     fn call(&mut self, input: Vec<YarnValue>, world: &mut World) -> Box<dyn TaskFinishedIndicator>;
     #[doc(hidden)]
     fn clone_box(&self) -> Box<dyn UntypedYarnCommand>;
+    /// The parameters this command accepts from Yarn, as derived from its [`YarnCommand::In`] type.
+    fn parameters(&self) -> Vec<CommandParameter>;
 }
 
 impl Clone for Box<dyn UntypedYarnCommand> {
@@ -192,6 +194,10 @@ where
     fn clone_box(&self) -> Box<dyn UntypedYarnCommand> {
         Box::new(self.clone())
     }
+
+    fn parameters(&self) -> Vec<CommandParameter> {
+        T::In::command_parameters()
+    }
 }
 
 pub(crate) struct YarnCommandWrapper<Marker, F>
@@ -1,6 +1,6 @@
 pub use self::events::{
-    DialogueCompleteEvent, DialogueStartEvent, ExecuteCommandEvent, LineHintsEvent,
-    NodeCompleteEvent, NodeStartEvent, PresentLineEvent, PresentOptionsEvent,
+    CommandDiagnosticsEvent, DialogueCompleteEvent, DialogueStartEvent, ExecuteCommandEvent,
+    LineHintsEvent, NodeCompleteEvent, NodeStartEvent, PresentLineEvent, PresentOptionsEvent,
 };
 pub use self::{
     builder::DialogueRunnerBuilder,
@@ -18,6 +18,8 @@ pub struct DialogueRunnerBuilder {
     asset_providers: HashMap<TypeId, Box<dyn AssetProvider>>,
     library: YarnLibrary,
     commands: YarnCommands,
+    marker_processors: HashMap<String, Box<dyn AttributeMarkerProcessor>>,
+    saliency_strategy: Option<Box<dyn SaliencyStrategy>>,
     compilation: Compilation,
     localizations: Option<Localizations>,
     asset_server: SkipDebug<AssetServer>,
@@ -34,6 +36,8 @@ impl DialogueRunnerBuilder {
             asset_providers: HashMap::new(),
             library: create_extended_standard_library(),
             commands: YarnCommands::builtin_commands(),
+            marker_processors: HashMap::new(),
+            saliency_strategy: None,
             compilation: yarn_project.compilation().clone(),
             localizations: yarn_project.localizations().cloned(),
             asset_server: yarn_project.asset_server.clone(),
@@ -62,6 +66,27 @@ impl DialogueRunnerBuilder {
         self
     }
 
+    /// Registers an [`AttributeMarkerProcessor`] that produces replacement text for markers with the name `attribute_name`,
+    /// e.g. `playername` for `[playername/]`. See [`Dialogue::add_marker_processor`] for details.
+    #[must_use]
+    pub fn add_marker_processor(
+        mut self,
+        attribute_name: impl Into<String>,
+        processor: Box<dyn AttributeMarkerProcessor>,
+    ) -> Self {
+        self.marker_processors
+            .insert(attribute_name.into(), processor);
+        self
+    }
+
+    /// Replaces the [`SaliencyStrategy`] that picks the content of line groups and node groups.
+    /// By default, this is a [`BestLeastRecentlySeenSaliencyStrategy`](yarnspinner::runtime::BestLeastRecentlySeenSaliencyStrategy).
+    #[must_use]
+    pub fn with_saliency_strategy(mut self, strategy: impl SaliencyStrategy + 'static) -> Self {
+        self.saliency_strategy = Some(Box::new(strategy));
+        self
+    }
+
     /// Builds the [`DialogueRunner`]. See [`DialogueRunnerBuilder::try_build`] for the fallible version.
     pub fn build(self) -> DialogueRunner {
         self.try_build().unwrap_or_else(|error| {
@@ -79,6 +104,12 @@ impl DialogueRunnerBuilder {
             .library_mut()
             .extend(self.library);
         dialogue.add_program(self.compilation.program.unwrap());
+        for (attribute_name, processor) in self.marker_processors {
+            dialogue.add_marker_processor(attribute_name, processor);
+        }
+        if let Some(saliency_strategy) = self.saliency_strategy {
+            dialogue.set_saliency_strategy(saliency_strategy);
+        }
 
         for asset_provider in self.asset_providers.values_mut() {
             if let Some(ref localizations) = self.localizations {
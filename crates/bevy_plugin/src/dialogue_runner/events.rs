@@ -1,6 +1,7 @@
 use crate::prelude::*;
 use crate::UnderlyingYarnCommand;
 use bevy::prelude::*;
+use yarnspinner::compiler::Diagnostic;
 
 pub(crate) fn dialogue_runner_events_plugin(app: &mut App) {
     app.add_event::<PresentLineEvent>()
@@ -10,7 +11,8 @@ pub(crate) fn dialogue_runner_events_plugin(app: &mut App) {
         .add_event::<NodeStartEvent>()
         .add_event::<LineHintsEvent>()
         .add_event::<DialogueCompleteEvent>()
-        .add_event::<DialogueStartEvent>();
+        .add_event::<DialogueStartEvent>()
+        .add_event::<CommandDiagnosticsEvent>();
 }
 
 /// An event that is fired after a dialogue advances and wishes to present a line to the user.
@@ -95,3 +97,16 @@ pub struct DialogueCompleteEvent {
     /// The [`DialogueRunner`] that has completed this dialogue.
     pub source: Entity,
 }
+
+/// An event that is fired when the command statements of the [`YarnProject`] do not match the [`YarnCommands`] of a [`DialogueRunner`],
+/// e.g. because a command is misspelled or called with the wrong parameters.
+/// The project is checked for every new [`DialogueRunner`] and again after it is recompiled because of changes in Yarn files.
+/// The diagnostics are also logged as errors.
+/// Handling this event is **optional**. Commands that are handled via [`ExecuteCommandEvent`] instead of [`YarnCommands`] are reported as well.
+#[derive(Debug, Clone, PartialEq, Event)]
+pub struct CommandDiagnosticsEvent {
+    /// The errors the compiler found in command statements.
+    pub diagnostics: Vec<Diagnostic>,
+    /// The [`DialogueRunner`] whose [`YarnCommands`] the project was checked against.
+    pub source: Entity,
+}
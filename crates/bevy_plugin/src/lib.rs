@@ -122,8 +122,8 @@ pub mod default_impl {
 pub mod events {
     //! Events that are sent by the [`DialogueRunner`](crate::prelude::DialogueRunner). A dialogue view is expected to at least handle [`PresentLineEvent`] and [`PresentOptionsEvent`].
     pub use crate::dialogue_runner::{
-        DialogueCompleteEvent, DialogueStartEvent, ExecuteCommandEvent, LineHintsEvent,
-        NodeCompleteEvent, NodeStartEvent, PresentLineEvent, PresentOptionsEvent,
+        CommandDiagnosticsEvent, DialogueCompleteEvent, DialogueStartEvent, ExecuteCommandEvent,
+        LineHintsEvent, NodeCompleteEvent, NodeStartEvent, PresentLineEvent, PresentOptionsEvent,
     };
 }
 
@@ -148,8 +148,9 @@ pub mod prelude {
     pub(crate) use serde::{Deserialize, Serialize};
     pub(crate) use yarnspinner::prelude::*;
     pub use yarnspinner::prelude::{
-        IntoYarnValueFromNonYarnValue, Language, LineId, MarkupAttribute, MarkupValue, OptionId,
-        VariableStorage, YarnFn, YarnLibrary, YarnValue,
+        AttributeMarkerProcessor, IntoYarnValueFromNonYarnValue, Language, LineId, MarkupAttribute,
+        MarkupAttributeMarker, MarkupValue, OptionId, SaliencyStrategy, VariableStorage, YarnFn,
+        YarnLibrary, YarnValue,
     };
     pub(crate) type SystemResult = Result<()>;
 }
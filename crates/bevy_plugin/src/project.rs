@@ -3,7 +3,7 @@ use crate::prelude::*;
 use bevy::prelude::*;
 use bevy::utils::{HashMap, HashSet};
 pub(crate) use compilation::{
-    RecompileLoadedYarnFilesEvent, YarnFilesBeingLoaded, YarnProjectConfigToLoad,
+    check_commands, RecompileLoadedYarnFilesEvent, YarnFilesBeingLoaded, YarnProjectConfigToLoad,
 };
 use std::fmt::Debug;
 use std::iter;
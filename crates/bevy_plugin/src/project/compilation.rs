@@ -7,6 +7,8 @@ use anyhow::bail;
 use bevy::prelude::*;
 use bevy::utils::{error, HashSet};
 use std::fmt::Debug;
+use yarnspinner::compiler::{Diagnostic, DiagnosticSeverity};
+use yarnspinner::core::CommandLibrary;
 
 pub(crate) fn project_compilation_plugin(app: &mut App) {
     app.register_type::<YarnFilesToLoad>()
@@ -287,3 +289,31 @@ fn compile_yarn_files(
     let compilation = YarnCompiler::new().add_files(inner_yarn_files).compile()?;
     Ok(Some(compilation))
 }
+
+/// Compiles the declarations of the Yarn files of the project again, this time checking the command statements against the given [`CommandLibrary`].
+/// Returns the errors found in command statements.
+pub(crate) fn check_commands(
+    yarn_project: &YarnProject,
+    yarn_files: &Assets<YarnFile>,
+    command_library: CommandLibrary,
+) -> Vec<Diagnostic> {
+    let inner_yarn_files = yarn_project
+        .yarn_files()
+        .filter_map(|handle| yarn_files.get(handle))
+        .map(|yarn_file| yarn_file.file.clone());
+    let result = YarnCompiler::new()
+        .add_files(inner_yarn_files)
+        .extend_command_library(command_library)
+        .with_compilation_type(CompilationType::DeclarationsOnly)
+        .compile();
+    match result {
+        Ok(_) => Vec::new(),
+        // The project itself already compiled successfully, so all errors must be about commands.
+        // Warnings were already reported when compiling the project.
+        Err(error) => error
+            .0
+            .into_iter()
+            .filter(|diagnostic| diagnostic.severity == DiagnosticSeverity::Error)
+            .collect(),
+    }
+}
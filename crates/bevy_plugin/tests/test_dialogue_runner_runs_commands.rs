@@ -110,12 +110,47 @@ fn executes_commands_and_fns() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn reports_misspelled_commands() -> Result<()> {
+    let mut app = App::new();
+    let mut asserter = EventAsserter::new();
+    app.setup_dialogue_runner_for_source("title: Start\n---\n<<set_dat \"foo\">>\n===\n");
+    app.update();
+    let source = app.dialogue_runner_entity();
+    assert_events!(asserter, app contains [
+        CommandDiagnosticsEvent with |event|
+            event.diagnostics.len() == 1 &&
+            event.diagnostics[0].message == "Unknown command \"set_dat\"",
+    ]);
+    let events = app.world().resource::<Events<CommandDiagnosticsEvent>>();
+    assert!(events
+        .iter_current_update_events()
+        .all(|event| event.source == source));
+
+    app.update();
+    assert_events!(asserter, app contains CommandDiagnosticsEvent (n = 0));
+    Ok(())
+}
+
+#[test]
+fn does_not_report_registered_commands() -> Result<()> {
+    let mut app = App::new();
+    let mut asserter = EventAsserter::new();
+    app.setup_dialogue_runner_for_source(
+        "title: Start\n---\n<<set_data \"foo\">>\n<<wait 1>>\n===\n",
+    );
+    app.update();
+    assert_events!(asserter, app contains CommandDiagnosticsEvent (n = 0));
+    Ok(())
+}
+
 #[derive(Debug, Resource)]
 struct Data(String);
 
 trait CommandAppExt {
     fn setup_dialogue_runner(&mut self) -> Mut<DialogueRunner>;
     fn setup_dialogue_runner_for_wait(&mut self) -> Mut<DialogueRunner>;
+    fn setup_dialogue_runner_for_source(&mut self, source: &str) -> Mut<DialogueRunner>;
 }
 
 impl CommandAppExt for App {
@@ -147,4 +182,18 @@ impl CommandAppExt for App {
             )))
             .dialogue_runner_mut()
     }
+
+    fn setup_dialogue_runner_for_source(&mut self, source: &str) -> Mut<DialogueRunner> {
+        let yarn_file = YarnFile::new("commands.yarn", source);
+        let mut dialogue_runner = self
+            .setup_default_plugins()
+            .add_plugins(YarnSpinnerPlugin::with_yarn_source(
+                YarnFileSource::InMemory(yarn_file),
+            ))
+            .dialogue_runner_mut();
+        dialogue_runner
+            .commands_mut()
+            .add_command("set_data", |_: In<String>| {});
+        dialogue_runner
+    }
 }
@@ -12,6 +12,7 @@ pub struct EventAsserter {
     pub node_complete_reader: ManualEventReader<NodeCompleteEvent>,
     pub line_hints_reader: ManualEventReader<LineHintsEvent>,
     pub execute_command_reader: ManualEventReader<ExecuteCommandEvent>,
+    pub command_diagnostics_reader: ManualEventReader<CommandDiagnosticsEvent>,
 }
 
 impl EventAsserter {
@@ -36,6 +37,8 @@ impl EventAsserter {
             .clear(app.world().resource::<Events<LineHintsEvent>>());
         self.execute_command_reader
             .clear(app.world().resource::<Events<ExecuteCommandEvent>>());
+        self.command_diagnostics_reader
+            .clear(app.world().resource::<Events<CommandDiagnosticsEvent>>());
     }
 }
 
@@ -65,6 +68,9 @@ macro_rules! get_reader {
     ($asserter:ident, ExecuteCommandEvent) => {
         &mut $asserter.execute_command_reader
     };
+    ($asserter:ident, CommandDiagnosticsEvent) => {
+        &mut $asserter.command_diagnostics_reader
+    };
 }
 
 #[macro_export]
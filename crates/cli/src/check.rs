@@ -0,0 +1,54 @@
+use crate::input::Inputs;
+use crate::CONTENT_ERROR;
+use clap::Args;
+use std::process::ExitCode;
+use yarnspinner::compiler::*;
+
+#[derive(Debug, Args)]
+pub(crate) struct CheckArgs {
+    #[command(flatten)]
+    inputs: Inputs,
+
+    /// Fails if there are any warnings, not only if there are errors.
+    #[arg(long)]
+    deny_warnings: bool,
+}
+
+pub(crate) fn check(args: CheckArgs) -> anyhow::Result<ExitCode> {
+    let diagnostics = match args.inputs.compiler()?.compile() {
+        Ok(compilation) => compilation.warnings,
+        Err(CompilerError(diagnostics)) => diagnostics,
+    };
+    for diagnostic in &diagnostics {
+        eprintln!("{diagnostic}");
+    }
+
+    let errors = count(&diagnostics, DiagnosticSeverity::Error);
+    let warnings = count(&diagnostics, DiagnosticSeverity::Warning);
+    eprintln!(
+        "{errors} {}, {warnings} {}",
+        plural(errors, "error"),
+        plural(warnings, "warning")
+    );
+    let failed = errors > 0 || (args.deny_warnings && warnings > 0);
+    Ok(if failed {
+        ExitCode::from(CONTENT_ERROR)
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+fn count(diagnostics: &[Diagnostic], severity: DiagnosticSeverity) -> usize {
+    diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == severity)
+        .count()
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        noun.to_owned()
+    } else {
+        format!("{noun}s")
+    }
+}
@@ -0,0 +1,57 @@
+use crate::input::{self, Inputs};
+use crate::strings::{write_lines, write_metadata};
+use crate::CONTENT_ERROR;
+use anyhow::Context;
+use clap::Args;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Debug, Args)]
+pub(crate) struct CompileArgs {
+    #[command(flatten)]
+    inputs: Inputs,
+
+    /// The directory to write the compiled files to.
+    #[arg(short = 'o', long, default_value = ".")]
+    output_directory: PathBuf,
+
+    /// The name of the compiled files, i.e. `<NAME>.yarnc`, `<NAME>-Lines.csv` and `<NAME>-Metadata.csv`.
+    #[arg(short = 'n', long, default_value = "Output", value_name = "NAME")]
+    output_name: String,
+
+    /// Optimizes the compiled program. See `Compiler::with_optimization`.
+    #[arg(long)]
+    optimize: bool,
+}
+
+pub(crate) fn compile(args: CompileArgs) -> anyhow::Result<ExitCode> {
+    let result = args
+        .inputs
+        .compiler()?
+        .with_optimization(args.optimize)
+        .compile();
+    let Some(compilation) = input::report(result) else {
+        return Ok(ExitCode::from(CONTENT_ERROR));
+    };
+    let program = compilation
+        .program
+        .as_ref()
+        .expect("A full compilation always produces a program");
+
+    std::fs::create_dir_all(&args.output_directory)
+        .with_context(|| format!("Failed to create \"{}\"", args.output_directory.display()))?;
+    let path = |suffix: &str| {
+        args.output_directory
+            .join(format!("{}{suffix}", args.output_name))
+    };
+    let program_path = path(".yarnc");
+    std::fs::write(&program_path, program.to_bytes())
+        .with_context(|| format!("Failed to write \"{}\"", program_path.display()))?;
+    write_lines(&compilation.string_table, create(&path("-Lines.csv"))?)?;
+    write_metadata(&compilation.string_table, create(&path("-Metadata.csv"))?)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn create(path: &Path) -> anyhow::Result<std::fs::File> {
+    std::fs::File::create(path).with_context(|| format!("Failed to create \"{}\"", path.display()))
+}
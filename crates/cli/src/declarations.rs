@@ -0,0 +1,99 @@
+use crate::input::{self, Inputs};
+use crate::CONTENT_ERROR;
+use clap::Args;
+use std::fmt::Write;
+use std::process::ExitCode;
+use yarnspinner::compiler::*;
+use yarnspinner::core::YarnValue;
+
+#[derive(Debug, Args)]
+pub(crate) struct DeclarationsArgs {
+    #[command(flatten)]
+    inputs: Inputs,
+}
+
+pub(crate) fn declarations(args: DeclarationsArgs) -> anyhow::Result<ExitCode> {
+    let result = args
+        .inputs
+        .compiler()?
+        .with_compilation_type(CompilationType::DeclarationsOnly)
+        .compile();
+    let Some(compilation) = input::report(result) else {
+        return Ok(ExitCode::from(CONTENT_ERROR));
+    };
+    print!("{}", describe(&compilation.declarations));
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Describes all variables declared in Yarn files, one per line, sorted by name.
+/// Declarations made by the compiler for its own bookkeeping are left out.
+fn describe(declarations: &[Declaration]) -> String {
+    let mut declarations: Vec<_> = declarations
+        .iter()
+        .filter(|declaration| {
+            declaration.source_file_name != DeclarationSource::External
+                && !declaration.name.starts_with("$Yarn.Internal")
+        })
+        .collect();
+    declarations.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut output = String::new();
+    for declaration in declarations {
+        write!(output, "{}: {}", declaration.name, declaration.r#type).unwrap();
+        match &declaration.default_value {
+            // Quoted so that empty strings are visible.
+            Some(YarnValue::String(value)) => write!(output, " = {value:?}").unwrap(),
+            Some(value) => write!(output, " = {value}").unwrap(),
+            None => {}
+        }
+        if declaration.is_smart_variable {
+            output.push_str(" (smart variable)");
+        } else if declaration.is_implicit {
+            output.push_str(" (inferred)");
+        }
+        if let DeclarationSource::File(file) = &declaration.source_file_name {
+            write!(output, " at {file}").unwrap();
+            if let Some(line) = declaration.source_file_line() {
+                // Lines are zero-indexed internally, but editors show them one-indexed.
+                write!(output, ":{}", line + 1).unwrap();
+            }
+        }
+        if let Some(description) = &declaration.description {
+            write!(output, " - {description}").unwrap();
+        }
+        output.push('\n');
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_declared_and_inferred_variables() {
+        let compilation = Compiler::new()
+            .add_file(File {
+                file_name: "test.yarn".to_owned(),
+                source: "\
+title: Start
+---
+/// How much gold the player has.
+<<declare $gold = 10>>
+<<declare $is_rich = $gold > 100>>
+<<set $name to \"Ada\">>
+===
+"
+                .to_owned(),
+            })
+            .with_compilation_type(CompilationType::DeclarationsOnly)
+            .compile()
+            .unwrap();
+        assert_eq!(
+            "$gold: Number = 10 at test.yarn:4 - How much gold the player has.\n\
+             $is_rich: Bool (smart variable) at test.yarn:5\n\
+             $name: String = \"\" (inferred) at test.yarn:6 - Implicitly declared in test.yarn, node Start\n",
+            describe(&compilation.declarations)
+        );
+    }
+}
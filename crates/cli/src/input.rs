@@ -0,0 +1,122 @@
+use anyhow::Context;
+use clap::Args;
+use std::path::{Path, PathBuf};
+use yarnspinner::compiler::*;
+use yarnspinner::core::Library;
+use yarnspinner::runtime::{Dialogue, MemoryVariableStorage, StringTableTextProvider};
+
+/// The Yarn files a subcommand works on.
+#[derive(Debug, Args)]
+pub(crate) struct Inputs {
+    /// The `.yarn` files to use, or directories to search for them.
+    #[arg(required = true, value_name = "PATH")]
+    pub(crate) paths: Vec<PathBuf>,
+}
+
+impl Inputs {
+    /// Returns the paths of all `.yarn` files given directly or found in the given directories, sorted and without duplicates.
+    pub(crate) fn yarn_files(&self) -> anyhow::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for path in &self.paths {
+            if path.is_dir() {
+                let found = Compiler::find_yarn_files(path)
+                    .with_context(|| format!("Failed to search \"{}\"", path.display()))?;
+                files.extend(found);
+            } else {
+                files.push(path.clone());
+            }
+        }
+        files.sort();
+        files.dedup();
+        Ok(files)
+    }
+
+    /// Reads all `.yarn` files, see [`Inputs::yarn_files`].
+    pub(crate) fn read(&self) -> anyhow::Result<Vec<File>> {
+        self.yarn_files()?
+            .into_iter()
+            .map(|path| read_file(&path))
+            .collect()
+    }
+
+    /// Creates a compiler for all `.yarn` files that knows the functions available at runtime.
+    pub(crate) fn compiler(&self) -> anyhow::Result<Compiler> {
+        let mut compiler = Compiler::new();
+        compiler
+            .add_files(self.read()?)
+            .extend_library(runtime_library());
+        Ok(compiler)
+    }
+}
+
+pub(crate) fn read_file(path: &Path) -> anyhow::Result<File> {
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read \"{}\"", path.display()))?;
+    Ok(File {
+        file_name: path.display().to_string(),
+        source,
+    })
+}
+
+/// The functions every [`Dialogue`] provides, i.e. the standard library and functions like `visited`.
+pub(crate) fn runtime_library() -> Library {
+    let dialogue = Dialogue::new(
+        Box::new(MemoryVariableStorage::new()),
+        Box::new(StringTableTextProvider::new()),
+    );
+    dialogue.library().clone()
+}
+
+/// Prints the warnings of a successful compilation or the errors of a failed one to the standard error.
+///
+/// Returns the compilation if it was successful.
+pub(crate) fn report(result: Result<Compilation>) -> Option<Compilation> {
+    match result {
+        Ok(compilation) => {
+            print_diagnostics(&compilation.warnings);
+            Some(compilation)
+        }
+        Err(CompilerError(diagnostics)) => {
+            print_diagnostics(&diagnostics);
+            None
+        }
+    }
+}
+
+pub(crate) fn print_diagnostics(diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        eprintln!("{diagnostic}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_yarn_files_in_directories() {
+        let directory = std::env::temp_dir().join(format!("ysc-input-{}", std::process::id()));
+        let nested = directory.join("nested");
+        let hidden = directory.join(".hidden");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::create_dir_all(&hidden).unwrap();
+        for path in [
+            directory.join("b.yarn"),
+            nested.join("a.yarn"),
+            nested.join("notes.txt"),
+            hidden.join("c.yarn"),
+        ] {
+            std::fs::write(path, "").unwrap();
+        }
+
+        let inputs = Inputs {
+            paths: vec![directory.clone(), directory.join("b.yarn")],
+        };
+        let files = inputs.yarn_files();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(
+            vec![directory.join("b.yarn"), nested.join("a.yarn")],
+            files.unwrap()
+        );
+    }
+}
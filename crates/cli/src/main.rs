@@ -0,0 +1,71 @@
+//! `ysc`, the command-line compiler for Yarn Spinner for Rust.
+//!
+//! Compiles, checks and inspects `.yarn` files without writing any Rust, e.g. as part of CI or a build script.
+//! Every subcommand accepts any number of `.yarn` files and directories, which are searched recursively for `.yarn` files.
+//!
+//! ## Exit codes
+//!
+//! - `0`: Everything went fine.
+//! - `1`: The Yarn files contain errors.
+//! - `2`: The command could not be run, e.g. because of invalid arguments or a file that could not be read or written.
+
+#![warn(missing_docs, missing_debug_implementations)]
+
+use clap::{Parser, Subcommand};
+use std::process::ExitCode;
+
+mod check;
+mod compile;
+mod declarations;
+mod input;
+mod run;
+mod strings;
+mod tag;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "ysc",
+    version,
+    about = "Compiles and inspects Yarn Spinner dialogue"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Compiles Yarn files into a program and a string table.
+    Compile(compile::CompileArgs),
+    /// Checks Yarn files for errors and prints all diagnostics.
+    Check(check::CheckArgs),
+    /// Exports the lines of Yarn files as CSV.
+    Strings(strings::StringsArgs),
+    /// Adds `#line:` tags to all lines that don't have one yet, changing the files in place.
+    Tag(tag::TagArgs),
+    /// Lists the variables declared in Yarn files, including the ones whose type was inferred.
+    Declarations(declarations::DeclarationsArgs),
+    /// Plays a node in the terminal, reading the selected options from the standard input.
+    Run(run::RunArgs),
+}
+
+/// The exit code for when the Yarn files contain errors.
+const CONTENT_ERROR: u8 = 1;
+/// The exit code for when the command could not be run at all.
+const USAGE_ERROR: u8 = 2;
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Compile(args) => compile::compile(args),
+        Command::Check(args) => check::check(args),
+        Command::Strings(args) => strings::strings(args),
+        Command::Tag(args) => tag::tag(args),
+        Command::Declarations(args) => declarations::declarations(args),
+        Command::Run(args) => run::run(args),
+    };
+    result.unwrap_or_else(|error| {
+        eprintln!("error: {error:#}");
+        ExitCode::from(USAGE_ERROR)
+    })
+}
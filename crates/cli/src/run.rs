@@ -0,0 +1,115 @@
+use crate::input::{self, Inputs};
+use crate::CONTENT_ERROR;
+use clap::Args;
+use std::io::{BufRead, Write};
+use std::process::ExitCode;
+use yarnspinner::compiler::CompilerError;
+use yarnspinner_player::Player;
+
+#[derive(Debug, Args)]
+pub(crate) struct RunArgs {
+    #[command(flatten)]
+    inputs: Inputs,
+
+    /// The node to start the dialogue at.
+    #[arg(short, long, default_value = "Start", value_name = "NODE")]
+    start: String,
+}
+
+pub(crate) fn run(args: RunArgs) -> anyhow::Result<ExitCode> {
+    play(&args, std::io::stdin().lock(), std::io::stdout().lock())
+}
+
+/// Plays the dialogue with the [`Player`] of `yarnspinner_player`, writing lines, options and commands to `output`
+/// and reading the number of each selected option, or one of the player's commands like `/vars`, from `input`.
+///
+/// Stops early if `input` ends while waiting for an option to be selected.
+fn play(args: &RunArgs, input: impl BufRead, output: impl Write) -> anyhow::Result<ExitCode> {
+    let mut player = match Player::load(&args.inputs.paths, input::runtime_library()) {
+        Ok(player) => player,
+        Err(error) => {
+            return match error.downcast::<CompilerError>() {
+                Ok(error) => {
+                    input::print_diagnostics(&error.0);
+                    Ok(ExitCode::from(CONTENT_ERROR))
+                }
+                Err(error) => Err(anyhow::anyhow!(error)),
+            }
+        }
+    };
+    input::print_diagnostics(player.warnings());
+    player
+        .play(&args.start, input, output)
+        .map_err(|error| anyhow::anyhow!(error))?;
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "\
+title: Start
+---
+Hello
+<<fade_out 2>>
+-> Stay
+    You stay.
+-> Leave <<if false>>
+-> Go
+    You go.
+Bye
+===
+";
+
+    fn play_with_input(name: &str, source: &str, input: &str) -> (ExitCode, String) {
+        let directory = std::env::temp_dir().join(format!("ysc-run-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("test.yarn"), source).unwrap();
+        let args = RunArgs {
+            inputs: Inputs {
+                paths: vec![directory.clone()],
+            },
+            start: "Start".to_owned(),
+        };
+
+        let mut output = Vec::new();
+        let exit_code = play(&args, input.as_bytes(), &mut output);
+        std::fs::remove_dir_all(&directory).unwrap();
+        (exit_code.unwrap(), String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn plays_selected_options() {
+        let (exit_code, output) = play_with_input("options", SOURCE, "3\n");
+        assert_eq!(ExitCode::SUCCESS, exit_code);
+        assert_eq!(
+            "Hello\n<<fade_out 2>>\n  1) Stay\n  2) Leave (unavailable)\n  3) Go\n> You go.\nBye\n",
+            output
+        );
+    }
+
+    #[test]
+    fn asks_again_for_invalid_selections() {
+        let (_, output) = play_with_input("invalid", SOURCE, "two\n2\n1\n");
+        assert!(output.ends_with(
+            "> Please enter the number of an available option (1-3), or /help for commands\n\
+             > Please enter the number of an available option (1-3), or /help for commands\n\
+             > You stay.\nBye\n"
+        ));
+    }
+
+    #[test]
+    fn stops_when_input_ends() {
+        let (_, output) = play_with_input("end", SOURCE, "");
+        assert!(output.ends_with("  3) Go\n> "));
+    }
+
+    #[test]
+    fn reports_errors_in_the_yarn_files() {
+        let (exit_code, output) =
+            play_with_input("errors", "title: Start\n---\n<<if $gold>>\n===\n", "");
+        assert_eq!(ExitCode::from(CONTENT_ERROR), exit_code);
+        assert!(output.is_empty());
+    }
+}
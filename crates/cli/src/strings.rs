@@ -0,0 +1,140 @@
+use crate::input::{self, Inputs};
+use crate::CONTENT_ERROR;
+use anyhow::Context;
+use clap::Args;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use yarnspinner::compiler::*;
+use yarnspinner::core::LineId;
+
+#[derive(Debug, Args)]
+pub(crate) struct StringsArgs {
+    #[command(flatten)]
+    inputs: Inputs,
+
+    /// The CSV file to write the lines to. Prints them to the standard output if omitted.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+pub(crate) fn strings(args: StringsArgs) -> anyhow::Result<ExitCode> {
+    let result = args
+        .inputs
+        .compiler()?
+        .with_compilation_type(CompilationType::StringsOnly)
+        .compile();
+    let Some(compilation) = input::report(result) else {
+        return Ok(ExitCode::from(CONTENT_ERROR));
+    };
+    match args.output {
+        Some(path) => {
+            let file = std::fs::File::create(&path)
+                .with_context(|| format!("Failed to create \"{}\"", path.display()))?;
+            write_lines(&compilation.string_table, file)
+        }
+        None => write_lines(&compilation.string_table, std::io::stdout().lock()),
+    }?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Writes the lines of a string table as CSV in the format of the original Yarn Spinner's `*-Lines.csv`,
+/// ordered by file and line number.
+pub(crate) fn write_lines(
+    string_table: &HashMap<LineId, StringInfo>,
+    writer: impl Write,
+) -> anyhow::Result<()> {
+    let mut csv = csv::Writer::from_writer(writer);
+    csv.write_record(["id", "text", "file", "node", "lineNumber"])?;
+    for (id, info) in sorted(string_table) {
+        csv.write_record([
+            id.0.as_str(),
+            info.text.as_str(),
+            info.file_name.as_str(),
+            info.node_name.as_str(),
+            info.line_number.to_string().as_str(),
+        ])?;
+    }
+    csv.flush()?;
+    Ok(())
+}
+
+/// Writes the tags of all lines that have any as CSV in the format of the original Yarn Spinner's `*-Metadata.csv`.
+/// The `#line:` tags are left out, since they are already the IDs.
+pub(crate) fn write_metadata(
+    string_table: &HashMap<LineId, StringInfo>,
+    writer: impl Write,
+) -> anyhow::Result<()> {
+    let mut csv = csv::Writer::from_writer(writer);
+    csv.write_record(["id", "node", "lineNumber", "tags"])?;
+    for (id, info) in sorted(string_table) {
+        let tags: Vec<_> = info
+            .metadata
+            .iter()
+            .filter(|tag| !tag.starts_with("line:"))
+            .map(String::as_str)
+            .collect();
+        if tags.is_empty() {
+            continue;
+        }
+        csv.write_record([
+            id.0.as_str(),
+            info.node_name.as_str(),
+            info.line_number.to_string().as_str(),
+            tags.join(" ").as_str(),
+        ])?;
+    }
+    csv.flush()?;
+    Ok(())
+}
+
+fn sorted(string_table: &HashMap<LineId, StringInfo>) -> Vec<(&LineId, &StringInfo)> {
+    let mut lines: Vec<_> = string_table.iter().collect();
+    lines.sort_by(|(_, a), (_, b)| {
+        (&a.file_name, a.line_number).cmp(&(&b.file_name, b.line_number))
+    });
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(source: &str) -> Compilation {
+        Compiler::new()
+            .add_file(File {
+                file_name: "test.yarn".to_owned(),
+                source: source.to_owned(),
+            })
+            .with_compilation_type(CompilationType::StringsOnly)
+            .compile()
+            .unwrap()
+    }
+
+    #[test]
+    fn writes_lines_in_source_order() {
+        let compilation =
+            compile("title: Start\n---\nHello, \"friend\" #line:hello\nBye #line:bye #sad\n===\n");
+        let mut output = Vec::new();
+        write_lines(&compilation.string_table, &mut output).unwrap();
+        assert_eq!(
+            "id,text,file,node,lineNumber\n\
+             line:hello,\"Hello, \"\"friend\"\"\",test.yarn,Start,3\n\
+             line:bye,Bye,test.yarn,Start,4\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[test]
+    fn writes_metadata_of_tagged_lines() {
+        let compilation =
+            compile("title: Start\n---\nHello #line:hello\nBye #line:bye #sad #quiet\n===\n");
+        let mut output = Vec::new();
+        write_metadata(&compilation.string_table, &mut output).unwrap();
+        assert_eq!(
+            "id,node,lineNumber,tags\nline:bye,Start,4,sad quiet\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use crate::input::{self, Inputs};
+use crate::CONTENT_ERROR;
+use anyhow::Context;
+use clap::Args;
+use std::process::ExitCode;
+use yarnspinner::compiler::*;
+use yarnspinner::core::LineId;
+
+#[derive(Debug, Args)]
+pub(crate) struct TagArgs {
+    #[command(flatten)]
+    inputs: Inputs,
+}
+
+pub(crate) fn tag(args: TagArgs) -> anyhow::Result<ExitCode> {
+    let files = args.inputs.read()?;
+    // Tags must be unique across all files, not only within one.
+    let mut existing_tags = match explicit_line_ids(files.clone()) {
+        Ok(ids) => ids,
+        Err(CompilerError(diagnostics)) => {
+            input::print_diagnostics(&diagnostics);
+            return Ok(ExitCode::from(CONTENT_ERROR));
+        }
+    };
+
+    for file in files {
+        let tagged = match Compiler::add_tags_to_lines(file.source, existing_tags.clone()) {
+            Ok(tagged) => tagged,
+            Err(CompilerError(diagnostics)) => {
+                input::print_diagnostics(&diagnostics);
+                return Ok(ExitCode::from(CONTENT_ERROR));
+            }
+        };
+        let Some(source) = tagged else {
+            continue;
+        };
+        std::fs::write(&file.file_name, &source)
+            .with_context(|| format!("Failed to write \"{}\"", file.file_name))?;
+        eprintln!("Tagged {}", file.file_name);
+
+        let file = File {
+            file_name: file.file_name,
+            source,
+        };
+        existing_tags.extend(explicit_line_ids([file]).unwrap_or_default());
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn explicit_line_ids(files: impl IntoIterator<Item = File>) -> Result<Vec<LineId>> {
+    let compilation = Compiler::new()
+        .add_files(files)
+        .extend_library(input::runtime_library())
+        .with_compilation_type(CompilationType::StringsOnly)
+        .compile()?;
+    let ids = compilation
+        .string_table
+        .into_iter()
+        .filter(|(_, info)| !info.is_implicit_tag)
+        .map(|(id, _)| id)
+        .collect();
+    Ok(ids)
+}
@@ -1,12 +1,17 @@
 mod add_initial_value_registrations;
+mod add_once_declarations;
 mod add_tracking_declarations;
+mod check_smart_variable_cycles;
 mod check_types;
 mod clean_up_diagnostics;
 mod create_declarations_for_tracking_nodes;
 mod early_breaks;
+mod find_once_variables;
 mod find_tracking_nodes;
 mod generate_code;
 mod get_declarations;
+mod get_enum_declarations;
+mod optimize_code;
 mod parse_files;
 mod register_initial_variables;
 mod register_strings;
@@ -14,9 +19,10 @@ mod resolve_deferred_type_diagnostic;
 mod validate_unique_node_names;
 
 pub(crate) use self::{
-    add_initial_value_registrations::*, add_tracking_declarations::*, check_types::*,
-    clean_up_diagnostics::*, create_declarations_for_tracking_nodes::*, early_breaks::*,
-    find_tracking_nodes::*, generate_code::*, get_declarations::*, parse_files::*,
-    register_initial_variables::*, register_strings::*, resolve_deferred_type_diagnostic::*,
-    validate_unique_node_names::*,
+    add_initial_value_registrations::*, add_once_declarations::*, add_tracking_declarations::*,
+    check_smart_variable_cycles::*, check_types::*, clean_up_diagnostics::*,
+    create_declarations_for_tracking_nodes::*, early_breaks::*, find_once_variables::*,
+    find_tracking_nodes::*, generate_code::*, get_declarations::*, get_enum_declarations::*,
+    optimize_code::*, parse_files::*, register_initial_variables::*, register_strings::*,
+    resolve_deferred_type_diagnostic::*, validate_unique_node_names::*,
 };
@@ -15,7 +15,11 @@ pub(crate) fn add_initial_value_registrations(
     let declarations = state
         .known_variable_declarations
         .iter()
-        .filter(|decl| !matches!(decl.r#type, Type::Function(_)));
+        .filter(|decl| !matches!(decl.r#type, Type::Function(_)))
+        // Locals are initialized when their node starts and must never end up in the variable storage
+        .filter(|decl| !Library::is_local_variable_name(&decl.name))
+        // Smart variables are computed whenever they are read, so they are never stored either
+        .filter(|decl| !decl.is_smart_variable);
 
     for declaration in declarations {
         let Some(default_value) = declaration.default_value.clone() else {
@@ -28,7 +32,8 @@ pub(crate) fn add_initial_value_registrations(
         };
         if let Some(ref mut program) = compilation.program {
             let value = match &declaration.r#type {
-                    Type::String => Operand::from(String::from(default_value)),
+                    // Enum values are stored as the name of their case
+                    Type::String | Type::Enum(_) => Operand::from(String::from(default_value)),
                     Type::Number => Operand::from(f32::try_from(default_value).unwrap()),
                     Type::Boolean => Operand::from(bool::try_from(default_value).unwrap()),
                     _ => panic!("Cannot create initial value registration for type {}. This is a bug. Please report it at https://github.com/YarnSpinnerTool/YarnSpinner-Rust/issues/new", declaration.r#type.format()),
@@ -39,8 +44,7 @@ pub(crate) fn add_initial_value_registrations(
         }
     }
 
-    compilation
-        .declarations
-        .clone_from(&state.derived_variable_declarations);
+    (compilation.declarations, compilation.local_declarations) =
+        partition_local_declarations(&state.derived_variable_declarations);
     state
 }
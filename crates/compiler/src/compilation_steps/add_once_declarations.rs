@@ -0,0 +1,26 @@
+use crate::prelude::*;
+use yarnspinner_core::types::Type;
+
+pub(crate) fn add_once_declarations(mut state: CompilationIntermediate) -> CompilationIntermediate {
+    let once_declarations: Vec<_> = state
+        .once_variables
+        .iter()
+        .map(|name| {
+            Declaration::new(name, Type::Boolean)
+                .with_default_value(false)
+                .with_description(
+                    "The generated variable for tracking whether a <<once>> block or #once line was run",
+                )
+                .with_implicit()
+        })
+        .collect();
+
+    // Declaring them means that they are saved and loaded along with all other variables
+    state
+        .known_variable_declarations
+        .extend(once_declarations.clone());
+    state
+        .derived_variable_declarations
+        .extend(once_declarations);
+    state
+}
@@ -0,0 +1,59 @@
+use crate::prelude::*;
+use crate::visitors::SmartVariable;
+use std::collections::HashMap;
+
+/// Smart variables are evaluated whenever they are read,
+/// so a smart variable whose expression ends up reading the variable itself could never be evaluated.
+pub(crate) fn check_smart_variable_cycles(
+    mut state: CompilationIntermediate,
+) -> CompilationIntermediate {
+    let smart_variables: HashMap<_, _> = state
+        .smart_variables
+        .iter()
+        .map(|smart_variable| (smart_variable.name.as_str(), smart_variable))
+        .collect();
+
+    for smart_variable in &state.smart_variables {
+        let mut path = vec![smart_variable.name.as_str()];
+        if find_cycle(smart_variable, &smart_variables, &mut path) {
+            let message = format!(
+                "Smart variable {} has a cyclic definition: {}",
+                smart_variable.name,
+                path.join(" -> ")
+            );
+            state.diagnostics.push(Diagnostic {
+                message,
+                ..smart_variable.diagnostic.clone()
+            });
+        }
+    }
+    state
+}
+
+/// Searches the dependencies of the last variable in `path` for the first one.
+/// If found, returns `true` and leaves the cycle in `path`.
+fn find_cycle<'a>(
+    current: &'a SmartVariable,
+    smart_variables: &HashMap<&'a str, &'a SmartVariable>,
+    path: &mut Vec<&'a str>,
+) -> bool {
+    for dependency in &current.dependencies {
+        if dependency == path[0] {
+            path.push(path[0]);
+            return true;
+        }
+        // Cycles that don't lead back to the start are reported for the variables that are part of them
+        if path.contains(&dependency.as_str()) {
+            continue;
+        }
+        let Some(next) = smart_variables.get(dependency.as_str()) else {
+            continue;
+        };
+        path.push(&next.name);
+        if find_cycle(next, smart_variables, path) {
+            return true;
+        }
+        path.pop();
+    }
+    false
+}
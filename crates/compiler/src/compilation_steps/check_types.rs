@@ -3,9 +3,14 @@ use crate::visitors::TypeCheckVisitor;
 use antlr_rust::tree::ParseTreeVisitorCompat;
 
 pub(crate) fn check_types(mut state: CompilationIntermediate) -> CompilationIntermediate {
+    let command_library = state.job.command_library.as_ref();
     for (file, known_types) in &mut state.parsed_files {
-        let mut visitor =
-            TypeCheckVisitor::new(state.known_variable_declarations.clone(), file.clone());
+        let mut visitor = TypeCheckVisitor::new(
+            state.known_variable_declarations.clone(),
+            state.known_enums.clone(),
+            command_library,
+            file.clone(),
+        );
         visitor.visit(file.tree.as_ref());
         state
             .known_variable_declarations
@@ -19,8 +19,11 @@ pub(crate) fn break_on_job_with_only_declarations(
     mut state: CompilationIntermediate,
 ) -> CompilationIntermediate {
     if state.job.compilation_type == CompilationType::DeclarationsOnly {
+        let (declarations, local_declarations) =
+            partition_local_declarations(&state.derived_variable_declarations);
         state.result = Some(Ok(Compilation {
-            declarations: state.derived_variable_declarations.clone(),
+            declarations,
+            local_declarations,
             warnings: state.diagnostics.clone(),
             file_tags: state.file_tags.clone(),
             ..Default::default()
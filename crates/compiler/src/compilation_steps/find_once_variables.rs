@@ -0,0 +1,14 @@
+use crate::prelude::*;
+use crate::visitors::OnceTrackingVisitor;
+use antlr_rust::tree::ParseTreeVisitorCompat;
+
+pub(crate) fn find_once_variables(mut state: CompilationIntermediate) -> CompilationIntermediate {
+    // Like the tracking nodes, this needs to be done before we finish up with declarations
+    // so that the variables of `<<once>>` blocks and `#once` lines are included in the compiled declarations
+    for (file, _) in &state.parsed_files {
+        let mut visitor = OnceTrackingVisitor::new(file.clone());
+        visitor.visit(file.tree.as_ref());
+        state.once_variables.extend(visitor.once_variables);
+    }
+    state
+}
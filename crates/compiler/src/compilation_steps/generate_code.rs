@@ -18,12 +18,14 @@ pub(crate) fn generate_code(mut state: CompilationIntermediate) -> CompilationIn
             file_tags: state.file_tags.clone(),
             ..Default::default()
         };
+        let variable_declarations = state.known_variable_declarations.clone();
         state
             .parsed_files
             .iter()
             .map(|(file, known_types)| {
                 generate_code_for_file(
                     &mut state.tracking_nodes,
+                    variable_declarations.clone(),
                     known_types.clone(),
                     template.clone(),
                     file,
@@ -55,12 +57,14 @@ pub(crate) fn generate_code(mut state: CompilationIntermediate) -> CompilationIn
 
 fn generate_code_for_file<'a, 'b: 'a, 'input: 'a + 'b>(
     tracking_nodes: &mut HashSet<String>,
+    variable_declarations: Vec<Declaration>,
     known_types: KnownTypes,
     result_template: Compilation,
     file: &'a FileParseResult<'input>,
 ) -> Result<Compilation> {
     let compiler_listener = Box::new(CompilerListener::new(
         tracking_nodes.clone(),
+        variable_declarations,
         known_types,
         file.clone(),
     ));
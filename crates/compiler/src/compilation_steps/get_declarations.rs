@@ -5,8 +5,11 @@ use antlr_rust::tree::ParseTreeVisitorCompat;
 pub(crate) fn get_declarations(mut state: CompilationIntermediate) -> CompilationIntermediate {
     // Find the variable declarations in these files.
     for (file, _) in &state.parsed_files {
-        let mut variable_declaration_visitor =
-            DeclarationVisitor::new(state.known_variable_declarations.clone(), file.clone());
+        let mut variable_declaration_visitor = DeclarationVisitor::new(
+            state.known_variable_declarations.clone(),
+            state.known_enums.clone(),
+            file.clone(),
+        );
 
         variable_declaration_visitor.visit(file.tree.as_ref());
 
@@ -21,6 +24,10 @@ pub(crate) fn get_declarations(mut state: CompilationIntermediate) -> Compilatio
             .diagnostics
             .extend_from_slice(&variable_declaration_visitor.diagnostics);
 
+        state
+            .smart_variables
+            .extend(variable_declaration_visitor.smart_variables);
+
         state
             .file_tags
             .insert(file.name.clone(), variable_declaration_visitor.file_tags);
@@ -0,0 +1,20 @@
+use crate::prelude::*;
+use crate::visitors::EnumDeclarationVisitor;
+use antlr_rust::tree::ParseTreeVisitorCompat;
+
+pub(crate) fn get_enum_declarations(mut state: CompilationIntermediate) -> CompilationIntermediate {
+    // Find the enum declarations in these files. This needs to happen before
+    // getting the variable declarations, as variables can be declared with enum values.
+    for (file, _) in &state.parsed_files {
+        let mut enum_declaration_visitor =
+            EnumDeclarationVisitor::new(state.known_enums.clone(), file.clone());
+
+        enum_declaration_visitor.visit(file.tree.as_ref());
+
+        state.known_enums.extend(enum_declaration_visitor.new_enums);
+        state
+            .diagnostics
+            .extend(enum_declaration_visitor.diagnostics);
+    }
+    state
+}
@@ -0,0 +1,332 @@
+use crate::prelude::*;
+use std::collections::{HashSet, VecDeque};
+use yarnspinner_core::prelude::*;
+
+/// Rewrites the generated code into shorter code that behaves the same, if [`Compiler::optimize`] is set.
+///
+/// The following passes are run on every node until none of them finds anything left to do:
+/// - Calls of standard library functions whose arguments are all constants are replaced by their result.
+///   The functions are assumed to be the ones in [`Library::standard_library`], which are all pure.
+/// - Conditional jumps on constants are replaced by unconditional jumps or removed, and constants that are popped right away are removed.
+/// - Jumps to unconditional jumps go directly to the final destination, and jumps to the very next instruction are removed.
+/// - Labels that are not referenced by any instruction are removed.
+/// - Instructions that can never be reached are removed.
+///
+/// The [`DebugInfo`] of every node is updated so that it keeps pointing to the source of each remaining instruction.
+pub(crate) fn optimize_code(mut state: CompilationIntermediate) -> CompilationIntermediate {
+    if !state.job.optimize {
+        return state;
+    }
+    let Some(Ok(compilation)) = state.result.as_mut() else {
+        return state;
+    };
+    let Some(program) = compilation.program.as_mut() else {
+        return state;
+    };
+    let library = Library::standard_library();
+    for node in program.nodes.values_mut() {
+        let debug_info = compilation.debug_info.get_mut(&node.name);
+        let mut optimizer = NodeOptimizer::new(node, debug_info.as_deref(), &library);
+        optimizer.optimize();
+        let positions = optimizer.positions;
+        if let Some(debug_info) = debug_info {
+            debug_info.line_positions = positions
+                .into_iter()
+                .enumerate()
+                .filter_map(|(index, position)| Some((index, position?)))
+                .collect();
+        }
+    }
+    state
+}
+
+struct NodeOptimizer<'a> {
+    node: &'a mut Node,
+    /// The source position of every instruction, as found in [`DebugInfo::line_positions`].
+    positions: Vec<Option<Option<Position>>>,
+    library: &'a Library,
+}
+
+impl<'a> NodeOptimizer<'a> {
+    fn new(node: &'a mut Node, debug_info: Option<&DebugInfo>, library: &'a Library) -> Self {
+        let positions = (0..node.instructions.len())
+            .map(|index| debug_info.and_then(|info| info.line_positions.get(&index).copied()))
+            .collect();
+        Self {
+            node,
+            positions,
+            library,
+        }
+    }
+
+    fn optimize(&mut self) {
+        loop {
+            // Every pass must run, so don't short-circuit
+            let changed = [
+                self.fold_constants(),
+                self.simplify_constant_conditions(),
+                self.thread_jumps(),
+                self.remove_unused_labels(),
+                self.remove_unreachable_instructions(),
+            ];
+            if !changed.contains(&true) {
+                break;
+            }
+        }
+    }
+
+    /// Replaces `PUSH a, PUSH b, PUSH_FLOAT 2, CALL_FUNC f` by `PUSH f(a, b)`.
+    fn fold_constants(&mut self) -> bool {
+        let label_positions = self.label_positions();
+        let mut removed = vec![false; self.node.instructions.len()];
+        for end in 0..self.node.instructions.len() {
+            let instruction = &self.node.instructions[end];
+            if instruction.opcode != OpCode::CallFunc as i32 {
+                continue;
+            }
+            let Some(function) = string_operand(instruction, 0)
+                .and_then(|function_name| self.library.get(&function_name))
+            else {
+                continue;
+            };
+            let parameter_count = function.parameter_types().len();
+            let Some(start) = end.checked_sub(parameter_count + 1) else {
+                continue;
+            };
+            let pushes_parameter_count = match constant(&self.node.instructions[end - 1]) {
+                Some(YarnValue::Number(count)) => count == parameter_count as f32,
+                _ => false,
+            };
+            // Jumps into the middle of the expression would skip some of its values
+            let is_interrupted = removed[start..=end].contains(&true)
+                || (start + 1..=end).any(|index| label_positions.contains(&index));
+            if !pushes_parameter_count || is_interrupted {
+                continue;
+            }
+            let Some(parameters) = self.node.instructions[start..end - 1]
+                .iter()
+                .map(constant)
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            // Invalid calls are left alone so they fail when the dialogue is run like they would without optimizations
+            let Ok(result) = function.call(parameters) else {
+                continue;
+            };
+            self.node.instructions[end] = push(result);
+            removed[start..end].fill(true);
+        }
+        self.remove_instructions(&removed)
+    }
+
+    /// Removes conditional jumps and pops whose outcome is known in advance.
+    fn simplify_constant_conditions(&mut self) -> bool {
+        let label_positions = self.label_positions();
+        let instructions = &mut self.node.instructions;
+        let mut removed = vec![false; instructions.len()];
+        let mut changed = false;
+        for index in 1..instructions.len() {
+            if removed[index - 1] || label_positions.contains(&index) {
+                continue;
+            }
+            let opcode = OpCode::try_from(instructions[index].opcode);
+            let previous_value = constant(&instructions[index - 1]);
+            match (opcode, previous_value) {
+                (Ok(OpCode::JumpIfFalse), Some(YarnValue::Boolean(false))) => {
+                    // The value stays on the stack either way
+                    instructions[index].opcode = OpCode::JumpTo.into();
+                    changed = true;
+                }
+                (Ok(OpCode::JumpIfFalse), Some(YarnValue::Boolean(true))) => {
+                    let is_followed_by_pop = instructions
+                        .get(index + 1)
+                        .is_some_and(|next| next.opcode == OpCode::Pop as i32)
+                        && !label_positions.contains(&(index + 1));
+                    if is_followed_by_pop {
+                        removed[index - 1..=index + 1].fill(true);
+                    }
+                }
+                (Ok(OpCode::Pop), Some(_)) => {
+                    removed[index - 1..=index].fill(true);
+                }
+                _ => {}
+            }
+        }
+        self.remove_instructions(&removed) || changed
+    }
+
+    /// Lets jumps skip over unconditional jumps they would land on and removes jumps to the next instruction.
+    fn thread_jumps(&mut self) -> bool {
+        let mut changed = false;
+        for index in 0..self.node.instructions.len() {
+            let instruction = &self.node.instructions[index];
+            let is_jump =
+                [OpCode::JumpTo as i32, OpCode::JumpIfFalse as i32].contains(&instruction.opcode);
+            let Some(label) = string_operand(instruction, 0).filter(|_| is_jump) else {
+                continue;
+            };
+            let destination = self.final_destination(label.clone());
+            if destination != label {
+                self.node.instructions[index].operands[0] = Operand::from(destination);
+                changed = true;
+            }
+        }
+
+        let removed: Vec<_> = self
+            .node
+            .instructions
+            .iter()
+            .enumerate()
+            .map(|(index, instruction)| {
+                instruction.opcode == OpCode::JumpTo as i32
+                    && string_operand(instruction, 0).and_then(|label| self.label_position(&label))
+                        == Some(index + 1)
+            })
+            .collect();
+        self.remove_instructions(&removed) || changed
+    }
+
+    /// Follows the chain of unconditional jumps starting at the given label.
+    fn final_destination(&self, mut label: String) -> String {
+        let mut visited = HashSet::new();
+        while let Some(position) = self.label_position(&label) {
+            let Some(instruction) = self.node.instructions.get(position) else {
+                break;
+            };
+            let next_label = string_operand(instruction, 0)
+                .filter(|_| instruction.opcode == OpCode::JumpTo as i32);
+            match next_label {
+                // A loop of jumps never ends, so leave it as is
+                Some(next_label) if visited.insert(next_label.clone()) => label = next_label,
+                _ => break,
+            }
+        }
+        label
+    }
+
+    /// Removes labels that are not used by any instruction.
+    /// Since [`OpCode::Jump`] reads the label from the stack, every string operand is considered a potential use.
+    fn remove_unused_labels(&mut self) -> bool {
+        let used_strings: HashSet<_> = self
+            .node
+            .instructions
+            .iter()
+            .flat_map(|instruction| &instruction.operands)
+            .filter_map(|operand| String::try_from(operand.clone()).ok())
+            .collect();
+        let label_count = self.node.labels.len();
+        self.node
+            .labels
+            .retain(|label, _| used_strings.contains(label));
+        self.node.labels.len() != label_count
+    }
+
+    /// Removes instructions that no path from the start of the node reaches.
+    /// The last instruction is always kept, since it marks where the node ends by itself, see [`OpCode::Stop`].
+    fn remove_unreachable_instructions(&mut self) -> bool {
+        let instruction_count = self.node.instructions.len();
+        let mut reachable = vec![false; instruction_count];
+        if let Some(last) = reachable.last_mut() {
+            *last = true;
+        }
+        let mut queue = VecDeque::from([0]);
+        while let Some(index) = queue.pop_front() {
+            if index >= instruction_count || reachable[index] {
+                continue;
+            }
+            reachable[index] = true;
+            let instruction = &self.node.instructions[index];
+            let jump_destination =
+                || string_operand(instruction, 0).and_then(|label| self.label_position(&label));
+            match OpCode::try_from(instruction.opcode) {
+                Ok(OpCode::JumpTo) => queue.extend(jump_destination()),
+                Ok(OpCode::JumpIfFalse) => {
+                    queue.push_back(index + 1);
+                    queue.extend(jump_destination());
+                }
+                // The label is on the stack, so it could be any of them
+                Ok(OpCode::Jump) => queue.extend(self.label_positions()),
+                Ok(OpCode::Stop | OpCode::Return | OpCode::RunNode) => {}
+                _ => queue.push_back(index + 1),
+            }
+        }
+        let removed: Vec<_> = reachable.iter().map(|reachable| !reachable).collect();
+        self.remove_instructions(&removed)
+    }
+
+    /// Removes the instructions marked in `removed`. Labels pointing to a removed instruction will point to the instruction after it.
+    /// Returns whether any instruction was removed.
+    fn remove_instructions(&mut self, removed: &[bool]) -> bool {
+        if !removed.contains(&true) {
+            return false;
+        }
+        let mut new_indices = Vec::with_capacity(removed.len() + 1);
+        let mut next_index = 0;
+        for &is_removed in removed {
+            new_indices.push(next_index);
+            if !is_removed {
+                next_index += 1;
+            }
+        }
+        new_indices.push(next_index);
+
+        for position in self.node.labels.values_mut() {
+            if let Some(&new_index) = usize::try_from(*position)
+                .ok()
+                .and_then(|position| new_indices.get(position))
+            {
+                *position = new_index;
+            }
+        }
+        let mut is_removed = removed.iter();
+        self.node
+            .instructions
+            .retain(|_| !is_removed.next().unwrap());
+        let mut is_removed = removed.iter();
+        self.positions.retain(|_| !is_removed.next().unwrap());
+        true
+    }
+
+    fn label_position(&self, label: &str) -> Option<usize> {
+        self.node
+            .labels
+            .get(label)
+            .and_then(|&position| usize::try_from(position).ok())
+    }
+
+    fn label_positions(&self) -> HashSet<usize> {
+        self.node
+            .labels
+            .values()
+            .filter_map(|&position| usize::try_from(position).ok())
+            .collect()
+    }
+}
+
+fn string_operand(instruction: &Instruction, index: usize) -> Option<String> {
+    String::try_from(instruction.operands.get(index)?.clone()).ok()
+}
+
+/// Returns the value pushed by the instruction if it pushes a constant.
+fn constant(instruction: &Instruction) -> Option<YarnValue> {
+    let operand = instruction.operands.first()?.clone();
+    match OpCode::try_from(instruction.opcode).ok()? {
+        OpCode::PushString => String::try_from(operand).ok().map(YarnValue::String),
+        OpCode::PushFloat => f32::try_from(operand).ok().map(YarnValue::Number),
+        OpCode::PushBool => bool::try_from(operand).ok().map(YarnValue::Boolean),
+        _ => None,
+    }
+}
+
+fn push(value: YarnValue) -> Instruction {
+    let (opcode, operand) = match value {
+        YarnValue::String(value) => (OpCode::PushString, Operand::from(value)),
+        YarnValue::Number(value) => (OpCode::PushFloat, Operand::from(value)),
+        YarnValue::Boolean(value) => (OpCode::PushBool, Operand::from(value)),
+    };
+    Instruction {
+        opcode: opcode.into(),
+        operands: vec![operand],
+    }
+}
@@ -9,17 +9,21 @@ pub(crate) fn validate_unique_node_names(
     // Ensure that all nodes names in this compilation are unique. Node
     // name uniqueness is important for several processes, so we do this
     // check here.
-    let all_nodes = state.parsed_files.iter().flat_map(|(file, _)| {
-        file.tree
-            .node_all()
-            .iter()
-            .map(|node| (node.clone(), file))
-            .collect::<Vec<_>>()
-    });
+    let all_nodes: Vec<_> = state
+        .parsed_files
+        .iter()
+        .flat_map(|(file, _)| {
+            file.tree
+                .node_all()
+                .iter()
+                .map(|node| (node.clone(), file))
+                .collect::<Vec<_>>()
+        })
+        .collect();
 
     // Pair up every node with its name, and filter out any that don't
     // have a name
-    let nodes_with_names = all_nodes.filter_map(|(node, file)| {
+    let nodes_with_names = all_nodes.iter().filter_map(|(node, file)| {
         node.header_all()
             .iter()
             .find(|header| header.header_key.as_ref().unwrap().get_text() == "title")
@@ -34,7 +38,7 @@ pub(crate) fn validate_unique_node_names(
             })
     });
 
-    let nodes_by_name = nodes_with_names.fold(
+    let nodes_by_name: HashMap<_, Vec<_>> = nodes_with_names.fold(
         HashMap::new(),
         |mut map: HashMap<_, Vec<_>>, (name, header_context, file)| {
             map.entry(name).or_default().push((header_context, file));
@@ -44,10 +48,7 @@ pub(crate) fn validate_unique_node_names(
 
     // Find groups of nodes with the same name and generate diagnostics
     // for each
-    for (name, nodes) in nodes_by_name
-        .into_iter()
-        .filter(|(_, nodes)| nodes.len() > 1)
-    {
+    for (name, nodes) in nodes_by_name.iter().filter(|(_, nodes)| nodes.len() > 1) {
         // More than one node has this name! Report an error on both.
         for (header_context, file) in nodes {
             state.diagnostics.push(
@@ -57,5 +58,56 @@ pub(crate) fn validate_unique_node_names(
             );
         }
     }
+
+    // The nodes of a node group were renamed by the lexer, so they don't clash with each other.
+    // They still must not clash with a node without `when:` headers that has the same title,
+    // since the node that selects one of the group's nodes is named after that title.
+    let node_groups = all_nodes.iter().flat_map(|(node, file)| {
+        node.header_all()
+            .iter()
+            .filter(|header| {
+                header.header_key.as_ref().unwrap().get_text() == Node::NODE_GROUP_HEADER
+            })
+            .map(|header| {
+                let name = header.header_value.as_ref().unwrap().get_text().to_owned();
+                (name, header.clone(), file)
+            })
+            .collect::<Vec<_>>()
+    });
+    let node_groups_by_name = node_groups.fold(
+        HashMap::new(),
+        |mut map: HashMap<_, Vec<_>>, (name, header_context, file)| {
+            map.entry(name).or_default().push((header_context, file));
+            map
+        },
+    );
+    for (name, members) in node_groups_by_name {
+        if let Some(nodes) = nodes_by_name.get(&name) {
+            for (header_context, file) in nodes {
+                state.diagnostics.push(
+                    Diagnostic::from_message(format!(
+                        "All nodes named {name} must have a when: header, since some of them do",
+                    ))
+                    .with_file_name(file.name.clone())
+                    .with_parser_context(header_context.as_ref(), file.tokens()),
+                );
+            }
+        }
+        let first_file_name = &members[0].1.name;
+        if members
+            .iter()
+            .any(|(_, file)| &file.name != first_file_name)
+        {
+            for (header_context, file) in &members {
+                state.diagnostics.push(
+                    Diagnostic::from_message(format!(
+                        "All nodes named {name} must be in the same file, since they have when: headers",
+                    ))
+                    .with_file_name(file.name.clone())
+                    .with_parser_context(header_context.as_ref(), file.tokens()),
+                );
+            }
+        }
+    }
     state
 }
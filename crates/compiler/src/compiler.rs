@@ -2,11 +2,12 @@
 //! and <https://github.com/YarnSpinnerTool/YarnSpinner/blob/da39c7195107d8211f21c263e4084f773b84eaff/YarnSpinner.Compiler/CompilationJob.cs>
 
 use crate::prelude::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use yarnspinner_core::prelude::*;
 
 mod add_tags_to_lines;
 pub(crate) mod antlr_rust_ext;
+mod format;
 pub(crate) mod run_compilation;
 pub(crate) mod utils;
 
@@ -38,11 +39,23 @@ pub struct Compiler {
     #[cfg_attr(feature = "serde", serde(skip))]
     pub library: Library,
 
+    /// The [`CommandLibrary`] that contains declarations for the commands the game provides.
+    ///
+    /// If this is [`None`], which is the default, commands are not checked at all, since the compiler cannot know which commands exist.
+    /// Otherwise, calling an unknown command, passing a wrong number of parameters or passing a literal that can never be converted to the declared parameter type are errors.
+    #[cfg_attr(feature = "bevy", reflect(ignore))]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub command_library: Option<CommandLibrary>,
+
     /// The types of compilation that the compiler will do.
     pub compilation_type: CompilationType,
 
     /// The declarations for variables.
     pub variable_declarations: Vec<Declaration>,
+
+    /// Whether the generated code is optimized, e.g. by evaluating constant expressions at compile time and removing code that can never run.
+    /// The optimized program behaves the same, but is smaller and faster. By default, this is `false`.
+    pub optimize: bool,
 }
 
 impl Compiler {
@@ -79,18 +92,54 @@ impl Compiler {
         self.try_read_file(file_path).unwrap()
     }
 
+    /// Returns the paths of all `.yarn` files in the directory and its subdirectories, sorted.
+    /// Hidden files and directories, i.e. the ones whose name starts with a `.`, and `target` directories are skipped.
+    pub fn find_yarn_files(directory: impl AsRef<Path>) -> std::io::Result<Vec<PathBuf>> {
+        Self::find_files(directory, |path| {
+            path.extension()
+                .is_some_and(|extension| extension == "yarn")
+        })
+    }
+
+    /// Like [`Compiler::find_yarn_files`], but returns the files for which `is_match` returns `true`,
+    /// e.g. to find other files belonging to the Yarn project such as translations.
+    pub fn find_files(
+        directory: impl AsRef<Path>,
+        is_match: impl Fn(&Path) -> bool,
+    ) -> std::io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        collect_files(directory.as_ref(), &is_match, &mut files)?;
+        files.sort();
+        Ok(files)
+    }
+
     /// Extends the Yarn function library with the given [`Library`]. The standard library is only added if this is called with [`Library::standard_library`].
     pub fn extend_library(&mut self, library: Library) -> &mut Self {
         self.library.extend(library);
         self
     }
 
+    /// Extends the declarations of the commands the game provides with the given [`CommandLibrary`].
+    /// From then on, all command statements are checked against it. See [`Compiler::command_library`].
+    pub fn extend_command_library(&mut self, command_library: CommandLibrary) -> &mut Self {
+        self.command_library
+            .get_or_insert_with(CommandLibrary::default)
+            .import(command_library);
+        self
+    }
+
     /// Sets the compilation type, which allows premature stopping of the compilation process. By default, this is [`CompilationType::FullCompilation`].
     pub fn with_compilation_type(&mut self, compilation_type: CompilationType) -> &mut Self {
         self.compilation_type = compilation_type;
         self
     }
 
+    /// Sets whether the generated code is optimized. See [`Compiler::optimize`].
+    pub fn with_optimization(&mut self, optimize: bool) -> &mut Self {
+        self.optimize = optimize;
+        self
+    }
+
     /// Adds a variable declaration to the compilation.
     pub fn declare_variable(&mut self, declaration: Declaration) -> &mut Self {
         self.variable_declarations.push(declaration);
@@ -103,6 +152,28 @@ impl Compiler {
     }
 }
 
+fn collect_files(
+    directory: &Path,
+    is_match: &impl Fn(&Path) -> bool,
+    files: &mut Vec<PathBuf>,
+) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        let is_hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if is_hidden || path.ends_with("target") {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(&path, is_match, files)?;
+        } else if is_match(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
 /// Represents the contents of a file to compile.
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 #[cfg_attr(feature = "bevy", derive(Reflect))]
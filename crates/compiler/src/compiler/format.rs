@@ -0,0 +1,678 @@
+use super::utils::parse_syntax_tree;
+use crate::prelude::generated::yarnspinnerlexer::{
+    self, YarnSpinnerLexer as GeneratedYarnSpinnerLexer,
+};
+use crate::prelude::*;
+use antlr_rust::input_stream::CodePoint32BitCharStream;
+use antlr_rust::token::{Token, TOKEN_EOF};
+use antlr_rust::TokenSource;
+
+/// The whitespace used for each level of indentation in formatted source code.
+const INDENTATION: &str = "    ";
+
+impl Compiler {
+    /// Given Yarn source code, returns the same source code in a canonical style.
+    ///
+    /// The formatting applies the following rules:
+    /// - The `title` header comes first, followed by the other headers sorted by name.
+    /// - Nodes are separated by exactly one blank line, and runs of blank lines inside a node are collapsed to one.
+    /// - The contents of options, line groups, `<<if>>`, `<<once>>` and `<<enum>>` blocks are indented by four spaces per level.
+    /// - Operators, commas and keywords inside commands and expressions are separated by single spaces.
+    /// - Hashtags follow the rest of the line, with the `#line:` tag being the last one.
+    ///
+    /// Comments are preserved, the text of lines and custom commands is left as written,
+    /// and the bodies of nodes tagged with `rawText` are not touched at all.
+    /// Formatting already formatted source code returns it unchanged.
+    ///
+    /// ## Return value
+    /// Returns the formatted source code, or the diagnostics if the source code could not be parsed.
+    ///
+    /// ## Implementation notes
+    ///
+    /// The source is checked with the same parse used for compilation, but the formatting itself works on the tokens
+    /// of the generated lexer, since the indentation aware lexer wrapping it rewrites tokens in ways that lose parts of the source,
+    /// such as the `<<once>>` keyword.
+    pub fn format(contents: impl Into<String>) -> crate::Result<String> {
+        let contents = contents.into();
+        let chars: Vec<_> = contents.chars().map(|c| c as u32).collect();
+        let file = File {
+            file_name: "<input>".to_string(),
+            source: contents,
+        };
+        let mut diagnostics = Vec::new();
+        parse_syntax_tree(&file, &chars, &mut diagnostics);
+        if diagnostics.has_errors() {
+            // We are not confident that we understand the structure of the source code well enough to change it.
+            return Err(CompilerError(diagnostics));
+        }
+
+        let lines = split_into_lines(read_tokens(&chars));
+        let mut formatter = Formatter::default();
+        formatter.format_file(&lines, &file.source);
+        Ok(formatter.finish())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SourceToken {
+    token_type: isize,
+    /// The source code of the token, including any characters the lexer skipped right before it,
+    /// such as the backslash of an escaped character.
+    text: String,
+}
+
+impl SourceToken {
+    fn is(&self, token_type: isize) -> bool {
+        self.token_type == token_type
+    }
+
+    fn is_comment(&self) -> bool {
+        [
+            yarnspinnerlexer::COMMENT,
+            yarnspinnerlexer::TEXT_COMMENT,
+            yarnspinnerlexer::TEXT_COMMANDHASHTAG_COMMENT,
+        ]
+        .contains(&self.token_type)
+    }
+}
+
+/// A line of source code, without its indentation and whitespace tokens.
+#[derive(Debug, Clone, Default)]
+struct SourceLine {
+    /// The zero-based index of the line in the source code.
+    index: usize,
+    /// The width of the indentation, counting tabs as eight spaces like the lexer does.
+    indentation: usize,
+    tokens: Vec<SourceToken>,
+}
+
+impl SourceLine {
+    fn is_blank(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    fn is_comment(&self) -> bool {
+        self.tokens.len() == 1 && self.tokens[0].is_comment()
+    }
+
+    fn starts_with(&self, token_type: isize) -> bool {
+        self.tokens
+            .first()
+            .is_some_and(|token| token.is(token_type))
+    }
+
+    fn comment(&self) -> Option<String> {
+        self.tokens
+            .iter()
+            .find(|token| token.is_comment())
+            .map(|token| token.text.trim().to_owned())
+    }
+}
+
+fn read_tokens(chars: &[u32]) -> Vec<SourceToken> {
+    let input = CodePoint32BitCharStream::new(chars);
+    let mut lexer = GeneratedYarnSpinnerLexer::new(input);
+    let mut tokens = Vec::new();
+    let mut next_start = 0;
+    loop {
+        let token = lexer.next_token();
+        if token.get_token_type() == TOKEN_EOF {
+            break;
+        }
+        let stop = usize::try_from(token.get_stop() + 1).unwrap_or_default();
+        let text = chars[next_start.min(stop)..stop]
+            .iter()
+            .filter_map(|&c| char::from_u32(c))
+            .collect();
+        next_start = next_start.max(stop);
+        tokens.push(SourceToken {
+            token_type: token.get_token_type(),
+            text,
+        });
+    }
+    tokens
+}
+
+fn split_into_lines(tokens: Vec<SourceToken>) -> Vec<SourceLine> {
+    let mut lines = vec![SourceLine::default()];
+    for token in tokens {
+        let line = lines.last_mut().unwrap();
+        if token.is(yarnspinnerlexer::NEWLINE) {
+            // The newline token also contains the indentation of the next line and any blank lines in between
+            let (blank_lines, indentation) = token.text.rsplit_once('\n').unwrap_or_default();
+            let previous_index = line.index;
+            let index = previous_index + 1 + blank_lines.matches('\n').count();
+            lines.extend((previous_index + 1..index).map(|index| SourceLine {
+                index,
+                ..Default::default()
+            }));
+            lines.push(SourceLine {
+                index,
+                indentation: indentation_width(indentation),
+                tokens: Vec::new(),
+            });
+        } else if is_whitespace(&token) {
+            if line.tokens.is_empty() {
+                line.indentation += indentation_width(&token.text);
+            }
+        } else {
+            line.tokens.push(token);
+        }
+    }
+    lines
+}
+
+fn is_whitespace(token: &SourceToken) -> bool {
+    [
+        yarnspinnerlexer::WS,
+        yarnspinnerlexer::BODY_WS,
+        yarnspinnerlexer::EXPR_WS,
+        yarnspinnerlexer::HASHTAG_WS,
+        yarnspinnerlexer::COMMAND_WS,
+        yarnspinnerlexer::TEXT_COMMANDHASHTAG_WS,
+    ]
+    .contains(&token.token_type)
+}
+
+fn indentation_width(whitespace: &str) -> usize {
+    whitespace
+        .chars()
+        .map(|c| match c {
+            '\t' => 8,
+            ' ' => 1,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Collects the formatted lines of a file.
+#[derive(Debug, Default)]
+struct Formatter {
+    output: Vec<String>,
+    /// The depth of the blank line that should be written before the next line, if any.
+    pending_blank_line: Option<usize>,
+    /// The blocks that contain the current line of a node body, innermost last.
+    blocks: Vec<Block>,
+    /// The indentation of the previous line in the source code.
+    last_indentation: usize,
+    /// Whether the previous line was an option or line group item.
+    follows_option: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    /// An `<<if>>`, `<<once>>` or `<<enum>>` block.
+    Command,
+    /// The contents of an option or line group item, indented at least by the given width in the source code.
+    Option { indentation: usize },
+}
+
+/// How a statement changes the nesting of the statements that follow it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StatementKind {
+    Plain,
+    Option,
+    OpenBlock,
+    /// `<<elseif>>` and `<<else>>`.
+    ContinueBlock,
+    CloseBlock,
+}
+
+#[derive(Debug, Clone)]
+struct Statement {
+    text: String,
+    kind: StatementKind,
+}
+
+impl Formatter {
+    fn format_file(&mut self, lines: &[SourceLine], source: &str) {
+        let mut index = 0;
+        while let Some(line) = lines.get(index) {
+            if line.is_blank() {
+                self.pending_blank_line = Some(0);
+            } else if line.starts_with(yarnspinnerlexer::HASHTAG) {
+                let tag = line
+                    .tokens
+                    .iter()
+                    .filter(|token| token.is(yarnspinnerlexer::HASHTAG_TEXT))
+                    .map(|token| token.text.trim())
+                    .collect::<String>();
+                let comment = line.comment();
+                self.push_line(0, join([Some(format!("#{tag}")), comment]));
+            } else if line.is_comment() {
+                self.push_line(0, line.comment().unwrap());
+            } else {
+                index = self.format_node(lines, index, source);
+                // The next node or comment after this node is always separated from it
+                self.pending_blank_line = Some(0);
+            }
+            index += 1;
+        }
+    }
+
+    /// Formats the node starting at the given line and returns the index of its last line.
+    fn format_node(&mut self, lines: &[SourceLine], start: usize, source: &str) -> usize {
+        // Comments above a header belong to that header
+        let mut headers = Vec::new();
+        let mut comments = Vec::new();
+        let mut index = start;
+        while let Some(line) = lines.get(index) {
+            if line.starts_with(yarnspinnerlexer::BODY_START) {
+                break;
+            } else if line.is_comment() {
+                comments.push(line.comment().unwrap());
+            } else if line.starts_with(yarnspinnerlexer::ID) {
+                let (key, header) = format_header(line);
+                headers.push((key, std::mem::take(&mut comments), header));
+            }
+            index += 1;
+        }
+        // Sorting is stable, so repeated headers keep their order
+        headers.sort_by(|(a, ..), (b, ..)| (a != "title", a).cmp(&(b != "title", b)));
+        let is_raw_text = headers.iter().any(|(key, _, header)| {
+            key == "tags"
+                && header
+                    .split_once(':')
+                    .is_some_and(|(_, tags)| tags.split_whitespace().any(|tag| tag == "rawText"))
+        });
+        for (_, header_comments, header) in headers {
+            for comment in header_comments {
+                self.push_line(0, comment);
+            }
+            self.push_line(0, header);
+        }
+        for comment in comments {
+            self.push_line(0, comment);
+        }
+        self.pending_blank_line = None;
+        if let Some(line) = lines.get(index) {
+            self.push_line(0, join([Some("---".to_owned()), line.comment()]));
+        }
+        index += 1;
+
+        let body_end = lines[index.min(lines.len())..]
+            .iter()
+            .position(|line| line.starts_with(yarnspinnerlexer::BODY_END))
+            .map_or(lines.len(), |position| index + position);
+        let body = &lines[index.min(body_end)..body_end];
+        if is_raw_text {
+            // The text of raw text nodes is used exactly as written
+            let source_lines: Vec<_> = source.lines().collect();
+            for line in body {
+                let text = source_lines.get(line.index).copied().unwrap_or_default();
+                self.output.push(text.to_owned());
+            }
+        } else {
+            self.format_body(body);
+        }
+        if let Some(line) = lines.get(body_end) {
+            self.pending_blank_line = None;
+            self.push_line(0, join([Some("===".to_owned()), line.comment()]));
+        }
+        body_end
+    }
+
+    fn format_body(&mut self, body: &[SourceLine]) {
+        self.blocks.clear();
+        self.last_indentation = 0;
+        self.follows_option = false;
+        let mut has_content = false;
+        for line in body {
+            self.track_option_indentation(line.indentation);
+            if line.is_blank() {
+                // Blank lines take part in the indentation of options, so one that is still part of an option must stay indented
+                let is_in_option = self
+                    .blocks
+                    .iter()
+                    .any(|block| matches!(block, Block::Option { .. }));
+                let depth = if is_in_option { self.blocks.len() } else { 0 };
+                self.pending_blank_line = has_content.then_some(depth);
+                continue;
+            }
+            let statement = if line.is_comment() {
+                Statement {
+                    text: line.comment().unwrap(),
+                    kind: StatementKind::Plain,
+                }
+            } else {
+                format_statement(&line.tokens)
+            };
+            let depth = self.statement_depth(statement.kind);
+            self.push_line(depth, statement.text);
+            has_content = true;
+        }
+        self.pending_blank_line = None;
+    }
+
+    /// Opens and closes the contents of options the same way the lexer does, given the indentation of the next line.
+    ///
+    /// The contents of an option start if the line following it is indented further than it,
+    /// and end at the first line that is indented less than that line, including blank lines.
+    fn track_option_indentation(&mut self, indentation: usize) {
+        if std::mem::take(&mut self.follows_option) && indentation > self.last_indentation {
+            self.blocks.push(Block::Option { indentation });
+        }
+        while let Some(&Block::Option {
+            indentation: option_indentation,
+        }) = self.blocks.last()
+        {
+            if indentation >= option_indentation {
+                break;
+            }
+            self.blocks.pop();
+        }
+        self.last_indentation = indentation;
+    }
+
+    /// Returns how deeply a statement is nested and updates the blocks for the statements following it.
+    fn statement_depth(&mut self, kind: StatementKind) -> usize {
+        let depth = self.blocks.len();
+        match kind {
+            StatementKind::Plain => depth,
+            StatementKind::Option => {
+                self.follows_option = true;
+                depth
+            }
+            StatementKind::OpenBlock => {
+                self.blocks.push(Block::Command);
+                depth
+            }
+            StatementKind::ContinueBlock | StatementKind::CloseBlock => {
+                while matches!(self.blocks.last(), Some(Block::Option { .. })) {
+                    self.blocks.pop();
+                }
+                let depth = self.blocks.len().saturating_sub(1);
+                if kind == StatementKind::CloseBlock {
+                    self.blocks.pop();
+                }
+                depth
+            }
+        }
+    }
+
+    fn push_line(&mut self, depth: usize, text: String) {
+        if let Some(blank_line_depth) = self.pending_blank_line.take() {
+            if !self.output.is_empty() {
+                self.output.push(INDENTATION.repeat(blank_line_depth));
+            }
+        }
+        self.output
+            .push(format!("{}{text}", INDENTATION.repeat(depth)));
+    }
+
+    fn finish(self) -> String {
+        let mut text = self.output.join("\n");
+        text.push('\n');
+        text
+    }
+}
+
+/// Returns the name of the header and the formatted header line.
+fn format_header(line: &SourceLine) -> (String, String) {
+    let key = line.tokens[0].text.trim().to_owned();
+    let value = line
+        .tokens
+        .iter()
+        .find(|token| token.is(yarnspinnerlexer::REST_OF_LINE))
+        .map(|token| token.text.trim())
+        .unwrap_or_default();
+    let header = if value.is_empty() {
+        format!("{key}:")
+    } else {
+        format!("{key}: {value}")
+    };
+    (key, header)
+}
+
+fn format_statement(tokens: &[SourceToken]) -> Statement {
+    let mut kind = StatementKind::Plain;
+    let mut prefix = "";
+    let mut text = String::new();
+    let mut commands = Vec::new();
+    let mut tags = Vec::new();
+    let mut comment = None;
+
+    let mut index = 0;
+    while let Some(token) = tokens.get(index) {
+        match token.token_type {
+            yarnspinnerlexer::SHORTCUT_ARROW => {
+                prefix = "-> ";
+                kind = StatementKind::Option;
+            }
+            yarnspinnerlexer::EXPRESSION_START => {
+                let end = find_token(tokens, index, &[yarnspinnerlexer::EXPRESSION_END]);
+                text.push_str(&format_inline_expression(&tokens[index..end]));
+                index = end;
+            }
+            yarnspinnerlexer::COMMAND_START => {
+                let end = find_token(
+                    tokens,
+                    index,
+                    &[
+                        yarnspinnerlexer::COMMAND_END,
+                        yarnspinnerlexer::COMMAND_TEXT_END,
+                    ],
+                );
+                commands.push(format_command(&tokens[index + 1..end]));
+                index = end;
+            }
+            yarnspinnerlexer::HASHTAG => {
+                let tag = tokens
+                    .get(index + 1)
+                    .filter(|token| token.is(yarnspinnerlexer::HASHTAG_TEXT));
+                if tag.is_some() {
+                    index += 1;
+                }
+                tags.push(format!(
+                    "#{}",
+                    tag.map(|tag| tag.text.trim()).unwrap_or_default()
+                ));
+            }
+            _ if token.is_comment() => comment = Some(token.text.trim().to_owned()),
+            _ => text.push_str(&token.text),
+        }
+        index += 1;
+    }
+
+    let mut text = trim_unescaped(&text).to_owned();
+    if kind == StatementKind::Plain && text.starts_with("=>") {
+        // The generated lexer does not know about line groups, so their marker is part of the text
+        prefix = "=> ";
+        text = text["=>".len()..].trim_start().to_owned();
+        kind = StatementKind::Option;
+    }
+    let content = if prefix.is_empty() && text.is_empty() && !commands.is_empty() {
+        let (command, command_kind) = commands.remove(0);
+        kind = command_kind;
+        command
+    } else {
+        format!("{prefix}{text}")
+    };
+    // The line ID is the last tag, like when it is added by `Compiler::add_tags_to_lines`
+    tags.sort_by_key(|tag| tag.starts_with("#line:"));
+    let commands = commands.into_iter().map(|(command, _)| Some(command));
+    let tags = tags.into_iter().map(Some);
+    let text = join(
+        std::iter::once(Some(content))
+            .chain(commands)
+            .chain(tags)
+            .chain([comment]),
+    );
+    Statement { text, kind }
+}
+
+/// Returns the index of the first token after `start` with one of the given types, or the index of the last token if there is none.
+fn find_token(tokens: &[SourceToken], start: usize, token_types: &[isize]) -> usize {
+    tokens
+        .iter()
+        .skip(start + 1)
+        .position(|token| token_types.contains(&token.token_type))
+        .map_or(tokens.len() - 1, |position| start + 1 + position)
+}
+
+/// Formats the tokens between `<<` and `>>` and returns the formatted command and how it affects nesting.
+fn format_command(tokens: &[SourceToken]) -> (String, StatementKind) {
+    let keyword = tokens
+        .first()
+        .filter(|token| is_command_keyword(token))
+        .map(|token| token.text.trim());
+    let rest = if keyword.is_some() {
+        &tokens[1..]
+    } else {
+        tokens
+    };
+    let is_text_command = rest
+        .iter()
+        .any(|token| token.is(yarnspinnerlexer::COMMAND_TEXT))
+        || keyword.is_none();
+    let body = if is_text_command {
+        let mut text = String::new();
+        let mut index = 0;
+        while let Some(token) = rest.get(index) {
+            if token.is(yarnspinnerlexer::COMMAND_EXPRESSION_START) {
+                let end = find_token(rest, index, &[yarnspinnerlexer::EXPRESSION_END]);
+                text.push_str(&format_inline_expression(&rest[index..end]));
+                index = end;
+            } else {
+                text.push_str(&token.text);
+            }
+            index += 1;
+        }
+        let text = text.trim();
+        // <<detour>> has no token of its own, but unlike custom commands its syntax is known
+        match text.strip_prefix("detour") {
+            Some(destination) if destination.starts_with(char::is_whitespace) => {
+                format!("detour {}", destination.trim_start())
+            }
+            _ => text.to_owned(),
+        }
+    } else {
+        format_expression(rest)
+    };
+
+    let first_word = keyword.or_else(|| body.split_whitespace().next());
+    let kind = match first_word {
+        Some("if" | "once" | "enum") => StatementKind::OpenBlock,
+        Some("elseif" | "else") => StatementKind::ContinueBlock,
+        Some("endif" | "endonce" | "endenum") => StatementKind::CloseBlock,
+        _ => StatementKind::Plain,
+    };
+    let command = join([keyword.map(ToOwned::to_owned), Some(body)]);
+    (format!("<<{command}>>"), kind)
+}
+
+fn is_command_keyword(token: &SourceToken) -> bool {
+    [
+        yarnspinnerlexer::COMMAND_IF,
+        yarnspinnerlexer::COMMAND_ELSEIF,
+        yarnspinnerlexer::COMMAND_ELSE,
+        yarnspinnerlexer::COMMAND_SET,
+        yarnspinnerlexer::COMMAND_ENDIF,
+        yarnspinnerlexer::COMMAND_CALL,
+        yarnspinnerlexer::COMMAND_DECLARE,
+        yarnspinnerlexer::COMMAND_JUMP,
+        yarnspinnerlexer::COMMAND_ENUM,
+        yarnspinnerlexer::COMMAND_CASE,
+        yarnspinnerlexer::COMMAND_ENDENUM,
+        yarnspinnerlexer::COMMAND_LOCAL,
+    ]
+    .contains(&token.token_type)
+}
+
+/// Formats an expression in braces, given the tokens from the opening brace up to and including the closing one.
+fn format_inline_expression(tokens: &[SourceToken]) -> String {
+    let inner = match tokens.last() {
+        Some(last) if tokens.len() > 1 && last.is(yarnspinnerlexer::EXPRESSION_END) => {
+            &tokens[1..tokens.len() - 1]
+        }
+        _ => tokens.get(1..).unwrap_or_default(),
+    };
+    format!("{{{}}}", format_expression(inner))
+}
+
+/// Formats an expression with single spaces around binary operators and after commas.
+fn format_expression(tokens: &[SourceToken]) -> String {
+    let mut result = String::new();
+    let mut previous: Option<&SourceToken> = None;
+    let mut previous_is_unary = false;
+    for token in tokens.iter().filter(|token| !is_whitespace(token)) {
+        let follows_operand = previous.is_some_and(is_operand);
+        if let Some(previous) = previous {
+            if needs_space_between(previous, previous_is_unary, token) {
+                result.push(' ');
+            }
+        }
+        previous_is_unary = match token.token_type {
+            yarnspinnerlexer::OPERATOR_MATHS_SUBTRACTION => !follows_operand,
+            yarnspinnerlexer::OPERATOR_LOGICAL_NOT => token.text.trim() == "!",
+            _ => false,
+        };
+        result.push_str(token.text.trim());
+        previous = Some(token);
+    }
+    result
+}
+
+fn needs_space_between(
+    previous: &SourceToken,
+    previous_is_unary: bool,
+    next: &SourceToken,
+) -> bool {
+    let after_opening = [
+        yarnspinnerlexer::LPAREN,
+        yarnspinnerlexer::EXPRESSION_START,
+        yarnspinnerlexer::COMMAND_EXPRESSION_START,
+        yarnspinnerlexer::DOT,
+    ]
+    .contains(&previous.token_type);
+    let before_closing = [
+        yarnspinnerlexer::RPAREN,
+        yarnspinnerlexer::COMMA,
+        yarnspinnerlexer::EXPRESSION_END,
+    ]
+    .contains(&next.token_type);
+    let is_call = previous.is(yarnspinnerlexer::FUNC_ID) && next.is(yarnspinnerlexer::LPAREN);
+    // `Food.Apple`, as opposed to `== .Apple`
+    let is_member_access = next.is(yarnspinnerlexer::DOT) && is_operand(previous);
+    !(after_opening || before_closing || is_call || is_member_access || previous_is_unary)
+}
+
+fn is_operand(token: &SourceToken) -> bool {
+    [
+        yarnspinnerlexer::ID,
+        yarnspinnerlexer::FUNC_ID,
+        yarnspinnerlexer::VAR_ID,
+        yarnspinnerlexer::NUMBER,
+        yarnspinnerlexer::STRING,
+        yarnspinnerlexer::KEYWORD_TRUE,
+        yarnspinnerlexer::KEYWORD_FALSE,
+        yarnspinnerlexer::KEYWORD_NULL,
+        yarnspinnerlexer::RPAREN,
+        yarnspinnerlexer::EXPRESSION_END,
+    ]
+    .contains(&token.token_type)
+}
+
+/// Trims whitespace from both ends of a text, except for whitespace that is escaped with a backslash.
+fn trim_unescaped(text: &str) -> &str {
+    let text = text.trim_start();
+    let mut end = text.len();
+    for (index, character) in text.char_indices().rev() {
+        let is_escaped = text[..index].ends_with('\\') && !text[..index].ends_with("\\\\");
+        if !character.is_whitespace() || is_escaped {
+            break;
+        }
+        end = index;
+    }
+    &text[..end]
+}
+
+/// Joins the present parts with single spaces, ignoring empty ones.
+fn join(parts: impl IntoIterator<Item = Option<String>>) -> String {
+    parts
+        .into_iter()
+        .flatten()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
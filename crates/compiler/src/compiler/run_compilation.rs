@@ -5,6 +5,7 @@ use crate::string_table_manager::StringTableManager;
 use crate::visitors::*;
 use crate::Result;
 use std::collections::{HashMap, HashSet};
+use yarnspinner_core::types::EnumType;
 
 /// Compile Yarn code, as specified by a compilation job.
 pub(crate) fn compile(compiler: &Compiler) -> Result<Compilation> {
@@ -14,14 +15,19 @@ pub(crate) fn compile(compiler: &Compiler) -> Result<Compilation> {
         &register_strings,
         &validate_unique_node_names,
         &break_on_job_with_only_strings,
+        &get_enum_declarations,
         &get_declarations,
+        &check_smart_variable_cycles,
         &check_types,
         &find_tracking_nodes,
         &create_declarations_for_tracking_nodes,
         &add_tracking_declarations,
+        &find_once_variables,
+        &add_once_declarations,
         &resolve_deferred_type_diagnostic,
         &break_on_job_with_only_declarations,
         &generate_code,
+        &optimize_code,
         &add_initial_value_registrations,
     ];
 
@@ -65,9 +71,15 @@ pub(crate) struct CompilationIntermediate<'input> {
     pub(crate) known_variable_declarations: Vec<Declaration>,
     /// All variable declarations that we've encountered during this compilation job
     pub(crate) derived_variable_declarations: Vec<Declaration>,
+    /// All enums declared in the files of this compilation job
+    pub(crate) known_enums: Vec<EnumType>,
     pub(crate) potential_issues: Vec<DeferredTypeDiagnostic>,
     pub(crate) parsed_files: Vec<(FileParseResult<'input>, KnownTypes)>,
     pub(crate) tracking_nodes: HashSet<String>,
+    /// The variables that track whether `<<once>>` blocks and `#once` lines were already run
+    pub(crate) once_variables: HashSet<String>,
+    /// The smart variables declared in the files of this compilation job
+    pub(crate) smart_variables: Vec<SmartVariable>,
     pub(crate) string_table: StringTableManager,
     pub(crate) diagnostics: Vec<Diagnostic>,
     pub(crate) file_tags: HashMap<String, Vec<String>>,
@@ -82,9 +94,12 @@ impl<'input> CompilationIntermediate<'input> {
             result: Default::default(),
             known_variable_declarations: Default::default(),
             derived_variable_declarations: Default::default(),
+            known_enums: Default::default(),
             potential_issues: Default::default(),
             parsed_files: Default::default(),
             tracking_nodes: Default::default(),
+            once_variables: Default::default(),
+            smart_variables: Default::default(),
             string_table: Default::default(),
             diagnostics: Default::default(),
             file_tags: Default::default(),
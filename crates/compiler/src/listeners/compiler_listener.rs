@@ -3,19 +3,28 @@
 use crate::prelude::*;
 use antlr_rust::parser_rule_context::ParserRuleContext;
 use antlr_rust::token::Token;
-use antlr_rust::tree::{ParseTreeListener, ParseTreeVisitorCompat};
+use antlr_rust::tree::{ParseTree, ParseTreeListener, ParseTreeVisitorCompat};
 use std::cell::RefCell;
 use std::collections::HashSet;
+use std::ops::Deref;
 use std::rc::Rc;
 use yarnspinner_core::prelude::*;
 
 mod emit;
 use crate::parser::generated::yarnspinnerparser::{
-    BodyContext, HeaderContext, NodeContext, YarnSpinnerParserContextType,
+    BodyContext, Declare_statementContext, DialogueContext, ExpressionContextAll, HeaderContext,
+    NodeContext, YarnSpinnerParserContextType,
+};
+use crate::parser_rule_context_ext::ParserRuleContextExt;
+use crate::prelude::generated::yarnspinnerparser::{
+    BodyContextAttrs, Declare_statementContextAttrs, If_clauseContextAttrs,
+    If_statementContextAttrs, StatementContextAttrs,
 };
-use crate::prelude::generated::yarnspinnerparser::BodyContextAttrs;
 use crate::prelude::generated::yarnspinnerparserlistener::YarnSpinnerParserListener;
-use crate::visitors::{CodeGenerationVisitor, KnownTypes};
+use crate::visitors::{
+    get_complexity_score, is_when_clause, smart_variable_expression, CodeGenerationVisitor,
+    KnownTypes,
+};
 pub(crate) use emit::*;
 use yarnspinner_core::prelude::OpCode;
 
@@ -25,6 +34,8 @@ pub(crate) struct CompilerListener<'input> {
     pub(crate) program: Rc<RefCell<Program>>,
     /// the list of nodes we have to ensure we track visitation
     pub(crate) tracking_nodes: Rc<RefCell<HashSet<String>>>,
+    /// The declarations of all variables, used to tell locals and smart variables apart from the others.
+    variable_declarations: Vec<Declaration>,
     pub(crate) diagnostics: Rc<RefCell<Vec<Diagnostic>>>,
     pub(crate) types: KnownTypes,
     /// The current node to which instructions are being added.
@@ -34,13 +45,30 @@ pub(crate) struct CompilerListener<'input> {
     /// Whether we are currently parsing the
     /// current node as a 'raw text' node, or as a fully syntactic node.
     is_current_node_raw_text: bool,
-    file: FileParseResult<'input>,
+    pub(crate) file: FileParseResult<'input>,
     label_count: usize,
+    /// The node groups found so far, i.e. nodes that share a title and have `when:` headers.
+    node_groups: Vec<NodeGroup<'input>>,
+    /// The smart variables found so far, together with the expressions that compute their values.
+    smart_variables: Vec<(String, Rc<ExpressionContextAll<'input>>)>,
+}
+
+/// The nodes of a node group, which are selected by a generated node that is named after the group.
+struct NodeGroup<'input> {
+    name: String,
+    members: Vec<NodeGroupMember<'input>>,
+}
+
+struct NodeGroupMember<'input> {
+    name: String,
+    /// The conditions of the member's `when:` headers.
+    conditions: Vec<Rc<ExpressionContextAll<'input>>>,
 }
 
 impl<'input> CompilerListener<'input> {
     pub(crate) fn new(
         tracking_nodes: HashSet<String>,
+        variable_declarations: Vec<Declaration>,
         types: KnownTypes,
         file: FileParseResult<'input>,
     ) -> Self {
@@ -48,6 +76,7 @@ impl<'input> CompilerListener<'input> {
             file,
             types,
             tracking_nodes: Rc::new(RefCell::new(tracking_nodes)),
+            variable_declarations,
             current_node: Default::default(),
             current_debug_info: Default::default(),
             is_current_node_raw_text: Default::default(),
@@ -55,6 +84,8 @@ impl<'input> CompilerListener<'input> {
             program: Default::default(),
             label_count: Default::default(),
             debug_infos: Default::default(),
+            node_groups: Default::default(),
+            smart_variables: Default::default(),
         }
     }
 
@@ -68,11 +99,218 @@ impl<'input> CompilerListener<'input> {
         self.label_count += 1;
         label
     }
+
+    /// Returns the name under which a variable is stored, which differs from
+    /// the name used in the source if the variable is a local of the current node.
+    pub(crate) fn resolve_variable_name(&self, variable_name: &str) -> String {
+        let node_name = &self.current_node.as_ref().unwrap().name;
+        let local_name = Library::generate_local_variable_name(node_name, variable_name);
+        if self
+            .variable_declarations
+            .iter()
+            .any(|decl| decl.name == local_name)
+        {
+            local_name
+        } else {
+            variable_name.to_owned()
+        }
+    }
+
+    /// Initializes the locals of the current node when it starts,
+    /// so that they have a value no matter where in the node they are declared.
+    fn emit_local_variable_initializers(&mut self) {
+        let node_name = self.current_node.as_ref().unwrap().name.clone();
+        let locals: Vec<_> = self
+            .variable_declarations
+            .iter()
+            .filter(|decl| Library::is_local_variable_name(&decl.name))
+            .filter(|decl| decl.source_node_name.as_ref() == Some(&node_name))
+            .cloned()
+            .collect();
+        for local in locals {
+            // Locals are required to have a constant value, so there's always a default value
+            let push = match local.default_value.unwrap() {
+                YarnValue::Number(value) => {
+                    Emit::from_op_code(OpCode::PushFloat).with_operand(value)
+                }
+                YarnValue::String(value) => {
+                    Emit::from_op_code(OpCode::PushString).with_operand(value)
+                }
+                YarnValue::Boolean(value) => {
+                    Emit::from_op_code(OpCode::PushBool).with_operand(value)
+                }
+            };
+            let store = Emit::from_op_code(OpCode::StoreVariable).with_operand(local.name);
+            for emit in [push, store, Emit::from_op_code(OpCode::Pop)] {
+                match &local.range {
+                    Some(range) => self.emit(emit.with_source(range.start)),
+                    None => self.emit(emit),
+                }
+            }
+        }
+    }
+
+    fn add_node_group_member(&mut self, group_name: String, member: NodeGroupMember<'input>) {
+        match self
+            .node_groups
+            .iter_mut()
+            .find(|group| group.name == group_name)
+        {
+            Some(group) => group.members.push(member),
+            None => self.node_groups.push(NodeGroup {
+                name: group_name,
+                members: vec![member],
+            }),
+        }
+    }
+
+    /// Generates the node that is run when jumping to a node group.
+    /// It adds every member of the group as a saliency candidate and runs the one selected by the saliency strategy.
+    /// If none is selected, the dialogue stops.
+    fn generate_node_group_node(&mut self, group: NodeGroup<'input>) {
+        self.current_node = Some(Node {
+            name: group.name.clone(),
+            headers: vec![Header {
+                key: "title".to_owned(),
+                value: group.name.clone(),
+            }],
+            ..Default::default()
+        });
+        self.current_debug_info = Default::default();
+        let label = self.register_label(None);
+        self.current_node.as_mut().unwrap().labels.insert(label, 0);
+
+        let and_function = Type::Boolean.get_canonical_name_for_method(&Operator::And.to_string());
+        for member in &group.members {
+            for (index, condition) in member.conditions.iter().enumerate() {
+                CodeGenerationVisitor::new(self, None).visit(condition.as_ref());
+                // Multiple `when:` headers must all be true
+                if index > 0 {
+                    let token = condition.start();
+                    self.emit(
+                        Emit::from_op_code(OpCode::PushFloat)
+                            .with_token(token.deref())
+                            .with_operand(2),
+                    );
+                    self.emit(
+                        Emit::from_op_code(OpCode::CallFunc)
+                            .with_token(token.deref())
+                            .with_operand(and_function.clone()),
+                    );
+                }
+            }
+            let complexity_score: usize = member
+                .conditions
+                .iter()
+                .map(|condition| get_complexity_score(Some(condition)))
+                .sum();
+            let mut emit = Emit::from_op_code(OpCode::AddSaliencyCandidate)
+                .with_operand(member.name.clone())
+                .with_operand(complexity_score)
+                .with_operand(member.name.clone());
+            if let Some(condition) = member.conditions.first() {
+                emit = emit.with_token(condition.start().deref());
+            } else {
+                // Only happens if the conditions could not be parsed, in which case there already is an error
+                self.emit(Emit::from_op_code(OpCode::PushBool).with_operand(false));
+            }
+            self.emit(emit);
+        }
+
+        // The top of the stack is now either `true` on top of the name of the selected node,
+        // or `false` if no node was selected.
+        let no_candidate_label = self.register_label("nodegroup_empty");
+        self.emit(Emit::from_op_code(OpCode::SelectSaliencyCandidate));
+        self.emit(Emit::from_op_code(OpCode::JumpIfFalse).with_operand(no_candidate_label.clone()));
+        self.emit(Emit::from_op_code(OpCode::Pop));
+        if self.tracking_nodes.borrow().contains(&group.name) {
+            let track = Library::generate_unique_visited_variable_for_node(&group.name);
+            CodeGenerationVisitor::generate_tracking_code(self, track);
+        }
+        self.emit(Emit::from_op_code(OpCode::RunNode));
+
+        let current_node = self.current_node.as_mut().unwrap();
+        current_node
+            .labels
+            .insert(no_candidate_label, current_node.instructions.len() as i32);
+        self.emit(Emit::from_op_code(OpCode::Pop));
+        self.emit(Emit::from_op_code(OpCode::Stop));
+
+        self.current_debug_info.node_name.clone_from(&group.name);
+        self.current_debug_info
+            .file_name
+            .clone_from(&self.file.name);
+        self.debug_infos
+            .borrow_mut()
+            .push(self.current_debug_info.clone());
+        let node = self.current_node.take().unwrap();
+        self.program.borrow_mut().nodes.insert(group.name, node);
+    }
+
+    /// Generates the node that computes the value of a smart variable. It is named after the variable
+    /// and contains nothing but the code for its expression, which leaves the value on the stack.
+    fn generate_smart_variable_node(
+        &mut self,
+        name: String,
+        expression: Rc<ExpressionContextAll<'input>>,
+    ) {
+        let expression_text = expression.get_text_with_whitespace(self.file.tokens());
+        self.current_node = Some(Node {
+            name: name.clone(),
+            headers: vec![
+                Header {
+                    key: "title".to_owned(),
+                    value: name.clone(),
+                },
+                Header {
+                    key: Node::SMART_VARIABLE_HEADER.to_owned(),
+                    value: expression_text,
+                },
+            ],
+            ..Default::default()
+        });
+        self.current_debug_info = Default::default();
+        CodeGenerationVisitor::new(self, None).visit(expression.as_ref());
+
+        self.current_debug_info.node_name.clone_from(&name);
+        self.current_debug_info
+            .file_name
+            .clone_from(&self.file.name);
+        self.debug_infos
+            .borrow_mut()
+            .push(self.current_debug_info.clone());
+        let node = self.current_node.take().unwrap();
+        self.program.borrow_mut().nodes.insert(name, node);
+    }
 }
 
 impl<'input> ParseTreeListener<'input, YarnSpinnerParserContextType> for CompilerListener<'input> {}
 
 impl<'input> YarnSpinnerParserListener<'input> for CompilerListener<'input> {
+    fn exit_dialogue(&mut self, _ctx: &DialogueContext<'input>) {
+        for group in std::mem::take(&mut self.node_groups) {
+            self.generate_node_group_node(group);
+        }
+        for (name, expression) in std::mem::take(&mut self.smart_variables) {
+            self.generate_smart_variable_node(name, expression);
+        }
+    }
+
+    fn enter_declare_statement(&mut self, ctx: &Declare_statementContext<'input>) {
+        let Some(expression) = smart_variable_expression(ctx) else {
+            return;
+        };
+        // Constant values like `-1` look like smart variables to the parser, but are declared as regular variables
+        let name = ctx.variable().unwrap().get_text();
+        if self
+            .variable_declarations
+            .iter()
+            .any(|decl| decl.name == name && decl.is_smart_variable)
+        {
+            self.smart_variables.push((name, expression));
+        }
+    }
+
     fn enter_node(&mut self, _ctx: &NodeContext<'input>) {
         // we have found a new node set up the currentNode var ready to hold it and otherwise continue
         self.current_node = Some(Node::default());
@@ -166,6 +404,24 @@ impl<'input> YarnSpinnerParserListener<'input> for CompilerListener<'input> {
             current_node
                 .labels
                 .insert(label, current_node.instructions.len() as i32);
+            self.emit_local_variable_initializers();
+
+            let current_node = self.current_node.as_ref().unwrap();
+            if let Some(group_name) = current_node.node_group() {
+                let conditions = ctx
+                    .statement_all()
+                    .iter()
+                    .filter_map(|statement| statement.if_statement()?.if_clause())
+                    .filter(|if_clause| is_when_clause(if_clause))
+                    .filter_map(|if_clause| if_clause.expression())
+                    .collect();
+                let member = NodeGroupMember {
+                    name: current_node.name.clone(),
+                    conditions,
+                };
+                self.add_node_group_member(group_name.to_owned(), member);
+            }
+            let current_node = self.current_node.as_ref().unwrap();
             let track = (self.tracking_nodes.borrow().contains(&current_node.name))
                 .then(|| Library::generate_unique_visited_variable_for_node(&current_node.name));
 
@@ -1,13 +1,14 @@
 //! Adapted from <https://github.com/YarnSpinnerTool/YarnSpinner/blob/da39c7195107d8211f21c263e4084f773b84eaff/YarnSpinner.Compiler/CompilationResult.cs>
 
 use crate::listeners::*;
-pub use crate::output::{debug_info::*, declaration::*, string_info::*};
+pub use crate::output::{compiled_project::*, debug_info::*, declaration::*, string_info::*};
 use crate::prelude::*;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::{Debug, Display};
 use yarnspinner_core::prelude::*;
 
+mod compiled_project;
 mod debug_info;
 mod declaration;
 mod string_info;
@@ -53,6 +54,13 @@ pub struct Compilation {
     /// [`CompilationType`] value was not [`CompilationType::FullCompilation`].
     pub declarations: Vec<Declaration>,
 
+    /// The variables declared via `<<local>>`. Their [`Declaration::name`] is the one used in the source code
+    /// and their [`Declaration::source_node_name`] is the node they belong to.
+    ///
+    /// They are not part of [`Compilation::declarations`] because they only exist while their node is running
+    /// and are never stored in the variable storage.
+    pub local_declarations: Vec<Declaration>,
+
     /// A value indicating whether the compiler had to create line IDs
     /// for lines in the source code that lacked `#line:` tags.
     ///
@@ -96,6 +104,7 @@ impl Compilation {
     ) -> Self {
         let mut programs = Vec::new();
         let mut declarations = Vec::new();
+        let mut local_declarations = Vec::new();
         let mut tags = HashMap::new();
         let mut diagnostics = Vec::new();
         let mut node_debug_infos = HashMap::new();
@@ -103,6 +112,7 @@ impl Compilation {
         for compilation in compilations {
             programs.push(compilation.program.unwrap());
             declarations.extend(compilation.declarations);
+            local_declarations.extend(compilation.local_declarations);
             tags.extend(compilation.file_tags);
             diagnostics.extend(compilation.warnings);
             node_debug_infos.extend(compilation.debug_info);
@@ -113,6 +123,7 @@ impl Compilation {
             program: combined_program,
             string_table: string_table_manager.0,
             declarations,
+            local_declarations,
             debug_info: node_debug_infos,
             contains_implicit_string_tags,
             file_tags: tags,
@@ -0,0 +1,154 @@
+//! Not part of the original, which leaves bundling the program with its strings to the game engine integrations.
+//!
+//! A compiled project is encoded as the following protobuf message, whose `program` field holds the exact bytes of a `.yarnc` file:
+//!
+//! ```proto
+//! message CompiledProject {
+//!     bytes program = 1;
+//!     repeated StringTableEntry string_table = 2;
+//! }
+//!
+//! message StringTableEntry {
+//!     string line_id = 1;
+//!     string text = 2;
+//!     string node_name = 3;
+//!     int32 line_number = 4;
+//!     string file_name = 5;
+//!     bool is_implicit_tag = 6;
+//!     repeated string metadata = 7;
+//! }
+//! ```
+//!
+//! The string table is sorted by line ID, so the encoding is deterministic.
+
+use crate::prelude::*;
+use prost::Message;
+use std::collections::HashMap;
+use yarnspinner_core::prelude::*;
+
+/// Everything needed to run a compiled Yarn project without compiling it again:
+/// the [`Program`] together with the string table containing the text and metadata of its lines.
+///
+/// Use [`CompiledProject::to_bytes`] to save it and [`CompiledProject::from_bytes`] to load it again.
+/// The program alone can also be saved as a `.yarnc` file via [`Program::to_bytes`].
+/// Neither format can be exchanged with the original Yarn Spinner, see [`Program::to_bytes`] for why.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "bevy", derive(Reflect))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "bevy", reflect(Debug, Default))]
+#[cfg_attr(
+    all(feature = "bevy", feature = "serde"),
+    reflect(Serialize, Deserialize)
+)]
+pub struct CompiledProject {
+    /// The compiled Yarn program.
+    pub program: Program,
+
+    /// The text and metadata of every line and option in [`CompiledProject::program`], by line ID.
+    pub string_table: HashMap<LineId, StringInfo>,
+}
+
+impl CompiledProject {
+    /// Encodes the project in a binary format that can be loaded with [`CompiledProject::from_bytes`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut string_table: Vec<_> = self
+            .string_table
+            .iter()
+            .map(|(line_id, string_info)| EncodedStringTableEntry {
+                line_id: line_id.0.clone(),
+                text: string_info.text.clone(),
+                node_name: string_info.node_name.clone(),
+                line_number: string_info.line_number as i32,
+                file_name: string_info.file_name.clone(),
+                is_implicit_tag: string_info.is_implicit_tag,
+                metadata: string_info.metadata.clone(),
+            })
+            .collect();
+        string_table.sort_unstable_by(|a, b| a.line_id.cmp(&b.line_id));
+        EncodedCompiledProject {
+            program: self.program.to_bytes(),
+            string_table,
+        }
+        .encode_to_vec()
+    }
+
+    /// Decodes a project that was encoded with [`CompiledProject::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ProgramDecodeError> {
+        let encoded = EncodedCompiledProject::decode(bytes)?;
+        let program = Program::from_bytes(&encoded.program)?;
+        let string_table = encoded
+            .string_table
+            .into_iter()
+            .map(|entry| {
+                let string_info = StringInfo {
+                    text: entry.text,
+                    node_name: entry.node_name,
+                    line_number: entry.line_number.max(0) as usize,
+                    file_name: entry.file_name,
+                    is_implicit_tag: entry.is_implicit_tag,
+                    metadata: entry.metadata,
+                };
+                (LineId(entry.line_id), string_info)
+            })
+            .collect();
+        Ok(Self {
+            program,
+            string_table,
+        })
+    }
+}
+
+impl Compilation {
+    /// Bundles the program and string table of this compilation so they can be saved and loaded without compiling again.
+    ///
+    /// Returns [`None`] if the compilation has no program, i.e. if it was not a [`CompilationType::FullCompilation`].
+    pub fn to_compiled_project(&self) -> Option<CompiledProject> {
+        Some(CompiledProject {
+            program: self.program.clone()?,
+            string_table: self.string_table.clone(),
+        })
+    }
+}
+
+impl From<CompiledProject> for Compilation {
+    /// Creates a compilation that can be used wherever one is expected, e.g. by game engine integrations.
+    /// Everything that is not part of a [`CompiledProject`], like declarations and debug information, is left empty.
+    fn from(project: CompiledProject) -> Self {
+        let contains_implicit_string_tags = project
+            .string_table
+            .values()
+            .any(|string_info| string_info.is_implicit_tag);
+        Self {
+            program: Some(project.program),
+            string_table: project.string_table,
+            contains_implicit_string_tags,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct EncodedCompiledProject {
+    #[prost(bytes = "vec", tag = "1")]
+    program: Vec<u8>,
+    #[prost(message, repeated, tag = "2")]
+    string_table: Vec<EncodedStringTableEntry>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct EncodedStringTableEntry {
+    #[prost(string, tag = "1")]
+    line_id: String,
+    #[prost(string, tag = "2")]
+    text: String,
+    #[prost(string, tag = "3")]
+    node_name: String,
+    #[prost(int32, tag = "4")]
+    line_number: i32,
+    #[prost(string, tag = "5")]
+    file_name: String,
+    #[prost(bool, tag = "6")]
+    is_implicit_tag: bool,
+    #[prost(string, repeated, tag = "7")]
+    metadata: Vec<String>,
+}
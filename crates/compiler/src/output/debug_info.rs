@@ -62,6 +62,34 @@ impl DebugInfo {
     }
 }
 
+impl Compilation {
+    /// Returns a human-readable listing of the compiled program as produced by [`Program::disassemble`],
+    /// with every instruction annotated with the position in the source code it was produced from, e.g. `; Start.yarn:3:5`.
+    /// Lines and characters are one-based, like in text editors.
+    ///
+    /// Returns [`None`] if the compilation has no program, i.e. if it was not a [`CompilationType::FullCompilation`].
+    pub fn disassemble(&self) -> Option<String> {
+        let program = self.program.as_ref()?;
+        let listing = program.disassemble_with(|node, instruction_number| {
+            let line_info = self
+                .debug_info
+                .get(&node.name)?
+                .try_get_line_info(instruction_number)?;
+            let annotation = match line_info.position {
+                Some(position) => format!(
+                    "{}:{}:{}",
+                    line_info.file_name,
+                    position.line + 1,
+                    position.character + 1
+                ),
+                None => line_info.file_name,
+            };
+            Some(annotation)
+        });
+        Some(listing)
+    }
+}
+
 /// Contains positional information about an instruction.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "bevy", derive(Reflect))]
@@ -56,6 +56,12 @@ pub struct Declaration {
     /// If `false`, this declaration appears in the source code.
     pub is_implicit: bool,
 
+    /// A value indicating whether this declaration is a smart variable,
+    /// i.e. a variable whose value is an expression that is evaluated every time it is read.
+    ///
+    /// Smart variables have no default value and cannot be assigned to.
+    pub is_smart_variable: bool,
+
     /// The type of the variable, as represented by an object found
     /// in a variant of [`Type`].
     pub r#type: Type,
@@ -69,6 +75,30 @@ pub struct Declaration {
     pub range: Option<Range<Position>>,
 }
 
+/// Splits declarations into the ones that go into [`Compilation::declarations`]
+/// and the ones that go into [`Compilation::local_declarations`].
+/// The latter are renamed from their name in the variable storage to the name used in the source code.
+pub(crate) fn partition_local_declarations(
+    declarations: &[Declaration],
+) -> (Vec<Declaration>, Vec<Declaration>) {
+    let (local_declarations, declarations): (Vec<_>, Vec<_>) = declarations
+        .iter()
+        .cloned()
+        .partition(|declaration| Library::is_local_variable_name(&declaration.name));
+    let local_declarations = local_declarations
+        .into_iter()
+        .map(|mut declaration| {
+            let node_name = declaration.source_node_name.as_deref().unwrap_or_default();
+            let prefix = Library::generate_local_variable_name(node_name, "");
+            if let Some(name) = declaration.name.strip_prefix(&prefix) {
+                declaration.name = format!("${name}");
+            }
+            declaration
+        })
+        .collect();
+    (declarations, local_declarations)
+}
+
 impl Declaration {
     /// Gets the line number at which this Declaration was found in the
     /// source file.
@@ -89,6 +119,7 @@ impl Declaration {
             source_file_name: Default::default(),
             source_node_name: Default::default(),
             is_implicit: Default::default(),
+            is_smart_variable: Default::default(),
             range: Default::default(),
         }
     }
@@ -150,6 +181,12 @@ impl Declaration {
         self
     }
 
+    #[doc(hidden)]
+    pub fn with_smart_variable(mut self) -> Self {
+        self.is_smart_variable = true;
+        self
+    }
+
     #[doc(hidden)]
     pub fn with_range(mut self, range: impl Into<Range<Position>>) -> Self {
         self.range = Some(range.into());
@@ -163,6 +200,7 @@ impl Declaration {
             && self.source_file_name == other.source_file_name
             && self.source_node_name == other.source_node_name
             && self.is_implicit == other.is_implicit
+            && self.is_smart_variable == other.is_smart_variable
             && self.r#type == other.r#type
             && self.range == other.range
             && match (&self.default_value, &other.default_value) {
@@ -11,14 +11,17 @@ use super::generated::yarnspinnerlexer::{
 use crate::collections::*;
 use crate::listeners::Diagnostic;
 use crate::prelude::{create_common_token, DiagnosticSeverity, TokenExt};
+use crate::visitors::SMART_VARIABLE_FUNCTION;
 use antlr_rust::token::CommonToken;
 use antlr_rust::{
     char_stream::CharStream,
-    token::{Token, TOKEN_DEFAULT_CHANNEL},
+    token::{Token, TOKEN_DEFAULT_CHANNEL, TOKEN_HIDDEN_CHANNEL},
     token_factory::{CommonTokenFactory, TokenFactory},
-    Lexer, TokenSource,
+    InputStream, Lexer, TokenSource,
 };
+use std::borrow::Cow;
 use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
 use std::ops::{Deref, DerefMut, Range};
 use std::rc::Rc;
 use yarnspinner_core::prelude::*;
@@ -58,9 +61,16 @@ pub(crate) struct IndentAwareYarnSpinnerLexer<
     last_indent: isize,
     /// A stack keeping track of the levels of indentations we have seen so far that are relevant to shortcuts.
     unbalanced_indents: Stack<isize>,
+    /// Tokens that were read from the generated lexer while looking ahead,
+    /// but have not been processed yet.
+    lookahead: VecDeque<TF::Tok>,
     /// holds the line number of the last seen option.
     /// Lets us work out if the blank line needs to end the option.
     last_seen_option_content: Option<isize>,
+    /// Whether the headers of the current node were already checked for `when:` headers.
+    node_headers_scanned: bool,
+    /// How many nodes of each node group were seen so far. Used to generate unique names for them.
+    node_group_sizes: HashMap<String, usize>,
     file_name: String,
     pub(crate) diagnostics: Rc<RefCell<Vec<Diagnostic>>>,
 }
@@ -120,6 +130,7 @@ impl<'input, Input: CharStream<From<'input>>> TokenSource<'input>
 
 /// Copied from generated/yarnspinnerlexer.rs
 type From<'a> = <LocalTokenFactory<'a> as TokenFactory<'a>>::From;
+type Tok<'a> = <LocalTokenFactory<'a> as TokenFactory<'a>>::Tok;
 
 impl<'input, Input: CharStream<From<'input>>> IndentAwareYarnSpinnerLexer<'input, Input>
 where
@@ -132,16 +143,19 @@ where
             hit_eof: false,
             last_token: Default::default(),
             pending_tokens: Default::default(),
+            lookahead: Default::default(),
             line_contains_shortcut: false,
             last_indent: Default::default(),
             unbalanced_indents: Default::default(),
             last_seen_option_content: None,
+            node_headers_scanned: false,
+            node_group_sizes: Default::default(),
             diagnostics: Default::default(),
         }
     }
 
     fn check_next_token(&mut self) {
-        let current = self.base.next_token();
+        let current = self.next_base_token();
 
         match current.token_type {
             // Insert indents or dedents depending on the next token's
@@ -157,11 +171,27 @@ where
             // we are at the end of the node
             // depth no longer matters
             // clear the stack
+            yarnspinnerlexer::COMMAND_TEXT if self.is_at_start_of_command() => {
+                self.handle_command_text_token(current.clone())
+            }
             yarnspinnerlexer::COMMAND_TEXT => {
                 self.diagnose_newlines_in_commands(&current);
                 self.pending_tokens.enqueue(current.clone());
             }
+            yarnspinnerlexer::COMMAND_ENUM
+            | yarnspinnerlexer::COMMAND_CASE
+            | yarnspinnerlexer::COMMAND_ENDENUM => self.handle_enum_command_token(current.clone()),
+            yarnspinnerlexer::COMMAND_LOCAL => self.handle_local_command_token(current.clone()),
+            yarnspinnerlexer::COMMAND_DECLARE => self.handle_declare_command_token(current.clone()),
+            yarnspinnerlexer::FUNC_ID => self.handle_function_id_token(current.clone()),
+            yarnspinnerlexer::ID if !self.node_headers_scanned => {
+                self.handle_header_token(current.clone())
+            }
+            yarnspinnerlexer::TEXT if current.get_text() == "=" && self.is_at_start_of_line() => {
+                self.handle_line_group_token(current.clone())
+            }
             yarnspinnerlexer::BODY_END => {
+                self.node_headers_scanned = false;
                 self.line_contains_shortcut = false;
                 self.last_indent = 0;
                 self.unbalanced_indents.0.clear();
@@ -176,6 +206,465 @@ where
         self.last_token = Some(current);
     }
 
+    fn next_base_token(&mut self) -> Box<CommonToken<'input>> {
+        self.lookahead
+            .pop_front()
+            .unwrap_or_else(|| self.base.next_token())
+    }
+
+    /// The generated parser has no rules for enum declarations, so `<<enum Mood>>`, `<<case Happy>>`
+    /// and `<<endenum>>` are each collapsed into a single [`yarnspinnerlexer::COMMAND_TEXT`] token,
+    /// which turns them into regular commands. See [`crate::visitors::EnumCommand`] for how they are picked up again.
+    fn handle_enum_command_token(&mut self, current_token: Box<CommonToken<'input>>) {
+        let mut command_text = current_token.clone();
+        command_text.token_type = yarnspinnerlexer::COMMAND_TEXT;
+        let mut text = current_token.get_text().to_owned();
+        let command_end = loop {
+            let next = self.next_base_token();
+            match next.token_type {
+                yarnspinnerlexer::COMMAND_END | yarnspinnerlexer::COMMAND_TEXT_END => {
+                    break Some(next);
+                }
+                antlr_rust::token::TOKEN_EOF => {
+                    self.lookahead.push_front(next);
+                    break None;
+                }
+                _ => {
+                    text.push_str(next.get_text());
+                    command_text.stop = next.stop;
+                }
+            }
+        };
+        command_text.text = Cow::Owned(text);
+        self.pending_tokens.enqueue(command_text);
+
+        if let Some(mut command_end) = command_end {
+            command_end.token_type = yarnspinnerlexer::COMMAND_TEXT_END;
+            self.pending_tokens.enqueue(command_end);
+        }
+    }
+
+    fn is_at_start_of_command(&self) -> bool {
+        self.last_token
+            .as_ref()
+            .is_some_and(|token| token.token_type == yarnspinnerlexer::COMMAND_START)
+    }
+
+    /// The generated parser has no rules for once blocks, so `<<once>>` and `<<once if $condition>>` are rewritten into
+    /// an `<<if>>` whose `COMMAND_IF` token has the text `once`, and `<<endonce>>` into an `<<endif>>` with the text `endonce`.
+    /// A plain `<<once>>` gets an empty `true` as its condition.
+    /// The tokens keep their text, which is how the visitors tell them apart from regular if statements.
+    fn handle_command_text_token(&mut self, current_token: Box<CommonToken<'input>>) {
+        let mut command_texts = vec![current_token.clone()];
+        let mut next = self.next_base_token();
+        while next.token_type == yarnspinnerlexer::COMMAND_TEXT {
+            command_texts.push(next);
+            next = self.next_base_token();
+        }
+        // The generated lexer drops some characters of command texts, e.g. a lone `>`, so the text is read from the input instead
+        let text = self.base.input.as_ref().unwrap().get_text(
+            command_texts.first().unwrap().start,
+            command_texts.last().unwrap().stop,
+        );
+        let text = text.to_string();
+        let once_condition = text
+            .strip_prefix("once")
+            .filter(|rest| rest.starts_with(char::is_whitespace))
+            .map(str::trim_start)
+            .and_then(|rest| rest.strip_prefix("if"))
+            .filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace));
+        let is_once = text.trim_end() == "once" || once_condition.is_some();
+        let is_endonce = text.trim_end() == "endonce";
+        if next.token_type != yarnspinnerlexer::COMMAND_TEXT_END || !(is_once || is_endonce) {
+            self.lookahead.push_front(next);
+            for token in command_texts.drain(1..).rev() {
+                self.lookahead.push_front(token);
+            }
+            self.diagnose_newlines_in_commands(&current_token);
+            self.pending_tokens.enqueue(current_token);
+            return;
+        }
+
+        let mut keyword = current_token.clone();
+        keyword.stop = command_texts.last().unwrap().stop;
+        let mut command_end = next;
+        command_end.token_type = yarnspinnerlexer::COMMAND_END;
+        if is_endonce {
+            keyword.token_type = yarnspinnerlexer::COMMAND_ENDIF;
+            keyword.text = Cow::Borrowed("endonce");
+            self.pending_tokens.enqueue(keyword);
+            self.pending_tokens.enqueue(command_end);
+            return;
+        }
+
+        keyword.token_type = yarnspinnerlexer::COMMAND_IF;
+        keyword.text = Cow::Borrowed("once");
+        let mut always = command_end.clone();
+        always.token_type = yarnspinnerlexer::KEYWORD_TRUE;
+        always.text = Cow::Borrowed("");
+        always.stop = always.start - 1;
+        let condition_tokens = match once_condition.map(str::trim) {
+            None => vec![always],
+            Some("") => {
+                self.diagnostics.borrow_mut().push(
+                    Diagnostic::from_message("<<once if>> must contain a condition")
+                        .with_range(get_token_range(&keyword))
+                        .with_context(text.clone())
+                        .with_start_line(keyword.get_line_as_usize() - 1)
+                        .with_file_name(self.file_name.clone())
+                        .with_severity(DiagnosticSeverity::Error),
+                );
+                vec![always]
+            }
+            Some(condition) => {
+                // Commands can't contain newlines, so the condition is on the same line as the keyword
+                let offset = text.find(condition).unwrap();
+                let offset = text[..offset].chars().count() as isize;
+                let mut condition_token = current_token.clone();
+                condition_token.text = Cow::Owned(condition.to_owned());
+                condition_token.start += offset;
+                condition_token.column += offset;
+                self.lex_condition(&condition_token, "<<once if>>")
+            }
+        };
+        self.pending_tokens.enqueue(keyword);
+        for token in condition_tokens {
+            self.pending_tokens.enqueue(token);
+        }
+        self.pending_tokens.enqueue(command_end);
+    }
+
+    /// The generated parser has no rules for local variables, so `<<local $x = 0>>` is lexed like `<<declare $x = 0>>`.
+    /// The token keeps its text, which is how the visitors tell the two apart.
+    fn handle_local_command_token(&mut self, current_token: Box<CommonToken<'input>>) {
+        let mut declare = current_token;
+        declare.token_type = yarnspinnerlexer::COMMAND_DECLARE;
+        // `COMMAND_LOCAL` does not enter the expression mode that `COMMAND_DECLARE` does
+        self.base.push_mode(yarnspinnerlexer::ExpressionMode);
+        self.pending_tokens.enqueue(declare);
+    }
+
+    /// The generated parser only accepts single values in declarations, so the value of a smart variable,
+    /// e.g. `<<declare $can_afford = $gold >= 10>>`, is wrapped into a call of the function [`SMART_VARIABLE_FUNCTION`].
+    /// Since function names can never contain a `$`, this cannot clash with actual functions.
+    /// Single values, i.e. literals and enum cases, are left alone. `<<local>>` declarations never reach this.
+    /// Wrapped values can still be constant, e.g. `-1`, which the [`crate::visitors::DeclarationVisitor`] decides.
+    fn handle_declare_command_token(&mut self, current_token: Box<CommonToken<'input>>) {
+        self.pending_tokens.enqueue(current_token);
+        let mut tokens = Vec::new();
+        loop {
+            let next = self.next_base_token();
+            let token_type = next.token_type;
+            tokens.push(next);
+            if [
+                yarnspinnerlexer::COMMAND_END,
+                yarnspinnerlexer::COMMAND_TEXT_END,
+                antlr_rust::token::TOKEN_EOF,
+            ]
+            .contains(&token_type)
+            {
+                break;
+            }
+        }
+
+        let value_start = tokens
+            .iter()
+            .position(|token| token.token_type == yarnspinnerlexer::OPERATOR_ASSIGNMENT)
+            .map(|assignment| assignment + 1);
+        let value_end = tokens
+            .iter()
+            .position(|token| token.token_type == yarnspinnerlexer::EXPRESSION_AS)
+            .unwrap_or(tokens.len() - 1);
+        if let Some(value_start) = value_start.filter(|&start| start < value_end) {
+            let value_types: Vec<_> = tokens[value_start..value_end]
+                .iter()
+                .filter(|token| token.channel == TOKEN_DEFAULT_CHANNEL)
+                .map(|token| token.token_type)
+                .collect();
+            if !is_single_value(&value_types) {
+                let first = &tokens[value_start];
+                let mut function_id = first.clone();
+                function_id.token_type = yarnspinnerlexer::FUNC_ID;
+                function_id.text = Cow::Borrowed(SMART_VARIABLE_FUNCTION);
+                function_id.stop = first.start - 1;
+                function_id.channel = TOKEN_DEFAULT_CHANNEL;
+                // The parentheses don't appear in the input, so they're empty, just like the indents and dedents
+                let mut left_parenthesis = function_id.clone();
+                left_parenthesis.token_type = yarnspinnerlexer::LPAREN;
+                left_parenthesis.text = Cow::Borrowed("");
+                let last = &tokens[value_end - 1];
+                let mut right_parenthesis = last.clone();
+                right_parenthesis.token_type = yarnspinnerlexer::RPAREN;
+                right_parenthesis.text = Cow::Borrowed("");
+                right_parenthesis.start = last.stop + 1;
+                right_parenthesis.column = last.column + last.get_text().chars().count() as isize;
+                right_parenthesis.channel = TOKEN_DEFAULT_CHANNEL;
+                tokens.insert(value_end, right_parenthesis);
+                tokens.insert(value_start, left_parenthesis);
+                tokens.insert(value_start, function_id);
+            }
+        }
+        // Run the tokens through the usual processing, e.g. to rewrite enum cases
+        for token in tokens.into_iter().rev() {
+            self.lookahead.push_front(token);
+        }
+    }
+
+    /// The generated parser has no rules for accessing enum cases or calling functions with a `.` in their name,
+    /// so the parts of a dotted name like `Mood.Happy` or `Number.Add` are joined into a single function name.
+    /// If the name is followed by parentheses, e.g. `Number.Add(1, 2)`, it is a regular function call.
+    /// Otherwise, it is an enum case, which is rewritten into a call without any arguments
+    /// whose parentheses are empty. See [`crate::visitors::enum_case_name`] for how the visitors tell the two apart.
+    fn handle_function_id_token(&mut self, current_token: Box<CommonToken<'input>>) {
+        let mut function_id = current_token;
+        loop {
+            let dot = self.next_base_token();
+            if dot.token_type != yarnspinnerlexer::DOT {
+                self.lookahead.push_front(dot);
+                break;
+            }
+            let member = self.next_base_token();
+            if member.token_type != yarnspinnerlexer::FUNC_ID {
+                self.lookahead.push_front(member);
+                self.lookahead.push_front(dot);
+                break;
+            }
+            function_id.text =
+                Cow::Owned(format!("{}.{}", function_id.get_text(), member.get_text()));
+            function_id.stop = member.stop;
+        }
+
+        let next = self.next_base_token();
+        let is_enum_case =
+            next.token_type != yarnspinnerlexer::LPAREN && function_id.get_text().contains('.');
+        self.lookahead.push_front(next);
+        if !is_enum_case {
+            self.pending_tokens.enqueue(function_id);
+            return;
+        }
+
+        // The parentheses don't appear in the input, so they're empty, just like the indents and dedents
+        let mut left_parenthesis = function_id.clone();
+        left_parenthesis.token_type = yarnspinnerlexer::LPAREN;
+        left_parenthesis.text = Cow::Borrowed("");
+        left_parenthesis.start = function_id.stop + 1;
+        left_parenthesis.column =
+            function_id.column + function_id.get_text().chars().count() as isize;
+        let mut right_parenthesis = left_parenthesis.clone();
+        right_parenthesis.token_type = yarnspinnerlexer::RPAREN;
+        self.pending_tokens.enqueue(function_id);
+        self.pending_tokens.enqueue(left_parenthesis);
+        self.pending_tokens.enqueue(right_parenthesis);
+    }
+
+    fn is_at_start_of_line(&self) -> bool {
+        self.last_token.as_ref().is_some_and(|token| {
+            matches!(
+                token.token_type,
+                yarnspinnerlexer::NEWLINE | yarnspinnerlexer::BODY_START
+            )
+        })
+    }
+
+    /// The generated parser has no rules for line groups, so the `=>` at the start of a line is lexed
+    /// like the `->` of a shortcut option, including the indentation tracking for its body.
+    /// The token keeps its text, which is how the visitors tell the two apart.
+    fn handle_line_group_token(&mut self, current_token: Box<CommonToken<'input>>) {
+        let next = self.next_base_token();
+        if next.token_type != yarnspinnerlexer::TEXT || !next.get_text().starts_with('>') {
+            self.lookahead.push_front(next);
+            self.pending_tokens.enqueue(current_token);
+            return;
+        }
+        let mut arrow = current_token;
+        arrow.token_type = yarnspinnerlexer::SHORTCUT_ARROW;
+        arrow.text = Cow::Borrowed("=>");
+        arrow.stop = next.start;
+        self.pending_tokens.enqueue(arrow);
+        self.line_contains_shortcut = true;
+
+        // Like after a `->`, the whitespace between the arrow and the line is not part of the line
+        let text = next.get_text();
+        let line_text = text[1..].trim_start();
+        if !line_text.is_empty() {
+            let skipped_chars = (text.chars().count() - line_text.chars().count()) as isize;
+            let mut line = next.clone();
+            line.text = Cow::Owned(line_text.to_owned());
+            line.start += skipped_chars;
+            line.column += skipped_chars;
+            self.lookahead.push_front(line);
+        }
+        // Keep the whitespace in the token stream so that the source can be reconstructed from it, e.g. for diagnostics
+        let whitespace: String = text[1..]
+            .chars()
+            .take_while(|c| c.is_whitespace())
+            .collect();
+        if !whitespace.is_empty() {
+            let mut whitespace_token = next.clone();
+            whitespace_token.token_type = yarnspinnerlexer::WS;
+            whitespace_token.channel = TOKEN_HIDDEN_CHANNEL;
+            whitespace_token.start += 1;
+            whitespace_token.column += 1;
+            whitespace_token.stop =
+                whitespace_token.start + whitespace.chars().count() as isize - 1;
+            whitespace_token.text = Cow::Owned(whitespace);
+            self.lookahead.push_front(whitespace_token);
+        }
+    }
+
+    /// The generated parser has no rules for node groups, so the headers of every node are checked for `when:` headers
+    /// before they are passed on. If there are any, the node is turned into a member of the node group named after its title:
+    /// - The title is replaced by a unique name, so that the nodes of a group don't clash.
+    /// - A header with the key [`Node::NODE_GROUP_HEADER`] containing the original title is added.
+    /// - Each condition is inserted at the start of the body as an empty `<<if>>` statement whose `COMMAND_IF` token has the text `when`.
+    ///
+    /// See [`crate::listeners::CompilerListener`] for how the node that selects one of the members is generated.
+    fn handle_header_token(&mut self, current_token: Box<CommonToken<'input>>) {
+        self.node_headers_scanned = true;
+        let mut tokens = vec![current_token];
+        while !matches!(
+            tokens.last().unwrap().token_type,
+            yarnspinnerlexer::BODY_START
+                | yarnspinnerlexer::BODY_END
+                | antlr_rust::token::TOKEN_EOF
+        ) {
+            tokens.push(self.next_base_token());
+        }
+        self.rewrite_node_group_headers(&mut tokens);
+
+        let mut tokens = tokens.into_iter();
+        self.pending_tokens.enqueue(tokens.next().unwrap());
+        for token in tokens.rev() {
+            self.lookahead.push_front(token);
+        }
+    }
+
+    fn rewrite_node_group_headers(&mut self, tokens: &mut Vec<Tok<'input>>) {
+        // (index of key, index of value)
+        let headers: Vec<_> = (0..tokens.len())
+            .filter(|&i| {
+                tokens[i].token_type == yarnspinnerlexer::ID
+                    && tokens
+                        .get(i + 1)
+                        .is_some_and(|t| t.token_type == yarnspinnerlexer::HEADER_DELIMITER)
+            })
+            .map(|i| {
+                let value = tokens
+                    .get(i + 2)
+                    .filter(|t| t.token_type == yarnspinnerlexer::REST_OF_LINE)
+                    .map(|_| i + 2);
+                (i, value)
+            })
+            .collect();
+
+        let mut conditions = Vec::new();
+        for &(key, value) in &headers {
+            if tokens[key].get_text() != "when" {
+                continue;
+            }
+            match value {
+                Some(value) if !tokens[value].get_text().trim().is_empty() => {
+                    conditions.push(tokens[value].clone())
+                }
+                _ => self.diagnostics.borrow_mut().push(
+                    Diagnostic::from_message("A when: header must contain a condition")
+                        .with_range(get_token_range(&tokens[key]))
+                        .with_context(tokens[key].get_text().to_owned())
+                        .with_start_line(tokens[key].get_line_as_usize() - 1)
+                        .with_file_name(self.file_name.clone())
+                        .with_severity(DiagnosticSeverity::Error),
+                ),
+            }
+        }
+        let title = headers
+            .iter()
+            .find(|&&(key, _)| tokens[key].get_text() == "title")
+            .and_then(|&(key, value)| Some((key, value?)));
+        let (Some((title_key, title_value)), false) = (title, conditions.is_empty()) else {
+            return;
+        };
+
+        let group_name = tokens[title_value].get_text().trim().to_owned();
+        let group_size = self.node_group_sizes.entry(group_name.clone()).or_default();
+        let member_name = format!("{group_name}.{group_size}");
+        *group_size += 1;
+        tokens[title_value].text = Cow::Owned(member_name);
+
+        let mut group_key = tokens[title_key].clone();
+        group_key.text = Cow::Borrowed(Node::NODE_GROUP_HEADER);
+        let group_delimiter = tokens[title_key + 1].clone();
+        let mut group_value = tokens[title_value].clone();
+        group_value.text = Cow::Owned(group_name);
+        tokens.splice(
+            title_value + 1..title_value + 1,
+            [group_key, group_delimiter, group_value],
+        );
+
+        if tokens.last().unwrap().token_type != yarnspinnerlexer::BODY_START {
+            return;
+        }
+        for condition in conditions {
+            let empty_token = |token_type, text: &'static str| {
+                let mut token = condition.clone();
+                token.token_type = token_type;
+                token.text = Cow::Borrowed(text);
+                token.stop = token.start - 1;
+                token
+            };
+            tokens.extend([
+                empty_token(yarnspinnerlexer::COMMAND_START, ""),
+                empty_token(yarnspinnerlexer::COMMAND_IF, "when"),
+            ]);
+            tokens.extend(self.lex_when_condition(&condition));
+            tokens.extend([
+                empty_token(yarnspinnerlexer::COMMAND_END, ""),
+                empty_token(yarnspinnerlexer::COMMAND_START, ""),
+                empty_token(yarnspinnerlexer::COMMAND_ENDIF, ""),
+                empty_token(yarnspinnerlexer::COMMAND_END, ""),
+            ]);
+        }
+    }
+
+    /// Lexes the value of a `when:` header, which the generated lexer treats as plain text, as an expression.
+    fn lex_when_condition(&mut self, condition: &CommonToken<'input>) -> Vec<Tok<'input>> {
+        if condition.get_text().trim() == "always" {
+            let mut always = Box::new(condition.clone());
+            always.token_type = yarnspinnerlexer::KEYWORD_TRUE;
+            return vec![always];
+        }
+        self.lex_condition(condition, "when: header")
+    }
+
+    /// Lexes text that the generated lexer treats as plain text, as an expression.
+    /// `description` names where the condition comes from in the diagnostic for invalid conditions.
+    fn lex_condition(
+        &mut self,
+        condition: &CommonToken<'input>,
+        description: &str,
+    ) -> Vec<Tok<'input>> {
+        let text = condition.get_text();
+        let mut tokens = lex_expression(text);
+        for token in &mut tokens {
+            token.line = condition.line;
+            token.column += condition.column;
+            token.start += condition.start;
+            token.stop += condition.start;
+        }
+        let lexed_chars: usize = tokens.iter().map(|t| t.get_text().chars().count()).sum();
+        // Characters the lexer could not make sense of are dropped
+        if lexed_chars != text.chars().count() {
+            self.diagnostics.borrow_mut().push(
+                Diagnostic::from_message(format!("Invalid condition in {description}: {text}"))
+                    .with_range(get_token_range(condition))
+                    .with_context(text.to_owned())
+                    .with_start_line(condition.get_line_as_usize() - 1)
+                    .with_file_name(self.file_name.clone())
+                    .with_severity(DiagnosticSeverity::Error),
+            );
+        }
+        tokens
+    }
+
     fn handle_newline_token(
         &mut self,
         current_token: Box<antlr_rust::token::GenericToken<std::borrow::Cow<'input, str>>>,
@@ -388,6 +877,55 @@ fn get_newline_indentation_range(token: &CommonToken<'_>) -> Range<Position> {
     start..stop
 }
 
+/// Returns `true` if the tokens of a declaration's value form a single literal or an enum case.
+fn is_single_value(token_types: &[isize]) -> bool {
+    matches!(
+        token_types,
+        [yarnspinnerlexer::NUMBER
+            | yarnspinnerlexer::STRING
+            | yarnspinnerlexer::KEYWORD_TRUE
+            | yarnspinnerlexer::KEYWORD_FALSE
+            | yarnspinnerlexer::KEYWORD_NULL]
+            | [
+                yarnspinnerlexer::FUNC_ID,
+                yarnspinnerlexer::DOT,
+                yarnspinnerlexer::FUNC_ID
+            ]
+    )
+}
+
+/// Lexes a standalone expression, e.g. `$gold > 5`, with the generated lexer.
+/// Characters that are not valid in an expression are skipped.
+fn lex_expression(text: &str) -> Vec<Tok<'static>> {
+    let mut lexer = GeneratedYarnSpinnerLexer::new(InputStream::new(text));
+    lexer.remove_error_listeners();
+    lexer.push_mode(yarnspinnerlexer::ExpressionMode);
+    let mut tokens = Vec::new();
+    loop {
+        let token = lexer.next_token();
+        if token.token_type == antlr_rust::token::TOKEN_EOF {
+            break tokens;
+        }
+        let mut owned = create_common_token(token.token_type, token.get_text());
+        owned.channel = token.channel;
+        owned.column = token.column;
+        owned.start = token.start;
+        owned.stop = token.stop;
+        tokens.push(owned);
+    }
+}
+
+fn get_token_range(token: &CommonToken<'_>) -> Range<Position> {
+    let line = token.get_line_as_usize().saturating_sub(1);
+    let character = token.get_column_as_usize();
+    let start = Position { line, character };
+    let stop = Position {
+        line,
+        character: character + token.get_text().chars().count(),
+    };
+    start..stop
+}
+
 fn get_newline_indentation_text(token: &CommonToken<'_>) -> String {
     // Skip newline
     token.get_text().chars().skip(1).collect()
@@ -567,6 +1105,218 @@ This is the one and only line
         assert_eq!(expected, symbols);
     }
 
+    #[test]
+    fn rewrites_enum_syntax_into_commands_and_function_calls() {
+        let input = "title: Start
+---
+<<enum Mood>>
+<<declare $mood = Mood.Happy>>
+===";
+        let mut indent_aware_lexer =
+            IndentAwareYarnSpinnerLexer::new(InputStream::new(input), "input.yarn".to_owned());
+
+        let mut tokens = Vec::new();
+        loop {
+            let token = indent_aware_lexer.next_token();
+            if token.token_type == TOKEN_EOF {
+                break;
+            }
+            if token.channel == TOKEN_DEFAULT_CHANNEL {
+                let symbol = yarnspinnerlexer::_SYMBOLIC_NAMES[token.token_type as usize].unwrap();
+                tokens.push((symbol, token.get_text().to_owned()));
+            }
+        }
+        let body: Vec<_> = tokens
+            .iter()
+            .skip_while(|(symbol, _)| *symbol != "BODY_START")
+            .map(|(symbol, text)| (*symbol, text.as_str()))
+            .collect();
+
+        let expected = vec![
+            ("BODY_START", "---"),
+            ("COMMAND_START", "<<"),
+            ("COMMAND_TEXT", "enum Mood"),
+            ("COMMAND_TEXT_END", ">>"),
+            ("COMMAND_START", "<<"),
+            ("COMMAND_DECLARE", "declare "),
+            ("VAR_ID", "$mood"),
+            ("OPERATOR_ASSIGNMENT", "="),
+            ("FUNC_ID", "Mood.Happy"),
+            ("LPAREN", ""),
+            ("RPAREN", ""),
+            ("COMMAND_END", ">>"),
+            ("BODY_END", "==="),
+        ];
+
+        assert_eq!(expected, body);
+    }
+
+    #[test]
+    fn rewrites_line_groups_and_node_groups() {
+        let input = "title: Start
+when: $gold > 5
+---
+=> Hello
+=> Bye
+===";
+        let mut indent_aware_lexer =
+            IndentAwareYarnSpinnerLexer::new(InputStream::new(input), "input.yarn".to_owned());
+
+        let mut tokens = Vec::new();
+        loop {
+            let token = indent_aware_lexer.next_token();
+            if token.token_type == TOKEN_EOF {
+                break;
+            }
+            if token.channel == TOKEN_DEFAULT_CHANNEL {
+                let symbol = yarnspinnerlexer::_SYMBOLIC_NAMES[token.token_type as usize].unwrap();
+                tokens.push((symbol, token.get_text().to_owned()));
+            }
+        }
+        let tokens: Vec<_> = tokens
+            .iter()
+            .map(|(symbol, text)| (*symbol, text.as_str()))
+            .collect();
+
+        let expected = vec![
+            ("ID", "title"),
+            ("HEADER_DELIMITER", ": "),
+            ("REST_OF_LINE", "Start.0"),
+            ("ID", "$Yarn.Internal.NodeGroup"),
+            ("HEADER_DELIMITER", ": "),
+            ("REST_OF_LINE", "Start"),
+            ("ID", "when"),
+            ("HEADER_DELIMITER", ": "),
+            ("REST_OF_LINE", "$gold > 5"),
+            ("BODY_START", "---"),
+            ("COMMAND_START", ""),
+            ("COMMAND_IF", "when"),
+            ("VAR_ID", "$gold"),
+            ("OPERATOR_LOGICAL_GREATER", ">"),
+            ("NUMBER", "5"),
+            ("COMMAND_END", ""),
+            ("COMMAND_START", ""),
+            ("COMMAND_ENDIF", ""),
+            ("COMMAND_END", ""),
+            ("SHORTCUT_ARROW", "=>"),
+            ("TEXT", "Hello"),
+            ("NEWLINE", "\n"),
+            ("SHORTCUT_ARROW", "=>"),
+            ("TEXT", "Bye"),
+            ("NEWLINE", "\n"),
+            ("BODY_END", "==="),
+        ];
+
+        assert_eq!(expected, tokens);
+    }
+
+    #[test]
+    fn rewrites_once_commands_into_if_statements() {
+        let input = "title: Start
+---
+<<once>>
+<<endonce>>
+<<once if $visits > 1>>
+<<endonce>>
+===";
+        let mut indent_aware_lexer =
+            IndentAwareYarnSpinnerLexer::new(InputStream::new(input), "input.yarn".to_owned());
+
+        let mut tokens = Vec::new();
+        loop {
+            let token = indent_aware_lexer.next_token();
+            if token.token_type == TOKEN_EOF {
+                break;
+            }
+            if token.channel == TOKEN_DEFAULT_CHANNEL {
+                let symbol = yarnspinnerlexer::_SYMBOLIC_NAMES[token.token_type as usize].unwrap();
+                tokens.push((symbol, token.get_text().to_owned()));
+            }
+        }
+        let body: Vec<_> = tokens
+            .iter()
+            .skip_while(|(symbol, _)| *symbol != "BODY_START")
+            .map(|(symbol, text)| (*symbol, text.as_str()))
+            .collect();
+
+        let expected = vec![
+            ("BODY_START", "---"),
+            ("COMMAND_START", "<<"),
+            ("COMMAND_IF", "once"),
+            ("KEYWORD_TRUE", ""),
+            ("COMMAND_END", ">>"),
+            ("COMMAND_START", "<<"),
+            ("COMMAND_ENDIF", "endonce"),
+            ("COMMAND_END", ">>"),
+            ("COMMAND_START", "<<"),
+            ("COMMAND_IF", "once"),
+            ("VAR_ID", "$visits"),
+            ("OPERATOR_LOGICAL_GREATER", ">"),
+            ("NUMBER", "1"),
+            ("COMMAND_END", ">>"),
+            ("COMMAND_START", "<<"),
+            ("COMMAND_ENDIF", "endonce"),
+            ("COMMAND_END", ">>"),
+            ("BODY_END", "==="),
+        ];
+
+        assert_eq!(expected, body);
+    }
+
+    #[test]
+    fn rewrites_smart_variable_declarations_into_function_calls() {
+        let input = "title: Start
+---
+<<declare $gold = 5>>
+<<declare $can_afford = $gold >= 10 as bool>>
+===";
+        let mut indent_aware_lexer =
+            IndentAwareYarnSpinnerLexer::new(InputStream::new(input), "input.yarn".to_owned());
+
+        let mut tokens = Vec::new();
+        loop {
+            let token = indent_aware_lexer.next_token();
+            if token.token_type == TOKEN_EOF {
+                break;
+            }
+            if token.channel == TOKEN_DEFAULT_CHANNEL {
+                let symbol = yarnspinnerlexer::_SYMBOLIC_NAMES[token.token_type as usize].unwrap();
+                tokens.push((symbol, token.get_text().to_owned()));
+            }
+        }
+        let body: Vec<_> = tokens
+            .iter()
+            .skip_while(|(symbol, _)| *symbol != "BODY_START")
+            .map(|(symbol, text)| (*symbol, text.as_str()))
+            .collect();
+
+        let expected = vec![
+            ("BODY_START", "---"),
+            ("COMMAND_START", "<<"),
+            ("COMMAND_DECLARE", "declare "),
+            ("VAR_ID", "$gold"),
+            ("OPERATOR_ASSIGNMENT", "="),
+            ("NUMBER", "5"),
+            ("COMMAND_END", ">>"),
+            ("COMMAND_START", "<<"),
+            ("COMMAND_DECLARE", "declare "),
+            ("VAR_ID", "$can_afford"),
+            ("OPERATOR_ASSIGNMENT", "="),
+            ("FUNC_ID", SMART_VARIABLE_FUNCTION),
+            ("LPAREN", ""),
+            ("VAR_ID", "$gold"),
+            ("OPERATOR_LOGICAL_GREATER_THAN_EQUALS", ">="),
+            ("NUMBER", "10"),
+            ("RPAREN", ""),
+            ("EXPRESSION_AS", "as"),
+            ("FUNC_ID", "bool"),
+            ("COMMAND_END", ">>"),
+            ("BODY_END", "==="),
+        ];
+
+        assert_eq!(expected, body);
+    }
+
     #[test]
     fn generated_lexer_output_is_same_as_reference() {
         let option_indentation_relevant_input: &str = include_str!("significant_whitespace.yarn");
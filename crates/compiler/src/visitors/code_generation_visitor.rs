@@ -5,9 +5,12 @@ use crate::prelude::generated::yarnspinnerlexer;
 use crate::prelude::generated::yarnspinnerparser::*;
 use crate::prelude::generated::yarnspinnerparservisitor::YarnSpinnerParserVisitorCompat;
 use crate::prelude::*;
+use crate::visitors::{enum_case_name, EnumCommand};
 use antlr_rust::parser_rule_context::ParserRuleContext;
-use antlr_rust::token::Token;
+use antlr_rust::token::{Token, TOKEN_DEFAULT_CHANNEL};
+use antlr_rust::token_stream::TokenStream;
 use antlr_rust::tree::{ParseTree, ParseTreeVisitorCompat, Tree};
+use std::collections::HashMap;
 use std::ops::Deref;
 use std::rc::Rc;
 use yarnspinner_core::prelude::OpCode;
@@ -17,6 +20,8 @@ use yarnspinner_core::types::Type;
 pub(crate) struct CodeGenerationVisitor<'a, 'input: 'a> {
     compiler_listener: &'a mut CompilerListener<'input>,
     tracking_enabled: Option<String>,
+    /// The keys of the `<<once>>` blocks of the current node visited so far.
+    once_block_keys: OnceBlockKeys,
     _dummy: (),
 }
 
@@ -28,6 +33,7 @@ impl<'a, 'input: 'a> CodeGenerationVisitor<'a, 'input> {
         Self {
             compiler_listener,
             tracking_enabled: tracking_enabled.into(),
+            once_block_keys: Default::default(),
             _dummy: Default::default(),
         }
     }
@@ -73,6 +79,13 @@ impl<'a, 'input: 'a> CodeGenerationVisitor<'a, 'input> {
         compiler.emit(Emit::from_op_code(OpCode::StoreVariable).with_operand(variable_name));
         compiler.emit(Emit::from_op_code(OpCode::Pop));
     }
+
+    /// Marks a `<<once>>` block or `#once` line as seen.
+    pub(crate) fn generate_once_code(compiler: &mut CompilerListener, variable_name: String) {
+        compiler.emit(Emit::from_op_code(OpCode::PushBool).with_operand(true));
+        compiler.emit(Emit::from_op_code(OpCode::StoreVariable).with_operand(variable_name));
+        compiler.emit(Emit::from_op_code(OpCode::Pop));
+    }
 }
 
 impl<'a, 'input: 'a> ParseTreeVisitorCompat<'input> for CodeGenerationVisitor<'a, 'input> {
@@ -87,28 +100,58 @@ impl<'a, 'input: 'a> ParseTreeVisitorCompat<'input> for CodeGenerationVisitor<'a
 impl<'a, 'input: 'a> YarnSpinnerParserVisitorCompat<'input> for CodeGenerationVisitor<'a, 'input> {
     /// a regular ol' line of text
     fn visit_line_statement(&mut self, ctx: &Line_statementContext<'input>) -> Self::Return {
-        // [sic] TODO: add support for line conditions:
+        // Lines with a condition:
         //
         // Mae: here's a line <<if true>>
         //
-        // is identical to
+        // are identical to
         //
         // <<if true>> Mae: here's a line <<endif>>
+        //
+        // Lines tagged with #once are additionally skipped once they were run.
+        let condition = ctx.line_condition().and_then(|ctx| ctx.expression());
+        let once_variable = has_once_tag(ctx).then(|| self.once_line_variable(ctx));
+        let token = ctx.start();
+        let skip_line_label = self
+            .generate_code_for_availability(condition, once_variable.clone(), token.deref())
+            .then(|| {
+                let skip_line_label = self.compiler_listener.register_label("skipline");
+                self.compiler_listener.emit(
+                    Emit::from_op_code(OpCode::JumpIfFalse)
+                        .with_token(token.deref())
+                        .with_operand(skip_line_label.clone()),
+                );
+                // The condition is still on the stack, since JumpIfFalse only peeks it
+                self.compiler_listener
+                    .emit(Emit::from_op_code(OpCode::Pop).with_token(token.deref()));
+                skip_line_label
+            });
+        if let Some(once_variable) = once_variable {
+            Self::generate_once_code(self.compiler_listener, once_variable);
+        }
 
-        // Evaluate the inline expressions and push the results onto the
-        // stack.
-        let formatted_text = ctx.line_formatted_text().unwrap();
-        let expression_count =
-            self.generate_code_for_expressions_in_formatted_text(formatted_text.get_children());
-        let line_id_tag = get_line_id_tag(&ctx.hashtag_all())
-            .expect("Internal error: line should have an implicit or explicit line ID tag, but none was found. This is a bug. Please report it at https://github.com/YarnSpinnerTool/YarnSpinner-Rust/issues/new");
-        let line_id = line_id_tag.text.as_ref().unwrap().get_text().to_owned();
-        self.compiler_listener.emit(
-            Emit::from_op_code(OpCode::RunLine)
-                .with_token(ctx.start().deref())
-                .with_operand(line_id)
-                .with_operand(expression_count),
-        );
+        self.generate_code_for_line(ctx);
+
+        if let Some(skip_line_label) = skip_line_label {
+            let end_of_line_label = self.compiler_listener.register_label("endline");
+            self.compiler_listener.emit(
+                Emit::from_op_code(OpCode::JumpTo)
+                    .with_token(ctx.stop().deref())
+                    .with_operand(end_of_line_label.clone()),
+            );
+
+            let current_node = self.compiler_listener.current_node.as_mut().unwrap();
+            current_node
+                .labels
+                .insert(skip_line_label, current_node.instructions.len() as i32);
+            self.compiler_listener
+                .emit(Emit::from_op_code(OpCode::Pop).with_token(ctx.stop().deref()));
+
+            let current_node = self.compiler_listener.current_node.as_mut().unwrap();
+            current_node
+                .labels
+                .insert(end_of_line_label, current_node.instructions.len() as i32);
+        }
     }
 
     /// (expression)
@@ -263,7 +306,9 @@ impl<'a, 'input: 'a> YarnSpinnerParserVisitorCompat<'input> for CodeGenerationVi
     }
 
     fn visit_variable(&mut self, ctx: &VariableContext<'input>) -> Self::Return {
-        let variable_name = ctx.VAR_ID().unwrap().get_text();
+        let variable_name = self
+            .compiler_listener
+            .resolve_variable_name(&ctx.VAR_ID().unwrap().get_text());
         self.compiler_listener.emit(
             Emit::from_op_code(OpCode::PushVariable)
                 .with_token(ctx.start().deref())
@@ -273,6 +318,18 @@ impl<'a, 'input: 'a> YarnSpinnerParserVisitorCompat<'input> for CodeGenerationVi
 
     /// handles emitting the correct instructions for the function
     fn visit_function_call(&mut self, ctx: &Function_callContext<'input>) -> Self::Return {
+        let function_name = ctx.FUNC_ID().unwrap().get_text();
+        // Enum cases look like functions to the parser, e.g. `Mood.Happy`,
+        // but are simply represented by the name of the case at runtime
+        if let Some((_enum_name, case)) = enum_case_name(ctx) {
+            self.compiler_listener.emit(
+                Emit::from_op_code(OpCode::PushString)
+                    .with_token(ctx.start().deref())
+                    .with_operand(case),
+            );
+            return;
+        }
+
         // generate the instructions for all of the parameters
         let expressions = ctx.expression_all();
         for parameter in &expressions {
@@ -288,7 +345,6 @@ impl<'a, 'input: 'a> YarnSpinnerParserVisitorCompat<'input> for CodeGenerationVi
         );
 
         // then call the function itself
-        let function_name = ctx.FUNC_ID().unwrap().get_text();
         self.compiler_listener.emit(
             Emit::from_op_code(OpCode::CallFunc)
                 .with_token(token.deref())
@@ -301,17 +357,36 @@ impl<'a, 'input: 'a> YarnSpinnerParserVisitorCompat<'input> for CodeGenerationVi
         // Implementation note: Idk what this is supposed to do. Looks like a noop.
         // context.AddErrorNode(null);
 
+        // The conditions of `when:` headers are evaluated by the node that selects a member of the node group,
+        // not by the member itself.
+        if ctx
+            .if_clause()
+            .is_some_and(|if_clause| is_when_clause(&if_clause))
+        {
+            return;
+        }
+
         // label to give us a jump point for when the if finishes
         let end_of_if_statement_label = self.compiler_listener.register_label("endif");
 
         // handle the if
         let if_clause = ctx.if_clause().unwrap();
-        self.generate_code_for_clause(
-            end_of_if_statement_label.clone(),
-            if_clause.as_ref(),
-            &if_clause.statement_all(),
-            if_clause.expression().unwrap(),
-        );
+        if is_once_clause(&if_clause) {
+            self.generate_code_for_once_clause(end_of_if_statement_label.clone(), ctx);
+        } else {
+            if ctx
+                .COMMAND_ENDIF()
+                .is_some_and(|endif| endif.get_text() == "endonce")
+            {
+                self.push_diagnostic("Unexpected <<endonce>> without a <<once>>", ctx);
+            }
+            self.generate_code_for_clause(
+                end_of_if_statement_label.clone(),
+                if_clause.as_ref(),
+                &if_clause.statement_all(),
+                if_clause.expression().unwrap(),
+            );
+        }
 
         // all elseifs
         for else_if_clause in &ctx.else_if_clause_all() {
@@ -388,7 +463,9 @@ impl<'a, 'input: 'a> YarnSpinnerParserVisitorCompat<'input> for CodeGenerationVi
         }
 
         // now store the variable and clean up the stack
-        let variable_name = variable.get_text();
+        let variable_name = self
+            .compiler_listener
+            .resolve_variable_name(&variable.get_text());
         let token = variable.start();
         self.compiler_listener.emit(
             Emit::from_op_code(OpCode::StoreVariable)
@@ -410,6 +487,10 @@ impl<'a, 'input: 'a> YarnSpinnerParserVisitorCompat<'input> for CodeGenerationVi
     /// semi-free form text that gets passed along to the game for things
     /// like <<turn fred left>> or <<unlockAchievement FacePlant>>
     fn visit_command_statement(&mut self, ctx: &Command_statementContext<'input>) -> Self::Return {
+        // Enum declarations are parsed as commands, but only matter during type checking
+        if EnumCommand::parse(ctx).is_some() {
+            return;
+        }
         let formatted_text = ctx.command_formatted_text().unwrap();
         let (composed_string, expression_count) = formatted_text.get_children().fold(
             (String::new(), 0_usize),
@@ -439,6 +520,19 @@ impl<'a, 'input: 'a> YarnSpinnerParserVisitorCompat<'input> for CodeGenerationVi
                     Emit::from_op_code(OpCode::Stop).with_token(formatted_text.start().deref()),
                 );
             }
+            "return" => {
+                // "return" completes the current node early, continuing
+                // where the detour into it started, if any
+                if let Some(tracking_enabled) = self.tracking_enabled.clone() {
+                    Self::generate_tracking_code(self.compiler_listener, tracking_enabled);
+                }
+                self.compiler_listener.emit(
+                    Emit::from_op_code(OpCode::Return).with_token(formatted_text.start().deref()),
+                );
+            }
+            detour if detour.split_whitespace().next() == Some("detour") => {
+                self.generate_code_for_detour(&formatted_text, detour, expression_count);
+            }
             _ => {
                 self.compiler_listener.emit(
                     Emit::from_op_code(OpCode::RunCommand)
@@ -455,6 +549,10 @@ impl<'a, 'input: 'a> YarnSpinnerParserVisitorCompat<'input> for CodeGenerationVi
         &mut self,
         ctx: &Shortcut_option_statementContext<'input>,
     ) -> Self::Return {
+        if is_line_group(ctx) {
+            self.generate_code_for_line_group(ctx);
+            return;
+        }
         let end_of_group_label = self.compiler_listener.register_label("group_end");
         let mut labels = Vec::new();
 
@@ -483,23 +581,24 @@ impl<'a, 'input: 'a> YarnSpinnerParserVisitorCompat<'input> for CodeGenerationVi
             // This line statement may have a condition on it. If it does,
             // emit code that evaluates the condition, and add a flag on the
             // 'Add Option' instruction that indicates that a condition exists.
-            let has_line_condition = if let Some(expression) = shortcut
-                .line_statement()
-                .and_then(|ctx| ctx.line_condition())
-                .and_then(|ctx| ctx.expression())
-            {
-                // Evaluate the condition, and leave it on the stack
-                self.visit(expression.as_ref());
-                true
-            } else {
-                false
-            };
+            // Options tagged with #once are only available until they were selected once.
+            let line_statement = shortcut.line_statement().unwrap();
+            let condition = line_statement
+                .line_condition()
+                .and_then(|ctx| ctx.expression());
+            let once_variable = has_once_tag(&line_statement)
+                .then(|| self.once_line_variable(&line_statement));
+            // Evaluate the condition, and leave it on the stack
+            let has_line_condition = self.generate_code_for_availability(
+                condition,
+                once_variable,
+                line_statement.start().deref(),
+            );
 
             // We can now prepare and add the option.
 
             // Start by figuring out the text that we want to add. This will
             // involve evaluating any inline expressions.
-            let line_statement = shortcut.line_statement().unwrap();
             let expression_count = self.generate_code_for_expressions_in_formatted_text(
                 line_statement.line_formatted_text().unwrap().get_children(),
             );
@@ -538,6 +637,12 @@ impl<'a, 'input: 'a> YarnSpinnerParserVisitorCompat<'input> for CodeGenerationVi
                 current_node.instructions.len() as i32,
             );
 
+            let line_statement = shortcut.line_statement().unwrap();
+            if has_once_tag(&line_statement) {
+                let once_variable = self.once_line_variable(&line_statement);
+                Self::generate_once_code(self.compiler_listener, once_variable);
+            }
+
             // Run through all the children statements of the shortcut option
             for child in shortcut.statement_all() {
                 self.visit(child.as_ref());
@@ -561,7 +666,8 @@ impl<'a, 'input: 'a> YarnSpinnerParserVisitorCompat<'input> for CodeGenerationVi
     }
 
     fn visit_declare_statement(&mut self, _ctx: &Declare_statementContext<'input>) -> Self::Return {
-        // Declare statements do not participate in code generation
+        // Declare statements do not participate in code generation.
+        // Locals are initialized when their node starts instead.
     }
 
     /// A <<jump>> command, which immediately jumps to another node, given its name.
@@ -614,6 +720,190 @@ impl<'a, 'input: 'a> CodeGenerationVisitor<'a, 'input> {
             .count()
     }
 
+    /// Evaluates the inline expressions of a line and runs it, ignoring its condition.
+    fn generate_code_for_line(&mut self, ctx: &Line_statementContext<'input>) {
+        // Evaluate the inline expressions and push the results onto the
+        // stack.
+        let formatted_text = ctx.line_formatted_text().unwrap();
+        let expression_count =
+            self.generate_code_for_expressions_in_formatted_text(formatted_text.get_children());
+        let line_id_tag = get_line_id_tag(&ctx.hashtag_all())
+            .expect("Internal error: line should have an implicit or explicit line ID tag, but none was found. This is a bug. Please report it at https://github.com/YarnSpinnerTool/YarnSpinner-Rust/issues/new");
+        let line_id = line_id_tag.text.as_ref().unwrap().get_text().to_owned();
+        self.compiler_listener.emit(
+            Emit::from_op_code(OpCode::RunLine)
+                .with_token(ctx.start().deref())
+                .with_operand(line_id)
+                .with_operand(expression_count),
+        );
+    }
+
+    /// A <<detour>> command, which runs another node and continues here once it completes.
+    /// The destination is either a node name or a single expression, whose value was already pushed by the caller.
+    fn generate_code_for_detour(
+        &mut self,
+        formatted_text: &Command_formatted_textContext<'input>,
+        composed_string: &str,
+        expression_count: usize,
+    ) {
+        let destination = composed_string.trim_start_matches("detour").trim();
+        let is_valid_destination = match expression_count {
+            0 => !destination.is_empty() && !destination.contains(char::is_whitespace),
+            1 => destination == "{0}",
+            _ => false,
+        };
+        if !is_valid_destination {
+            let diagnostic = Diagnostic::from_message(
+                "<<detour>> expects the name of a node or a single expression, e.g. <<detour Shop>> or <<detour {$destination}>>",
+            )
+            .with_file_name(&self.compiler_listener.file.name)
+            .with_parser_context(formatted_text, self.compiler_listener.file.tokens());
+            self.compiler_listener
+                .diagnostics
+                .borrow_mut()
+                .push(diagnostic);
+            return;
+        }
+        if expression_count == 0 {
+            self.compiler_listener.emit(
+                Emit::from_op_code(OpCode::PushString)
+                    .with_token(formatted_text.start().deref())
+                    .with_operand(destination.to_owned()),
+            );
+        }
+        self.compiler_listener.emit(
+            Emit::from_op_code(OpCode::DetourToNode).with_token(formatted_text.start().deref()),
+        );
+    }
+
+    /// for line groups (=> line of text <<if expression>> indent statements dedent)+
+    ///
+    /// Every line is added as a saliency candidate, of which the saliency strategy selects at most one.
+    /// The selected line is run, followed by its statements. If none is selected, the whole group is skipped.
+    fn generate_code_for_line_group(&mut self, ctx: &Shortcut_option_statementContext<'input>) {
+        let items = ctx.shortcut_option_all();
+        if let Some(option) = items
+            .iter()
+            .find(|item| item.SHORTCUT_ARROW().unwrap().get_text() != "=>")
+        {
+            // The option itself may end in a dedent, which has no position in the input,
+            // so only its arrow and line are marked
+            let arrow = &option.SHORTCUT_ARROW().unwrap().symbol;
+            let line_statement = option.line_statement().unwrap();
+            let start = Position {
+                line: arrow.get_line_as_usize().saturating_sub(1),
+                character: arrow.get_column_as_usize(),
+            };
+            let end = line_statement
+                .line_formatted_text()
+                .map_or(start, |text| text.range().end);
+            let diagnostic = Diagnostic::from_message(
+                "Options (->) and lines of a line group (=>) can't be mixed. Separate them with an empty line",
+            )
+            .with_file_name(&self.compiler_listener.file.name)
+            .with_parser_context(line_statement.as_ref(), self.compiler_listener.file.tokens())
+            .with_range(start..end);
+            self.compiler_listener
+                .diagnostics
+                .borrow_mut()
+                .push(diagnostic);
+            return;
+        }
+
+        let end_of_group_label = self.compiler_listener.register_label("linegroup_end");
+        let mut labels = Vec::new();
+        let name = self
+            .compiler_listener
+            .current_node
+            .as_ref()
+            .unwrap()
+            .name
+            .clone();
+        for (line_count, item) in items.iter().enumerate() {
+            let line_statement = item.line_statement().unwrap();
+            let condition = line_statement
+                .line_condition()
+                .and_then(|ctx| ctx.expression());
+
+            // Leave whether the line is available on the stack
+            let once_variable = has_once_tag(&line_statement)
+                .then(|| self.once_line_variable(&line_statement));
+            if !self.generate_code_for_availability(
+                condition.clone(),
+                once_variable,
+                line_statement.start().deref(),
+            ) {
+                self.compiler_listener.emit(
+                    Emit::from_op_code(OpCode::PushBool)
+                        .with_token(line_statement.start().deref())
+                        .with_operand(true),
+                );
+            }
+
+            let line_destination_label = self
+                .compiler_listener
+                .register_label(format!("linegroup_{name}_{}", line_count + 1).as_str());
+            labels.push(line_destination_label.clone());
+
+            let line_id_tag = get_line_id_tag(&line_statement.hashtag_all())
+                .expect("Internal error: no line ID provided. This is a bug. Please report it at https://github.com/YarnSpinnerTool/YarnSpinner-Rust/issues/new");
+            let line_id = line_id_tag.text.as_ref().unwrap().get_text().to_owned();
+            self.compiler_listener.emit(
+                Emit::from_op_code(OpCode::AddSaliencyCandidate)
+                    .with_token(line_statement.start().deref())
+                    .with_operand(line_id)
+                    .with_operand(get_complexity_score(condition.as_deref()))
+                    .with_operand(line_destination_label),
+            );
+        }
+
+        // The top of the stack is now either `true` on top of the label of the selected line,
+        // or `false` if no line was selected. In both cases, the bottom value is popped at the end of the group.
+        let token = ctx.stop();
+        self.compiler_listener
+            .emit(Emit::from_op_code(OpCode::SelectSaliencyCandidate).with_token(token.deref()));
+        self.compiler_listener.emit(
+            Emit::from_op_code(OpCode::JumpIfFalse)
+                .with_token(token.deref())
+                .with_operand(end_of_group_label.clone()),
+        );
+        self.compiler_listener
+            .emit(Emit::from_op_code(OpCode::Pop).with_token(token.deref()));
+        self.compiler_listener
+            .emit(Emit::from_op_code(OpCode::Jump).with_token(token.deref()));
+
+        for (line_count, item) in items.iter().enumerate() {
+            let current_node = self.compiler_listener.current_node.as_mut().unwrap();
+            current_node.labels.insert(
+                labels[line_count].clone(),
+                current_node.instructions.len() as i32,
+            );
+
+            let line_statement = item.line_statement().unwrap();
+            if has_once_tag(&line_statement) {
+                let once_variable = self.once_line_variable(&line_statement);
+                Self::generate_once_code(self.compiler_listener, once_variable);
+            }
+            self.generate_code_for_line(&line_statement);
+            for child in item.statement_all() {
+                self.visit(child.as_ref());
+            }
+
+            self.compiler_listener.emit(
+                Emit::from_op_code(OpCode::JumpTo)
+                    .with_token(item.stop().deref())
+                    .with_operand(end_of_group_label.clone()),
+            );
+        }
+
+        let current_node = self.compiler_listener.current_node.as_mut().unwrap();
+        current_node
+            .labels
+            .insert(end_of_group_label, current_node.instructions.len() as i32);
+        self.compiler_listener
+            .emit(Emit::from_op_code(OpCode::Pop).with_token(token.deref()));
+    }
+
     /// Emits code that calls a method appropriate for the operator
     fn generate_code_for_operation(
         &mut self,
@@ -653,6 +943,138 @@ impl<'a, 'input: 'a> CodeGenerationVisitor<'a, 'input> {
         );
     }
 
+    /// A `<<once>>` block: `<<once if expression>> statements (<<else>> statements)? <<endonce>>`
+    ///
+    /// Behaves like an if statement whose condition additionally requires that the block was never run before.
+    fn generate_code_for_once_clause(
+        &mut self,
+        end_of_if_statement_label: String,
+        ctx: &If_statementContext<'input>,
+    ) {
+        if ctx
+            .COMMAND_ENDIF()
+            .is_none_or(|endif| endif.get_text() != "endonce")
+        {
+            self.push_diagnostic(
+                "Expected an <<endonce>> to match the <<once>> statement",
+                ctx,
+            );
+        }
+        if !ctx.else_if_clause_all().is_empty() {
+            self.push_diagnostic("<<once>> statements can't have <<elseif>> clauses", ctx);
+        }
+
+        let if_clause = ctx.if_clause().unwrap();
+        let command_if = if_clause.COMMAND_IF().unwrap();
+        let token = &command_if.symbol;
+        let key = self
+            .once_block_keys
+            .next_key(&if_clause, self.compiler_listener.file.tokens());
+        let once_variable = self.once_variable(&key);
+        // A plain <<once>> has an empty `true` as its condition, which doesn't need to be checked
+        let condition = if_clause
+            .expression()
+            .filter(|expression| !expression.get_text().is_empty());
+        let end_of_clause_label = self.compiler_listener.register_label("skipclause");
+        self.generate_code_for_availability(condition, Some(once_variable.clone()), token.deref());
+        self.compiler_listener.emit(
+            Emit::from_op_code(OpCode::JumpIfFalse)
+                .with_token(token.deref())
+                .with_operand(end_of_clause_label.clone()),
+        );
+        // The condition is still on the stack, since JumpIfFalse only peeks it
+        self.compiler_listener
+            .emit(Emit::from_op_code(OpCode::Pop).with_token(token.deref()));
+        Self::generate_once_code(self.compiler_listener, once_variable);
+
+        for child in if_clause.statement_all() {
+            self.visit(child.as_ref());
+        }
+        self.compiler_listener.emit(
+            Emit::from_op_code(OpCode::JumpTo)
+                .with_token(if_clause.stop().deref())
+                .with_operand(end_of_if_statement_label),
+        );
+
+        let current_node = self.compiler_listener.current_node.as_mut().unwrap();
+        current_node
+            .labels
+            .insert(end_of_clause_label, current_node.instructions.len() as i32);
+        self.compiler_listener
+            .emit(Emit::from_op_code(OpCode::Pop).with_token(token.deref()));
+    }
+
+    fn push_diagnostic(&mut self, message: &str, ctx: &If_statementContext<'input>) {
+        let diagnostic = Diagnostic::from_message(message)
+            .with_file_name(&self.compiler_listener.file.name)
+            .with_parser_context(ctx, self.compiler_listener.file.tokens());
+        self.compiler_listener
+            .diagnostics
+            .borrow_mut()
+            .push(diagnostic);
+    }
+
+    /// The variable that tracks whether the `<<once>>` block or `#once` line with the given key in the current node was already run.
+    fn once_variable(&self, key: &str) -> String {
+        let node_name = &self.compiler_listener.current_node.as_ref().unwrap().name;
+        Library::generate_unique_once_variable(node_name, key)
+    }
+
+    /// The variable that tracks whether the `#once` line in the current node was already run.
+    fn once_line_variable(&self, ctx: &Line_statementContext<'input>) -> String {
+        self.once_variable(&once_line_key(ctx, self.compiler_listener.file.tokens()))
+    }
+
+    /// Pushes whether a piece of content is available, i.e. its condition (if any) passes
+    /// and it is not a `<<once>>` block or `#once` line that was already run.
+    ///
+    /// Returns `false` without emitting any code if there is nothing to check.
+    fn generate_code_for_availability(
+        &mut self,
+        condition: Option<Rc<ExpressionContextAll<'input>>>,
+        once_variable: Option<String>,
+        token: &impl Token,
+    ) -> bool {
+        if let Some(once_variable) = &once_variable {
+            self.compiler_listener.emit(
+                Emit::from_op_code(OpCode::PushVariable)
+                    .with_token(token)
+                    .with_operand(once_variable.clone()),
+            );
+            self.compiler_listener.emit(
+                Emit::from_op_code(OpCode::PushFloat)
+                    .with_token(token)
+                    .with_operand(1.),
+            );
+            let not_function =
+                Type::Boolean.get_canonical_name_for_method(&Operator::Not.to_string());
+            self.compiler_listener.emit(
+                Emit::from_op_code(OpCode::CallFunc)
+                    .with_token(token)
+                    .with_operand(not_function),
+            );
+        }
+        let Some(condition) = condition else {
+            return once_variable.is_some();
+        };
+        self.visit(condition.as_ref());
+        if once_variable.is_some() {
+            self.compiler_listener.emit(
+                Emit::from_op_code(OpCode::PushFloat)
+                    .with_token(token)
+                    .with_operand(2.),
+            );
+            let and_function =
+                Type::Boolean.get_canonical_name_for_method(&Operator::And.to_string());
+            self.compiler_listener.emit(
+                Emit::from_op_code(OpCode::CallFunc)
+                    .with_token(token)
+                    .with_operand(and_function),
+            );
+        }
+        true
+    }
+
     fn generate_code_for_clause(
         &mut self,
         jump_label: String,
@@ -672,6 +1094,11 @@ impl<'a, 'input: 'a> CodeGenerationVisitor<'a, 'input> {
                     .with_token(expression.start().deref())
                     .with_operand(end_of_clause_label.clone()),
             );
+            // ## Implementation note
+            // The original leaves the condition on the stack when the clause is run,
+            // which leaves the stack unbalanced and trips up `Program::verify`.
+            self.compiler_listener
+                .emit(Emit::from_op_code(OpCode::Pop).with_token(expression.start().deref()));
         }
 
         // running through all of the children statements
@@ -695,3 +1122,142 @@ impl<'a, 'input: 'a> CodeGenerationVisitor<'a, 'input> {
         }
     }
 }
+
+/// Returns `true` if the if clause was generated by the lexer from a `when:` header of a node group member.
+pub(crate) fn is_when_clause(ctx: &If_clauseContext<'_>) -> bool {
+    ctx.COMMAND_IF()
+        .is_some_and(|command_if| command_if.get_text() == "when")
+}
+
+/// Returns `true` if the if clause was generated by the lexer from a `<<once>>` or `<<once if $condition>>` command.
+pub(crate) fn is_once_clause(ctx: &If_clauseContext<'_>) -> bool {
+    ctx.COMMAND_IF()
+        .is_some_and(|command_if| command_if.get_text() == "once")
+}
+
+/// Returns `true` if the line is tagged with `#once`, i.e. should only ever be run or offered as an option until it was seen once.
+pub(crate) fn has_once_tag(ctx: &Line_statementContext<'_>) -> bool {
+    ctx.hashtag_all().iter().any(|hashtag| {
+        hashtag
+            .text
+            .as_ref()
+            .is_some_and(|text| text.get_text() == "once")
+    })
+}
+
+/// Identifies a `#once` line within its node, so that adding, removing or moving other content keeps its once variable.
+///
+/// A line with an explicit `#line:` tag is identified by it. Otherwise, it is identified by its text,
+/// since its implicit line ID contains the path of its file and the number of lines before it.
+/// Untagged `#once` lines with the same text in the same node are thus only run once in total.
+pub(crate) fn once_line_key<'input>(
+    ctx: &Line_statementContext<'input>,
+    tokens: &ActualTokenStream<'input>,
+) -> String {
+    // Implicit line IDs are added to the parse tree, but never to the tokens
+    let explicit_line_id = source_tokens(ctx, tokens)
+        .into_iter()
+        .find(|(token_type, text)| {
+            *token_type == yarnspinnerlexer::HASHTAG_TEXT && text.starts_with("line:")
+        })
+        .map(|(_, text)| text);
+    explicit_line_id.unwrap_or_else(|| {
+        let text = ctx.line_formatted_text().unwrap();
+        format!("text.{:016x}", stable_hash(&source_text(text.as_ref(), tokens)))
+    })
+}
+
+/// Hands out the keys that identify the `<<once>>` blocks of a node, see [`OnceBlockKeys::next_key`].
+#[derive(Debug, Clone, Default)]
+pub(crate) struct OnceBlockKeys {
+    /// How often each block content was seen so far.
+    counts: HashMap<u64, usize>,
+}
+
+impl OnceBlockKeys {
+    /// Identifies a `<<once>>` block within its node by its condition and the content before any `<<else>>`,
+    /// so that adding, removing or moving other content keeps its once variable.
+    /// Only blocks with the same content are told apart by how many of them come before.
+    pub(crate) fn next_key<'input>(
+        &mut self,
+        ctx: &If_clauseContext<'input>,
+        tokens: &ActualTokenStream<'input>,
+    ) -> String {
+        let hash = stable_hash(&source_text(ctx, tokens));
+        let count = self.counts.entry(hash).or_default();
+        let key = match *count {
+            0 => format!("block.{hash:016x}"),
+            count => format!("block.{hash:016x}.{count}"),
+        };
+        *count += 1;
+        key
+    }
+}
+
+/// The types and texts of the tokens of a context that the parser sees, i.e. without whitespace and comments.
+fn source_tokens<'input>(
+    ctx: &impl ParserRuleContext<'input>,
+    tokens: &ActualTokenStream<'input>,
+) -> Vec<(isize, String)> {
+    (ctx.start().get_token_index()..=ctx.stop().get_token_index())
+        .map(|index| tokens.get(index))
+        .filter(|token| token.get_channel() == TOKEN_DEFAULT_CHANNEL)
+        .map(|token| (token.get_token_type(), token.get_text().to_owned()))
+        .collect()
+}
+
+/// The text of a context without any whitespace, so that formatting the source does not change it.
+fn source_text<'input>(
+    ctx: &impl ParserRuleContext<'input>,
+    tokens: &ActualTokenStream<'input>,
+) -> String {
+    source_tokens(ctx, tokens)
+        .into_iter()
+        .flat_map(|(_, text)| text.chars().collect::<Vec<_>>())
+        .filter(|character| !character.is_whitespace())
+        .collect()
+}
+
+/// The 64 bit FNV-1a hash of the text. Unlike [`std::hash::DefaultHasher`], it never changes between Rust versions,
+/// which matters because it ends up in the names of variables that are part of saved games.
+fn stable_hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Returns `true` if the shortcut option statement is actually a line group, i.e. uses `=>` instead of `->`.
+pub(crate) fn is_line_group(ctx: &Shortcut_option_statementContext<'_>) -> bool {
+    ctx.shortcut_option_all().iter().any(|item| {
+        item.SHORTCUT_ARROW()
+            .is_some_and(|arrow| arrow.get_text() == "=>")
+    })
+}
+
+/// The complexity score of a saliency candidate's condition, which is the number of terms joined by `and`.
+/// A missing condition and a condition of `true` (or `always`) have a score of 0.
+pub(crate) fn get_complexity_score(expression: Option<&ExpressionContextAll<'_>>) -> usize {
+    let Some(expression) = expression else {
+        return 0;
+    };
+    match expression {
+        ExpressionContextAll::ExpAndOrXorContext(ctx)
+            if ctx.op.as_ref().unwrap().token_type == yarnspinnerlexer::OPERATOR_LOGICAL_AND =>
+        {
+            get_complexity_score(ctx.expression(0).as_deref())
+                + get_complexity_score(ctx.expression(1).as_deref())
+        }
+        ExpressionContextAll::ExpParensContext(ctx) => {
+            get_complexity_score(ctx.expression().as_deref())
+        }
+        ExpressionContextAll::ExpValueContext(ctx)
+            if matches!(
+                ctx.value().as_deref(),
+                Some(ValueContextAll::ValueTrueContext(_))
+            ) =>
+        {
+            0
+        }
+        _ => 1,
+    }
+}
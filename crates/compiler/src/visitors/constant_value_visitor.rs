@@ -1,35 +1,65 @@
 //! Adapted from <https://github.com/YarnSpinnerTool/YarnSpinner/blob/da39c7195107d8211f21c263e4084f773b84eaff/YarnSpinner.Compiler/ConstantValueVisitor.cs>
 
+use crate::parser_rule_context_ext::ParserRuleContextExt;
 use crate::prelude::generated::yarnspinnerparser::*;
 use crate::prelude::generated::yarnspinnerparservisitor::YarnSpinnerParserVisitorCompat;
 use crate::prelude::*;
+use crate::visitors::{enum_of_case, resolve_enum_case};
 use antlr_rust::parser::ParserNodeType;
+use antlr_rust::rule_context::CustomRuleContext;
+use antlr_rust::token::Token;
+use antlr_rust::token_factory::TokenFactory;
 use antlr_rust::tree::{ParseTree, ParseTreeVisitorCompat, VisitChildren};
 use std::mem;
 use std::ops::{Deref, DerefMut};
 use yarnspinner_core::prelude::*;
+use yarnspinner_core::types::{EnumType, Type};
 
 /// A visitor that visits any valid constant value, and returns a [`InternalValue`].
-/// Currently only supports terminals, not expressions,
-/// even if those expressions would be constant.
+/// Supports terminals, as well as parentheses, negations and `!` around them.
+/// Other expressions are reported as not constant, even if they would be.
 #[derive(Clone)]
-pub(crate) struct ConstantValueVisitor<'input> {
+pub(crate) struct ConstantValueVisitor<'a, 'input> {
     pub(crate) diagnostics: Vec<Diagnostic>,
+    enums: &'a [EnumType],
     _dummy: ConstantValue,
     file: FileParseResult<'input>,
 }
 
-impl<'input> ConstantValueVisitor<'input> {
-    pub(crate) fn new(diagnostics: Vec<Diagnostic>, file: FileParseResult<'input>) -> Self {
+impl<'a, 'input> ConstantValueVisitor<'a, 'input> {
+    pub(crate) fn new(
+        diagnostics: Vec<Diagnostic>,
+        enums: &'a [EnumType],
+        file: FileParseResult<'input>,
+    ) -> Self {
         Self {
             diagnostics,
+            enums,
             file,
             _dummy: ConstantValue::non_panicking_default(),
         }
     }
 }
 
-impl<'input> ParseTreeVisitorCompat<'input> for ConstantValueVisitor<'input> {
+impl<'a, 'input> ConstantValueVisitor<'a, 'input> {
+    /// Reports that the expression is not a constant value, e.g. because it `is an expression`.
+    fn not_constant<T>(&mut self, ctx: &T, reason: &str) -> ConstantValue
+    where
+        T: ParserRuleContextExt<'input>,
+    <<<<T as CustomRuleContext<'input>>::TF as TokenFactory<'input>>::Inner as Token>::Data as ToOwned>::Owned: Into<String>{
+        let text = ctx.get_text_with_whitespace(self.file.tokens());
+        let message =
+            format!("Variable declarations must be constant values, but `{text}` {reason}");
+        self.diagnostics.push(
+            Diagnostic::from_message(message)
+                .with_file_name(&self.file.name)
+                .with_parser_context(ctx, self.file.tokens()),
+        );
+        ConstantValue::non_panicking_default()
+    }
+}
+
+impl<'a, 'input> ParseTreeVisitorCompat<'input> for ConstantValueVisitor<'a, 'input> {
     type Node = YarnSpinnerParserContextType;
     type Return = ConstantValue;
 
@@ -46,7 +76,59 @@ impl<'input> ParseTreeVisitorCompat<'input> for ConstantValueVisitor<'input> {
     }
 }
 
-impl<'input> YarnSpinnerParserVisitorCompat<'input> for ConstantValueVisitor<'input> {
+impl<'a, 'input> YarnSpinnerParserVisitorCompat<'input> for ConstantValueVisitor<'a, 'input> {
+    fn visit_expValue(&mut self, ctx: &ExpValueContext<'input>) -> Self::Return {
+        self.visit(ctx.value().unwrap().as_ref())
+    }
+
+    fn visit_expParens(&mut self, ctx: &ExpParensContext<'input>) -> Self::Return {
+        self.visit(ctx.expression().unwrap().as_ref())
+    }
+
+    fn visit_expNegative(&mut self, ctx: &ExpNegativeContext<'input>) -> Self::Return {
+        let value = self.visit(ctx.expression().unwrap().as_ref());
+        match value.0 {
+            Some(InternalValue {
+                raw_value: YarnValue::Number(number),
+                ..
+            }) => InternalValue::from(-number).into(),
+            Some(_) => self.not_constant(ctx, "cannot be negated"),
+            None => value,
+        }
+    }
+
+    fn visit_expNot(&mut self, ctx: &ExpNotContext<'input>) -> Self::Return {
+        let value = self.visit(ctx.expression().unwrap().as_ref());
+        match value.0 {
+            Some(InternalValue {
+                raw_value: YarnValue::Boolean(boolean),
+                ..
+            }) => InternalValue::from(!boolean).into(),
+            Some(_) => self.not_constant(ctx, "cannot be inverted"),
+            None => value,
+        }
+    }
+
+    fn visit_expMultDivMod(&mut self, ctx: &ExpMultDivModContext<'input>) -> Self::Return {
+        self.not_constant(ctx, "is an expression")
+    }
+
+    fn visit_expAddSub(&mut self, ctx: &ExpAddSubContext<'input>) -> Self::Return {
+        self.not_constant(ctx, "is an expression")
+    }
+
+    fn visit_expComparison(&mut self, ctx: &ExpComparisonContext<'input>) -> Self::Return {
+        self.not_constant(ctx, "is an expression")
+    }
+
+    fn visit_expEquality(&mut self, ctx: &ExpEqualityContext<'input>) -> Self::Return {
+        self.not_constant(ctx, "is an expression")
+    }
+
+    fn visit_expAndOrXor(&mut self, ctx: &ExpAndOrXorContext<'input>) -> Self::Return {
+        self.not_constant(ctx, "is an expression")
+    }
+
     fn visit_valueNumber(&mut self, ctx: &ValueNumberContext<'input>) -> Self::Return {
         let text = ctx.get_text();
         if let Ok(number) = text.parse::<f32>() {
@@ -103,8 +185,44 @@ impl<'input> YarnSpinnerParserVisitorCompat<'input> for ConstantValueVisitor<'in
 
     fn visit_valueFunc(&mut self, ctx: &ValueFuncContext<'input>) -> Self::Return {
         let text = ctx.get_text();
-        let message =
-            format!("Variable declarations must be constant values, but `{text}` is a function",);
+        let enum_case = ctx
+            .function_call()
+            .and_then(|call| resolve_enum_case(self.enums, &call));
+        let message = match enum_case {
+            Some(Ok((enum_type, case))) => {
+                // Guaranteed to be `Some` because the case was resolved
+                return InternalValue {
+                    r#type: Type::Enum(enum_type.clone()),
+                    raw_value: enum_type.value_of(&case).unwrap(),
+                }
+                .into();
+            }
+            Some(Err(message)) => {
+                let enum_type = ctx
+                    .function_call()
+                    .and_then(|call| enum_of_case(self.enums, &call));
+                if let Some((enum_type, default_value)) = enum_type
+                    .and_then(|enum_type| Some((enum_type, enum_type.default_value()?)))
+                {
+                    self.diagnostics.push(
+                        Diagnostic::from_message(message)
+                            .with_file_name(&self.file.name)
+                            .with_parser_context(ctx, self.file.tokens()),
+                    );
+                    // Like for unparsable numbers, this value only lets the compiler continue and never ends up in a program.
+                    // Knowing the variable's type avoids follow-up errors about it.
+                    return InternalValue {
+                        r#type: Type::Enum(enum_type.clone()),
+                        raw_value: default_value,
+                    }
+                    .into();
+                }
+                message
+            }
+            None => {
+                format!("Variable declarations must be constant values, but `{text}` is a function",)
+            }
+        };
         self.diagnostics.push(
             Diagnostic::from_message(message)
                 .with_file_name(&self.file.name)
@@ -1,12 +1,18 @@
 //! Adapted from <https://github.com/YarnSpinnerTool/YarnSpinner/blob/da39c7195107d8211f21c263e4084f773b84eaff/YarnSpinner.Compiler/DeclarationVisitor.cs>
 
+use crate::parser_rule_context_ext::ParserRuleContextExt;
+use crate::prelude::generated::yarnspinnerlexer;
 use crate::prelude::generated::yarnspinnerparser::*;
 use crate::prelude::generated::yarnspinnerparservisitor::YarnSpinnerParserVisitorCompat;
 use crate::prelude::*;
 use crate::visitors::constant_value_visitor::ConstantValueVisitor;
+use crate::visitors::TypeCheckVisitor;
+use antlr_rust::parser_rule_context::ParserRuleContext;
 use antlr_rust::token::Token;
+use antlr_rust::token_stream::TokenStream;
 use antlr_rust::tree::{ParseTree, ParseTreeVisitorCompat};
 use regex::Regex;
+use std::rc::Rc;
 use yarnspinner_core::prelude::*;
 use yarnspinner_core::types::*;
 
@@ -34,6 +40,12 @@ pub(crate) struct DeclarationVisitor<'input> {
     /// The collection of variable declarations we know about before starting our work
     existing_declarations: Vec<Declaration>,
 
+    /// The enums declared in this compilation job
+    enums: Vec<EnumType>,
+
+    /// The smart variables found so far.
+    pub(crate) smart_variables: Vec<SmartVariable>,
+
     /// The name of the node that we're currently visiting.
     current_node_name: Option<String>,
 
@@ -46,15 +58,18 @@ pub(crate) struct DeclarationVisitor<'input> {
 impl<'input> DeclarationVisitor<'input> {
     pub(crate) fn new(
         existing_declarations: Vec<Declaration>,
+        enums: Vec<EnumType>,
         file: FileParseResult<'input>,
     ) -> Self {
         Self {
             file,
             existing_declarations,
+            enums,
             new_declarations: Default::default(),
             regex: Regex::new(r"[\[<>\]{}|:\s#$]").unwrap(),
             file_tags: Default::default(),
             diagnostics: Default::default(),
+            smart_variables: Default::default(),
             current_node_name: None,
             _dummy: Default::default(),
         }
@@ -116,11 +131,19 @@ impl<'input> YarnSpinnerParserVisitorCompat<'input> for DeclarationVisitor<'inpu
         let variable_context = ctx.variable().unwrap();
         let variable_name = variable_context.get_text();
 
+        // Locals live in their node, so they are declared under a name that is unique to it
+        let declared_name = match self.current_node_name.as_ref() {
+            Some(node_name) if is_local_declaration(ctx) => {
+                Library::generate_local_variable_name(node_name, &variable_name)
+            }
+            _ => variable_name.clone(),
+        };
+
         // Does this variable name already exist in our declarations?
         let existing_explicit_declaration = self
             .declarations()
             .into_iter()
-            .find(|d| !d.is_implicit && d.name == variable_name);
+            .find(|d| !d.is_implicit && d.name == declared_name);
         if let Some(existing_explicit_declaration) = existing_explicit_declaration {
             // Then this is an error, because you can't have two explicit declarations for the same variable.
             let line = existing_explicit_declaration
@@ -128,8 +151,8 @@ impl<'input> YarnSpinnerParserVisitorCompat<'input> for DeclarationVisitor<'inpu
                 .map(|l| format!(", line: {l}"))
                 .unwrap_or_default();
             let msg = format!(
-                "{} has already been declared in {}{line}",
-                existing_explicit_declaration.name, existing_explicit_declaration.source_file_name,
+                "{variable_name} has already been declared in {}{line}",
+                existing_explicit_declaration.source_file_name,
             );
             self.diagnostics.push(
                 Diagnostic::from_message(msg)
@@ -139,50 +162,14 @@ impl<'input> YarnSpinnerParserVisitorCompat<'input> for DeclarationVisitor<'inpu
             return;
         }
 
-        // Figure out the value and its type
-        let mut constant_value_visitor =
-            ConstantValueVisitor::new(self.diagnostics.clone(), self.file.clone());
-        let value_context = ctx.value().unwrap();
-        let value = constant_value_visitor.visit(value_context.as_ref());
-        self.diagnostics
-            .extend_from_slice(&constant_value_visitor.diagnostics);
-
         // Did the source code name an explicit type?
-        if let Some(declaration_type) = ctx.declaration_type.as_ref() {
-            let explicit_type = match keyword_to_type(declaration_type.get_text()) {
-                Some(builtin_type) => builtin_type,
-
-                // The type name provided didn't map to a built-in
-                // type. Look for the type in our type collection.
-                None => match Type::EXPLICITLY_CONSTRUCTABLE
-                    .iter()
-                    .find(|t| t.to_string() == declaration_type.get_text())
-                {
-                    Some(explicit_type) => explicit_type.clone(),
-                    None => {
-                        // We didn't find a type by this name.
-                        let msg = format!("Unknown type {}", declaration_type.get_text());
-                        self.diagnostics.push(
-                            Diagnostic::from_message(msg)
-                                .with_file_name(&self.file.name)
-                                .with_parser_context(ctx, self.file.tokens()),
-                        );
-                        return;
-                    }
-                },
-            };
-
-            // Check that the type we've found is compatible with the
-            // type of the value that was provided - if it doesn't,
-            // that's a type error
-            if let Some(value) = value.as_ref() {
-                if !value.r#type.is_sub_type_of(&explicit_type) {
-                    let msg = format!(
-                        "Type {} does not match value {} ({})",
-                        declaration_type.get_text(),
-                        value_context.get_text(),
-                        value.r#type.format()
-                    );
+        let explicit_type = match ctx.declaration_type.as_ref() {
+            None => None,
+            Some(declaration_type) => match self.resolve_type(declaration_type.get_text()) {
+                Some(explicit_type) => Some(explicit_type),
+                None => {
+                    // We didn't find a type by this name.
+                    let msg = format!("Unknown type {}", declaration_type.get_text());
                     self.diagnostics.push(
                         Diagnostic::from_message(msg)
                             .with_file_name(&self.file.name)
@@ -190,13 +177,57 @@ impl<'input> YarnSpinnerParserVisitorCompat<'input> for DeclarationVisitor<'inpu
                     );
                     return;
                 }
+            },
+        };
+
+        let description = get_document_comments(self.file.tokens(), ctx);
+        let description_as_option = (!description.is_empty()).then_some(description);
+        // The lexer wraps every value that is more than a single token, e.g. `-1` or `(5)`,
+        // so whether it is actually a smart variable depends on whether the value is constant.
+        let expression = smart_variable_expression(ctx);
+        let constant_expression_value = expression
+            .as_ref()
+            .and_then(|expression| self.constant_value(expression.as_ref()));
+        if is_smart_variable_declaration(ctx) && constant_expression_value.is_none() {
+            self.declare_smart_variable(ctx, declared_name, explicit_type, description_as_option);
+            return;
+        }
+
+        // Figure out the value and its type
+        let value_text = match expression.as_ref() {
+            Some(expression) => expression.get_text_with_whitespace(self.file.tokens()),
+            None => ctx.value().unwrap().get_text(),
+        };
+        let value = constant_expression_value.or_else(|| {
+            let mut constant_value_visitor =
+                ConstantValueVisitor::new(self.diagnostics.clone(), &self.enums, self.file.clone());
+            let value = constant_value_visitor.visit(ctx.value().unwrap().as_ref());
+            self.diagnostics = constant_value_visitor.diagnostics;
+            value.0
+        });
+
+        // Check that the type we've found is compatible with the
+        // type of the value that was provided - if it doesn't,
+        // that's a type error
+        if let (Some(explicit_type), Some(value)) = (explicit_type.as_ref(), value.as_ref()) {
+            if !value.r#type.is_sub_type_of(explicit_type) {
+                let msg = format!(
+                    "Type {} does not match value {} ({})",
+                    ctx.declaration_type.as_ref().unwrap().get_text(),
+                    value_text,
+                    value.r#type.format()
+                );
+                self.diagnostics.push(
+                    Diagnostic::from_message(msg)
+                        .with_file_name(&self.file.name)
+                        .with_parser_context(ctx, self.file.tokens()),
+                );
+                return;
             }
         }
         // We're done creating the declaration!
-        let description = get_document_comments(self.file.tokens(), ctx);
-        let description_as_option = (!description.is_empty()).then_some(description);
         if let Some(value) = value.as_ref() {
-            let declaration = Declaration::new(variable_name, value.r#type.clone())
+            let declaration = Declaration::new(declared_name, value.r#type.clone())
                 .with_default_value(value.raw_value.clone())
                 .with_description_optional(description_as_option)
                 .with_source_file_name(self.file.name.clone())
@@ -207,6 +238,156 @@ impl<'input> YarnSpinnerParserVisitorCompat<'input> for DeclarationVisitor<'inpu
     }
 }
 
+impl<'input> DeclarationVisitor<'input> {
+    /// Looks up a type named in a declaration, either a built-in one or an enum.
+    fn resolve_type(&self, type_name: &str) -> Option<Type> {
+        keyword_to_type(type_name).or_else(|| {
+            Type::EXPLICITLY_CONSTRUCTABLE
+                .iter()
+                .cloned()
+                .chain(self.enums.iter().cloned().map(Type::from))
+                .find(|t| t.to_string() == type_name)
+        })
+    }
+
+    /// Evaluates an expression if it is a constant value, e.g. `-1` or `(Mood.Happy)`.
+    fn constant_value(&self, expression: &ExpressionContextAll<'input>) -> Option<InternalValue> {
+        let mut constant_value_visitor =
+            ConstantValueVisitor::new(Vec::new(), &self.enums, self.file.clone());
+        let value = constant_value_visitor.visit(expression);
+        // Anything that isn't constant is a smart variable, whose problems are reported when declaring it
+        constant_value_visitor
+            .diagnostics
+            .is_empty()
+            .then_some(value.0)
+            .flatten()
+    }
+
+    /// Declares a variable whose value is an expression that is evaluated every time the variable is read.
+    /// Its type is inferred from the expression unless it is given explicitly.
+    /// Whether the expression actually has that type is checked later by the [`TypeCheckVisitor`].
+    fn declare_smart_variable(
+        &mut self,
+        ctx: &Declare_statementContext<'input>,
+        declared_name: String,
+        explicit_type: Option<Type>,
+        description: Option<String>,
+    ) {
+        let Some(expression) = smart_variable_expression(ctx) else {
+            let msg = format!(
+                "The value of {declared_name} must be a single expression, but is `{}`",
+                ctx.value()
+                    .unwrap()
+                    .get_text_with_whitespace(self.file.tokens())
+            );
+            self.diagnostics.push(
+                Diagnostic::from_message(msg)
+                    .with_file_name(&self.file.name)
+                    .with_parser_context(ctx, self.file.tokens()),
+            );
+            return;
+        };
+
+        let r#type = explicit_type.or_else(|| {
+            let mut type_check_visitor = TypeCheckVisitor::new(
+                self.declarations(),
+                self.enums.clone(),
+                None,
+                self.file.clone(),
+            );
+            // Any problems with the expression are reported when type checking the whole file
+            type_check_visitor.visit(expression.as_ref())
+        });
+        let Some(r#type) = r#type else {
+            let msg = format!(
+                "Can't figure out the type of smart variable {declared_name}. Declare the variables it uses before it, or specify its type with `as`."
+            );
+            self.diagnostics.push(
+                Diagnostic::from_message(msg)
+                    .with_file_name(&self.file.name)
+                    .with_parser_context(ctx, self.file.tokens()),
+            );
+            return;
+        };
+
+        // Collect the variables the expression reads to detect cyclic definitions later
+        let tokens = self.file.tokens();
+        let dependencies = (expression.start().get_token_index()
+            ..=expression.stop().get_token_index())
+            .map(|index| tokens.get(index))
+            .filter(|token| token.get_token_type() == yarnspinnerlexer::VAR_ID)
+            .map(|token| token.get_text().to_owned())
+            .collect();
+        self.smart_variables.push(SmartVariable {
+            name: declared_name.clone(),
+            dependencies,
+            // The message is filled in if the definition turns out to be cyclic
+            diagnostic: Diagnostic::from_message("")
+                .with_file_name(&self.file.name)
+                .with_parser_context(ctx, self.file.tokens()),
+        });
+
+        let declaration = Declaration::new(declared_name, r#type)
+            .with_description_optional(description)
+            .with_source_file_name(self.file.name.clone())
+            .with_source_node_name_optional(self.current_node_name.clone())
+            .with_range(ctx.variable().unwrap().range())
+            .with_smart_variable();
+        self.new_declarations.push(declaration);
+    }
+}
+
+/// A smart variable found by the [`DeclarationVisitor`], kept around to detect cyclic definitions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SmartVariable {
+    pub(crate) name: String,
+    /// The names of the variables read by the smart variable's expression.
+    pub(crate) dependencies: Vec<String>,
+    /// Points to the declaration of the smart variable.
+    pub(crate) diagnostic: Diagnostic,
+}
+
+/// The name of the function that the lexer wraps the value of a smart variable into,
+/// since the generated parser only accepts single values in declarations.
+pub(crate) const SMART_VARIABLE_FUNCTION: &str = "$Yarn.Internal.SmartVariable";
+
+/// Returns `true` if the lexer wrapped the statement's value, which makes it a smart variable unless the value is constant, e.g. `-1`.
+pub(crate) fn is_smart_variable_declaration(ctx: &Declare_statementContext<'_>) -> bool {
+    smart_variable_function_call(ctx).is_some()
+}
+
+/// Returns the expression of a smart variable's declaration.
+/// Returns [`None`] if the statement does not declare a smart variable, or if its value is not a single expression.
+pub(crate) fn smart_variable_expression<'input>(
+    ctx: &Declare_statementContext<'input>,
+) -> Option<Rc<ExpressionContextAll<'input>>> {
+    let expressions = smart_variable_function_call(ctx)?.expression_all();
+    match expressions.as_slice() {
+        [expression] => Some(expression.clone()),
+        _ => None,
+    }
+}
+
+fn smart_variable_function_call<'input>(
+    ctx: &Declare_statementContext<'input>,
+) -> Option<Rc<Function_callContextAll<'input>>> {
+    let value = ctx.value()?;
+    let ValueContextAll::ValueFuncContext(value) = value.as_ref() else {
+        return None;
+    };
+    value.function_call().filter(|call| {
+        call.FUNC_ID()
+            .is_some_and(|id| id.get_text() == SMART_VARIABLE_FUNCTION)
+    })
+}
+
+/// Returns `true` if the statement is a `<<local>>` rather than a `<<declare>>`.
+/// The lexer turns the former into the latter, but keeps its keyword.
+fn is_local_declaration(ctx: &Declare_statementContext<'_>) -> bool {
+    ctx.COMMAND_DECLARE()
+        .is_some_and(|keyword| keyword.get_text().trim() == "local")
+}
+
 fn keyword_to_type(keyword: &str) -> Option<Type> {
     match keyword {
         "string" => Some(Type::String),
@@ -236,8 +417,10 @@ mod tests {
         let result = Compiler {
             files: vec![file],
             library: Default::default(),
+            command_library: None,
             compilation_type: CompilationType::FullCompilation,
             variable_declarations: vec![],
+            optimize: false,
         }
         .compile()
         .unwrap();
@@ -326,8 +509,10 @@ mod tests {
         let result = Compiler {
             files: vec![file.clone()],
             library: Default::default(),
+            command_library: None,
             compilation_type: CompilationType::FullCompilation,
             variable_declarations: vec![],
+            optimize: false,
         }
         .compile();
 
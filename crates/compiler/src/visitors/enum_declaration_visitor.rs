@@ -0,0 +1,240 @@
+//! Not part of the original, which only gained enums in Yarn Spinner 3.
+//!
+//! ## Implementation notes
+//!
+//! The generated parser has no rules for enums, so the lexer collapses `<<enum>>`, `<<case>>` and `<<endenum>>`
+//! into regular commands and rewrites `Mood.Happy` into a call of a function named `Mood.Happy`.
+//! This visitor picks the enum declarations up from these commands.
+
+use crate::prelude::generated::yarnspinnerparser::*;
+use crate::prelude::generated::yarnspinnerparservisitor::YarnSpinnerParserVisitorCompat;
+use crate::prelude::*;
+use antlr_rust::tree::{ParseTree, ParseTreeVisitorCompat, Tree};
+use yarnspinner_core::types::*;
+
+/// A command that is part of an enum declaration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum EnumCommand {
+    /// `<<enum Mood>>`
+    Enum(String),
+    /// `<<case Happy>>`
+    Case(String),
+    /// `<<endenum>>`
+    EndEnum,
+}
+
+impl EnumCommand {
+    /// Returns the enum command represented by a command statement, if any.
+    pub(crate) fn parse(ctx: &Command_statementContext<'_>) -> Option<Self> {
+        let formatted_text = ctx.command_formatted_text()?;
+        // The lexer collapses enum commands into a single token.
+        if formatted_text.get_child_count() != 1 {
+            return None;
+        }
+        let text = formatted_text.get_text();
+        let (keyword, argument) = text
+            .trim()
+            .split_once(char::is_whitespace)
+            .unwrap_or((text.trim(), ""));
+        let argument = argument.trim().to_owned();
+        match keyword {
+            "enum" => Some(Self::Enum(argument)),
+            "case" => Some(Self::Case(argument)),
+            "endenum" if argument.is_empty() => Some(Self::EndEnum),
+            _ => None,
+        }
+    }
+}
+
+/// A visitor that extracts enum declarations from a parse tree.
+pub(crate) struct EnumDeclarationVisitor<'input> {
+    /// The enums that were declared in the visited file.
+    pub(crate) new_enums: Vec<EnumType>,
+
+    pub(crate) diagnostics: Vec<Diagnostic>,
+
+    /// The enums we know about before starting our work
+    existing_enums: Vec<EnumType>,
+
+    /// The enum whose cases we're currently visiting, along with the diagnostic to emit if it is never closed.
+    current_enum: Option<(EnumType, Diagnostic)>,
+
+    file: FileParseResult<'input>,
+    _dummy: (),
+}
+
+impl<'input> EnumDeclarationVisitor<'input> {
+    pub(crate) fn new(existing_enums: Vec<EnumType>, file: FileParseResult<'input>) -> Self {
+        Self {
+            new_enums: Default::default(),
+            diagnostics: Default::default(),
+            existing_enums,
+            current_enum: None,
+            file,
+            _dummy: Default::default(),
+        }
+    }
+
+    fn enums(&self) -> impl Iterator<Item = &EnumType> {
+        self.existing_enums.iter().chain(self.new_enums.iter())
+    }
+
+    fn push_diagnostic(
+        &mut self,
+        message: impl Into<String>,
+        ctx: &Command_statementContext<'input>,
+    ) {
+        self.diagnostics.push(
+            Diagnostic::from_message(message)
+                .with_file_name(&self.file.name)
+                .with_parser_context(ctx, self.file.tokens()),
+        );
+    }
+
+    fn visit_enum_command(&mut self, command: EnumCommand, ctx: &Command_statementContext<'input>) {
+        match command {
+            EnumCommand::Enum(name) => {
+                if let Some((current_enum, _)) = &self.current_enum {
+                    let message = format!(
+                        "Enums can't be nested, but <<enum {name}>> is inside <<enum {current_enum}>>"
+                    );
+                    self.push_diagnostic(message, ctx);
+                } else if !is_identifier(&name) {
+                    self.push_diagnostic(format!("\"{name}\" is not a valid enum name"), ctx);
+                } else if self.enums().any(|e| e.name == name) {
+                    self.push_diagnostic(format!("Enum {name} has already been declared"), ctx);
+                } else {
+                    let unclosed_diagnostic = Diagnostic::from_message(format!(
+                        "Expected an <<endenum>> to match the <<enum {name}>> statement"
+                    ))
+                    .with_file_name(&self.file.name)
+                    .with_parser_context(ctx, self.file.tokens());
+                    self.current_enum = Some((EnumType::new(name), unclosed_diagnostic));
+                }
+            }
+            EnumCommand::Case(name) => {
+                let Some((current_enum, _)) = &mut self.current_enum else {
+                    let message = format!("<<case {name}>> must be inside an <<enum>> statement");
+                    self.push_diagnostic(message, ctx);
+                    return;
+                };
+                if name.contains('=') {
+                    let message = format!(
+                        "The cases of enum {current_enum} can't have explicit values, as enum values are stored as the names of their cases"
+                    );
+                    self.push_diagnostic(message, ctx);
+                } else if !is_identifier(&name) {
+                    self.push_diagnostic(format!("\"{name}\" is not a valid enum case name"), ctx);
+                } else if current_enum.has_case(&name) {
+                    let message = format!("Enum {current_enum} already has a case named {name}");
+                    self.push_diagnostic(message, ctx);
+                } else {
+                    current_enum.add_case(name);
+                }
+            }
+            EnumCommand::EndEnum => match self.current_enum.take() {
+                None => self.push_diagnostic("Unexpected <<endenum>> without an <<enum>>", ctx),
+                Some((current_enum, _)) if current_enum.cases.is_empty() => {
+                    let message = format!("Enum {current_enum} must have at least one case");
+                    self.push_diagnostic(message, ctx);
+                }
+                Some((current_enum, _)) => self.new_enums.push(current_enum),
+            },
+        }
+    }
+}
+
+impl<'input> ParseTreeVisitorCompat<'input> for EnumDeclarationVisitor<'input> {
+    type Node = YarnSpinnerParserContextType;
+    type Return = ();
+
+    fn temp_result(&mut self) -> &mut Self::Return {
+        &mut self._dummy
+    }
+}
+
+impl<'input> YarnSpinnerParserVisitorCompat<'input> for EnumDeclarationVisitor<'input> {
+    fn visit_node(&mut self, ctx: &NodeContext<'input>) -> Self::Return {
+        if let Some(body) = ctx.body() {
+            self.visit(body.as_ref());
+        }
+        if let Some((_, unclosed_diagnostic)) = self.current_enum.take() {
+            self.diagnostics.push(unclosed_diagnostic);
+        }
+    }
+
+    fn visit_statement(&mut self, ctx: &StatementContext<'input>) -> Self::Return {
+        let command_statement = ctx.command_statement();
+        let enum_command = command_statement
+            .as_ref()
+            .and_then(|command_statement| EnumCommand::parse(command_statement));
+        match (command_statement, enum_command) {
+            (Some(command_statement), Some(enum_command)) => {
+                self.visit_enum_command(enum_command, &command_statement);
+            }
+            _ if self.current_enum.is_some() => {
+                let (current_enum, _) = self.current_enum.as_ref().unwrap();
+                let message =
+                    format!("Only <<case>> statements are allowed inside <<enum {current_enum}>>");
+                self.diagnostics.push(
+                    Diagnostic::from_message(message)
+                        .with_file_name(&self.file.name)
+                        .with_parser_context(ctx, self.file.tokens()),
+                );
+            }
+            _ => self.visit_children(ctx),
+        }
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Splits a qualified enum case like `Mood.Happy` into the names of the enum and the case.
+/// The lexer turns enum cases into a call of a function named `Mood.Happy` with parentheses that don't appear in the input.
+/// Calls of functions with a `.` in their name, e.g. `Number.Add(1, 2)`, have actual parentheses and return `None`.
+pub(crate) fn enum_case_name(ctx: &Function_callContext<'_>) -> Option<(String, String)> {
+    let has_parentheses = ctx
+        .LPAREN()
+        .is_some_and(|parenthesis| !parenthesis.get_text().is_empty());
+    if has_parentheses {
+        return None;
+    }
+    let qualified_name = ctx.FUNC_ID()?.get_text();
+    let (enum_name, case) = qualified_name.split_once('.')?;
+    Some((enum_name.to_owned(), case.to_owned()))
+}
+
+/// Resolves a qualified enum case like `Mood.Happy`.
+///
+/// Returns `None` if the function call does not refer to an enum case at all,
+/// and an error message if the enum or its case is unknown.
+/// Otherwise, returns the enum and the name of the case.
+pub(crate) fn resolve_enum_case<'a>(
+    enums: &'a [EnumType],
+    ctx: &Function_callContext<'_>,
+) -> Option<Result<(&'a EnumType, String), String>> {
+    let (enum_name, case) = enum_case_name(ctx)?;
+    let qualified_name = format!("{enum_name}.{case}");
+    let result = match enums.iter().find(|e| e.name == enum_name) {
+        None => Err(format!("Unknown enum {enum_name} in {qualified_name}")),
+        Some(enum_type) if !enum_type.has_case(&case) => {
+            Err(format!("Enum {enum_name} has no case named {case}"))
+        }
+        Some(enum_type) => Ok((enum_type, case)),
+    };
+    Some(result)
+}
+
+/// Returns the enum that a qualified enum case like `Mood.Happy` refers to, even if the enum has no such case.
+/// Used to keep the type of an expression known after reporting an unknown case, so that no follow-up errors are reported.
+pub(crate) fn enum_of_case<'a>(
+    enums: &'a [EnumType],
+    ctx: &Function_callContext<'_>,
+) -> Option<&'a EnumType> {
+    let (enum_name, _) = enum_case_name(ctx)?;
+    enums.iter().find(|e| e.name == enum_name)
+}
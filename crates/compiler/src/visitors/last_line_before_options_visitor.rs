@@ -3,6 +3,7 @@
 use crate::prelude::generated::yarnspinnerparser::*;
 use crate::prelude::generated::yarnspinnerparservisitor::YarnSpinnerParserVisitorCompat;
 use crate::prelude::*;
+use crate::visitors::is_line_group;
 use antlr_rust::tree::ParseTreeVisitorCompat;
 use std::rc::Rc;
 
@@ -88,7 +89,8 @@ impl LastLineBeforeOptionsVisitor {
             // we need to visit the option in case it has embedded statements
             self.visit(shortcut_option_statement.as_ref());
 
-            if i == 0 {
+            // Lines of a line group are run like regular lines, so the line before them is not special
+            if i == 0 || is_line_group(&shortcut_option_statement) {
                 // we are an option BUT there isn't a previous statement
                 continue;
             }
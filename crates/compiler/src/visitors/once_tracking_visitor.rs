@@ -0,0 +1,82 @@
+//! Not part of the original, which only gained `<<once>>` in Yarn Spinner 3.
+
+use crate::parser::generated::yarnspinnerparser::*;
+use crate::prelude::generated::yarnspinnerparservisitor::YarnSpinnerParserVisitorCompat;
+use crate::prelude::*;
+use crate::visitors::{has_once_tag, is_once_clause, once_line_key, OnceBlockKeys};
+use antlr_rust::token::Token;
+use antlr_rust::tree::ParseTreeVisitorCompat;
+use std::collections::HashSet;
+use yarnspinner_core::prelude::*;
+
+/// Finds all `<<once>>` blocks and lines or options tagged with `#once`,
+/// and collects the names of the variables that track whether they were already run.
+#[derive(Clone)]
+pub(crate) struct OnceTrackingVisitor<'input> {
+    pub(crate) once_variables: HashSet<String>,
+    file: FileParseResult<'input>,
+    current_node_name: String,
+    /// The keys of the `<<once>>` blocks of the current node visited so far.
+    once_block_keys: OnceBlockKeys,
+    _dummy: (),
+}
+
+impl<'input> OnceTrackingVisitor<'input> {
+    pub(crate) fn new(file: FileParseResult<'input>) -> Self {
+        Self {
+            once_variables: Default::default(),
+            file,
+            current_node_name: Default::default(),
+            once_block_keys: Default::default(),
+            _dummy: (),
+        }
+    }
+}
+
+impl<'input> ParseTreeVisitorCompat<'input> for OnceTrackingVisitor<'input> {
+    type Node = YarnSpinnerParserContextType;
+    type Return = ();
+
+    fn temp_result(&mut self) -> &mut Self::Return {
+        &mut self._dummy
+    }
+}
+
+impl<'input> YarnSpinnerParserVisitorCompat<'input> for OnceTrackingVisitor<'input> {
+    fn visit_node(&mut self, ctx: &NodeContext<'input>) -> Self::Return {
+        self.current_node_name = ctx
+            .header_all()
+            .iter()
+            .find(|header| header.header_key.as_ref().unwrap().get_text() == "title")
+            .and_then(|header| header.header_value.as_ref())
+            .map(|value| value.get_text().to_owned())
+            .unwrap_or_default();
+        self.once_block_keys = Default::default();
+        if let Some(body) = ctx.body() {
+            self.visit(body.as_ref());
+        }
+    }
+
+    fn visit_if_clause(&mut self, ctx: &If_clauseContext<'input>) -> Self::Return {
+        if is_once_clause(ctx) {
+            let key = self.once_block_keys.next_key(ctx, self.file.tokens());
+            self.once_variables
+                .insert(Library::generate_unique_once_variable(
+                    &self.current_node_name,
+                    &key,
+                ));
+        }
+        self.visit_children(ctx)
+    }
+
+    fn visit_line_statement(&mut self, ctx: &Line_statementContext<'input>) -> Self::Return {
+        if has_once_tag(ctx) {
+            self.once_variables
+                .insert(Library::generate_unique_once_variable(
+                    &self.current_node_name,
+                    &once_line_key(ctx, self.file.tokens()),
+                ));
+        }
+        self.visit_children(ctx)
+    }
+}
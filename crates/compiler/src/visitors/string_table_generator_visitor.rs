@@ -258,8 +258,10 @@ a {1 + 3} cool expression
         let result = Compiler {
             files: vec![file],
             library: Default::default(),
+            command_library: None,
             compilation_type: CompilationType::FullCompilation,
             variable_declarations: vec![],
+            optimize: false,
         }
         .compile()
         .unwrap();
@@ -316,8 +318,10 @@ a {very} cool expression
         let result = Compiler {
             files: vec![file],
             library: Default::default(),
+            command_library: None,
             compilation_type: CompilationType::FullCompilation,
             variable_declarations: vec![],
+            optimize: false,
         }
         .compile();
 
@@ -5,7 +5,10 @@ use crate::prelude::generated::yarnspinnerlexer;
 use crate::prelude::generated::yarnspinnerparser::*;
 use crate::prelude::generated::yarnspinnerparservisitor::YarnSpinnerParserVisitorCompat;
 use crate::prelude::*;
-use crate::visitors::{CodeGenerationVisitor, KnownTypes};
+use crate::visitors::{
+    enum_of_case, is_once_clause, is_when_clause, resolve_enum_case, smart_variable_expression,
+    CodeGenerationVisitor, KnownTypes,
+};
 use antlr_rust::parser_rule_context::ParserRuleContext;
 use antlr_rust::token::Token;
 use antlr_rust::tree::{ParseTree, ParseTreeVisitorCompat};
@@ -14,6 +17,7 @@ use std::path::Path;
 use yarnspinner_core::prelude::*;
 use yarnspinner_core::types::*;
 
+mod check_command;
 mod check_operation;
 
 /// A visitor that walks the parse tree, checking for type consistency
@@ -40,6 +44,12 @@ pub(crate) struct TypeCheckVisitor<'input> {
     // starting our work
     existing_declarations: Vec<Declaration>,
 
+    // The enums declared in this compilation job
+    enums: Vec<EnumType>,
+
+    // The commands the game provides, if they should be checked
+    command_library: Option<&'input CommandLibrary>,
+
     // The name of the node that we're currently visiting.
     current_node_name: Option<String>,
 
@@ -75,11 +85,15 @@ pub(crate) struct TypeCheckVisitor<'input> {
 impl<'input> TypeCheckVisitor<'input> {
     pub(crate) fn new(
         existing_declarations: Vec<Declaration>,
+        enums: Vec<EnumType>,
+        command_library: Option<&'input CommandLibrary>,
         file: FileParseResult<'input>,
     ) -> Self {
         Self {
             file,
             existing_declarations,
+            enums,
+            command_library,
             diagnostics: Default::default(),
             new_declarations: Default::default(),
             deferred_types: Default::default(),
@@ -105,6 +119,16 @@ impl<'input> TypeCheckVisitor<'input> {
             .iter_mut()
             .chain(self.new_declarations.iter_mut())
     }
+
+    /// Returns the name under which a variable is declared, which differs from
+    /// the name used in the source if the variable is a local of the current node.
+    pub(crate) fn resolve_variable_name(&self, variable_name: &str) -> String {
+        self.current_node_name
+            .as_ref()
+            .map(|node_name| Library::generate_local_variable_name(node_name, variable_name))
+            .filter(|local_name| self.declarations().any(|decl| &decl.name == local_name))
+            .unwrap_or_else(|| variable_name.to_owned())
+    }
 }
 
 impl<'input> ParseTreeVisitorCompat<'input> for TypeCheckVisitor<'input> {
@@ -259,13 +283,29 @@ impl<'input> YarnSpinnerParserVisitorCompat<'input> for TypeCheckVisitor<'input>
     }
 
     fn visit_valueFunc(&mut self, ctx: &ValueFuncContext<'input>) -> Self::Return {
-        let function_name = ctx
-            .function_call()
-            .unwrap()
+        let function_call = ctx.function_call().unwrap();
+        let function_name = function_call
             .get_token(yarnspinnerlexer::FUNC_ID, 0)
             .unwrap()
             .get_text();
 
+        // Enum cases look like functions to the parser
+        if let Some(enum_case) = resolve_enum_case(&self.enums, &function_call) {
+            return match enum_case {
+                Ok((enum_type, _)) => Some(Type::Enum(enum_type.clone())),
+                Err(message) => {
+                    self.diagnostics.push(
+                        Diagnostic::from_message(message)
+                            .with_file_name(&self.file.name)
+                            .with_parser_context(ctx, self.file.tokens()),
+                    );
+                    // If only the case is unknown, the type still is, so there's no need for further errors
+                    enum_of_case(&self.enums, &function_call)
+                        .map(|enum_type| Type::Enum(enum_type.clone()))
+                }
+            };
+        }
+
         let function_declaration = self
             .declarations()
             .find(|decl| decl.name == function_name)
@@ -391,7 +431,7 @@ impl<'input> YarnSpinnerParserVisitorCompat<'input> for TypeCheckVisitor<'input>
         // earlier stage if we don't have a variable name for
         // this Variable context; here, we'll bail out.
         let var_id = ctx.get_token(yarnspinnerlexer::VAR_ID, 0)?;
-        let name = var_id.get_text();
+        let name = self.resolve_variable_name(&var_id.get_text());
         if let Some(declaration) = self.declarations().find(|decl| decl.name == name) {
             return Some(declaration.r#type.clone());
         }
@@ -426,7 +466,14 @@ impl<'input> YarnSpinnerParserVisitorCompat<'input> for TypeCheckVisitor<'input>
         ParseTreeVisitorCompat::visit_children(self, ctx);
         // If clauses are required to be boolean
         let expressions = &[ctx.expression().unwrap().into()];
-        self.check_operation(ctx, expressions, None, "if statement", &[Type::Boolean])
+        let description = if is_when_clause(ctx) {
+            "when header"
+        } else if is_once_clause(ctx) {
+            "once statement"
+        } else {
+            "if statement"
+        };
+        self.check_operation(ctx, expressions, None, description, &[Type::Boolean])
     }
 
     fn visit_else_if_clause(&mut self, ctx: &Else_if_clauseContext<'input>) -> Self::Return {
@@ -436,9 +483,60 @@ impl<'input> YarnSpinnerParserVisitorCompat<'input> for TypeCheckVisitor<'input>
         self.check_operation(ctx, expressions, None, "elseif statement", &[Type::Boolean])
     }
 
+    fn visit_line_condition(&mut self, ctx: &Line_conditionContext<'input>) -> Self::Return {
+        ParseTreeVisitorCompat::visit_children(self, ctx);
+        // Line conditions are required to be boolean
+        let expressions = &[ctx.expression().unwrap().into()];
+        self.check_operation(ctx, expressions, None, "line condition", &[Type::Boolean])
+    }
+
+    fn visit_declare_statement(&mut self, ctx: &Declare_statementContext<'input>) -> Self::Return {
+        let Some(expression) = smart_variable_expression(ctx) else {
+            return ParseTreeVisitorCompat::visit_children(self, ctx);
+        };
+        let variable_name = ctx.variable()?.get_text();
+        let declared_type = self
+            .declarations()
+            .find(|decl| decl.name == variable_name && decl.is_smart_variable)
+            .map(|decl| decl.r#type.clone());
+        if let Some(declared_type) = declared_type.as_ref() {
+            self.hints
+                .insert(expression.as_ref(), declared_type.clone());
+        }
+        let expression_type = self.visit(expression.as_ref());
+        if let Some(declared_type) = declared_type {
+            if !expression_type.is_sub_type_of(&declared_type) {
+                self.diagnostics.push(
+                    Diagnostic::from_message(format!(
+                        "{variable_name} ({}) cannot be assigned a {}",
+                        declared_type.format(),
+                        expression_type.format(),
+                    ))
+                    .with_file_name(&self.file.name)
+                    .with_parser_context(ctx, self.file.tokens()),
+                );
+            }
+        }
+        None
+    }
+
     fn visit_set_statement(&mut self, ctx: &Set_statementContext<'input>) -> Self::Return {
         let variable_context = ctx.variable()?;
         let expression_context = ctx.expression()?;
+        let variable_name = self.resolve_variable_name(&variable_context.get_text());
+        if self
+            .declarations()
+            .any(|decl| decl.name == variable_name && decl.is_smart_variable)
+        {
+            self.diagnostics.push(
+                Diagnostic::from_message(format!(
+                    "{variable_name} is a smart variable, so it can't be assigned to"
+                ))
+                .with_file_name(&self.file.name)
+                .with_parser_context(ctx, self.file.tokens()),
+            );
+            return None;
+        }
         let variable_type = self.visit(variable_context.as_ref());
         if let Some(variable_type) = variable_type.as_ref() {
             // giving the expression a hint just in case it is needed to help resolve any ambiguity on the expression
@@ -547,6 +645,13 @@ impl<'input> YarnSpinnerParserVisitorCompat<'input> for TypeCheckVisitor<'input>
         expression_type
     }
 
+    fn visit_command_statement(&mut self, ctx: &Command_statementContext<'input>) -> Self::Return {
+        // Type check the inline expressions first
+        self.visit_children(ctx);
+        self.check_command(ctx);
+        None
+    }
+
     fn visit_jumpToExpression(&mut self, ctx: &JumpToExpressionContext<'input>) -> Self::Return {
         let expressions = &[ctx.expression().unwrap().into()];
         // The expression's type must resolve to a string.
@@ -594,6 +699,7 @@ impl DefaultValue for Type {
             Type::String => Some(YarnValue::String(Default::default())),
             Type::Number => Some(YarnValue::Number(Default::default())),
             Type::Boolean => Some(YarnValue::Boolean(Default::default())),
+            Type::Enum(enum_type) => enum_type.default_value(),
             _ => None,
         }
     }
@@ -627,8 +733,10 @@ mod tests {
         let _result = Compiler {
             files: vec![file],
             library: Default::default(),
+            command_library: None,
             compilation_type: CompilationType::FullCompilation,
             variable_declarations: vec![],
+            optimize: false,
         }
         .compile()
         .unwrap();
@@ -652,8 +760,10 @@ mod tests {
         let result = Compiler {
             files: vec![file],
             library: Default::default(),
+            command_library: None,
             compilation_type: CompilationType::FullCompilation,
             variable_declarations: vec![],
+            optimize: false,
         }
         .compile();
 
@@ -739,8 +849,10 @@ mod tests {
         let _result = Compiler {
             files: vec![file],
             library: Default::default(),
+            command_library: None,
             compilation_type: CompilationType::FullCompilation,
             variable_declarations: vec![],
+            optimize: false,
         }
         .compile()
         .unwrap();
@@ -762,8 +874,10 @@ mod tests {
         let result = Compiler {
             files: vec![file],
             library: Default::default(),
+            command_library: None,
             compilation_type: CompilationType::FullCompilation,
             variable_declarations: vec![],
+            optimize: false,
         }
         .compile();
 
@@ -0,0 +1,294 @@
+use crate::prelude::generated::yarnspinnerparser::*;
+use crate::prelude::*;
+use crate::visitors::*;
+use antlr_rust::tree::Tree;
+use yarnspinner_core::prelude::*;
+
+/// Commands that are handled by the compiler itself and thus never need to be declared.
+const BUILTIN_COMMANDS: &[&str] = &["stop", "return", "detour"];
+
+impl<'input> TypeCheckVisitor<'input> {
+    /// Checks a command statement against the declarations in the [`CommandLibrary`], if there is one.
+    ///
+    /// Only literal parameters are checked for their type, since the values of inline expressions are only known at runtime.
+    pub(super) fn check_command(&mut self, ctx: &Command_statementContext<'input>) {
+        let Some(command_library) = self.command_library else {
+            return;
+        };
+        // Enum declarations are parsed as commands
+        if EnumCommand::parse(ctx).is_some() {
+            return;
+        }
+        let Some(formatted_text) = ctx.command_formatted_text() else {
+            return;
+        };
+        let mut arguments = split_into_arguments(&formatted_text).into_iter();
+        let Some(CommandArgument::Literal(name)) = arguments.next() else {
+            // Either empty, which is already reported by the parser, or the name is computed at runtime
+            return;
+        };
+        if BUILTIN_COMMANDS.contains(&name.as_str()) {
+            return;
+        }
+
+        let Some(declaration) = command_library.get(&name) else {
+            self.push_command_diagnostic(ctx, format!("Unknown command \"{name}\""));
+            return;
+        };
+        let arguments: Vec<_> = arguments.collect();
+        let min = declaration.min_parameter_count();
+        let max = declaration.max_parameter_count();
+        if arguments.len() < min || max.is_some_and(|max| arguments.len() > max) {
+            let expected = match max {
+                Some(max) if max == min => format!("{min} {}", plural_parameters(min)),
+                Some(max) => format!("{min} to {max} parameters"),
+                None => format!("at least {min} {}", plural_parameters(min)),
+            };
+            let message = format!(
+                "Command \"{name}\" expects {expected}, but received {}",
+                arguments.len()
+            );
+            self.push_command_diagnostic(ctx, message);
+            return;
+        }
+
+        for (i, argument) in arguments.iter().enumerate() {
+            let CommandArgument::Literal(text) = argument else {
+                continue;
+            };
+            // Guaranteed to be Some because the number of arguments was checked above
+            let parameter = declaration.parameter_at(i).unwrap();
+            if !can_convert_literal(text, &parameter.r#type) {
+                let message = format!(
+                    "Command \"{name}\" parameter {} expects a {}, but \"{text}\" is not a {}",
+                    i + 1,
+                    parameter.r#type,
+                    parameter.r#type,
+                );
+                self.push_command_diagnostic(ctx, message);
+            }
+        }
+    }
+
+    fn push_command_diagnostic(&mut self, ctx: &Command_statementContext<'input>, message: String) {
+        self.diagnostics.push(
+            Diagnostic::from_message(message)
+                .with_file_name(&self.file.name)
+                .with_parser_context(ctx, self.file.tokens()),
+        );
+    }
+}
+
+/// A whitespace-separated part of a command, i.e. its name or one of its parameters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CommandArgument {
+    /// An argument consisting only of text, with quotes and escapes already resolved.
+    Literal(String),
+    /// An argument containing at least one inline expression, e.g. `{$gold}`.
+    Interpolated,
+}
+
+/// Splits a command into its arguments the same way the runtime does when running the command,
+/// i.e. by whitespace, except inside double quotes.
+fn split_into_arguments(
+    formatted_text: &Command_formatted_textContextAll<'_>,
+) -> Vec<CommandArgument> {
+    let children: Vec<_> = formatted_text.get_children().collect();
+    let is_expression = |index: usize| {
+        children
+            .get(index)
+            .is_some_and(|child| child.get_child_count() > 0)
+    };
+
+    let mut splitter = ArgumentSplitter::default();
+    for (index, child) in children.iter().enumerate() {
+        if is_expression(index) {
+            splitter.push_expression();
+            continue;
+        }
+        let text = child.get_text();
+        // The braces around an expression are terminals of their own
+        let is_expression_brace = (text == "{" && is_expression(index + 1))
+            || (text == "}" && index > 0 && is_expression(index - 1));
+        if !is_expression_brace {
+            splitter.push_text(&text);
+        }
+    }
+    splitter.finish()
+}
+
+#[derive(Debug, Default)]
+struct ArgumentSplitter {
+    arguments: Vec<CommandArgument>,
+    current: Option<CommandArgument>,
+    in_quotes: bool,
+    escaped: bool,
+}
+
+impl ArgumentSplitter {
+    fn push_text(&mut self, text: &str) {
+        for char in text.chars() {
+            if self.escaped {
+                self.escaped = false;
+                if char != '\\' && char != '"' {
+                    // Not a valid escape sequence, so the backslash is kept
+                    self.push_char('\\');
+                }
+                self.push_char(char);
+            } else if self.in_quotes && char == '\\' {
+                self.escaped = true;
+            } else if char == '"' {
+                self.in_quotes = !self.in_quotes;
+                // Makes sure that `""` results in an empty argument
+                self.current
+                    .get_or_insert_with(|| CommandArgument::Literal(String::new()));
+            } else if char.is_whitespace() && !self.in_quotes {
+                self.end_argument();
+            } else {
+                self.push_char(char);
+            }
+        }
+    }
+
+    fn push_char(&mut self, char: char) {
+        match self
+            .current
+            .get_or_insert_with(|| CommandArgument::Literal(String::new()))
+        {
+            CommandArgument::Literal(text) => text.push(char),
+            CommandArgument::Interpolated => {}
+        }
+    }
+
+    fn push_expression(&mut self) {
+        self.current = Some(CommandArgument::Interpolated);
+    }
+
+    fn end_argument(&mut self) {
+        if let Some(argument) = self.current.take() {
+            self.arguments.push(argument);
+        }
+    }
+
+    fn finish(mut self) -> Vec<CommandArgument> {
+        self.end_argument();
+        self.arguments
+    }
+}
+
+/// Returns `true` if the runtime can convert the literal to the given type when passing it to the command.
+fn can_convert_literal(text: &str, r#type: &Type) -> bool {
+    let value = YarnValue::String(text.to_owned());
+    match r#type {
+        Type::Number => f32::try_from(value).is_ok(),
+        Type::Boolean => bool::try_from(value).is_ok(),
+        _ => true,
+    }
+}
+
+fn plural_parameters(count: usize) -> &'static str {
+    if count == 1 {
+        "parameter"
+    } else {
+        "parameters"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command_errors(body: &str, command_library: Option<CommandLibrary>) -> Vec<String> {
+        let mut compiler = Compiler::new();
+        compiler.add_file(File {
+            file_name: "test.yarn".to_string(),
+            source: format!("title: test\n---\n{body}\n==="),
+        });
+        if let Some(command_library) = command_library {
+            compiler.extend_command_library(command_library);
+        }
+        match compiler.compile() {
+            Ok(_) => vec![],
+            Err(error) => error.0.into_iter().map(|d| d.message).collect(),
+        }
+    }
+
+    fn test_library() -> CommandLibrary {
+        [
+            CommandDeclaration::new("fade_out").with_optional_parameter(Type::Number),
+            CommandDeclaration::new("move")
+                .with_parameter(Type::String)
+                .with_parameter(Type::Number)
+                .with_parameter(Type::Number),
+            CommandDeclaration::new("set_visible").with_parameter(Type::Boolean),
+            CommandDeclaration::new("play").with_variadic_parameter(Type::String),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn allows_declared_commands() {
+        let errors = command_errors(
+            "<<declare $x = 1>>
+<<fade_out>>
+<<fade_out 1.5>>
+<<move \"Ada Lovelace\" -3 {$x + 1}>>
+<<set_visible true>>
+<<play>>
+<<play a b c>>
+<<stop>>",
+            Some(test_library()),
+        );
+        assert_eq!(Vec::<String>::new(), errors);
+    }
+
+    #[test]
+    fn does_not_check_commands_without_command_library() {
+        assert_eq!(
+            Vec::<String>::new(),
+            command_errors("<<anything goes 1 2 3>>", None)
+        );
+    }
+
+    #[test]
+    fn catches_unknown_commands() {
+        assert_eq!(
+            vec!["Unknown command \"fade_in\"".to_owned()],
+            command_errors("<<fade_in 1>>", Some(test_library()))
+        );
+    }
+
+    #[test]
+    fn catches_wrong_parameter_counts() {
+        let errors = command_errors(
+            "<<fade_out 1 2>>\n<<move Ada 1>>\n<<set_visible>>",
+            Some(test_library()),
+        );
+        assert_eq!(
+            vec![
+                "Command \"fade_out\" expects 0 to 1 parameters, but received 2".to_owned(),
+                "Command \"move\" expects 3 parameters, but received 2".to_owned(),
+                "Command \"set_visible\" expects 1 parameter, but received 0".to_owned(),
+            ],
+            errors
+        );
+    }
+
+    #[test]
+    fn catches_literals_of_wrong_type() {
+        let errors = command_errors(
+            "<<declare $name = \"Ada\">>\n<<move {$name} here 2>>\n<<set_visible yes>>",
+            Some(test_library()),
+        );
+        assert_eq!(
+            vec![
+                "Command \"move\" parameter 2 expects a Number, but \"here\" is not a Number"
+                    .to_owned(),
+                "Command \"set_visible\" parameter 1 expects a Bool, but \"yes\" is not a Bool"
+                    .to_owned(),
+            ],
+            errors
+        );
+    }
+}
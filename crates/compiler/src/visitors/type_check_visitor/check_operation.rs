@@ -214,9 +214,8 @@ impl<'input> TypeCheckVisitor<'input> {
         // declaration for. We'll check for explicit declarations first.
         let mut undefined_variable_contexts: Vec<_> = variable_contexts
             .filter(|v| {
-                !self
-                    .declarations()
-                    .any(|d| d.name == v.VAR_ID().unwrap().get_text())
+                let name = self.resolve_variable_name(&v.VAR_ID().unwrap().get_text());
+                !self.declarations().any(|d| d.name == name)
             })
             .collect();
         // Implementation note: The original compares by reference here. The interval should be unique for each context, so let's use that instead.
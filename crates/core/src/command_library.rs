@@ -0,0 +1,357 @@
+//! Declarations of the commands a game provides, so that the compiler can check `<<command>>` statements.
+
+use crate::prelude::*;
+use std::any::TypeId;
+use std::collections::hash_map;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// A collection of the commands that can be called from Yarn scripts, i.e. the command counterpart to [`Library`].
+///
+/// Commands are only run by the game, so the compiler knows nothing about them by default.
+/// Passing a [`CommandLibrary`] to the compiler allows it to report unknown commands, a wrong number of parameters
+/// and parameters that can never be converted to the declared type as errors.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandLibrary(HashMap<String, CommandDeclaration>);
+
+impl Extend<CommandDeclaration> for CommandLibrary {
+    fn extend<T: IntoIterator<Item = CommandDeclaration>>(&mut self, iter: T) {
+        for declaration in iter {
+            self.add_command(declaration);
+        }
+    }
+}
+
+impl FromIterator<CommandDeclaration> for CommandLibrary {
+    fn from_iter<T: IntoIterator<Item = CommandDeclaration>>(iter: T) -> Self {
+        let mut library = Self::new();
+        library.extend(iter);
+        library
+    }
+}
+
+impl IntoIterator for CommandLibrary {
+    type Item = CommandDeclaration;
+    type IntoIter = hash_map::IntoValues<String, CommandDeclaration>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_values()
+    }
+}
+
+impl CommandLibrary {
+    /// Creates a new empty command library.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the commands from another [`CommandLibrary`].
+    ///
+    /// Will overwrite any commands that have the same name.
+    pub fn import(&mut self, other: Self) {
+        self.0.extend(other.0);
+    }
+
+    /// Adds a command to the library. Will overwrite any command that has the same name.
+    pub fn add_command(&mut self, declaration: CommandDeclaration) -> &mut Self {
+        self.0.insert(declaration.name.clone(), declaration);
+        self
+    }
+
+    /// Gets a command by name.
+    pub fn get(&self, name: &str) -> Option<&CommandDeclaration> {
+        self.0.get(name)
+    }
+
+    /// Returns `true` if the library contains a command with the given name.
+    pub fn contains_command(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    /// Iterates over the names of all commands in the library.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(|name| name.as_str())
+    }
+
+    /// Iterates over all commands in the library.
+    pub fn commands(&self) -> impl Iterator<Item = &CommandDeclaration> {
+        self.0.values()
+    }
+}
+
+impl Display for CommandLibrary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut commands: Vec<_> = self.0.values().collect();
+        commands.sort_by_key(|command| &command.name);
+        writeln!(f, "{{")?;
+        for command in commands {
+            writeln!(f, "    {command}")?;
+        }
+        writeln!(f, "}}")?;
+        Ok(())
+    }
+}
+
+/// The name and parameters of a command that can be called from Yarn scripts. See [`CommandLibrary`].
+///
+/// ## Examples
+///
+/// Declaring the command called like `<<add_player "John" 42>>` or `<<add_player "John">>`:
+/// ```
+/// # use yarnspinner_core::prelude::*;
+/// let declaration = CommandDeclaration::new("add_player")
+///     .with_parameter(Type::String)
+///     .with_optional_parameter(Type::Number);
+/// ```
+///
+/// Declaring the same command from the Rust types of its parameters:
+/// ```
+/// # use yarnspinner_core::prelude::*;
+/// let declaration = CommandDeclaration::from_parameter_type::<(String, Option<f32>)>("add_player");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CommandDeclaration {
+    /// The name of the command, i.e. the first word between the `<<` and `>>`.
+    pub name: String,
+
+    /// The parameters of the command, in order.
+    /// Required parameters come first, followed by optional ones and at most one variadic parameter.
+    pub parameters: Vec<CommandParameter>,
+}
+
+impl CommandDeclaration {
+    /// Creates a declaration for a command without any parameters.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            parameters: Vec::new(),
+        }
+    }
+
+    /// Creates a declaration for a command whose parameters are read into the given [`YarnFnParam`],
+    /// e.g. `(String, Option<f32>)` for a command with a required string parameter and an optional number parameter.
+    pub fn from_parameter_type<T: YarnFnParam>(name: impl Into<String>) -> Self {
+        Self::new(name).with_parameters(T::command_parameters())
+    }
+
+    /// Adds a parameter that must always be passed.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if an optional or variadic parameter was added before.
+    #[must_use]
+    pub fn with_parameter(self, r#type: impl Into<Type>) -> Self {
+        self.with_parameters([CommandParameter::required(r#type)])
+    }
+
+    /// Adds a parameter that may be left out.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if a variadic parameter was added before.
+    #[must_use]
+    pub fn with_optional_parameter(self, r#type: impl Into<Type>) -> Self {
+        self.with_parameters([CommandParameter::optional(r#type)])
+    }
+
+    /// Adds a parameter that accepts any number of values, including none.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if a variadic parameter was added before.
+    #[must_use]
+    pub fn with_variadic_parameter(self, r#type: impl Into<Type>) -> Self {
+        self.with_parameters([CommandParameter::variadic(r#type)])
+    }
+
+    /// Adds multiple parameters. See [`CommandDeclaration::with_parameter`], [`CommandDeclaration::with_optional_parameter`]
+    /// and [`CommandDeclaration::with_variadic_parameter`] for the rules they have to follow.
+    #[must_use]
+    pub fn with_parameters(
+        mut self,
+        parameters: impl IntoIterator<Item = CommandParameter>,
+    ) -> Self {
+        for parameter in parameters {
+            if let Some(previous) = self.parameters.last() {
+                assert!(
+                    previous.kind <= parameter.kind
+                        && previous.kind != CommandParameterKind::Variadic,
+                    "Failed to declare command \"{}\": {} parameters cannot follow {} parameters",
+                    self.name,
+                    parameter.kind,
+                    previous.kind,
+                );
+            }
+            self.parameters.push(parameter);
+        }
+        self
+    }
+
+    /// The smallest number of parameters the command can be called with.
+    pub fn min_parameter_count(&self) -> usize {
+        self.parameters
+            .iter()
+            .filter(|parameter| parameter.kind == CommandParameterKind::Required)
+            .count()
+    }
+
+    /// The largest number of parameters the command can be called with, or [`None`] if it has a variadic parameter.
+    pub fn max_parameter_count(&self) -> Option<usize> {
+        let is_variadic = self
+            .parameters
+            .iter()
+            .any(|parameter| parameter.kind == CommandParameterKind::Variadic);
+        (!is_variadic).then_some(self.parameters.len())
+    }
+
+    /// Returns the parameter that receives the value at the given position, if any.
+    pub fn parameter_at(&self, index: usize) -> Option<&CommandParameter> {
+        self.parameters.get(index).or_else(|| {
+            self.parameters
+                .last()
+                .filter(|parameter| parameter.kind == CommandParameterKind::Variadic)
+        })
+    }
+}
+
+impl Display for CommandDeclaration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<<{}", self.name)?;
+        for parameter in &self.parameters {
+            write!(f, " {parameter}")?;
+        }
+        write!(f, ">>")
+    }
+}
+
+/// A parameter of a [`CommandDeclaration`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CommandParameter {
+    /// The type the passed value must be convertible to. Use [`Type::Any`] to accept any value.
+    pub r#type: Type,
+
+    /// Whether the parameter must be passed, may be left out or accepts any number of values.
+    pub kind: CommandParameterKind,
+}
+
+impl CommandParameter {
+    /// Creates a parameter that must always be passed.
+    pub fn required(r#type: impl Into<Type>) -> Self {
+        Self {
+            r#type: r#type.into(),
+            kind: CommandParameterKind::Required,
+        }
+    }
+
+    /// Creates a parameter that may be left out.
+    pub fn optional(r#type: impl Into<Type>) -> Self {
+        Self {
+            r#type: r#type.into(),
+            kind: CommandParameterKind::Optional,
+        }
+    }
+
+    /// Creates a parameter that accepts any number of values, including none.
+    pub fn variadic(r#type: impl Into<Type>) -> Self {
+        Self {
+            r#type: r#type.into(),
+            kind: CommandParameterKind::Variadic,
+        }
+    }
+
+    /// Creates a required parameter for the Yarn type corresponding to the given Rust type, e.g. [`Type::Number`] for [`f32`].
+    /// Rust types without a corresponding Yarn type, like [`YarnValue`], result in [`Type::Any`].
+    pub fn of<T: ?Sized + 'static>() -> Self {
+        Self::required(Type::try_from(TypeId::of::<T>()).unwrap_or(Type::Any))
+    }
+
+    /// Turns a required parameter into an optional one. Variadic parameters stay variadic.
+    #[must_use]
+    pub fn into_optional(mut self) -> Self {
+        if self.kind == CommandParameterKind::Required {
+            self.kind = CommandParameterKind::Optional;
+        }
+        self
+    }
+}
+
+impl Display for CommandParameter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            CommandParameterKind::Required => write!(f, "{}", self.r#type),
+            CommandParameterKind::Optional => write!(f, "[{}]", self.r#type),
+            CommandParameterKind::Variadic => write!(f, "{}...", self.r#type),
+        }
+    }
+}
+
+/// Whether a [`CommandParameter`] must be passed, may be left out or accepts any number of values.
+///
+/// The variants are ordered by the position they may have in a parameter list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CommandParameterKind {
+    /// The parameter must always be passed.
+    Required,
+    /// The parameter may be left out. Only optional and variadic parameters may follow it.
+    Optional,
+    /// The parameter accepts any number of values, including none. Must be the last parameter.
+    Variadic,
+}
+
+impl Display for CommandParameterKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Required => f.write_str("required"),
+            Self::Optional => f.write_str("optional"),
+            Self::Variadic => f.write_str("variadic"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn declares_parameters_from_rust_types() {
+        let declaration =
+            CommandDeclaration::from_parameter_type::<(&str, (f32, bool), Option<YarnValue>)>(
+                "test",
+            );
+        assert_eq!(
+            vec![
+                CommandParameter::required(Type::String),
+                CommandParameter::required(Type::Number),
+                CommandParameter::required(Type::Boolean),
+                CommandParameter::optional(Type::Any),
+            ],
+            declaration.parameters
+        );
+        assert_eq!("<<test String Number Bool [Any]>>", declaration.to_string());
+    }
+
+    #[test]
+    fn counts_parameters() {
+        let declaration = CommandDeclaration::new("test")
+            .with_parameter(Type::String)
+            .with_optional_parameter(Type::Number);
+        assert_eq!(1, declaration.min_parameter_count());
+        assert_eq!(Some(2), declaration.max_parameter_count());
+        assert_eq!(None, declaration.parameter_at(2));
+
+        let declaration = declaration.with_variadic_parameter(Type::Boolean);
+        assert_eq!(None, declaration.max_parameter_count());
+        assert_eq!(
+            Some(&CommandParameter::variadic(Type::Boolean)),
+            declaration.parameter_at(5)
+        );
+    }
+
+    #[test]
+    #[should_panic = "required parameters cannot follow optional parameters"]
+    fn rejects_required_parameter_after_optional_one() {
+        let _ = CommandDeclaration::new("test")
+            .with_optional_parameter(Type::Number)
+            .with_parameter(Type::String);
+    }
+}
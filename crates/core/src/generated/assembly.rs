@@ -0,0 +1,590 @@
+//! A human-readable text format for [`Program`]s, meant for debugging the compiler and writing programs by hand in tests.
+//!
+//! A listing looks like this:
+//!
+//! ```text
+//! .program "Example"
+//! .initial "$gold" 5
+//!
+//! .node "Start"
+//! .header "title" "Start"
+//! .tag "rare"
+//! L0:
+//!     0000  PUSH_STRING "line:a1b2"
+//!     0001  RUN_LINE "line:a1b2" 0
+//!     0002  JUMP_TO "L0"              ; Comments run until the end of the line
+//! ```
+//!
+//! - Directives start with a `.`: `.program`, `.initial`, `.node`, `.header`, `.tag`, `.source_text` for
+//!   [`Node::source_text_string_id`] and `.label "name" position` for labels that don't point at an instruction.
+//! - A label written as `name:` points at the instruction that follows it.
+//! - Instructions are an [`OpCode`] name followed by their operands. Strings are quoted, numbers and `true` / `false` are not.
+//!   The leading instruction index is optional and ignored by the assembler.
+//! - Opcodes that are not known to this version are written as `OPCODE(n)`, operands without a value as `none`.
+
+use crate::prelude::*;
+use std::error::Error;
+use std::fmt::{Debug, Display, Write};
+
+/// The column at which annotations of instructions start in a disassembled listing, if the instruction is short enough.
+const ANNOTATION_COLUMN: usize = 48;
+
+impl Program {
+    /// Returns a human-readable listing of the program that can be turned back into a program with [`Program::assemble`].
+    ///
+    /// Nodes and initial values are listed sorted by name, so the listing of a program is always the same.
+    pub fn disassemble(&self) -> String {
+        self.disassemble_with(|_, _| None)
+    }
+
+    /// Like [`Program::disassemble`], but calls `annotate` with every node and instruction index
+    /// and appends the returned text to the instruction as a comment.
+    ///
+    /// This is used by the compiler to annotate instructions with their position in the source code.
+    pub fn disassemble_with(&self, annotate: impl Fn(&Node, usize) -> Option<String>) -> String {
+        let mut listing = String::new();
+        if !self.name.is_empty() {
+            writeln!(listing, ".program {}", quote(&self.name)).unwrap();
+        }
+        let mut initial_values: Vec<_> = self.initial_values.iter().collect();
+        initial_values.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        for (name, value) in initial_values {
+            writeln!(
+                listing,
+                ".initial {} {}",
+                quote(name),
+                format_operand(value)
+            )
+            .unwrap();
+        }
+
+        let mut nodes: Vec<_> = self.nodes.values().collect();
+        nodes.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        for node in nodes {
+            if !listing.is_empty() {
+                listing.push('\n');
+            }
+            disassemble_node(node, &annotate, &mut listing);
+        }
+        listing
+    }
+
+    /// Creates a program from a listing in the format produced by [`Program::disassemble`].
+    ///
+    /// Returns an [`AssemblyError`] describing the first line that could not be read.
+    pub fn assemble(listing: &str) -> Result<Self, AssemblyError> {
+        let mut program = Program::default();
+        let mut current_node: Option<Node> = None;
+        for (index, line) in listing.lines().enumerate() {
+            let error = |message: String| AssemblyError {
+                line: index + 1,
+                message,
+            };
+            let tokens = tokenize(line).map_err(error)?;
+            assemble_line(&tokens, &mut program, &mut current_node).map_err(error)?;
+        }
+        if let Some(node) = current_node {
+            program.nodes.insert(node.name.clone(), node);
+        }
+        Ok(program)
+    }
+}
+
+/// The error returned by [`Program::assemble`] when a listing is malformed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssemblyError {
+    /// The one-based number of the line that could not be read.
+    pub line: usize,
+
+    /// A description of what is wrong with the line.
+    pub message: String,
+}
+
+impl Error for AssemblyError {}
+
+impl Display for AssemblyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+fn disassemble_node(
+    node: &Node,
+    annotate: &impl Fn(&Node, usize) -> Option<String>,
+    listing: &mut String,
+) {
+    writeln!(listing, ".node {}", quote(&node.name)).unwrap();
+    for header in &node.headers {
+        writeln!(
+            listing,
+            ".header {} {}",
+            quote(&header.key),
+            quote(&header.value)
+        )
+        .unwrap();
+    }
+    for tag in &node.tags {
+        writeln!(listing, ".tag {}", quote(tag)).unwrap();
+    }
+    if !node.source_text_string_id.is_empty() {
+        writeln!(
+            listing,
+            ".source_text {}",
+            quote(&node.source_text_string_id)
+        )
+        .unwrap();
+    }
+
+    let mut labels: Vec<_> = node.labels.iter().collect();
+    labels.sort_unstable_by(|(a_name, a), (b_name, b)| a.cmp(b).then(a_name.cmp(b_name)));
+    let (dangling_labels, labels): (Vec<_>, Vec<_>) =
+        labels.into_iter().partition(|(_, &position)| {
+            usize::try_from(position).map_or(true, |position| position >= node.instructions.len())
+        });
+    for (name, position) in dangling_labels {
+        writeln!(listing, ".label {} {position}", quote(name)).unwrap();
+    }
+    let mut labels = labels.into_iter().peekable();
+
+    for (index, instruction) in node.instructions.iter().enumerate() {
+        while let Some((name, _)) = labels.next_if(|(_, &position)| position as usize == index) {
+            writeln!(listing, "{}:", format_label(name)).unwrap();
+        }
+        let mut line = format!("    {index:04}  {}", format_opcode(instruction.opcode));
+        for operand in &instruction.operands {
+            write!(line, " {}", format_operand(operand)).unwrap();
+        }
+        if let Some(annotation) = annotate(node, index) {
+            let padding = ANNOTATION_COLUMN
+                .saturating_sub(line.chars().count())
+                .max(1);
+            write!(line, "{:padding$}; {annotation}", "").unwrap();
+        }
+        writeln!(listing, "{line}").unwrap();
+    }
+}
+
+fn format_opcode(opcode: i32) -> String {
+    match OpCode::try_from(opcode) {
+        Ok(opcode) => opcode.as_str_name().to_owned(),
+        Err(_) => format!("OPCODE({opcode})"),
+    }
+}
+
+fn format_operand(operand: &Operand) -> String {
+    match &operand.value {
+        Some(OperandValue::StringValue(value)) => quote(value),
+        Some(OperandValue::BoolValue(value)) => value.to_string(),
+        Some(OperandValue::FloatValue(value)) => value.to_string(),
+        None => "none".to_owned(),
+    }
+}
+
+fn format_label(name: &str) -> String {
+    // Labels starting with a dot would be mistaken for directives
+    let is_plain = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '$'));
+    if is_plain {
+        name.to_owned()
+    } else {
+        quote(name)
+    }
+}
+
+fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => write!(quoted, "\\u{{{:x}}}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// A quoted string, already unescaped.
+    String(String),
+    /// Anything else, e.g. a directive, opcode, number or label.
+    Word(String),
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            ';' => break,
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '"' => {
+                chars.next();
+                tokens.push(Token::String(read_string(&mut chars)?));
+                // A quoted label is followed by a colon
+                if chars.next_if_eq(&':').is_some() {
+                    tokens.push(Token::Word(":".to_owned()));
+                }
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !matches!(c, ';' | '"'))
+                {
+                    word.push(c);
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn read_string(chars: &mut impl Iterator<Item = char>) -> Result<String, String> {
+    let mut text = String::new();
+    loop {
+        match chars.next() {
+            None => return Err("Unterminated string".to_owned()),
+            Some('"') => return Ok(text),
+            Some('\\') => match chars.next() {
+                Some('"') => text.push('"'),
+                Some('\\') => text.push('\\'),
+                Some('n') => text.push('\n'),
+                Some('r') => text.push('\r'),
+                Some('t') => text.push('\t'),
+                Some('u') => {
+                    let code: String = chars
+                        .take_while(|&c| c != '}')
+                        .collect::<String>()
+                        .trim_start_matches('{')
+                        .to_owned();
+                    let c = u32::from_str_radix(&code, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| format!("Invalid unicode escape \\u{{{code}}}"))?;
+                    text.push(c);
+                }
+                Some(c) => return Err(format!("Unknown escape sequence \\{c}")),
+                None => return Err("Unterminated string".to_owned()),
+            },
+            Some(c) => text.push(c),
+        }
+    }
+}
+
+fn assemble_line(
+    tokens: &[Token],
+    program: &mut Program,
+    current_node: &mut Option<Node>,
+) -> Result<(), String> {
+    let Some(first) = tokens.first() else {
+        return Ok(());
+    };
+    let arguments = &tokens[1..];
+    match first {
+        Token::Word(directive) if directive.starts_with('.') => {
+            assemble_directive(directive, arguments, program, current_node)
+        }
+        Token::String(label) if arguments == [Token::Word(":".to_owned())] => {
+            add_label(label, current_node)
+        }
+        Token::Word(word) if tokens.len() == 1 && word.ends_with(':') => {
+            add_label(word.trim_end_matches(':'), current_node)
+        }
+        Token::Word(word) => {
+            // The instruction index is just there for readability
+            let tokens = if word.parse::<usize>().is_ok() {
+                arguments
+            } else {
+                tokens
+            };
+            let instruction = assemble_instruction(tokens)?;
+            node_mut(current_node, "Instructions")?
+                .instructions
+                .push(instruction);
+            Ok(())
+        }
+        Token::String(string) => Err(format!(
+            "Expected a directive, label or instruction, but found {}",
+            quote(string)
+        )),
+    }
+}
+
+fn assemble_directive(
+    directive: &str,
+    arguments: &[Token],
+    program: &mut Program,
+    current_node: &mut Option<Node>,
+) -> Result<(), String> {
+    match (directive, arguments) {
+        (".program", [Token::String(name)]) => {
+            program.name = name.clone();
+        }
+        (".initial", [Token::String(name), value]) => {
+            program
+                .initial_values
+                .insert(name.clone(), parse_operand(value)?);
+        }
+        (".node", [Token::String(name)]) => {
+            if program.nodes.contains_key(name)
+                || current_node.as_ref().is_some_and(|node| &node.name == name)
+            {
+                return Err(format!("Duplicate node {}", quote(name)));
+            }
+            let previous_node = current_node.replace(Node {
+                name: name.clone(),
+                ..Default::default()
+            });
+            if let Some(node) = previous_node {
+                program.nodes.insert(node.name.clone(), node);
+            }
+        }
+        (".header", [Token::String(key), Token::String(value)]) => {
+            node_mut(current_node, "Headers")?.headers.push(Header {
+                key: key.clone(),
+                value: value.clone(),
+            });
+        }
+        (".tag", [Token::String(tag)]) => {
+            node_mut(current_node, "Tags")?.tags.push(tag.clone());
+        }
+        (".source_text", [Token::String(id)]) => {
+            node_mut(current_node, "Source text IDs")?.source_text_string_id = id.clone();
+        }
+        (".label", [Token::String(name), Token::Word(position)]) => {
+            let position = position
+                .parse()
+                .map_err(|_| format!("Invalid label position {position}"))?;
+            insert_label(name, position, current_node)?;
+        }
+        (".program" | ".initial" | ".node" | ".header" | ".tag" | ".source_text" | ".label", _) => {
+            return Err(format!("Invalid arguments for {directive}"));
+        }
+        _ => return Err(format!("Unknown directive {directive}")),
+    }
+    Ok(())
+}
+
+fn add_label(name: &str, current_node: &mut Option<Node>) -> Result<(), String> {
+    let position = current_node
+        .as_ref()
+        .map_or(0, |node| node.instructions.len() as i32);
+    insert_label(name, position, current_node)
+}
+
+fn insert_label(name: &str, position: i32, current_node: &mut Option<Node>) -> Result<(), String> {
+    let node = node_mut(current_node, "Labels")?;
+    if node.labels.insert(name.to_owned(), position).is_some() {
+        return Err(format!("Duplicate label {}", quote(name)));
+    }
+    Ok(())
+}
+
+fn node_mut<'a>(current_node: &'a mut Option<Node>, what: &str) -> Result<&'a mut Node, String> {
+    current_node
+        .as_mut()
+        .ok_or_else(|| format!("{what} must come after a .node directive"))
+}
+
+fn assemble_instruction(tokens: &[Token]) -> Result<Instruction, String> {
+    let Some((Token::Word(opcode), operands)) = tokens.split_first() else {
+        return Err("Expected an opcode".to_owned());
+    };
+    let opcode = OpCode::from_str_name(opcode)
+        .map(Into::into)
+        .or_else(|| {
+            opcode
+                .strip_prefix("OPCODE(")?
+                .strip_suffix(')')?
+                .parse()
+                .ok()
+        })
+        .ok_or_else(|| format!("Unknown opcode {opcode}"))?;
+    let operands = operands
+        .iter()
+        .map(parse_operand)
+        .collect::<Result<_, _>>()?;
+    Ok(Instruction { opcode, operands })
+}
+
+fn parse_operand(token: &Token) -> Result<Operand, String> {
+    match token {
+        Token::String(value) => Ok(Operand::from(value.clone())),
+        Token::Word(word) => match word.as_str() {
+            "true" => Ok(Operand::from(true)),
+            "false" => Ok(Operand::from(false)),
+            "none" => Ok(Operand { value: None }),
+            _ => word
+                .parse::<f32>()
+                .map(Operand::from)
+                .map_err(|_| format!("Invalid operand {word}")),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instruction(opcode: OpCode, operands: Vec<Operand>) -> Instruction {
+        Instruction {
+            opcode: opcode.into(),
+            operands,
+        }
+    }
+
+    fn program() -> Program {
+        let mut program = Program {
+            name: "Example".to_owned(),
+            ..Default::default()
+        };
+        program
+            .initial_values
+            .insert("$gold".to_owned(), Operand::from(5.0));
+        program.initial_values.insert(
+            "$name".to_owned(),
+            Operand::from("Sally \"the\" \\ \n".to_owned()),
+        );
+        program.nodes.insert(
+            "Start".to_owned(),
+            Node {
+                name: "Start".to_owned(),
+                instructions: vec![
+                    instruction(OpCode::PushString, vec![Operand::from("line:a".to_owned())]),
+                    instruction(
+                        OpCode::RunLine,
+                        vec![Operand::from("line:a".to_owned()), Operand::from(0.5)],
+                    ),
+                    instruction(OpCode::PushBool, vec![Operand::from(false)]),
+                    instruction(OpCode::JumpTo, vec![Operand::from("L0".to_owned())]),
+                    Instruction {
+                        opcode: 1234,
+                        operands: vec![Operand { value: None }],
+                    },
+                ],
+                labels: [
+                    ("L0", 0),
+                    ("L1", 2),
+                    ("label with spaces", 2),
+                    ("End", 5),
+                    ("Nowhere", -1),
+                ]
+                .into_iter()
+                .map(|(name, position)| (name.to_owned(), position))
+                .collect(),
+                tags: vec!["rare".to_owned()],
+                source_text_string_id: "line:Start".to_owned(),
+                headers: vec![Header {
+                    key: "title".to_owned(),
+                    value: "Start".to_owned(),
+                }],
+            },
+        );
+        program.nodes.insert(
+            "Empty".to_owned(),
+            Node {
+                name: "Empty".to_owned(),
+                ..Default::default()
+            },
+        );
+        program
+    }
+
+    #[test]
+    fn disassembles_programs() {
+        let expected = r#".program "Example"
+.initial "$gold" 5
+.initial "$name" "Sally \"the\" \\ \n"
+
+.node "Empty"
+
+.node "Start"
+.header "title" "Start"
+.tag "rare"
+.source_text "line:Start"
+.label "Nowhere" -1
+.label "End" 5
+L0:
+    0000  PUSH_STRING "line:a"
+    0001  RUN_LINE "line:a" 0.5
+L1:
+"label with spaces":
+    0002  PUSH_BOOL false
+    0003  JUMP_TO "L0"
+    0004  OPCODE(1234) none
+"#;
+        assert_eq!(expected, program().disassemble());
+    }
+
+    #[test]
+    fn round_trips_programs() {
+        let program = program();
+        let assembled = Program::assemble(&program.disassemble()).unwrap();
+        assert_eq!(program, assembled);
+    }
+
+    #[test]
+    fn annotates_instructions() {
+        let listing = program().disassemble_with(|node, index| {
+            (node.name == "Start" && index == 1).then(|| "input.yarn:3:1".to_owned())
+        });
+        assert!(listing
+            .contains("    0001  RUN_LINE \"line:a\" 0.5                 ; input.yarn:3:1\n"));
+        assert_eq!(program(), Program::assemble(&listing).unwrap());
+    }
+
+    #[test]
+    fn assembles_handwritten_programs() {
+        let listing = "
+            .node \"Start\"
+            loop:
+                PUSH_FLOAT 1  ; indices are optional
+                0001 POP
+                JUMP_TO \"loop\"
+        ";
+        let program = Program::assemble(listing).unwrap();
+        let node = &program.nodes["Start"];
+        assert_eq!(
+            vec![
+                instruction(OpCode::PushFloat, vec![Operand::from(1.0)]),
+                instruction(OpCode::Pop, vec![]),
+                instruction(OpCode::JumpTo, vec![Operand::from("loop".to_owned())]),
+            ],
+            node.instructions
+        );
+        assert_eq!(Some(&0), node.labels.get("loop"));
+    }
+
+    #[test]
+    fn reports_the_line_of_errors() {
+        let cases = [
+            (
+                "POP",
+                "Line 1: Instructions must come after a .node directive",
+            ),
+            (".node \"A\"\n\nFLY", "Line 3: Unknown opcode FLY"),
+            (
+                ".node \"A\"\nPUSH_STRING \"oops",
+                "Line 2: Unterminated string",
+            ),
+            (".node \"A\"\n.node \"A\"", "Line 2: Duplicate node \"A\""),
+            (".nod \"A\"", "Line 1: Unknown directive .nod"),
+            (".node A", "Line 1: Invalid arguments for .node"),
+        ];
+        for (listing, expected) in cases {
+            let error = Program::assemble(listing).unwrap_err();
+            assert_eq!(expected, error.to_string(), "{listing}");
+        }
+    }
+}
@@ -0,0 +1,240 @@
+//! Encoding and decoding of [`Program`]s in the binary format used by `.yarnc` files.
+//!
+//! The format is the protobuf encoding of the messages in `yarn_spinner.proto`, so the bytes can also be decoded with [`prost::Message::decode`]
+//! and vice versa.
+//!
+//! The files are not compatible with the ones of the original Yarn Spinner, in either direction.
+//! The opcodes 17 to 20, i.e. the ones for saliency, `<<detour>>` and `<<return>>`, are specific to this implementation
+//! and have no counterpart with the same number in the original, see [`OpCode`].
+//! In contrast to the derived [`prost::Message::encode`], maps are encoded sorted by their keys,
+//! so compiling the same source always produces the same bytes.
+
+use crate::prelude::*;
+use prost::encoding::{self, encode_key, encode_varint, WireType};
+use prost::Message;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Debug, Display};
+
+impl Program {
+    /// Encodes the program in the binary format of `.yarnc` files.
+    /// These files are not compatible with the ones of the original Yarn Spinner,
+    /// since the opcodes for saliency, `<<detour>>` and `<<return>>` are specific to this implementation.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.encoded_len());
+        encode_program(self, &mut buf);
+        buf
+    }
+
+    /// Decodes a program from the binary format of `.yarnc` files,
+    /// as written by [`Program::to_bytes`] or by [`prost::Message::encode`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ProgramDecodeError> {
+        Self::decode(bytes).map_err(ProgramDecodeError)
+    }
+}
+
+/// The error returned by [`Program::from_bytes`] when the bytes are not a valid encoded program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramDecodeError(prost::DecodeError);
+
+impl Error for ProgramDecodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+impl Display for ProgramDecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Failed to decode Yarn program: {}", self.0)
+    }
+}
+
+impl From<prost::DecodeError> for ProgramDecodeError {
+    fn from(error: prost::DecodeError) -> Self {
+        Self(error)
+    }
+}
+
+fn encode_program(program: &Program, buf: &mut Vec<u8>) {
+    if !program.name.is_empty() {
+        encoding::string::encode(1, &program.name, buf);
+    }
+    encode_sorted_map(
+        2,
+        &program.nodes,
+        |tag, node, buf| {
+            encode_key(tag, WireType::LengthDelimited, buf);
+            encode_varint(node.encoded_len() as u64, buf);
+            encode_node(node, buf);
+        },
+        encoding::message::encoded_len,
+        buf,
+    );
+    encode_sorted_map(
+        3,
+        &program.initial_values,
+        encoding::message::encode,
+        encoding::message::encoded_len,
+        buf,
+    );
+}
+
+/// Encodes the fields of a node in the order of their tags, which is what the derived implementation does as well.
+fn encode_node(node: &Node, buf: &mut Vec<u8>) {
+    if !node.name.is_empty() {
+        encoding::string::encode(1, &node.name, buf);
+    }
+    encoding::message::encode_repeated(2, &node.instructions, buf);
+    encode_sorted_map(
+        3,
+        &node.labels,
+        encoding::int32::encode,
+        encoding::int32::encoded_len,
+        buf,
+    );
+    encoding::string::encode_repeated(4, &node.tags, buf);
+    if !node.source_text_string_id.is_empty() {
+        encoding::string::encode(5, &node.source_text_string_id, buf);
+    }
+    encoding::message::encode_repeated(6, &node.headers, buf);
+}
+
+/// Encodes a `map<string, V>` field like [`encoding::hash_map::encode`] does, but sorted by key.
+/// Keys and values that have their default value are omitted from their entry.
+fn encode_sorted_map<V: Default + PartialEq>(
+    tag: u32,
+    map: &HashMap<String, V>,
+    value_encode: impl Fn(u32, &V, &mut Vec<u8>),
+    value_encoded_len: impl Fn(u32, &V) -> usize,
+    buf: &mut Vec<u8>,
+) {
+    let default_value = V::default();
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+    for (key, value) in entries {
+        let skip_key = key.is_empty();
+        let skip_value = value == &default_value;
+        let len = (if skip_key {
+            0
+        } else {
+            encoding::string::encoded_len(1, key)
+        }) + (if skip_value {
+            0
+        } else {
+            value_encoded_len(2, value)
+        });
+        encode_key(tag, WireType::LengthDelimited, buf);
+        encode_varint(len as u64, buf);
+        if !skip_key {
+            encoding::string::encode(1, key, buf);
+        }
+        if !skip_value {
+            value_encode(2, value, buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program() -> Program {
+        let mut program = Program::default();
+        for name in ["Start", "Shop", "End"] {
+            let node = Node {
+                name: name.to_owned(),
+                instructions: vec![
+                    Instruction {
+                        opcode: OpCode::PushString.into(),
+                        operands: vec![Operand::from(format!("line:{name}"))],
+                    },
+                    Instruction {
+                        opcode: OpCode::Return.into(),
+                        operands: vec![],
+                    },
+                ],
+                labels: [("L0", 0), ("L1", 1), ("L2", 1)]
+                    .into_iter()
+                    .map(|(label, position)| (label.to_owned(), position))
+                    .collect(),
+                headers: vec![Header {
+                    key: "title".to_owned(),
+                    value: name.to_owned(),
+                }],
+                ..Default::default()
+            };
+            program.nodes.insert(name.to_owned(), node);
+        }
+        program
+            .initial_values
+            .insert("$gold".to_owned(), Operand::from(5.0));
+        program
+            .initial_values
+            .insert("$name".to_owned(), Operand::from(String::new()));
+        program
+    }
+
+    #[test]
+    fn round_trips_programs() {
+        let program = program();
+        assert_eq!(program, Program::from_bytes(&program.to_bytes()).unwrap());
+    }
+
+    #[test]
+    fn encodes_deterministically() {
+        let program = program();
+        let bytes = program.to_bytes();
+        assert_eq!(program.encoded_len(), bytes.len());
+        // Every new map iterates in a different order
+        for _ in 0..10 {
+            assert_eq!(bytes, self::program().to_bytes());
+        }
+    }
+
+    #[test]
+    fn decodes_derived_encoding() {
+        let program = program();
+        assert_eq!(
+            program,
+            Program::from_bytes(&program.encode_to_vec()).unwrap()
+        );
+    }
+
+    #[test]
+    fn encodes_minimal_program_like_protobuf() {
+        let mut program = Program::default();
+        program.nodes.insert(
+            "A".to_owned(),
+            Node {
+                name: "A".to_owned(),
+                instructions: vec![Instruction {
+                    opcode: OpCode::Stop.into(),
+                    operands: vec![],
+                }],
+                ..Default::default()
+            },
+        );
+        let stop = OpCode::Stop as u8;
+        #[rustfmt::skip]
+        let expected = [
+            // Program.nodes, an entry of 12 bytes
+            0x12, 12,
+                // The entry's key: "A"
+                0x0a, 1, b'A',
+                // The entry's value: a Node of 7 bytes
+                0x12, 7,
+                    // Node.name: "A"
+                    0x0a, 1, b'A',
+                    // Node.instructions: an Instruction of 2 bytes
+                    0x12, 2,
+                        // Instruction.opcode
+                        0x08, stop,
+        ];
+        assert_eq!(expected.to_vec(), program.to_bytes());
+    }
+
+    #[test]
+    fn rejects_invalid_bytes() {
+        assert!(Program::from_bytes(&[0x12, 0xff]).is_err());
+    }
+}
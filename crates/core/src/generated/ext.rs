@@ -136,6 +136,32 @@ impl Program {
     }
 }
 
+impl Node {
+    /// The header that the compiler adds to every node of a node group, i.e. to nodes that share a title and have `when:` headers.
+    /// Its value is the shared title, which is also the name of the node that runs when jumping to the group.
+    pub const NODE_GROUP_HEADER: &'static str = "$Yarn.Internal.NodeGroup";
+
+    /// The header that the compiler adds to the node that computes the value of a smart variable,
+    /// i.e. a variable declared with an expression instead of a constant value, like `<<declare $can_afford = $gold >= 10>>`.
+    /// The node is named after the variable and the header's value is the expression.
+    pub const SMART_VARIABLE_HEADER: &'static str = "$Yarn.Internal.SmartVariable";
+
+    /// Returns the name of the node group this node belongs to, if any.
+    pub fn node_group(&self) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|header| header.key == Self::NODE_GROUP_HEADER)
+            .map(|header| header.value.as_str())
+    }
+
+    /// Returns `true` if this node computes the value of a smart variable rather than containing dialogue.
+    pub fn is_smart_variable(&self) -> bool {
+        self.headers
+            .iter()
+            .any(|header| header.key == Self::SMART_VARIABLE_HEADER)
+    }
+}
+
 impl Instruction {
     pub fn read_operand<T>(&self, index: usize) -> T
     where
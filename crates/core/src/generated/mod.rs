@@ -3,7 +3,10 @@
 //! Equivalent to <https://github.com/YarnSpinnerTool/YarnSpinner/blob/da39c7195107d8211f21c263e4084f773b84eaff/YarnSpinner/YarnSpinner.cs>
 
 use crate::prelude::*;
+mod assembly;
+mod binary_format;
 mod ext;
-pub use self::ext::*;
+mod verification;
+pub use self::{assembly::*, binary_format::*, ext::*, verification::*};
 
 include!("yarn.rs");
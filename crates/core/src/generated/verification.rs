@@ -0,0 +1,746 @@
+//! Static checks for [`Program`]s, so that broken programs are noticed before they are run instead of failing halfway through a dialogue.
+
+use crate::prelude::*;
+use std::collections::{BTreeSet, VecDeque};
+use std::error::Error;
+use std::fmt::{Debug, Display};
+
+impl Program {
+    /// Checks the program for mistakes that would otherwise only be noticed by the virtual machine while running it:
+    /// - Every label used by an instruction exists in its node.
+    /// - Every instruction has the operands its [`OpCode`] expects.
+    /// - The stack never underflows and has the same depth whichever path reaches an instruction.
+    /// - Every [`OpCode::CallFunc`] calls a function in `library` with the right number of parameters.
+    /// - Every [`OpCode::RunNode`] and [`OpCode::DetourToNode`] with a known target refers to an existing node.
+    ///
+    /// The compiler never produces programs that fail these checks, so this is mostly useful for programs that were assembled by hand
+    /// or loaded from elsewhere. Note that [`OpCode::SelectSaliencyCandidate`] must be directly followed by an [`OpCode::JumpIfFalse`],
+    /// as the stack depth after it depends on whether a candidate was selected.
+    pub fn verify(&self, library: &Library) -> Result<(), VerificationError> {
+        self.verify_with(Some(library))
+    }
+
+    /// Like [`Program::verify`], but does not check whether the functions called by [`OpCode::CallFunc`] exist,
+    /// for when they may still be added to the library before the program runs.
+    pub fn verify_without_library(&self) -> Result<(), VerificationError> {
+        self.verify_with(None)
+    }
+
+    fn verify_with(&self, library: Option<&Library>) -> Result<(), VerificationError> {
+        let mut nodes: Vec<_> = self.nodes.values().collect();
+        nodes.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        let problems: Vec<_> = nodes
+            .into_iter()
+            .flat_map(|node| {
+                let mut verifier = NodeVerifier {
+                    program: self,
+                    library,
+                    node,
+                    problems: BTreeSet::new(),
+                };
+                verifier.verify();
+                verifier
+                    .problems
+                    .into_iter()
+                    .map(|(instruction, message)| VerificationProblem {
+                        node_name: node.name.clone(),
+                        instruction,
+                        message,
+                    })
+            })
+            .collect();
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(VerificationError(problems))
+        }
+    }
+}
+
+/// The error returned by [`Program::verify`], containing every problem that was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationError(pub Vec<VerificationProblem>);
+
+impl Error for VerificationError {}
+
+impl Display for VerificationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "The Yarn program is invalid:")?;
+        for problem in &self.0 {
+            writeln!(f, "- {problem}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A single problem found by [`Program::verify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationProblem {
+    /// The name of the node containing the problem.
+    pub node_name: String,
+
+    /// The index of the instruction containing the problem, or [`None`] if the problem concerns the node as a whole.
+    pub instruction: Option<usize>,
+
+    /// A description of the problem.
+    pub message: String,
+}
+
+impl Display for VerificationProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.instruction {
+            Some(instruction) => write!(
+                f,
+                "Node \"{}\", instruction {instruction}: {}",
+                self.node_name, self.message
+            ),
+            None => write!(f, "Node \"{}\": {}", self.node_name, self.message),
+        }
+    }
+}
+
+/// What the verifier knows about a value on the stack.
+#[derive(Debug, Clone, PartialEq)]
+enum StackValue {
+    /// A string that is one of the given constants, if they are known.
+    String(Option<BTreeSet<String>>),
+    Float(Option<f32>),
+    Bool(Option<bool>),
+    Unknown,
+}
+
+impl StackValue {
+    fn constant_string(value: String) -> Self {
+        Self::String(Some(BTreeSet::from([value])))
+    }
+
+    fn merge(&self, other: &Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a.clone(),
+            (Self::String(Some(a)), Self::String(Some(b))) => {
+                Self::String(Some(a.union(b).cloned().collect()))
+            }
+            (Self::String(_), Self::String(_)) => Self::String(None),
+            (Self::Float(_), Self::Float(_)) => Self::Float(None),
+            (Self::Bool(_), Self::Bool(_)) => Self::Bool(None),
+            _ => Self::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OperandKind {
+    String,
+    Float,
+    /// A float that is used as a count, so it must be a non-negative whole number.
+    Count,
+    Bool,
+}
+
+fn expected_operands(opcode: OpCode) -> &'static [OperandKind] {
+    use OperandKind::*;
+    match opcode {
+        OpCode::JumpTo | OpCode::JumpIfFalse => &[String],
+        OpCode::RunLine | OpCode::RunCommand => &[String, Count],
+        OpCode::AddOption => &[String, String, Count, Bool],
+        OpCode::PushString | OpCode::CallFunc | OpCode::PushVariable | OpCode::StoreVariable => {
+            &[String]
+        }
+        OpCode::PushFloat => &[Float],
+        OpCode::PushBool => &[Bool],
+        OpCode::AddSaliencyCandidate => &[String, Count, String],
+        OpCode::Jump
+        | OpCode::ShowOptions
+        | OpCode::PushNull
+        | OpCode::Pop
+        | OpCode::Stop
+        | OpCode::RunNode
+        | OpCode::SelectSaliencyCandidate
+        | OpCode::DetourToNode
+        | OpCode::Return => &[],
+    }
+}
+
+type Stack = Vec<StackValue>;
+
+/// What the verifier knows about the virtual machine before running an instruction.
+#[derive(Debug, Clone, PartialEq, Default)]
+struct State {
+    stack: Stack,
+    /// The destinations of the options added since the last [`OpCode::ShowOptions`].
+    option_destinations: BTreeSet<String>,
+    /// The destinations of the saliency candidates added since the last [`OpCode::SelectSaliencyCandidate`].
+    candidate_destinations: BTreeSet<String>,
+}
+
+impl State {
+    /// Merges the states of two paths that have the same stack depth.
+    fn merge(&self, other: &Self) -> Self {
+        Self {
+            stack: self
+                .stack
+                .iter()
+                .zip(&other.stack)
+                .map(|(a, b)| a.merge(b))
+                .collect(),
+            option_destinations: self
+                .option_destinations
+                .union(&other.option_destinations)
+                .cloned()
+                .collect(),
+            candidate_destinations: self
+                .candidate_destinations
+                .union(&other.candidate_destinations)
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+struct NodeVerifier<'a> {
+    program: &'a Program,
+    /// The library to check function calls against. If `None`, only the parameter count on the stack is checked.
+    library: Option<&'a Library>,
+    node: &'a Node,
+    problems: BTreeSet<(Option<usize>, String)>,
+}
+
+impl NodeVerifier<'_> {
+    fn verify(&mut self) {
+        let mut labels: Vec<_> = self.node.labels.iter().collect();
+        labels.sort_unstable();
+        for (name, &position) in labels {
+            if usize::try_from(position)
+                .map_or(true, |position| position > self.node.instructions.len())
+            {
+                self.problems.insert((
+                    None,
+                    format!(
+                        "Label \"{name}\" points to instruction {position}, which does not exist"
+                    ),
+                ));
+            }
+        }
+
+        let opcodes: Vec<_> = self
+            .node
+            .instructions
+            .iter()
+            .enumerate()
+            .map(
+                |(index, instruction)| match self.check_operands(instruction) {
+                    Ok(opcode) => Some(opcode),
+                    Err(message) => {
+                        self.problems.insert((Some(index), message));
+                        None
+                    }
+                },
+            )
+            .collect();
+        self.check_stack(&opcodes);
+    }
+
+    fn check_operands(&self, instruction: &Instruction) -> Result<OpCode, String> {
+        let opcode = OpCode::try_from(instruction.opcode)
+            .map_err(|_| format!("Unknown opcode {}", instruction.opcode))?;
+        let name = opcode.as_str_name();
+        let expected = expected_operands(opcode);
+        if instruction.operands.len() != expected.len() {
+            return Err(format!(
+                "{name} has {} operands, but expects {}",
+                instruction.operands.len(),
+                expected.len()
+            ));
+        }
+        for (index, (operand, kind)) in instruction.operands.iter().zip(expected).enumerate() {
+            let is_valid = match (&operand.value, kind) {
+                (Some(OperandValue::StringValue(_)), OperandKind::String)
+                | (Some(OperandValue::FloatValue(_)), OperandKind::Float)
+                | (Some(OperandValue::BoolValue(_)), OperandKind::Bool) => true,
+                (Some(OperandValue::FloatValue(value)), OperandKind::Count) => is_count(*value),
+                _ => false,
+            };
+            if !is_valid {
+                let expected = match kind {
+                    OperandKind::String => "a string",
+                    OperandKind::Float => "a number",
+                    OperandKind::Count => "a non-negative whole number",
+                    OperandKind::Bool => "a bool",
+                };
+                return Err(format!("Operand {index} of {name} must be {expected}"));
+            }
+        }
+        Ok(opcode)
+    }
+
+    /// Follows every path through the node, keeping track of the values on the stack.
+    /// Constant bools are tracked so that the paths after [`OpCode::SelectSaliencyCandidate`] can be told apart,
+    /// and constant strings so that the destinations of [`OpCode::Jump`], [`OpCode::RunNode`] and [`OpCode::DetourToNode`] can be checked.
+    fn check_stack(&mut self, opcodes: &[Option<OpCode>]) {
+        let end = opcodes.len();
+        let mut states: Vec<Option<State>> = vec![None; end + 1];
+        states[0] = Some(State::default());
+        let mut queue = VecDeque::from([0]);
+        while let Some(index) = queue.pop_front() {
+            let state = states[index].clone().unwrap();
+            if index == end {
+                if self.node.is_smart_variable() && state.stack.len() != 1 {
+                    self.problems.insert((
+                        Some(index),
+                        format!(
+                            "A smart variable must leave exactly one value on the stack, but leaves {}",
+                            state.stack.len()
+                        ),
+                    ));
+                }
+                continue;
+            }
+            let Some(opcode) = opcodes[index] else {
+                continue;
+            };
+            let successors = match self.step(index, opcode, state) {
+                Ok(successors) => successors,
+                Err(message) => {
+                    self.problems.insert((Some(index), message));
+                    continue;
+                }
+            };
+            for (successor, state) in successors {
+                match &mut states[successor] {
+                    None => {
+                        states[successor] = Some(state);
+                        queue.push_back(successor);
+                    }
+                    Some(existing) if existing.stack.len() != state.stack.len() => {
+                        self.problems.insert((
+                            Some(successor),
+                            format!(
+                                "The stack depth is {} on one path to this instruction, but {} on another",
+                                existing.stack.len(),
+                                state.stack.len()
+                            ),
+                        ));
+                    }
+                    Some(existing) => {
+                        let merged = existing.merge(&state);
+                        if &merged != existing {
+                            *existing = merged;
+                            queue.push_back(successor);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the instructions that can run after the one at `index`, together with the state they see.
+    fn step(
+        &self,
+        index: usize,
+        opcode: OpCode,
+        mut state: State,
+    ) -> Result<Vec<(usize, State)>, String> {
+        let instruction = &self.node.instructions[index];
+        let stack = &mut state.stack;
+        let next = index + 1;
+        let successors = match opcode {
+            OpCode::JumpTo => vec![(self.label(instruction, 0)?, state)],
+            OpCode::Jump => {
+                let targets = match peek(stack)? {
+                    StackValue::String(Some(labels)) => labels
+                        .iter()
+                        .map(|label| self.label_position(label))
+                        .collect::<Result<Vec<_>, _>>()?,
+                    StackValue::String(None) | StackValue::Unknown => self.all_jump_destinations(),
+                    _ => return Err("JUMP expects a label name on top of the stack".to_owned()),
+                };
+                targets
+                    .into_iter()
+                    .map(|target| (target, state.clone()))
+                    .collect()
+            }
+            OpCode::RunLine | OpCode::RunCommand => {
+                pop_many(stack, count(instruction, 1))?;
+                vec![(next, state)]
+            }
+            OpCode::AddOption => {
+                self.label(instruction, 1)?;
+                pop_many(stack, count(instruction, 2))?;
+                if bool::try_from(instruction.operands[3].clone()).unwrap_or_default() {
+                    pop(stack)?;
+                }
+                let destination = String::try_from(instruction.operands[1].clone()).unwrap();
+                state.option_destinations.insert(destination);
+                vec![(next, state)]
+            }
+            OpCode::ShowOptions => {
+                // Without options, the dialogue ends here
+                if state.option_destinations.is_empty() {
+                    return Ok(vec![]);
+                }
+                let destinations = std::mem::take(&mut state.option_destinations);
+                state.stack.push(StackValue::String(Some(destinations)));
+                vec![(next, state)]
+            }
+            OpCode::PushString => {
+                let value = String::try_from(instruction.operands[0].clone()).unwrap();
+                stack.push(StackValue::constant_string(value));
+                vec![(next, state)]
+            }
+            OpCode::PushFloat => {
+                let value = f32::try_from(instruction.operands[0].clone()).ok();
+                stack.push(StackValue::Float(value));
+                vec![(next, state)]
+            }
+            OpCode::PushBool => {
+                let value = bool::try_from(instruction.operands[0].clone()).ok();
+                stack.push(StackValue::Bool(value));
+                vec![(next, state)]
+            }
+            OpCode::PushNull => {
+                return Err(
+                    "PUSH_NULL is no longer valid, because null is no longer a valid value from Yarn Spinner 2.0 onwards"
+                        .to_owned(),
+                )
+            }
+            OpCode::JumpIfFalse => {
+                let target = self.label(instruction, 0)?;
+                match peek(stack)? {
+                    StackValue::Bool(Some(true)) => vec![(next, state)],
+                    StackValue::Bool(Some(false)) => vec![(target, state)],
+                    StackValue::Bool(None) | StackValue::Unknown => {
+                        vec![(next, state.clone()), (target, state)]
+                    }
+                    _ => return Err("JUMP_IF_FALSE expects a bool on top of the stack".to_owned()),
+                }
+            }
+            OpCode::Pop => {
+                pop(stack)?;
+                vec![(next, state)]
+            }
+            OpCode::CallFunc => {
+                let function_name = String::try_from(instruction.operands[0].clone()).unwrap();
+                let parameter_count = match pop(stack)? {
+                    StackValue::Float(Some(value)) if is_count(value) => value as usize,
+                    _ => {
+                        return Err(format!(
+                            "CALL_FUNC for \"{function_name}\" expects the number of parameters on top of the stack, pushed as a non-negative whole number"
+                        ))
+                    }
+                };
+                if let Some(library) = self.library {
+                    let function = library.get(&function_name).ok_or_else(|| {
+                        format!("Function \"{function_name}\" is not in the library")
+                    })?;
+                    let expected_parameter_count = function.parameter_types().len();
+                    if parameter_count != expected_parameter_count {
+                        return Err(format!(
+                            "Function \"{function_name}\" expects {expected_parameter_count} parameters, but is called with {parameter_count}"
+                        ));
+                    }
+                }
+                pop_many(stack, parameter_count)?;
+                stack.push(StackValue::Unknown);
+                vec![(next, state)]
+            }
+            OpCode::PushVariable => {
+                stack.push(StackValue::Unknown);
+                vec![(next, state)]
+            }
+            OpCode::StoreVariable => {
+                peek(stack)?;
+                vec![(next, state)]
+            }
+            OpCode::Stop | OpCode::Return => vec![],
+            OpCode::RunNode => {
+                self.check_node_destination(pop(stack)?, opcode)?;
+                vec![]
+            }
+            OpCode::DetourToNode => {
+                self.check_node_destination(pop(stack)?, opcode)?;
+                vec![(next, state)]
+            }
+            OpCode::AddSaliencyCandidate => {
+                pop(stack)?;
+                let destination = String::try_from(instruction.operands[2].clone()).unwrap();
+                state.candidate_destinations.insert(destination);
+                vec![(next, state)]
+            }
+            OpCode::SelectSaliencyCandidate => {
+                // Pushes the selected destination and `true`, or only `false` if no candidate was selected.
+                // The next instruction tells those cases apart, so the paths are followed from after it.
+                let jump_if_false = self
+                    .node
+                    .instructions
+                    .get(next)
+                    .filter(|instruction| instruction.opcode == OpCode::JumpIfFalse as i32)
+                    .ok_or_else(|| {
+                        "SELECT_SALIENCY_CANDIDATE must be followed by JUMP_IF_FALSE".to_owned()
+                    })?;
+                let target = self.label(jump_if_false, 0)?;
+                let destinations = std::mem::take(&mut state.candidate_destinations);
+                let mut not_selected = state.clone();
+                not_selected.stack.push(StackValue::Bool(Some(false)));
+                let mut successors = vec![(target, not_selected)];
+                if !destinations.is_empty() {
+                    let mut selected = state;
+                    selected
+                        .stack
+                        .push(StackValue::String(Some(destinations)));
+                    selected.stack.push(StackValue::Bool(Some(true)));
+                    successors.push((next + 1, selected));
+                }
+                successors
+            }
+        };
+        Ok(successors)
+    }
+
+    fn label(&self, instruction: &Instruction, operand: usize) -> Result<usize, String> {
+        let name = String::try_from(instruction.operands[operand].clone()).unwrap();
+        self.label_position(&name)
+    }
+
+    fn label_position(&self, name: &str) -> Result<usize, String> {
+        self.node
+            .labels
+            .get(name)
+            .and_then(|&position| usize::try_from(position).ok())
+            .filter(|&position| position <= self.node.instructions.len())
+            .ok_or_else(|| format!("Unknown label \"{name}\""))
+    }
+
+    /// The labels that a [`OpCode::Jump`] can go to when nothing is known about the label on the stack,
+    /// i.e. the destinations of all options and saliency candidates in the node.
+    fn all_jump_destinations(&self) -> Vec<usize> {
+        let targets: BTreeSet<_> = self
+            .node
+            .instructions
+            .iter()
+            .filter_map(|instruction| {
+                let operand = match OpCode::try_from(instruction.opcode).ok()? {
+                    OpCode::AddOption => instruction.operands.get(1)?,
+                    OpCode::AddSaliencyCandidate => instruction.operands.get(2)?,
+                    _ => return None,
+                };
+                let name = String::try_from(operand.clone()).ok()?;
+                self.label_position(&name).ok()
+            })
+            .collect();
+        targets.into_iter().collect()
+    }
+
+    fn check_node_destination(&self, value: StackValue, opcode: OpCode) -> Result<(), String> {
+        match value {
+            StackValue::String(Some(names)) => match names
+                .iter()
+                .find(|name| !self.program.nodes.contains_key(*name))
+            {
+                Some(name) => Err(format!(
+                    "{} goes to node \"{name}\", which does not exist",
+                    opcode.as_str_name()
+                )),
+                None => Ok(()),
+            },
+            StackValue::String(None) | StackValue::Unknown => Ok(()),
+            _ => Err(format!(
+                "{} expects a node name on top of the stack",
+                opcode.as_str_name()
+            )),
+        }
+    }
+}
+
+fn count(instruction: &Instruction, operand: usize) -> usize {
+    usize::try_from(instruction.operands[operand].clone()).unwrap_or_default()
+}
+
+fn is_count(value: f32) -> bool {
+    value >= 0.0 && value.fract() == 0.0
+}
+
+fn pop(stack: &mut Stack) -> Result<StackValue, String> {
+    stack
+        .pop()
+        .ok_or_else(|| "Pops a value from an empty stack".to_owned())
+}
+
+fn pop_many(stack: &mut Stack, count: usize) -> Result<(), String> {
+    if stack.len() < count {
+        return Err(format!(
+            "Pops {count} values, but the stack only holds {}",
+            stack.len()
+        ));
+    }
+    stack.truncate(stack.len() - count);
+    Ok(())
+}
+
+fn peek(stack: &Stack) -> Result<&StackValue, String> {
+    stack
+        .last()
+        .ok_or_else(|| "Reads a value from an empty stack".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problems(listing: &str) -> Vec<String> {
+        let program = Program::assemble(listing).unwrap();
+        match program.verify(&Library::standard_library()) {
+            Ok(()) => vec![],
+            Err(error) => error.0.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn accepts_valid_programs() {
+        let listing = r#"
+.node "Start"
+    PUSH_FLOAT 1
+    PUSH_FLOAT 2
+    PUSH_FLOAT 2
+    CALL_FUNC "Number.Add"
+    PUSH_VARIABLE "$flag"
+    JUMP_IF_FALSE "skip"
+    POP
+    RUN_LINE "line:a" 1
+    JUMP_TO "options"
+skip:
+    POP
+    POP
+options:
+    ADD_OPTION "line:b" "option_b" 0 false
+    SHOW_OPTIONS
+    JUMP
+option_b:
+    POP
+    PUSH_STRING "Other"
+    DETOUR_TO_NODE
+    PUSH_STRING "Other"
+    RUN_NODE
+
+.node "Other"
+    STOP
+"#;
+        assert_eq!(Vec::<String>::new(), problems(listing));
+    }
+
+    #[test]
+    fn follows_both_paths_of_saliency_selection() {
+        let listing = r#"
+.node "Start"
+    PUSH_BOOL true
+    ADD_SALIENCY_CANDIDATE "line:a" 0 "line_a"
+    SELECT_SALIENCY_CANDIDATE
+    JUMP_IF_FALSE "end"
+    POP
+    JUMP
+line_a:
+    RUN_LINE "line:a" 0
+    JUMP_TO "end"
+end:
+    POP
+    STOP
+"#;
+        assert_eq!(Vec::<String>::new(), problems(listing));
+    }
+
+    #[test]
+    fn reports_problems() {
+        let listing = r#"
+.node "Start"
+.label "Broken" 99
+    JUMP_TO "nowhere"
+    PUSH_STRING 1
+    POP
+    CALL_FUNC "Number.Add"
+    PUSH_STRING "Missing"
+    RUN_NODE
+"#;
+        assert_eq!(
+            vec![
+                "Node \"Start\": Label \"Broken\" points to instruction 99, which does not exist",
+                "Node \"Start\", instruction 0: Unknown label \"nowhere\"",
+                "Node \"Start\", instruction 1: Operand 0 of PUSH_STRING must be a string",
+            ],
+            problems(listing)
+        );
+
+        let listing = r#"
+.node "Start"
+    POP
+.node "Other"
+    PUSH_FLOAT 1
+    PUSH_FLOAT 1
+    CALL_FUNC "Number.Add"
+.node "Third"
+    PUSH_FLOAT 0
+    CALL_FUNC "Nope"
+.node "Fourth"
+    PUSH_STRING "Missing"
+    RUN_NODE
+.node "Fifth"
+    PUSH_VARIABLE "$flag"
+    JUMP_IF_FALSE "end"
+    PUSH_FLOAT 1
+end:
+    STOP
+.node "Sixth"
+    PUSH_BOOL false
+    SELECT_SALIENCY_CANDIDATE
+    POP
+"#;
+        assert_eq!(
+            vec![
+                "Node \"Fifth\", instruction 3: The stack depth is 1 on one path to this instruction, but 2 on another",
+                "Node \"Fourth\", instruction 1: RUN_NODE goes to node \"Missing\", which does not exist",
+                "Node \"Other\", instruction 2: Function \"Number.Add\" expects 2 parameters, but is called with 1",
+                "Node \"Sixth\", instruction 1: SELECT_SALIENCY_CANDIDATE must be followed by JUMP_IF_FALSE",
+                "Node \"Start\", instruction 0: Pops a value from an empty stack",
+                "Node \"Third\", instruction 1: Function \"Nope\" is not in the library",
+            ],
+            problems(listing)
+        );
+    }
+
+    #[test]
+    fn skips_function_checks_without_library() {
+        let listing = r#"
+.node "Start"
+    PUSH_FLOAT 0
+    CALL_FUNC "Later"
+    POP
+    PUSH_FLOAT 0
+    CALL_FUNC
+"#;
+        let program = Program::assemble(listing).unwrap();
+        assert_eq!(
+            vec!["Node \"Start\", instruction 4: CALL_FUNC has 0 operands, but expects 1"],
+            program
+                .verify_without_library()
+                .unwrap_err()
+                .0
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn checks_smart_variable_nodes_leave_one_value() {
+        let listing = r#"
+.node "$smart"
+.header "$Yarn.Internal.SmartVariable" "1"
+    PUSH_FLOAT 1
+    PUSH_FLOAT 1
+"#;
+        assert_eq!(
+            vec![
+                "Node \"$smart\", instruction 2: A smart variable must leave exactly one value on the stack, but leaves 2"
+            ],
+            problems(listing)
+        );
+    }
+}
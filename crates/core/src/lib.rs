@@ -6,11 +6,13 @@
 //! - If you wish to write an adapter crate for an engine yourself, use the [`yarnspinner`](https://crates.io/crates/yarnspinner) crate.
 
 #![warn(missing_docs, missing_debug_implementations)]
+mod command_library;
 mod feature_gates;
 mod generated;
 mod internal_value;
 mod library;
 mod line_id;
+mod opcode;
 mod operator;
 mod position;
 pub mod types;
@@ -23,13 +25,15 @@ pub mod prelude {
     pub use crate::feature_gates::*;
 
     pub use crate::{
+        command_library::*,
         generated::{
-            instruction::OpCode, operand::Value as OperandValue, Header, Instruction,
-            InvalidOpCodeError, Node, Operand, Program,
+            operand::Value as OperandValue, AssemblyError, Header, Instruction, InvalidOpCodeError,
+            Node, Operand, Program, ProgramDecodeError, VerificationError, VerificationProblem,
         },
         internal_value::*,
         library::*,
         line_id::*,
+        opcode::*,
         operator::*,
         position::*,
         types::Type,
@@ -5,6 +5,8 @@ use std::borrow::Cow;
 use std::collections::hash_map;
 use std::fmt::Display;
 
+const LOCAL_VARIABLE_PREFIX: &str = "$Yarn.Internal.Local.";
+
 /// A collection of functions that can be called from Yarn scripts.
 ///
 /// Can be conveniently created with the [`yarn_library!`] macro.
@@ -64,6 +66,26 @@ impl Library {
         format!("$Yarn.Internal.Visiting.{node_name}")
     }
 
+    /// Generates the name of the variable that tracks whether a `<<once>>` block or `#once` line of a node has already been run.
+    /// The `key` identifies it within the node and must not change when unrelated parts of the node are edited,
+    /// since the variable is usually part of a saved game.
+    pub fn generate_unique_once_variable(node_name: &str, key: &str) -> String {
+        format!("$Yarn.Internal.Once.{node_name}.{key}")
+    }
+
+    /// Generates the name under which a variable declared via `<<local>>` is stored while its node is running.
+    /// Since the node name is part of it, locals with the same name in different nodes never clash.
+    pub fn generate_local_variable_name(node_name: &str, variable_name: &str) -> String {
+        let variable_name = variable_name.trim_start_matches('$');
+        format!("{LOCAL_VARIABLE_PREFIX}{node_name}.{variable_name}")
+    }
+
+    /// Returns `true` if the given variable name was generated by [`Library::generate_local_variable_name`].
+    /// Such variables are never stored in the variable storage.
+    pub fn is_local_variable_name(variable_name: &str) -> bool {
+        variable_name.starts_with(LOCAL_VARIABLE_PREFIX)
+    }
+
     /// Creates a [`Library`] with the standard functions that are included in Yarn Spinner.
     /// These are:
     /// - `string`: Converts a value to a string.
@@ -73,8 +95,8 @@ impl Library {
     pub fn standard_library() -> Self {
         let mut library = yarn_library!(
             "string" => <String as From<YarnValue >>::from,
-            "number" => |value: YarnValue| f32::try_from(value).expect("Failed to convert a Yarn value to a number"),
-            "bool" => |value: YarnValue| bool::try_from(value).expect("Failed to convert a Yarn value to a bool"),
+            "number" => |value: YarnValue| f32::try_from(value),
+            "bool" => |value: YarnValue| bool::try_from(value),
         );
         for r#type in [Type::Number, Type::String, Type::Boolean] {
             library.add_methods(r#type);
@@ -116,7 +138,7 @@ impl Library {
     where
         Marker: 'static,
         F: YarnFn<Marker> + 'static + Clone,
-        F::Out: IntoYarnValueFromNonYarnValue + 'static + Clone,
+        F::Out: YarnFnOut + 'static,
     {
         self.0.register_function(name, function);
         self
@@ -0,0 +1,245 @@
+//! The instructions of the Yarn virtual machine.
+//!
+//! Mirrors the `Instruction.OpCode` enum of the ProtoBuf definition, which the generated [`Instruction::opcode`](crate::prelude::Instruction) field refers to.
+//! It is written by hand because it extends the ProtoBuf definition with instructions for saliency, detours and returns.
+//! The values up to and including [`OpCode::RunNode`] are the ones of the ProtoBuf definition and must not change.
+//! The ones after it were made up for this implementation, so programs using them can't be run by the original Yarn Spinner.
+
+use crate::prelude::InvalidOpCodeError;
+#[cfg(any(feature = "bevy", feature = "serde"))]
+use crate::prelude::*;
+
+/// The type of instruction that this is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "bevy", derive(Reflect))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "bevy", reflect(Debug, PartialEq))]
+#[cfg_attr(
+    all(feature = "bevy", feature = "serde"),
+    reflect(Serialize, Deserialize)
+)]
+#[repr(i32)]
+pub enum OpCode {
+    /// Jumps to a named position in the node.
+    /// opA = string: label name
+    #[default]
+    JumpTo = 0,
+    /// Peeks a string from stack, and jumps to that named position in
+    /// the node.
+    /// No operands.
+    Jump = 1,
+    /// Delivers a string ID to the client.
+    /// opA = string: string ID
+    RunLine = 2,
+    /// Delivers a command to the client.
+    /// opA = string: command text
+    RunCommand = 3,
+    /// Adds an entry to the option list (see ShowOptions).
+    /// - opA = string: string ID for option to add
+    /// - opB = string: destination to go to if this option is selected
+    /// - opC = number: number of expressions on the stack to insert
+    ///   into the line
+    /// - opD = bool: whether the option has a condition on it (in which
+    ///   case a value should be popped off the stack and used to signal
+    ///   the game that the option should be not available)
+    AddOption = 4,
+    /// Presents the current list of options to the client, then clears
+    /// the list. The most recently selected option will be on the top
+    /// of the stack when execution resumes.
+    /// No operands.
+    ShowOptions = 5,
+    /// Pushes a string onto the stack.
+    /// opA = string: the string to push to the stack.
+    PushString = 6,
+    /// Pushes a floating point number onto the stack.
+    /// opA = float: number to push to stack
+    PushFloat = 7,
+    /// Pushes a boolean onto the stack.
+    /// opA = bool: the bool to push to stack
+    PushBool = 8,
+    /// Pushes a null value onto the stack.
+    /// No operands.
+    PushNull = 9,
+    /// Jumps to the named position in the the node, if the top of the
+    /// stack is not null, zero or false.
+    /// opA = string: label name
+    JumpIfFalse = 10,
+    /// Discards top of stack.
+    /// No operands.
+    Pop = 11,
+    /// Calls a function in the client. Pops as many arguments as the
+    /// client indicates the function receives, and the result (if any)
+    /// is pushed to the stack.
+    /// opA = string: name of the function
+    CallFunc = 12,
+    /// Pushes the contents of a variable onto the stack.
+    /// opA = name of variable
+    PushVariable = 13,
+    /// Stores the contents of the top of the stack in the named
+    /// variable.
+    /// opA = name of variable
+    StoreVariable = 14,
+    /// Stops execution of the program, including every node waiting for
+    /// a DetourToNode to complete. Emitted for `<<stop>>`.
+    /// The compiler also emits one as the last instruction of every node.
+    /// That one marks where the node ends by itself, so if the node was
+    /// entered via DetourToNode, it returns to where the detour started
+    /// instead.
+    /// No operands.
+    Stop = 15,
+    /// Pops a string off the top of the stack, and runs the node with
+    /// that name.
+    /// No operands.
+    RunNode = 16,
+    /// Pops a boolean off the top of the stack, and adds a candidate for
+    /// saliency selection whose availability is that boolean.
+    /// opA = string: the ID of the candidate's content (a line ID or a
+    /// node name).
+    /// opB = float: the complexity score of the candidate's condition.
+    /// opC = string: the destination to jump to if the candidate is
+    /// selected (a label or a node name).
+    AddSaliencyCandidate = 17,
+    /// Selects one of the saliency candidates added since the last
+    /// selection and clears them. If a candidate was selected, pushes its
+    /// destination followed by `true`. Otherwise, pushes `false`.
+    /// No operands.
+    SelectSaliencyCandidate = 18,
+    /// Pops a string off the top of the stack, and runs the node with
+    /// that name. Once that node completes, execution continues after
+    /// this instruction. A RunNode inside that node replaces it, so
+    /// execution continues here once the new node completes instead.
+    /// No operands.
+    DetourToNode = 19,
+    /// Completes the current node. If it was entered via DetourToNode,
+    /// returns to where the detour started. Otherwise, stops execution.
+    /// Emitted only for `<<return>>`.
+    /// No operands.
+    Return = 20,
+}
+
+impl OpCode {
+    /// The name of the opcode as used in the ProtoBuf definition, e.g. `RUN_LINE`.
+    /// Used by the textual assembly format.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            OpCode::JumpTo => "JUMP_TO",
+            OpCode::Jump => "JUMP",
+            OpCode::RunLine => "RUN_LINE",
+            OpCode::RunCommand => "RUN_COMMAND",
+            OpCode::AddOption => "ADD_OPTION",
+            OpCode::ShowOptions => "SHOW_OPTIONS",
+            OpCode::PushString => "PUSH_STRING",
+            OpCode::PushFloat => "PUSH_FLOAT",
+            OpCode::PushBool => "PUSH_BOOL",
+            OpCode::PushNull => "PUSH_NULL",
+            OpCode::JumpIfFalse => "JUMP_IF_FALSE",
+            OpCode::Pop => "POP",
+            OpCode::CallFunc => "CALL_FUNC",
+            OpCode::PushVariable => "PUSH_VARIABLE",
+            OpCode::StoreVariable => "STORE_VARIABLE",
+            OpCode::Stop => "STOP",
+            OpCode::RunNode => "RUN_NODE",
+            OpCode::AddSaliencyCandidate => "ADD_SALIENCY_CANDIDATE",
+            OpCode::SelectSaliencyCandidate => "SELECT_SALIENCY_CANDIDATE",
+            OpCode::DetourToNode => "DETOUR_TO_NODE",
+            OpCode::Return => "RETURN",
+        }
+    }
+    /// Parses a name returned by [`OpCode::as_str_name`].
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "JUMP_TO" => Some(Self::JumpTo),
+            "JUMP" => Some(Self::Jump),
+            "RUN_LINE" => Some(Self::RunLine),
+            "RUN_COMMAND" => Some(Self::RunCommand),
+            "ADD_OPTION" => Some(Self::AddOption),
+            "SHOW_OPTIONS" => Some(Self::ShowOptions),
+            "PUSH_STRING" => Some(Self::PushString),
+            "PUSH_FLOAT" => Some(Self::PushFloat),
+            "PUSH_BOOL" => Some(Self::PushBool),
+            "PUSH_NULL" => Some(Self::PushNull),
+            "JUMP_IF_FALSE" => Some(Self::JumpIfFalse),
+            "POP" => Some(Self::Pop),
+            "CALL_FUNC" => Some(Self::CallFunc),
+            "PUSH_VARIABLE" => Some(Self::PushVariable),
+            "STORE_VARIABLE" => Some(Self::StoreVariable),
+            "STOP" => Some(Self::Stop),
+            "RUN_NODE" => Some(Self::RunNode),
+            "ADD_SALIENCY_CANDIDATE" => Some(Self::AddSaliencyCandidate),
+            "SELECT_SALIENCY_CANDIDATE" => Some(Self::SelectSaliencyCandidate),
+            "DETOUR_TO_NODE" => Some(Self::DetourToNode),
+            "RETURN" => Some(Self::Return),
+            _ => None,
+        }
+    }
+
+    /// All opcodes, ordered by their value.
+    pub const ALL: [OpCode; 21] = [
+        OpCode::JumpTo,
+        OpCode::Jump,
+        OpCode::RunLine,
+        OpCode::RunCommand,
+        OpCode::AddOption,
+        OpCode::ShowOptions,
+        OpCode::PushString,
+        OpCode::PushFloat,
+        OpCode::PushBool,
+        OpCode::PushNull,
+        OpCode::JumpIfFalse,
+        OpCode::Pop,
+        OpCode::CallFunc,
+        OpCode::PushVariable,
+        OpCode::StoreVariable,
+        OpCode::Stop,
+        OpCode::RunNode,
+        OpCode::AddSaliencyCandidate,
+        OpCode::SelectSaliencyCandidate,
+        OpCode::DetourToNode,
+        OpCode::Return,
+    ];
+}
+
+impl TryFrom<i32> for OpCode {
+    type Error = InvalidOpCodeError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        usize::try_from(value)
+            .ok()
+            .and_then(|index| Self::ALL.get(index).copied())
+            .ok_or(InvalidOpCodeError(value))
+    }
+}
+
+impl From<OpCode> for i32 {
+    fn from(opcode: OpCode) -> Self {
+        opcode as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_from_and_to_values() {
+        for (value, opcode) in OpCode::ALL.into_iter().enumerate() {
+            assert_eq!(value as i32, i32::from(opcode));
+            assert_eq!(Ok(opcode), OpCode::try_from(value as i32));
+            assert_eq!(Some(opcode), OpCode::from_str_name(opcode.as_str_name()));
+        }
+        assert_eq!(Err(InvalidOpCodeError(21)), OpCode::try_from(21));
+        assert_eq!(Err(InvalidOpCodeError(-1)), OpCode::try_from(-1));
+    }
+
+    #[test]
+    fn matches_the_generated_opcodes() {
+        use crate::generated::instruction::OpCode as GeneratedOpCode;
+        for opcode in OpCode::ALL
+            .into_iter()
+            .take_while(|&op| op <= OpCode::RunNode)
+        {
+            let generated = GeneratedOpCode::try_from(i32::from(opcode)).unwrap();
+            assert_eq!(generated.as_str_name(), opcode.as_str_name());
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Not part of the original, which only gained enums in Yarn Spinner 3.
+
+use crate::prelude::*;
+use crate::types::TypeProperties;
+use std::fmt::Display;
+
+/// Enum values are represented by the name of their case at runtime, so they support the same comparisons as strings.
+pub(crate) fn enum_type_properties(enum_type: &EnumType) -> TypeProperties {
+    TypeProperties::from_name("Enum")
+        .with_description(format!("Enum {enum_type}"))
+        .with_methods(yarn_library! {
+            Operator::EqualTo => <RustType as PartialEq>::eq,
+            Operator::NotEqualTo => <RustType as PartialEq>::ne,
+        })
+}
+
+type RustType = String;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Hash)]
+#[cfg_attr(feature = "bevy", derive(Reflect))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "bevy", reflect(Debug, PartialEq, Default, Hash))]
+#[cfg_attr(
+    all(feature = "bevy", feature = "serde"),
+    reflect(Serialize, Deserialize)
+)]
+/// A type that represents an enumeration declared in Yarn, e.g.
+///
+/// ```text
+/// <<enum Mood>>
+///     <<case Happy>>
+///     <<case Sad>>
+/// <<endenum>>
+/// ```
+///
+/// Cases are referred to in Yarn by their qualified name, e.g. `Mood.Happy`.
+/// At runtime, a value of an enum is a [`YarnValue::String`] containing the name of the case, e.g. `"Happy"`.
+/// This means that values stored in a [`VariableStorage`](https://docs.rs/yarnspinner/latest/yarnspinner/runtime/trait.VariableStorage.html)
+/// stay valid when cases are reordered or new cases are added.
+pub struct EnumType {
+    /// The name of the enum, e.g. `Mood`.
+    pub name: String,
+
+    /// The names of the cases of the enum, in the order they were declared.
+    pub cases: Vec<String>,
+}
+
+impl From<EnumType> for Type {
+    fn from(enum_type: EnumType) -> Self {
+        Type::Enum(enum_type)
+    }
+}
+
+impl EnumType {
+    /// Creates a new enum without any cases.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            cases: Vec::new(),
+        }
+    }
+
+    /// Adds a case to this enum.
+    pub fn add_case(&mut self, case: impl Into<String>) -> &mut Self {
+        self.cases.push(case.into());
+        self
+    }
+
+    /// Returns `true` if this enum has a case with the given name.
+    pub fn has_case(&self, case: &str) -> bool {
+        self.cases.iter().any(|c| c == case)
+    }
+
+    /// Returns the runtime representation of the given case, or `None` if this enum has no such case.
+    pub fn value_of(&self, case: &str) -> Option<YarnValue> {
+        self.has_case(case)
+            .then(|| YarnValue::String(case.to_owned()))
+    }
+
+    /// The value that variables of this enum have if they are not explicitly initialized, i.e. the first case.
+    pub fn default_value(&self) -> Option<YarnValue> {
+        self.cases.first().cloned().map(YarnValue::String)
+    }
+}
+
+impl Display for EnumType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
@@ -2,6 +2,7 @@ use crate::prelude::*;
 use crate::types::any::any_type_properties;
 use crate::types::boolean::boolean_type_properties;
 use crate::types::number::number_type_properties;
+use crate::types::r#enum::enum_type_properties;
 use crate::types::string::string_type_properties;
 use crate::types::*;
 use std::any::TypeId;
@@ -36,6 +37,8 @@ pub enum Type {
     Any,
     /// The type representing booleans
     Boolean,
+    /// The type representing enums declared in Yarn
+    Enum(EnumType),
     /// The type representing functions
     Function(FunctionType),
     /// The type representing numbers
@@ -49,6 +52,7 @@ impl Display for Type {
         let name = self.name();
         match self {
             Type::Function(function) => Display::fmt(function, f),
+            Type::Enum(enum_type) => Display::fmt(enum_type, f),
             _ => write!(f, "{}", name),
         }
     }
@@ -96,6 +100,7 @@ impl Type {
         match self {
             Type::Any => any_type_properties(),
             Type::Boolean => boolean_type_properties(),
+            Type::Enum(enum_type) => enum_type_properties(enum_type),
             Type::Function(function_type) => function_type_properties(function_type),
             Type::Number => number_type_properties(),
             Type::String => string_type_properties(),
@@ -112,8 +117,13 @@ impl Type {
     }
 
     /// Does not check whether the method exists. Use [`Type::has_method`] for that.
+    ///
+    /// Enum values are strings at runtime (see [`EnumType`]), so their methods resolve to the ones of [`Type::String`].
     pub fn get_canonical_name_for_method(&self, method_name: &str) -> String {
-        format!("{}.{}", self.name(), method_name)
+        match self {
+            Type::Enum(_) => Type::String.get_canonical_name_for_method(method_name),
+            _ => format!("{}.{}", self.name(), method_name),
+        }
     }
 
     /// The types that can be explicitly constructed in Yarn with variable assignments.
@@ -123,6 +133,7 @@ impl Type {
         Type::String,
         Type::Boolean,
         // Functions are not explicitly constructable
+        // Enums are declared in Yarn, so they are not known ahead of time
     ];
 }
 
@@ -1,6 +1,7 @@
 use super::optionality::AllowedOptionalityChain;
 use crate::prelude::*;
 use std::any::TypeId;
+use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
 use std::marker::PhantomData;
 use yarnspinner_macros::all_tuples;
@@ -19,6 +20,8 @@ use yarnspinner_macros::all_tuples;
 ///   - [`bool`]
 ///   - A numeric type, i.e. one of [`f32`], [`f64`], [`i8`], [`i16`], [`i32`], [`i64`], [`i128`], [`u8`], [`u16`], [`u32`], [`u64`], [`u128`], [`usize`], [`isize`]
 ///   - [`String`]
+///   - A [`Result`] whose `Ok` variant is one of the above and whose `Err` variant can be turned into a [`YarnFnError`].
+///     Returning an `Err` stops the dialogue and reports the error to the caller of `Dialogue::continue_`.
 ///
 /// Note that in particular, no references can be returned.
 /// ## Examples
@@ -34,16 +37,59 @@ use yarnspinner_macros::all_tuples;
 /// <<set $is_cool to true>>
 /// Narrator: {give_summary($name, $age, $is_cool)}
 /// ```
+///
+/// A function that can fail returns a [`Result`]:
+/// ```rust
+/// fn load_gold(save_slot: usize) -> Result<f32, std::io::Error> {
+///     let gold = std::fs::read_to_string(format!("save_{save_slot}/gold.txt"))?;
+///     Ok(gold.trim().parse().unwrap_or_default())
+/// }
+/// ```
 pub trait YarnFn<Marker>: Clone + Send + Sync {
     /// The type of the value returned by this function. See [`YarnFn`] for more information about what is allowed.
-    type Out: IntoYarnValueFromNonYarnValue + 'static;
+    type Out: YarnFnOut + 'static;
     #[doc(hidden)]
     fn call(&self, input: Vec<YarnValue>) -> Self::Out;
     /// The [`TypeId`]s of the parameters of this function.
     fn parameter_types(&self) -> Vec<TypeId>;
-    /// The [`TypeId`] of the return type of this function.
+    /// The [`TypeId`] of the return type of this function. For functions returning a [`Result`], this is the type of the `Ok` variant.
     fn return_type(&self) -> TypeId {
-        TypeId::of::<Self::Out>()
+        TypeId::of::<<Self::Out as YarnFnOut>::Value>()
+    }
+}
+
+/// The error returned by a fallible [`YarnFn`].
+pub type YarnFnError = Box<dyn Error + Send + Sync + 'static>;
+
+/// The return type of a [`YarnFn`]. This is either a value implementing [`IntoYarnValueFromNonYarnValue`] directly
+/// or a [`Result`] of such a value, for functions that can fail.
+pub trait YarnFnOut {
+    /// The type of the value returned when the function succeeds.
+    type Value: IntoYarnValueFromNonYarnValue + 'static;
+    #[doc(hidden)]
+    fn into_result(self) -> Result<Self::Value, YarnFnError>;
+}
+
+impl<T> YarnFnOut for T
+where
+    T: IntoYarnValueFromNonYarnValue + 'static,
+{
+    type Value = T;
+
+    fn into_result(self) -> Result<Self::Value, YarnFnError> {
+        Ok(self)
+    }
+}
+
+impl<T, E> YarnFnOut for Result<T, E>
+where
+    T: IntoYarnValueFromNonYarnValue + 'static,
+    E: Into<YarnFnError>,
+{
+    type Value = T;
+
+    fn into_result(self) -> Result<Self::Value, YarnFnError> {
+        self.map_err(Into::into)
     }
 }
 
@@ -51,7 +97,7 @@ pub trait YarnFn<Marker>: Clone + Send + Sync {
 /// See its documentation for more information about what kind of functions are allowed.
 pub trait UntypedYarnFn: Debug + Display + Send + Sync {
     #[doc(hidden)]
-    fn call(&self, input: Vec<YarnValue>) -> YarnValue;
+    fn call(&self, input: Vec<YarnValue>) -> Result<YarnValue, YarnFnError>;
     #[doc(hidden)]
     fn clone_box(&self) -> Box<dyn UntypedYarnFn>;
     /// The [`TypeId`]s of the parameters of this function.
@@ -70,11 +116,11 @@ impl<Marker, F> UntypedYarnFn for YarnFnWrapper<Marker, F>
 where
     Marker: 'static,
     F: YarnFn<Marker> + 'static + Clone,
-    F::Out: IntoYarnValueFromNonYarnValue + 'static + Clone,
+    F::Out: YarnFnOut + 'static,
 {
-    fn call(&self, input: Vec<YarnValue>) -> YarnValue {
-        let output = self.function.call(input);
-        output.into_yarn_value()
+    fn call(&self, input: Vec<YarnValue>) -> Result<YarnValue, YarnFnError> {
+        let output = self.function.call(input).into_result()?;
+        Ok(output.into_yarn_value())
     }
 
     fn clone_box(&self) -> Box<dyn UntypedYarnFn> {
@@ -179,7 +225,7 @@ macro_rules! impl_yarn_fn_tuple {
                 Send + Sync + Clone +
                 Fn($($param,)*) -> O +
                 Fn($(<$param as YarnFnParam>::Item<'a>,)*) -> O,
-            O: IntoYarnValueFromNonYarnValue + 'static,
+            O: YarnFnOut + 'static,
             $($param: YarnFnParam + 'static,)*
             ($(<$param as YarnFnParam>::Optionality,)*): AllowedOptionalityChain,
             {
@@ -349,6 +395,22 @@ mod tests {
         assert!(result);
     }
 
+    #[test]
+    fn accepts_result() {
+        fn f(_: &str) -> Result<bool, std::io::Error> {
+            Ok(true)
+        }
+        accept_yarn_fn(f);
+    }
+
+    #[test]
+    fn reports_return_type_of_result_as_ok_type() {
+        fn f() -> Result<f32, YarnValueCastError> {
+            Ok(1.0)
+        }
+        assert_eq!(TypeId::of::<f32>(), f.return_type());
+    }
+
     #[test]
     fn accepts_function_with_single_tuple_param() {
         fn f(_: (usize, isize, (String, &str))) -> bool {
@@ -65,6 +65,15 @@ pub trait YarnFnParam {
 
     #[doc(hidden)]
     fn retrieve<'a>(iter: &mut YarnValueWrapperIter<'a>) -> Self::Item<'a>;
+
+    /// The parameters this type reads from the values passed by Yarn, used to declare commands to the compiler.
+    /// See [`CommandDeclaration::from_parameter_type`].
+    ///
+    /// Defaults to a single variadic parameter of [`Type::Any`], i.e. accepting anything.
+    #[doc(hidden)]
+    fn command_parameters() -> Vec<CommandParameter> {
+        vec![CommandParameter::variadic(Type::Any)]
+    }
 }
 
 /// Shorthand way of accessing the associated type [`YarnFnParam::Item`] for a given [`YarnFnParam`].
@@ -81,6 +90,13 @@ impl<T: YarnFnParam> YarnFnParam for Option<T> {
             None
         }
     }
+
+    fn command_parameters() -> Vec<CommandParameter> {
+        T::command_parameters()
+            .into_iter()
+            .map(CommandParameter::into_optional)
+            .collect()
+    }
 }
 
 macro_rules! impl_yarn_fn_param_tuple {
@@ -97,6 +113,13 @@ macro_rules! impl_yarn_fn_param_tuple {
             fn retrieve<'a>(iter: &mut YarnValueWrapperIter<'a>) -> Self::Item<'a> {
                ($($param::retrieve(iter),)*)
             }
+
+            #[allow(unused_mut)] // for n = 0 tuples
+            fn command_parameters() -> Vec<CommandParameter> {
+                let mut parameters = Vec::new();
+                $(parameters.extend($param::command_parameters());)*
+                parameters
+            }
         }
     };
 }
@@ -130,6 +153,10 @@ where
             phantom_data: PhantomData,
         }
     }
+
+    fn command_parameters() -> Vec<CommandParameter> {
+        vec![CommandParameter::of::<T>()]
+    }
 }
 
 /// For types like `String`, of which a reference to `&str` and not `&String`.
@@ -165,6 +192,10 @@ where
             phantom_data: PhantomData,
         }
     }
+
+    fn command_parameters() -> Vec<CommandParameter> {
+        vec![CommandParameter::of::<T>()]
+    }
 }
 
 struct ResOwned<T>
@@ -190,6 +221,10 @@ where
         let value = *converted.downcast::<T>().unwrap();
         ResOwned { value }
     }
+
+    fn command_parameters() -> Vec<CommandParameter> {
+        vec![CommandParameter::of::<T>()]
+    }
 }
 
 macro_rules! impl_yarn_fn_param {
@@ -211,6 +246,10 @@ macro_rules! impl_yarn_fn_param_inner {
             fn retrieve<'a>(iter: &mut YarnValueWrapperIter<'a>) -> Self::Item<'a> {
                 ResRef::<$referenced>::retrieve(iter).value
             }
+
+            fn command_parameters() -> Vec<CommandParameter> {
+                ResRef::<$referenced>::command_parameters()
+            }
         }
 
         impl YarnFnParam for $referenced {
@@ -220,6 +259,10 @@ macro_rules! impl_yarn_fn_param_inner {
             fn retrieve<'a>(iter: &mut YarnValueWrapperIter<'a>) -> Self::Item<'a> {
                 ResOwned::<$referenced>::retrieve(iter).value
             }
+
+            fn command_parameters() -> Vec<CommandParameter> {
+                ResOwned::<$referenced>::command_parameters()
+            }
         }
     };
     ($referenced:ty => $owned:ty: YarnFnParam) => {
@@ -230,6 +273,10 @@ macro_rules! impl_yarn_fn_param_inner {
             fn retrieve<'a>(iter: &mut YarnValueWrapperIter<'a>) -> Self::Item<'a> {
                 ResRefBorrow::<$owned, $referenced>::retrieve(iter).value
             }
+
+            fn command_parameters() -> Vec<CommandParameter> {
+                ResRefBorrow::<$owned, $referenced>::command_parameters()
+            }
         }
 
         impl YarnFnParam for &$owned {
@@ -239,6 +286,10 @@ macro_rules! impl_yarn_fn_param_inner {
             fn retrieve<'a>(iter: &mut YarnValueWrapperIter<'a>) -> Self::Item<'a> {
                 ResRef::<$owned>::retrieve(iter).value
             }
+
+            fn command_parameters() -> Vec<CommandParameter> {
+                ResRef::<$owned>::command_parameters()
+            }
         }
 
         impl YarnFnParam for $owned {
@@ -248,6 +299,10 @@ macro_rules! impl_yarn_fn_param_inner {
             fn retrieve<'a>(iter: &mut YarnValueWrapperIter<'a>) -> Self::Item<'a> {
                 ResOwned::<$owned>::retrieve(iter).value
             }
+
+            fn command_parameters() -> Vec<CommandParameter> {
+                ResOwned::<$owned>::command_parameters()
+            }
         }
     };
 }
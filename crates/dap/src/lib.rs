@@ -0,0 +1,26 @@
+//! A debug adapter for Yarn files, built on the Yarn Spinner compiler and the [`Debugger`](yarnspinner::runtime::Debugger) of the runtime.
+//!
+//! It speaks the [Debug Adapter Protocol](https://microsoft.github.io/debug-adapter-protocol/) over stdio and runs the dialogue headless, so writers
+//! can step through their Yarn scripts from an editor without starting the game. It offers
+//! - breakpoints on lines, and function breakpoints that pause whenever the node with that name is entered,
+//! - stepping per line, or per instruction with the `instruction` stepping granularity,
+//! - the variables, the `<<local>>` variables of the current node and the value stack of the virtual machine,
+//! - a call stack of the current node and the nodes waiting for a `<<detour>>` to return.
+//!
+//! Lines and commands are written to the debug console. When options are presented, execution pauses until an option is selected
+//! by entering its number in the debug console. Variables can be looked up there as well, e.g. `$gold`.
+//!
+//! The `launch` request expects the following arguments:
+//! - `program`: the `.yarn` file to debug, or a directory whose `.yarn` files are debugged together.
+//! - `startNode`: the node to start at. Defaults to `Start`.
+//! - `stopOnEntry`: whether to pause before running the first instruction. Defaults to `false`.
+//!
+//! The `yarnspinner_dap` binary runs the adapter with the functions every [`Dialogue`](yarnspinner::runtime::Dialogue) offers.
+//! If your game registers functions of its own, run a [`DebugAdapter`] with your [`Library`](yarnspinner::core::Library) instead.
+#![warn(missing_docs, missing_debug_implementations)]
+
+mod protocol;
+mod server;
+mod session;
+
+pub use crate::server::{DebugAdapter, ServerError};
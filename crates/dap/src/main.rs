@@ -0,0 +1,10 @@
+use yarnspinner::runtime::{Dialogue, MemoryVariableStorage, StringTableTextProvider};
+use yarnspinner_dap::{DebugAdapter, ServerError};
+
+fn main() -> Result<(), ServerError> {
+    let dialogue = Dialogue::new(
+        Box::new(MemoryVariableStorage::new()),
+        Box::new(StringTableTextProvider::new()),
+    );
+    DebugAdapter::new(dialogue.library().clone()).run_stdio()
+}
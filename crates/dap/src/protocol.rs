@@ -0,0 +1,122 @@
+//! Reading and writing the messages of the Debug Adapter Protocol, which are JSON objects preceded by a `Content-Length` header.
+
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+
+/// Reads the next message. Returns `None` if the input ended.
+pub(crate) fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let content_length = content_length.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Message is missing a Content-Length header",
+        )
+    })?;
+    let mut content = vec![0; content_length];
+    input.read_exact(&mut content)?;
+    serde_json::from_slice(&content)
+        .map(Some)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+/// Writes messages and numbers them, as every message sent by the debug adapter needs a unique sequence number.
+#[derive(Debug)]
+pub(crate) struct Output<W> {
+    writer: W,
+    sequence: u64,
+}
+
+impl<W: Write> Output<W> {
+    pub(crate) fn new(writer: W) -> Self {
+        Self {
+            writer,
+            sequence: 0,
+        }
+    }
+
+    /// Answers a request successfully.
+    pub(crate) fn respond(&mut self, request: &Value, body: Value) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": true,
+            "body": body,
+        }))
+    }
+
+    /// Answers a request with an error that is shown to the user.
+    pub(crate) fn respond_with_error(&mut self, request: &Value, message: &str) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": false,
+            "message": message,
+            "body": { "error": { "id": 1, "format": message, "showUser": true } },
+        }))
+    }
+
+    pub(crate) fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({
+            "type": "event",
+            "event": event,
+            "body": body,
+        }))
+    }
+
+    /// Writes text to the debug console.
+    pub(crate) fn output(&mut self, category: &str, text: impl Into<String>) -> io::Result<()> {
+        self.event(
+            "output",
+            json!({ "category": category, "output": text.into() }),
+        )
+    }
+
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.sequence += 1;
+        message["seq"] = self.sequence.into();
+        let content = message.to_string();
+        write!(
+            self.writer,
+            "Content-Length: {}\r\n\r\n{content}",
+            content.len()
+        )?;
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_written_messages() {
+        let mut output = Output::new(Vec::new());
+        output.event("initialized", json!({})).unwrap();
+        output.output("stdout", "Hällo\n").unwrap();
+
+        let mut input = output.writer.as_slice();
+        let first = read_message(&mut input).unwrap().unwrap();
+        assert_eq!(json!(1), first["seq"]);
+        assert_eq!(json!("initialized"), first["event"]);
+        let second = read_message(&mut input).unwrap().unwrap();
+        assert_eq!(json!("Hällo\n"), second["body"]["output"]);
+        assert_eq!(None, read_message(&mut input).unwrap());
+    }
+}
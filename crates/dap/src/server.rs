@@ -0,0 +1,561 @@
+use crate::protocol::{read_message, Output};
+use crate::session::Session;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{self, Debug, Formatter};
+use std::io::{self, BufRead, Write};
+use std::ops::ControlFlow;
+use std::path::Path;
+use yarnspinner::core::{Library, YarnValue};
+use yarnspinner::runtime::{Breakpoint, DebugLocation, DebugPause, DialogueEvent, PauseReason};
+
+/// The error type returned when the connection to the editor fails.
+pub type ServerError = Box<dyn Error + Send + Sync>;
+
+/// A dialogue only ever runs on a single thread, which is reported to the editor with this ID.
+const THREAD_ID: u64 = 1;
+const VARIABLES_REFERENCE: u64 = 1;
+const LOCALS_REFERENCE: u64 = 2;
+const STACK_REFERENCE: u64 = 3;
+
+/// A debug adapter for Yarn files. See the [crate documentation](crate) for what it offers.
+///
+/// ## Example
+///
+/// ```no_run
+/// use yarnspinner::prelude::*;
+/// use yarnspinner_dap::DebugAdapter;
+///
+/// let mut library = YarnLibrary::standard_library();
+/// library.add_function("is_daytime", || true);
+/// DebugAdapter::new(library).run_stdio().unwrap();
+/// ```
+pub struct DebugAdapter {
+    library: Library,
+    session: Option<Session>,
+}
+
+impl Debug for DebugAdapter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DebugAdapter")
+            .field("session", &self.session)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DebugAdapter {
+    /// Creates a debug adapter that compiles and runs dialogues with the functions of the given [`Library`].
+    pub fn new(library: Library) -> Self {
+        Self {
+            library,
+            session: None,
+        }
+    }
+
+    /// Runs the debug adapter on stdin and stdout until the editor disconnects.
+    pub fn run_stdio(self) -> Result<(), ServerError> {
+        self.run(io::stdin().lock(), io::stdout().lock())
+    }
+
+    /// Runs the debug adapter on the given streams until the editor disconnects or the input ends.
+    pub fn run(mut self, mut input: impl BufRead, output: impl Write) -> Result<(), ServerError> {
+        let mut output = Output::new(output);
+        while let Some(message) = read_message(&mut input)? {
+            if message["type"] != "request" {
+                continue;
+            }
+            if self.handle_request(&message, &mut output)?.is_break() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request(
+        &mut self,
+        request: &Value,
+        output: &mut Output<impl Write>,
+    ) -> io::Result<ControlFlow<()>> {
+        let arguments = &request["arguments"];
+        match request["command"].as_str().unwrap_or_default() {
+            "initialize" => output.respond(request, capabilities())?,
+            "launch" => match Session::launch(arguments, &self.library) {
+                Ok(session) => {
+                    self.session = Some(session);
+                    output.respond(request, Value::Null)?;
+                    output.event("initialized", Value::Null)?;
+                }
+                Err(message) => output.respond_with_error(request, &message)?,
+            },
+            "setBreakpoints" => {
+                let path = arguments["source"]["path"].as_str().unwrap_or_default();
+                let lines: Vec<_> = arguments["breakpoints"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|breakpoint| breakpoint["line"].as_u64())
+                    .map(|line| line as usize)
+                    .collect();
+                let breakpoints: Vec<_> = match &mut self.session {
+                    Some(session) => session
+                        .set_line_breakpoints(path, &lines)
+                        .into_iter()
+                        .zip(&lines)
+                        .map(|(verified_line, line)| match verified_line {
+                            Some(verified_line) => json!({ "verified": true, "line": verified_line }),
+                            None => json!({
+                                "verified": false,
+                                "line": line,
+                                "message": "No dialogue runs on this line",
+                            }),
+                        })
+                        .collect(),
+                    None => lines
+                        .iter()
+                        .map(|line| json!({ "verified": false, "line": line }))
+                        .collect(),
+                };
+                output.respond(request, json!({ "breakpoints": breakpoints }))?;
+            }
+            "setFunctionBreakpoints" => {
+                let node_names: Vec<_> = arguments["breakpoints"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|breakpoint| breakpoint["name"].as_str())
+                    .map(ToOwned::to_owned)
+                    .collect();
+                let verified = match &mut self.session {
+                    Some(session) => session.set_node_breakpoints(&node_names),
+                    None => vec![false; node_names.len()],
+                };
+                let breakpoints: Vec<_> = verified
+                    .into_iter()
+                    .map(|verified| json!({ "verified": verified }))
+                    .collect();
+                output.respond(request, json!({ "breakpoints": breakpoints }))?;
+            }
+            "configurationDone" => {
+                let Some(session) = &mut self.session else {
+                    output.respond(request, Value::Null)?;
+                    return Ok(ControlFlow::Continue(()));
+                };
+                if let Err(error) = session.debugger.set_node(session.start_node.clone()) {
+                    output.respond_with_error(request, &error.to_string())?;
+                    return Ok(ControlFlow::Continue(()));
+                }
+                output.respond(request, Value::Null)?;
+                if session.stop_on_entry {
+                    stopped(output, "entry", None)?;
+                } else {
+                    let result = session.debugger.resume();
+                    report_pause(session, result, output)?;
+                }
+            }
+            "threads" => output.respond(
+                request,
+                json!({ "threads": [{ "id": THREAD_ID, "name": "Dialogue" }] }),
+            )?,
+            "stackTrace" => {
+                let frames: Vec<_> = self
+                    .session
+                    .iter()
+                    .flat_map(|session| session.debugger.call_stack())
+                    .enumerate()
+                    .map(|(id, location)| stack_frame(id, location))
+                    .collect();
+                output.respond(
+                    request,
+                    json!({ "stackFrames": frames, "totalFrames": frames.len() }),
+                )?;
+            }
+            "scopes" => output.respond(
+                request,
+                json!({ "scopes": [
+                    { "name": "Variables", "variablesReference": VARIABLES_REFERENCE, "expensive": false },
+                    { "name": "Locals", "variablesReference": LOCALS_REFERENCE, "expensive": false },
+                    { "name": "Stack", "variablesReference": STACK_REFERENCE, "expensive": false },
+                ]}),
+            )?,
+            "variables" => {
+                let variables = self
+                    .session
+                    .as_ref()
+                    .map(|session| variables(session, arguments["variablesReference"].as_u64()))
+                    .unwrap_or_default();
+                output.respond(request, json!({ "variables": variables }))?;
+            }
+            command @ ("continue" | "next" | "stepIn") => {
+                let Some(session) = &mut self.session else {
+                    output.respond_with_error(request, "No dialogue was launched")?;
+                    return Ok(ControlFlow::Continue(()));
+                };
+                if session.debugger.dialogue().is_waiting_for_option_selection() {
+                    output.respond_with_error(
+                        request,
+                        "Select an option first by entering its number in the debug console",
+                    )?;
+                    return Ok(ControlFlow::Continue(()));
+                }
+                output.respond(request, json!({ "allThreadsContinued": true }))?;
+                let result = if command == "continue" {
+                    session.debugger.resume()
+                } else if arguments["granularity"] == "instruction" {
+                    session.debugger.step_instruction()
+                } else {
+                    session.debugger.step_line()
+                };
+                report_pause(session, result, output)?;
+            }
+            "stepOut" => output.respond_with_error(
+                request,
+                "Stepping out of a node is not supported, use a function breakpoint on the node to return to instead",
+            )?,
+            // The dialogue only runs while handling a request, so it is always paused already.
+            "pause" => output.respond(request, Value::Null)?,
+            "evaluate" => {
+                let expression = arguments["expression"].as_str().unwrap_or_default();
+                match self.evaluate(expression.trim()) {
+                    Ok(result) => output.respond(
+                        request,
+                        json!({ "result": result, "variablesReference": 0 }),
+                    )?,
+                    Err(message) => output.respond_with_error(request, &message)?,
+                }
+            }
+            "disconnect" | "terminate" => {
+                output.respond(request, Value::Null)?;
+                return Ok(ControlFlow::Break(()));
+            }
+            command => {
+                output.respond_with_error(request, &format!("Unsupported request: {command}"))?;
+            }
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Selects an option by its number, or looks up a variable.
+    fn evaluate(&mut self, expression: &str) -> Result<String, String> {
+        let session = self.session.as_mut().ok_or("No dialogue was launched")?;
+        if let Ok(number) = expression.parse::<usize>() {
+            let option = session.select_option(number)?;
+            return Ok(format!("Selected \"{}\"", option.line.text));
+        }
+        if expression.starts_with('$') {
+            return session
+                .debugger
+                .dialogue()
+                .variable_storage()
+                .get(expression)
+                .map(|value| format_value(&value))
+                .map_err(|error| error.to_string());
+        }
+        Err("Enter the number of an option to select it, or the name of a variable like $gold to look it up".to_owned())
+    }
+}
+
+fn capabilities() -> Value {
+    json!({
+        "supportsConfigurationDoneRequest": true,
+        "supportsFunctionBreakpoints": true,
+        "supportsSteppingGranularity": true,
+        "supportsEvaluateForHovers": true,
+        "supportsTerminateRequest": true,
+    })
+}
+
+/// Writes the events that happened while running to the debug console and tells the editor why execution stopped.
+fn report_pause(
+    session: &mut Session,
+    result: yarnspinner::runtime::Result<DebugPause>,
+    output: &mut Output<impl Write>,
+) -> io::Result<()> {
+    let pause = match result {
+        Ok(pause) => pause,
+        Err(error) => {
+            output.output("stderr", format!("{error}\n"))?;
+            return terminate(output, 1);
+        }
+    };
+    for event in pause.events {
+        match event {
+            DialogueEvent::Line(line) => output.output("stdout", format!("{}\n", line.text))?,
+            DialogueEvent::Command(command) => {
+                output.output("console", format!("<<{}>>\n", command.raw))?
+            }
+            DialogueEvent::Options(options) => {
+                let mut text = String::new();
+                for (index, option) in options.iter().enumerate() {
+                    let unavailable = if option.is_available {
+                        ""
+                    } else {
+                        " (unavailable)"
+                    };
+                    text += &format!("{}: {}{unavailable}\n", index + 1, option.line.text);
+                }
+                text += "Enter the number of an option to select it.\n";
+                output.output("stdout", text)?;
+                session.options = options;
+            }
+            _ => {}
+        }
+    }
+    match pause.reason {
+        PauseReason::Breakpoint(Breakpoint::Line { .. }) => stopped(output, "breakpoint", None),
+        PauseReason::Breakpoint(Breakpoint::NodeEntry { .. }) => {
+            stopped(output, "function breakpoint", None)
+        }
+        PauseReason::Step => stopped(output, "step", None),
+        PauseReason::WaitingForOptionSelection => stopped(
+            output,
+            "pause",
+            Some("Waiting for an option to be selected"),
+        ),
+        PauseReason::DialogueComplete => terminate(output, 0),
+    }
+}
+
+fn stopped(
+    output: &mut Output<impl Write>,
+    reason: &str,
+    description: Option<&str>,
+) -> io::Result<()> {
+    let mut body = json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true });
+    if let Some(description) = description {
+        body["description"] = description.into();
+    }
+    output.event("stopped", body)
+}
+
+fn terminate(output: &mut Output<impl Write>, exit_code: i32) -> io::Result<()> {
+    output.event("terminated", Value::Null)?;
+    output.event("exited", json!({ "exitCode": exit_code }))
+}
+
+fn stack_frame(id: usize, location: DebugLocation) -> Value {
+    let mut frame = json!({
+        "id": id,
+        "name": location.node_name,
+        "line": 0,
+        "column": 0,
+    });
+    if let Some(file_name) = &location.file_name {
+        let name = Path::new(file_name)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| file_name.clone());
+        frame["source"] = json!({ "name": name, "path": file_name });
+    }
+    if let Some(position) = &location.position {
+        frame["line"] = (position.line + 1).into();
+        frame["column"] = (position.character + 1).into();
+    }
+    frame
+}
+
+fn variables(session: &Session, reference: Option<u64>) -> Vec<Value> {
+    let values: Vec<(String, YarnValue)> = match reference {
+        Some(VARIABLES_REFERENCE) => session
+            .debugger
+            .variables()
+            .into_iter()
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+            .collect(),
+        Some(LOCALS_REFERENCE) => session
+            .debugger
+            .locals()
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+            .collect(),
+        Some(STACK_REFERENCE) => session
+            .debugger
+            .stack()
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| (index.to_string(), value))
+            .collect(),
+        _ => Vec::new(),
+    };
+    values
+        .into_iter()
+        .map(|(name, value)| {
+            json!({ "name": name, "value": format_value(&value), "variablesReference": 0 })
+        })
+        .collect()
+}
+
+/// Quotes strings so they can be told apart from numbers and booleans.
+fn format_value(value: &YarnValue) -> String {
+    match value {
+        YarnValue::String(value) => format!("{value:?}"),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    struct Client {
+        input: Vec<u8>,
+        sequence: u64,
+    }
+
+    impl Client {
+        fn new() -> Self {
+            Self {
+                input: Vec::new(),
+                sequence: 0,
+            }
+        }
+
+        fn request(mut self, command: &str, arguments: Value) -> Self {
+            self.sequence += 1;
+            let content = json!({
+                "seq": self.sequence,
+                "type": "request",
+                "command": command,
+                "arguments": arguments,
+            })
+            .to_string();
+            self.input
+                .extend(format!("Content-Length: {}\r\n\r\n{content}", content.len()).bytes());
+            self
+        }
+
+        fn run(self) -> Vec<Value> {
+            let mut output = Vec::new();
+            DebugAdapter::new(Library::standard_library())
+                .run(self.input.as_slice(), &mut output)
+                .unwrap();
+            let mut output = output.as_slice();
+            let mut messages = Vec::new();
+            while let Some(message) = read_message(&mut output).unwrap() {
+                messages.push(message);
+            }
+            messages
+        }
+    }
+
+    fn write_yarn_file(name: &str, source: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("yarnspinner-dap-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("test.yarn");
+        std::fs::write(&path, source).unwrap();
+        path
+    }
+
+    fn response<'a>(messages: &'a [Value], command: &str) -> &'a Value {
+        messages
+            .iter()
+            .find(|message| message["type"] == "response" && message["command"] == command)
+            .unwrap()
+    }
+
+    fn events<'a>(messages: &'a [Value], event: &'a str) -> impl Iterator<Item = &'a Value> {
+        messages
+            .iter()
+            .filter(move |message| message["event"] == event)
+            .map(|message| &message["body"])
+    }
+
+    const SOURCE: &str = "\
+title: Start
+---
+<<declare $gold = 5>>
+Hello!
+<<set $gold to $gold + 1>>
+-> Buy
+    <<jump Shop>>
+-> Leave
+===
+title: Shop
+---
+Welcome to the shop.
+===
+";
+
+    #[test]
+    fn stops_at_breakpoints_and_selects_options() {
+        let path = write_yarn_file("breakpoints", SOURCE);
+        let path = path.to_str().unwrap();
+        let messages = Client::new()
+            .request("initialize", json!({ "adapterID": "yarnspinner" }))
+            .request("launch", json!({ "program": path }))
+            .request(
+                "setBreakpoints",
+                json!({ "source": { "path": path }, "breakpoints": [{ "line": 5 }, { "line": 13 }] }),
+            )
+            .request("setFunctionBreakpoints", json!({ "breakpoints": [{ "name": "Shop" }] }))
+            .request("configurationDone", json!({}))
+            .request("stackTrace", json!({ "threadId": 1 }))
+            .request("continue", json!({ "threadId": 1 }))
+            .request("variables", json!({ "variablesReference": VARIABLES_REFERENCE }))
+            .request("continue", json!({ "threadId": 1 }))
+            .request("evaluate", json!({ "expression": "1", "context": "repl" }))
+            .request("continue", json!({ "threadId": 1 }))
+            .request("continue", json!({ "threadId": 1 }))
+            .request("disconnect", json!({}))
+            .run();
+
+        assert_eq!(json!(true), response(&messages, "initialize")["success"]);
+        assert_eq!(
+            json!([{ "verified": true, "line": 5 }, { "verified": false, "line": 13, "message": "No dialogue runs on this line" }]),
+            response(&messages, "setBreakpoints")["body"]["breakpoints"]
+        );
+        assert_eq!(
+            json!([{ "verified": true }]),
+            response(&messages, "setFunctionBreakpoints")["body"]["breakpoints"]
+        );
+
+        let frame = &response(&messages, "stackTrace")["body"]["stackFrames"][0];
+        assert_eq!(json!("Start"), frame["name"]);
+        assert_eq!(json!(5), frame["line"]);
+        assert_eq!(json!(path), frame["source"]["path"]);
+
+        let variables = &response(&messages, "variables")["body"]["variables"];
+        assert!(variables
+            .as_array()
+            .unwrap()
+            .contains(&json!({ "name": "$gold", "value": "6", "variablesReference": 0 })));
+
+        let reasons: Vec<_> = events(&messages, "stopped")
+            .map(|body| body["reason"].as_str().unwrap())
+            .collect();
+        assert_eq!(vec!["breakpoint", "pause", "function breakpoint"], reasons);
+        let lines: Vec<_> = events(&messages, "output")
+            .filter(|body| body["category"] == "stdout")
+            .map(|body| body["output"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            vec![
+                "Hello!\n",
+                "1: Buy\n2: Leave\nEnter the number of an option to select it.\n",
+                "Welcome to the shop.\n",
+            ],
+            lines
+        );
+        assert_eq!(1, events(&messages, "terminated").count());
+    }
+
+    #[test]
+    fn reports_launch_errors() {
+        let path = write_yarn_file("errors", "title: Start\n---\n<<jump Nowhere>>\n===\n");
+        let messages = Client::new()
+            .request("initialize", json!({}))
+            .request("launch", json!({ "program": path, "startNode": "Missing" }))
+            .request("continue", json!({ "threadId": 1 }))
+            .run();
+
+        let launch = response(&messages, "launch");
+        assert_eq!(json!(false), launch["success"]);
+        assert_eq!(0, events(&messages, "initialized").count());
+        assert_eq!(json!(false), response(&messages, "continue")["success"]);
+    }
+}
@@ -0,0 +1,177 @@
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use yarnspinner::compiler::{Compiler, CompilerError, File};
+use yarnspinner::core::Library;
+use yarnspinner::runtime::{
+    Breakpoint, Debugger, Dialogue, DialogueOption, MemoryVariableStorage, StringTableTextProvider,
+};
+
+/// A launched dialogue and everything needed to debug it.
+#[derive(Debug)]
+pub(crate) struct Session {
+    pub(crate) debugger: Debugger,
+    pub(crate) start_node: String,
+    pub(crate) stop_on_entry: bool,
+    /// The options presented last, which can be selected by their number.
+    pub(crate) options: Vec<DialogueOption>,
+}
+
+impl Session {
+    /// Compiles the Yarn files given in the arguments of a `launch` request. Errors are meant to be shown to the user.
+    pub(crate) fn launch(arguments: &Value, library: &Library) -> Result<Self, String> {
+        let program = arguments["program"]
+            .as_str()
+            .ok_or("The launch configuration needs a \"program\", i.e. a .yarn file or a directory containing .yarn files")?;
+        let start_node = arguments["startNode"]
+            .as_str()
+            .unwrap_or("Start")
+            .to_owned();
+        let stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or_default();
+
+        let program = normalize_path(Path::new(program));
+        let paths = if program.is_dir() {
+            Compiler::find_yarn_files(&program)
+                .map_err(|error| format!("Failed to search \"{}\": {error}", program.display()))?
+        } else {
+            vec![program]
+        };
+        let files = paths
+            .iter()
+            .map(|path| {
+                let source = std::fs::read_to_string(path)
+                    .map_err(|error| format!("Failed to read \"{}\": {error}", path.display()))?;
+                Ok(File {
+                    file_name: path.display().to_string(),
+                    source,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let compilation = Compiler::new()
+            .add_files(files)
+            .extend_library(library.clone())
+            .compile()
+            .map_err(|CompilerError(diagnostics)| {
+                let messages: Vec<_> = diagnostics.iter().map(ToString::to_string).collect();
+                messages.join("\n")
+            })?;
+
+        let mut text_provider = StringTableTextProvider::new();
+        text_provider.extend_base_language(
+            compilation
+                .string_table
+                .into_iter()
+                .map(|(id, info)| (id, info.text))
+                .collect(),
+        );
+        let mut dialogue = Dialogue::new(
+            Box::new(MemoryVariableStorage::new()),
+            Box::new(text_provider),
+        );
+        dialogue.library_mut().import(library.clone());
+        dialogue.add_program(
+            compilation
+                .program
+                .expect("A full compilation always produces a program"),
+        );
+        if !dialogue.node_exists(&start_node) {
+            return Err(format!("There is no node named \"{start_node}\""));
+        }
+
+        let mut debugger = Debugger::new(dialogue);
+        for (node_name, debug_info) in compilation.debug_info {
+            debugger.add_node_source(node_name, debug_info.file_name, debug_info.line_positions);
+        }
+        Ok(Self {
+            debugger,
+            start_node,
+            stop_on_entry,
+            options: Vec::new(),
+        })
+    }
+
+    /// Replaces the line breakpoints of a file with breakpoints on the given one-based lines.
+    /// Returns the one-based line each breakpoint was moved to, or `None` if it can never be hit.
+    pub(crate) fn set_line_breakpoints(
+        &mut self,
+        path: &str,
+        lines: &[usize],
+    ) -> Vec<Option<usize>> {
+        let file_name = normalize_path(Path::new(path)).display().to_string();
+        let existing: Vec<_> = self
+            .debugger
+            .breakpoints()
+            .iter()
+            .filter(|breakpoint| {
+                matches!(breakpoint, Breakpoint::Line { file_name: name, .. } if *name == file_name)
+            })
+            .cloned()
+            .collect();
+        for breakpoint in &existing {
+            self.debugger.remove_breakpoint(breakpoint);
+        }
+        lines
+            .iter()
+            .map(|line| {
+                let breakpoint = Breakpoint::Line {
+                    file_name: file_name.clone(),
+                    line: line.checked_sub(1)?,
+                };
+                match self.debugger.add_breakpoint(breakpoint)? {
+                    Breakpoint::Line { line, .. } => Some(line + 1),
+                    Breakpoint::NodeEntry { .. } => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Replaces all breakpoints on node entries. Returns whether each node exists.
+    pub(crate) fn set_node_breakpoints(&mut self, node_names: &[String]) -> Vec<bool> {
+        let existing: Vec<_> = self
+            .debugger
+            .breakpoints()
+            .iter()
+            .filter(|breakpoint| matches!(breakpoint, Breakpoint::NodeEntry { .. }))
+            .cloned()
+            .collect();
+        for breakpoint in &existing {
+            self.debugger.remove_breakpoint(breakpoint);
+        }
+        node_names
+            .iter()
+            .map(|node_name| {
+                self.debugger
+                    .add_breakpoint(Breakpoint::NodeEntry {
+                        node_name: node_name.clone(),
+                    })
+                    .is_some()
+            })
+            .collect()
+    }
+
+    /// Selects the option with the given one-based number, if it is available.
+    pub(crate) fn select_option(&mut self, number: usize) -> Result<&DialogueOption, String> {
+        if !self.debugger.dialogue().is_waiting_for_option_selection() {
+            return Err("There are no options to select right now".to_owned());
+        }
+        let option = number
+            .checked_sub(1)
+            .and_then(|index| self.options.get(index))
+            .filter(|option| option.is_available)
+            .ok_or_else(|| {
+                format!(
+                    "Please enter the number of an available option (1-{})",
+                    self.options.len()
+                )
+            })?;
+        self.debugger
+            .select_option(option.id)
+            .map_err(|error| error.to_string())?;
+        Ok(option)
+    }
+}
+
+/// Makes paths comparable, since editors and the launch configuration may spell the same path differently.
+pub(crate) fn normalize_path(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_owned())
+}
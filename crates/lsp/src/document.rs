@@ -0,0 +1,294 @@
+use lsp_types::{Position as LspPosition, Range as LspRange};
+use std::ops::Range;
+use yarnspinner::core::Position;
+
+/// The text of a Yarn file, as currently known to the language server.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub(crate) struct Document {
+    pub(crate) text: String,
+    lines: Vec<String>,
+}
+
+/// A node as found in the headers of a [`Document`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct NodeOutline {
+    pub(crate) title: String,
+    /// The range of the title's value in the `title:` header.
+    pub(crate) title_range: LspRange,
+    /// The range from the first header to the `===` line, or to the end of the file if it is missing.
+    pub(crate) range: LspRange,
+    pub(crate) headers: Vec<(String, String)>,
+}
+
+/// A word of Yarn code, such as a variable, node or function name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Word {
+    pub(crate) text: String,
+    pub(crate) range: LspRange,
+    /// The text of the line before the word.
+    pub(crate) prefix: String,
+}
+
+impl Document {
+    pub(crate) fn new(text: impl Into<String>) -> Self {
+        let text = text.into();
+        let lines = text
+            .split('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line).to_owned())
+            .collect();
+        Self { text, lines }
+    }
+
+    pub(crate) fn line(&self, line: usize) -> &str {
+        self.lines.get(line).map(String::as_str).unwrap_or_default()
+    }
+
+    /// Converts a position from the compiler, which counts unicode code points, to one counting UTF-16 code units like LSP does.
+    pub(crate) fn to_lsp_position(&self, position: Position) -> LspPosition {
+        let line = self.line(position.line);
+        // The compiler strips the byte order mark before counting
+        let skipped_characters = usize::from(position.line == 0 && line.starts_with('\u{feff}'));
+        let character = line
+            .chars()
+            .take(position.character + skipped_characters)
+            .map(char::len_utf16)
+            .sum::<usize>();
+        LspPosition::new(position.line as u32, character as u32)
+    }
+
+    pub(crate) fn to_lsp_range(&self, range: &Range<Position>) -> LspRange {
+        LspRange::new(
+            self.to_lsp_position(range.start),
+            self.to_lsp_position(range.end),
+        )
+    }
+
+    /// Returns the range spanning the whole given line.
+    pub(crate) fn line_range(&self, line: usize) -> LspRange {
+        let length = self.line(line).encode_utf16().count();
+        LspRange::new(
+            LspPosition::new(line as u32, 0),
+            LspPosition::new(line as u32, length as u32),
+        )
+    }
+
+    /// Returns the byte offset into its line that the given position points to.
+    fn byte_offset(&self, position: LspPosition) -> usize {
+        let line = self.line(position.line as usize);
+        let mut utf16_offset = 0;
+        for (byte_offset, character) in line.char_indices() {
+            if utf16_offset >= position.character as usize {
+                return byte_offset;
+            }
+            utf16_offset += character.len_utf16();
+        }
+        line.len()
+    }
+
+    fn lsp_character(&self, line: usize, byte_offset: usize) -> u32 {
+        self.line(line)[..byte_offset].encode_utf16().count() as u32
+    }
+
+    /// Returns the text of the line up to the given position.
+    pub(crate) fn prefix(&self, position: LspPosition) -> &str {
+        &self.line(position.line as usize)[..self.byte_offset(position)]
+    }
+
+    /// Returns the identifier, optionally starting with `$`, that touches the given position.
+    pub(crate) fn word_at(&self, position: LspPosition) -> Option<Word> {
+        let line_index = position.line as usize;
+        let line = self.line(line_index);
+        let offset = self.byte_offset(position);
+        let start = line[..offset]
+            .char_indices()
+            .rev()
+            .take_while(|&(_, character)| is_identifier_character(character))
+            .last()
+            .map_or(offset, |(index, _)| index);
+        let start = match line[..start].strip_suffix('$') {
+            Some(before) => before.len(),
+            None => start,
+        };
+        // The position may also be right in front of the `$`
+        let identifier_start = if start == offset && line[offset..].starts_with('$') {
+            offset + 1
+        } else {
+            offset
+        };
+        let end = line[identifier_start..]
+            .char_indices()
+            .find(|&(_, character)| !is_identifier_character(character))
+            .map_or(line.len(), |(index, _)| identifier_start + index);
+        if line[start..end].trim_start_matches('$').is_empty() {
+            return None;
+        }
+        Some(Word {
+            text: line[start..end].to_owned(),
+            range: LspRange::new(
+                LspPosition::new(position.line, self.lsp_character(line_index, start)),
+                LspPosition::new(position.line, self.lsp_character(line_index, end)),
+            ),
+            prefix: line[..start].to_owned(),
+        })
+    }
+
+    /// Finds all nodes by reading the headers of the file.
+    /// This works even if the rest of the file has syntax errors.
+    pub(crate) fn nodes(&self) -> Vec<NodeOutline> {
+        let mut nodes = Vec::new();
+        let mut start_line = None;
+        let mut title = None;
+        let mut headers = Vec::new();
+        let mut is_in_body = false;
+        for (index, line) in self.lines.iter().enumerate() {
+            let trimmed = line.trim();
+            if is_in_body {
+                if trimmed == "===" {
+                    nodes.extend(self.node_outline(start_line, index, title.take(), &mut headers));
+                    start_line = None;
+                    is_in_body = false;
+                }
+                continue;
+            }
+            if trimmed == "---" {
+                is_in_body = true;
+                continue;
+            }
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            if key.trim().is_empty() || trimmed.starts_with("//") {
+                continue;
+            }
+            start_line.get_or_insert(index);
+            let key = key.trim().to_owned();
+            let value_start = key_value_start(line);
+            let value = value.trim().to_owned();
+            if key == "title" {
+                let start = self.lsp_character(index, value_start);
+                let end = self.lsp_character(index, value_start + value.len());
+                title = Some((
+                    value.clone(),
+                    LspRange::new(
+                        LspPosition::new(index as u32, start),
+                        LspPosition::new(index as u32, end),
+                    ),
+                ));
+            }
+            headers.push((key, value));
+        }
+        if is_in_body {
+            let last_line = self.lines.len().saturating_sub(1);
+            nodes.extend(self.node_outline(start_line, last_line, title, &mut headers));
+        }
+        nodes
+    }
+
+    fn node_outline(
+        &self,
+        start_line: Option<usize>,
+        end_line: usize,
+        title: Option<(String, LspRange)>,
+        headers: &mut Vec<(String, String)>,
+    ) -> Option<NodeOutline> {
+        let headers = std::mem::take(headers);
+        let (title, title_range) = title?;
+        let start = LspPosition::new(start_line.unwrap_or(end_line) as u32, 0);
+        Some(NodeOutline {
+            title,
+            title_range,
+            range: LspRange::new(start, self.line_range(end_line).end),
+            headers,
+        })
+    }
+
+    /// Returns the node the given line belongs to.
+    pub(crate) fn node_at(&self, line: u32) -> Option<NodeOutline> {
+        self.nodes()
+            .into_iter()
+            .find(|node| node.range.start.line <= line && line <= node.range.end.line)
+    }
+}
+
+/// Returns the byte offset of the first non-whitespace character after the `:` of a header.
+fn key_value_start(line: &str) -> usize {
+    let colon = line.find(':').unwrap_or_default() + 1;
+    let value = &line[colon..];
+    colon + (value.len() - value.trim_start().len())
+}
+
+fn is_identifier_character(character: char) -> bool {
+    character.is_alphanumeric() || character == '_' || character == '.'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_positions_to_utf16() {
+        let document = Document::new("title: Start\r\n---\r\nÄ😀 {$gold}\r\n===\r\n");
+        let position = document.to_lsp_position(Position {
+            line: 2,
+            character: 4,
+        });
+        assert_eq!(LspPosition::new(2, 5), position);
+    }
+
+    #[test]
+    fn finds_words_at_positions() {
+        let document = Document::new("<<jump Other.Node>> {$gold + 1}");
+        let word = document.word_at(LspPosition::new(0, 10)).unwrap();
+        assert_eq!("Other.Node", word.text);
+        assert_eq!("<<jump ", word.prefix);
+        assert_eq!(
+            LspRange::new(LspPosition::new(0, 7), LspPosition::new(0, 17)),
+            word.range
+        );
+
+        let word = document.word_at(LspPosition::new(0, 22)).unwrap();
+        assert_eq!("$gold", word.text);
+        assert_eq!(document.word_at(LspPosition::new(0, 21)), Some(word));
+
+        assert_eq!(None, document.word_at(LspPosition::new(0, 27)));
+    }
+
+    #[test]
+    fn finds_nodes() {
+        let document = Document::new(
+            "title: Start
+tags: intro
+---
+Hello
+===
+// A comment
+title:   Other
+---
+Unfinished node",
+        );
+        let nodes = document.nodes();
+        assert_eq!(2, nodes.len());
+        assert_eq!("Start", nodes[0].title);
+        assert_eq!(
+            vec![
+                ("title".to_owned(), "Start".to_owned()),
+                ("tags".to_owned(), "intro".to_owned())
+            ],
+            nodes[0].headers
+        );
+        assert_eq!(
+            LspRange::new(LspPosition::new(0, 0), LspPosition::new(4, 3)),
+            nodes[0].range
+        );
+        assert_eq!("Other", nodes[1].title);
+        assert_eq!(
+            LspRange::new(LspPosition::new(6, 9), LspPosition::new(6, 14)),
+            nodes[1].title_range
+        );
+        assert_eq!(8, nodes[1].range.end.line);
+        assert_eq!(
+            Some("Other".to_owned()),
+            document.node_at(8).map(|node| node.title)
+        );
+    }
+}
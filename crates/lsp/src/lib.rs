@@ -0,0 +1,18 @@
+//! A language server for Yarn files, built on the Yarn Spinner compiler.
+//!
+//! It speaks the [Language Server Protocol](https://microsoft.github.io/language-server-protocol/) over stdio and offers
+//! - diagnostics from the compiler while typing,
+//! - go to definition for the nodes in `<<jump>>` and `<<detour>>` and for variables,
+//! - hovers showing the types and descriptions of variables and the signatures of functions,
+//! - completion of node names, variables and functions,
+//! - an outline of the nodes in a file.
+//!
+//! The `yarnspinner_lsp` binary runs the server with the functions every [`Dialogue`](yarnspinner::runtime::Dialogue) offers.
+//! If your game registers functions of its own, run a [`LanguageServer`] with your [`Library`](yarnspinner::core::Library) instead.
+#![warn(missing_docs, missing_debug_implementations)]
+
+mod document;
+mod project;
+mod server;
+
+pub use crate::server::{LanguageServer, ServerError};
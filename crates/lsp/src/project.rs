@@ -0,0 +1,594 @@
+use crate::document::*;
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionTextEdit, Diagnostic as LspDiagnostic,
+    DiagnosticSeverity as LspDiagnosticSeverity, DocumentSymbol, Hover, HoverContents, Location,
+    MarkupContent, MarkupKind, Position as LspPosition, Range as LspRange, SymbolKind, TextEdit,
+    Url,
+};
+use std::any::TypeId;
+use std::collections::{BTreeMap, BTreeSet};
+use std::panic::{self, AssertUnwindSafe};
+use yarnspinner::compiler::*;
+use yarnspinner::core::{Library, Type, UntypedYarnFn, YarnValue};
+
+/// All Yarn files the language server knows about, together with what the compiler found out about them.
+///
+/// All files are compiled together, so that nodes and variables can be used across files like in a game.
+#[derive(Debug, Default)]
+pub(crate) struct Project {
+    library: Library,
+    documents: BTreeMap<Url, Document>,
+    /// The declarations of the last compilation that succeeded, so that they stay available while the user is typing.
+    declarations: Vec<Declaration>,
+    /// The `<<local>>` declarations of the last compilation that succeeded.
+    local_declarations: Vec<Declaration>,
+    diagnostics: BTreeMap<Url, Vec<LspDiagnostic>>,
+}
+
+impl Project {
+    pub(crate) fn new(library: Library) -> Self {
+        Self {
+            library,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn set_document(&mut self, url: Url, text: impl Into<String>) {
+        self.documents.insert(url, Document::new(text));
+    }
+
+    pub(crate) fn remove_document(&mut self, url: &Url) {
+        self.documents.remove(url);
+    }
+
+    /// Compiles all documents up to the point where all declarations are known and updates the diagnostics.
+    pub(crate) fn compile(&mut self) {
+        let files = self.documents.iter().map(|(url, document)| File {
+            file_name: url.to_string(),
+            source: document.text.clone(),
+        });
+        let mut compiler = Compiler::new();
+        compiler
+            .add_files(files)
+            .extend_library(self.library.clone())
+            .with_compilation_type(CompilationType::DeclarationsOnly);
+        // A crash of the compiler on some half-written code must not take down the whole editor integration,
+        // so keep the old diagnostics in that case
+        let result = panic::catch_unwind(AssertUnwindSafe(|| compiler.compile()));
+        let previous_diagnostics = std::mem::take(&mut self.diagnostics);
+        let diagnostics = match result {
+            Ok(Ok(compilation)) => {
+                self.declarations = compilation.declarations;
+                self.local_declarations = compilation.local_declarations;
+                compilation.warnings
+            }
+            Ok(Err(error)) => error.0,
+            Err(_) => {
+                self.diagnostics = previous_diagnostics;
+                Vec::new()
+            }
+        };
+
+        for url in self.documents.keys() {
+            self.diagnostics.entry(url.clone()).or_default();
+        }
+        self.diagnostics
+            .retain(|url, _| self.documents.contains_key(url));
+        for diagnostic in diagnostics {
+            let Some(url) = diagnostic
+                .file_name
+                .as_deref()
+                .and_then(|name| Url::parse(name).ok())
+            else {
+                continue;
+            };
+            let Some(document) = self.documents.get(&url) else {
+                continue;
+            };
+            let diagnostic = to_lsp_diagnostic(document, diagnostic);
+            self.diagnostics.entry(url).or_default().push(diagnostic);
+        }
+    }
+
+    /// The diagnostics of every document as of the last call to [`Project::compile`].
+    pub(crate) fn diagnostics(&self) -> &BTreeMap<Url, Vec<LspDiagnostic>> {
+        &self.diagnostics
+    }
+
+    /// Finds the declaration of the variable or the nodes that the identifier at the given position refers to.
+    pub(crate) fn definition(&self, url: &Url, position: LspPosition) -> Vec<Location> {
+        let Some(document) = self.documents.get(url) else {
+            return Vec::new();
+        };
+        let Some(word) = document.word_at(position) else {
+            return Vec::new();
+        };
+        if word.text.starts_with('$') {
+            let node = document.node_at(position.line);
+            return self
+                .variable(&word.text, node.as_ref())
+                .and_then(|(declaration, _)| self.declaration_location(declaration))
+                .into_iter()
+                .collect();
+        }
+        if !is_node_reference(&word.prefix) {
+            return Vec::new();
+        }
+        self.nodes_named(&word.text)
+            .map(|(url, node)| Location::new(url.clone(), node.title_range))
+            .collect()
+    }
+
+    /// Describes the variable, function or node at the given position.
+    pub(crate) fn hover(&self, url: &Url, position: LspPosition) -> Option<Hover> {
+        let document = self.documents.get(url)?;
+        let word = document.word_at(position)?;
+        let value = if word.text.starts_with('$') {
+            let node = document.node_at(position.line);
+            let (declaration, is_local) = self.variable(&word.text, node.as_ref())?;
+            describe_variable(declaration, is_local)
+        } else if is_node_reference(&word.prefix) {
+            let nodes: Vec<_> = self.nodes_named(&word.text).collect();
+            let (_, node) = nodes.first()?;
+            describe_node(node, nodes.len())
+        } else if word.prefix.trim_end().ends_with("<<") {
+            // Commands are not declared anywhere, so there is nothing to show for them
+            return None;
+        } else {
+            let function = self.library.get(&word.text)?;
+            format!("```yarn\n{}\n```", function_signature(&word.text, function))
+        };
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(word.range),
+        })
+    }
+
+    /// Suggests node names after `<<jump` and `<<detour`, variables after `$` and variables and functions inside of expressions.
+    pub(crate) fn completion(&self, url: &Url, position: LspPosition) -> Vec<CompletionItem> {
+        let Some(document) = self.documents.get(url) else {
+            return Vec::new();
+        };
+        let prefix = document.prefix(position);
+        let partial_word_start = prefix
+            .char_indices()
+            .rev()
+            .take_while(|&(_, character)| character.is_alphanumeric() || "_.$".contains(character))
+            .last()
+            .map_or(prefix.len(), |(index, _)| index);
+        let (before, partial_word) = prefix.split_at(partial_word_start);
+        let replaced_range = LspRange::new(
+            LspPosition::new(
+                position.line,
+                position.character - partial_word.encode_utf16().count() as u32,
+            ),
+            position,
+        );
+        let item = |label: String, kind, detail: Option<String>| CompletionItem {
+            label: label.clone(),
+            kind: Some(kind),
+            detail,
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(
+                replaced_range,
+                label.clone(),
+            ))),
+            filter_text: Some(label),
+            ..Default::default()
+        };
+
+        if is_node_reference(before) {
+            let titles: BTreeSet<_> = self
+                .documents
+                .values()
+                .flat_map(|document| document.nodes())
+                .map(|node| node.title)
+                .collect();
+            return titles
+                .into_iter()
+                .map(|title| item(title, CompletionItemKind::MODULE, None))
+                .collect();
+        }
+        if !partial_word.starts_with('$') && !is_in_expression(before) {
+            return Vec::new();
+        }
+
+        let node = document.node_at(position.line);
+        let mut items: Vec<_> = self
+            .visible_variables(node.as_ref())
+            .map(|declaration| {
+                let detail = Some(declaration.r#type.to_string());
+                item(
+                    declaration.name.clone(),
+                    CompletionItemKind::VARIABLE,
+                    detail,
+                )
+            })
+            .collect();
+        if !partial_word.starts_with('$') {
+            let mut functions: Vec<_> = self
+                .library
+                .iter()
+                .filter(|(name, _)| !is_operator(name))
+                .map(|(name, function)| {
+                    let detail = Some(function_signature(name, function));
+                    item(name.to_owned(), CompletionItemKind::FUNCTION, detail)
+                })
+                .collect();
+            functions.sort_by(|a, b| a.label.cmp(&b.label));
+            items.extend(functions);
+        }
+        items
+    }
+
+    /// Lists the nodes of the document.
+    pub(crate) fn document_symbols(&self, url: &Url) -> Vec<DocumentSymbol> {
+        let Some(document) = self.documents.get(url) else {
+            return Vec::new();
+        };
+        document
+            .nodes()
+            .into_iter()
+            .map(|node| {
+                let detail = node
+                    .headers
+                    .iter()
+                    .find(|(key, _)| key == "tags")
+                    .map(|(_, tags)| tags.clone())
+                    .filter(|tags| !tags.is_empty());
+                #[allow(deprecated)] // `deprecated` has to be set even though it's deprecated
+                DocumentSymbol {
+                    name: node.title,
+                    detail,
+                    kind: SymbolKind::MODULE,
+                    tags: None,
+                    deprecated: None,
+                    range: node.range,
+                    selection_range: node.title_range,
+                    children: None,
+                }
+            })
+            .collect()
+    }
+
+    /// Finds the declaration of a variable, preferring a `<<local>>` declaration of the given node.
+    /// Returns whether the declaration is a local one along with it.
+    fn variable(&self, name: &str, node: Option<&NodeOutline>) -> Option<(&Declaration, bool)> {
+        let local = self
+            .local_declarations(node)
+            .find(|declaration| declaration.name == name)
+            .map(|declaration| (declaration, true));
+        local.or_else(|| {
+            self.declarations
+                .iter()
+                .find(|declaration| declaration.name == name)
+                .map(|declaration| (declaration, false))
+        })
+    }
+
+    fn local_declarations(&self, node: Option<&NodeOutline>) -> impl Iterator<Item = &Declaration> {
+        let node_name = node.map(|node| node.title.clone());
+        self.local_declarations.iter().filter(move |declaration| {
+            node_name.is_some() && declaration.source_node_name == node_name
+        })
+    }
+
+    /// Returns the variables that can be used in the given node.
+    fn visible_variables<'a>(
+        &'a self,
+        node: Option<&NodeOutline>,
+    ) -> impl Iterator<Item = &'a Declaration> {
+        let globals = self
+            .declarations
+            .iter()
+            .filter(|declaration| !declaration.name.starts_with("$Yarn.Internal."));
+        // Locals shadow globals of the same name
+        let variables: BTreeMap<_, _> = globals
+            .chain(self.local_declarations(node))
+            .map(|declaration| (&declaration.name, declaration))
+            .collect();
+        variables.into_values()
+    }
+
+    fn declaration_location(&self, declaration: &Declaration) -> Option<Location> {
+        let DeclarationSource::File(file_name) = &declaration.source_file_name else {
+            return None;
+        };
+        let url = Url::parse(file_name).ok()?;
+        let document = self.documents.get(&url)?;
+        let range = document.to_lsp_range(declaration.range.as_ref()?);
+        Some(Location::new(url, range))
+    }
+
+    fn nodes_named<'a>(
+        &'a self,
+        title: &'a str,
+    ) -> impl Iterator<Item = (&'a Url, NodeOutline)> + 'a {
+        self.documents.iter().flat_map(move |(url, document)| {
+            document
+                .nodes()
+                .into_iter()
+                .filter(move |node| node.title == title)
+                .map(move |node| (url, node))
+        })
+    }
+}
+
+fn to_lsp_diagnostic(document: &Document, diagnostic: Diagnostic) -> LspDiagnostic {
+    let range = match &diagnostic.range {
+        Some(range) => document.to_lsp_range(range),
+        None => document.line_range(diagnostic.start_line),
+    };
+    let severity = match diagnostic.severity {
+        DiagnosticSeverity::Error => LspDiagnosticSeverity::ERROR,
+        DiagnosticSeverity::Warning => LspDiagnosticSeverity::WARNING,
+    };
+    LspDiagnostic {
+        range,
+        severity: Some(severity),
+        source: Some("yarnspinner".to_owned()),
+        message: diagnostic.message,
+        ..Default::default()
+    }
+}
+
+/// Returns `true` if the text before a word means that it is the destination of a `<<jump>>` or `<<detour>>`.
+fn is_node_reference(prefix: &str) -> bool {
+    let command = prefix.trim_end();
+    // The command must be separated from the node name
+    let has_space = command.len() != prefix.len();
+    has_space
+        && ["jump", "detour"].iter().any(|name| {
+            command
+                .strip_suffix(name)
+                .is_some_and(|before| before.trim_end().ends_with("<<"))
+        })
+}
+
+/// Returns `true` if the text before the cursor leaves it inside of an unclosed `<<command>>` or `{expression}`.
+fn is_in_expression(prefix: &str) -> bool {
+    let is_after = |open: &str, close: &str| {
+        prefix
+            .rfind(open)
+            .is_some_and(|open| prefix.rfind(close).is_none_or(|close| close < open))
+    };
+    is_after("<<", ">>") || is_after("{", "}")
+}
+
+/// The standard library implements operators as functions named like `Number.Add`, which are not meant to be called directly.
+/// Functions registered by the game may contain a `.` in their name as well, so only the names of the built-in types are checked.
+fn is_operator(function_name: &str) -> bool {
+    function_name.split_once('.').is_some_and(|(type_name, _)| {
+        [Type::Number, Type::String, Type::Boolean]
+            .iter()
+            .any(|r#type| r#type.name() == type_name)
+    })
+}
+
+fn describe_variable(declaration: &Declaration, is_local: bool) -> String {
+    let mut description = format!("```yarn\n{}: {}", declaration.name, declaration.r#type);
+    if let Some(default_value) = &declaration.default_value {
+        let default_value = match default_value {
+            YarnValue::String(value) => format!("{value:?}"),
+            value => value.to_string(),
+        };
+        description.push_str(&format!(" = {default_value}"));
+    }
+    description.push_str("\n```");
+    if let Some(text) = &declaration.description {
+        description.push_str(&format!("\n\n{text}"));
+    }
+    let kind = if is_local {
+        Some("Local variable")
+    } else if declaration.is_smart_variable {
+        Some("Smart variable")
+    } else if declaration.is_implicit {
+        Some("Implicitly declared")
+    } else {
+        None
+    };
+    if let Some(kind) = kind {
+        description.push_str(&format!("\n\n*{kind}*"));
+    }
+    description
+}
+
+fn describe_node(node: &NodeOutline, node_count: usize) -> String {
+    let mut description = format!("Node `{}`", node.title);
+    if node_count > 1 {
+        description.push_str(&format!(" (node group of {node_count} nodes)"));
+    }
+    let headers: Vec<_> = node
+        .headers
+        .iter()
+        .filter(|(key, _)| key != "title")
+        .map(|(key, value)| format!("- {key}: {value}"))
+        .collect();
+    if !headers.is_empty() {
+        description.push_str(&format!("\n\n{}", headers.join("\n")));
+    }
+    description
+}
+
+fn function_signature(name: &str, function: &dyn UntypedYarnFn) -> String {
+    let type_name = |type_id: TypeId| {
+        Type::try_from(type_id).map_or_else(|_| "Any".to_owned(), |r#type| r#type.to_string())
+    };
+    let parameters: Vec<_> = function
+        .parameter_types()
+        .into_iter()
+        .map(type_name)
+        .collect();
+    format!(
+        "{name}({}) -> {}",
+        parameters.join(", "),
+        type_name(function.return_type())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(name: &str) -> Url {
+        Url::parse(&format!("file:///project/{name}")).unwrap()
+    }
+
+    fn project() -> Project {
+        let mut project = Project::new(Library::standard_library());
+        project.set_document(
+            url("start.yarn"),
+            "title: Start
+---
+/// How much money the player has
+<<declare $gold = 10>>
+<<local $greeting = \"Hi\">>
+{$greeting}, you have {$gold} gold.
+<<jump Shop>>
+===
+",
+        );
+        project.set_document(
+            url("shop.yarn"),
+            "title: Shop
+tags: store
+---
+<<if $gold > 5>>
+    Buy something!
+<<endif>>
+<<detour Start>>
+===
+",
+        );
+        project.compile();
+        project
+    }
+
+    #[test]
+    fn reports_diagnostics_per_file() {
+        let mut project = project();
+        assert!(project.diagnostics().values().all(Vec::is_empty));
+
+        project.set_document(
+            url("shop.yarn"),
+            "title: Shop\n---\n<<set $gold to \"many\">>\n===\n",
+        );
+        project.compile();
+        let diagnostics = &project.diagnostics()[&url("shop.yarn")];
+        assert_eq!(1, diagnostics.len(), "{diagnostics:?}");
+        assert_eq!(2, diagnostics[0].range.start.line);
+        assert_eq!(Some(LspDiagnosticSeverity::ERROR), diagnostics[0].severity);
+        assert!(project.diagnostics()[&url("start.yarn")].is_empty());
+    }
+
+    #[test]
+    fn finds_definitions_of_nodes_and_variables() {
+        let project = project();
+        let shop = project.definition(&url("start.yarn"), LspPosition::new(6, 9));
+        assert_eq!(
+            vec![Location::new(
+                url("shop.yarn"),
+                LspRange::new(LspPosition::new(0, 7), LspPosition::new(0, 11))
+            )],
+            shop
+        );
+
+        let gold = project.definition(&url("shop.yarn"), LspPosition::new(3, 7));
+        assert_eq!(1, gold.len());
+        assert_eq!(url("start.yarn"), gold[0].uri);
+        assert_eq!(3, gold[0].range.start.line);
+
+        let greeting = project.definition(&url("start.yarn"), LspPosition::new(5, 3));
+        assert_eq!(4, greeting[0].range.start.line);
+
+        // Words outside of jumps are not node names, even if a node is called like that
+        assert!(project
+            .definition(&url("start.yarn"), LspPosition::new(0, 8))
+            .is_empty());
+    }
+
+    #[test]
+    fn describes_variables_functions_and_nodes() {
+        let project = project();
+        let hover = |name: &str, line, character| {
+            let hover = project.hover(&url(name), LspPosition::new(line, character));
+            match hover.map(|hover| hover.contents) {
+                Some(HoverContents::Markup(content)) => content.value,
+                other => panic!("Unexpected hover: {other:?}"),
+            }
+        };
+        assert_eq!(
+            "```yarn\n$gold: Number = 10\n```\n\nHow much money the player has",
+            hover("shop.yarn", 3, 8)
+        );
+        assert_eq!(
+            "```yarn\n$greeting: String = \"Hi\"\n```\n\n*Local variable*",
+            hover("start.yarn", 5, 5)
+        );
+        assert_eq!("Node `Shop`\n\n- tags: store", hover("start.yarn", 6, 8));
+
+        let mut project = project;
+        project.set_document(url("math.yarn"), "title: Math\n---\n{number(\"1\")}\n===\n");
+        let hover = project
+            .hover(&url("math.yarn"), LspPosition::new(2, 3))
+            .unwrap();
+        let HoverContents::Markup(content) = hover.contents else {
+            panic!("Unexpected hover: {hover:?}");
+        };
+        assert_eq!("```yarn\nnumber(Any) -> Number\n```", content.value);
+    }
+
+    #[test]
+    fn completes_nodes_variables_and_functions() {
+        let mut project = project();
+        let labels = |project: &Project, text: &str| {
+            project
+                .completion(
+                    &url("start.yarn"),
+                    LspPosition::new(5, text.encode_utf16().count() as u32),
+                )
+                .into_iter()
+                .map(|item| item.label)
+                .collect::<Vec<_>>()
+        };
+        let mut set_line = |text: &str| {
+            project.set_document(
+                url("start.yarn"),
+                format!(
+                    "title: Start\n---\n<<declare $gold = 10>>\n<<local $greeting = \"Hi\">>\n\n{text}\n===\n"
+                ),
+            );
+            project.compile();
+            labels(&project, text)
+        };
+
+        assert_eq!(vec!["Shop", "Start"], set_line("<<jump Sh"));
+        assert_eq!(vec!["$gold", "$greeting"], set_line("You have {$g"));
+        let in_expression = set_line("<<if ");
+        assert!(in_expression.contains(&"$gold".to_owned()));
+        assert!(in_expression.contains(&"number".to_owned()));
+        assert!(!in_expression.iter().any(|label| label.contains('.')));
+        assert!(set_line("Just text").is_empty());
+    }
+
+    #[test]
+    fn outlines_nodes() {
+        let project = project();
+        let symbols = project.document_symbols(&url("shop.yarn"));
+        assert_eq!(1, symbols.len());
+        assert_eq!("Shop", symbols[0].name);
+        assert_eq!(Some("store".to_owned()), symbols[0].detail);
+        assert_eq!(7, symbols[0].range.end.line);
+    }
+
+    #[test]
+    fn recognizes_node_references() {
+        assert!(is_node_reference("<<jump "));
+        assert!(is_node_reference("Text << detour  "));
+        assert!(!is_node_reference("<<jump"));
+        assert!(!is_node_reference("<<set $jump "));
+        assert!(!is_node_reference("I want to jump "));
+    }
+}
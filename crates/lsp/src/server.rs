@@ -0,0 +1,376 @@
+use crate::project::Project;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+    PublishDiagnostics,
+};
+use lsp_types::request::{
+    Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, Request as LspRequest,
+};
+use lsp_types::{
+    CompletionOptions, CompletionResponse, DocumentSymbolResponse, GotoDefinitionResponse,
+    HoverProviderCapability, InitializeParams, InitializeResult, OneOf, PublishDiagnosticsParams,
+    ServerCapabilities, ServerInfo, TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use std::error::Error;
+use std::fmt::{self, Debug, Formatter};
+use std::path::{Path, PathBuf};
+use yarnspinner::compiler::Compiler;
+use yarnspinner::core::Library;
+
+/// The error type returned when the connection to the editor fails.
+pub type ServerError = Box<dyn Error + Send + Sync>;
+
+/// A language server for Yarn files.
+///
+/// On startup, all `.yarn` files in the editor's workspace folders are loaded. These are compiled together with every
+/// file the editor opens, so that nodes and variables from other files are known.
+///
+/// ## Example
+///
+/// ```no_run
+/// use yarnspinner::prelude::*;
+/// use yarnspinner_lsp::LanguageServer;
+///
+/// let mut library = YarnLibrary::standard_library();
+/// library.add_function("is_daytime", || true);
+/// LanguageServer::new(library).run_stdio().unwrap();
+/// ```
+pub struct LanguageServer {
+    project: Project,
+    workspace_folders: Vec<PathBuf>,
+}
+
+impl Debug for LanguageServer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LanguageServer")
+            .field("workspace_folders", &self.workspace_folders)
+            .finish_non_exhaustive()
+    }
+}
+
+impl LanguageServer {
+    /// Creates a language server that offers the functions of the given [`Library`] in completions and hovers,
+    /// and uses them to type check function calls.
+    pub fn new(library: Library) -> Self {
+        Self {
+            project: Project::new(library),
+            workspace_folders: Vec::new(),
+        }
+    }
+
+    /// Runs the language server on stdin and stdout until the editor shuts it down.
+    pub fn run_stdio(self) -> Result<(), ServerError> {
+        let (connection, io_threads) = Connection::stdio();
+        self.run(&connection)?;
+        io_threads.join()?;
+        Ok(())
+    }
+
+    /// Runs the language server on the given connection until the editor shuts it down.
+    pub fn run(mut self, connection: &Connection) -> Result<(), ServerError> {
+        let (id, params) = connection.initialize_start()?;
+        let params: InitializeParams = serde_json::from_value(params)?;
+        let result = InitializeResult {
+            capabilities: capabilities(),
+            server_info: Some(ServerInfo {
+                name: env!("CARGO_PKG_NAME").to_owned(),
+                version: Some(env!("CARGO_PKG_VERSION").to_owned()),
+            }),
+        };
+        connection.initialize_finish(id, serde_json::to_value(result)?)?;
+
+        self.load_workspace(&params);
+        self.publish_diagnostics(connection)?;
+        for message in &connection.receiver {
+            match message {
+                Message::Request(request) => {
+                    if connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    let response = self.handle_request(request);
+                    connection.sender.send(response.into())?;
+                }
+                Message::Notification(notification) => {
+                    self.handle_notification(notification, connection)?;
+                }
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    #[allow(deprecated)] // `root_uri` is still sent by editors that don't support multiple workspace folders
+    fn load_workspace(&mut self, params: &InitializeParams) {
+        let folders: Vec<_> = match &params.workspace_folders {
+            Some(folders) => folders.iter().map(|folder| folder.uri.clone()).collect(),
+            None => params.root_uri.iter().cloned().collect(),
+        };
+        self.workspace_folders = folders
+            .iter()
+            .filter_map(|folder| folder.to_file_path().ok())
+            .collect();
+        for folder in self.workspace_folders.clone() {
+            self.load_yarn_files(&folder);
+        }
+        self.project.compile();
+    }
+
+    fn load_yarn_files(&mut self, directory: &Path) {
+        for path in Compiler::find_yarn_files(directory).unwrap_or_default() {
+            let (Ok(url), Ok(text)) = (Url::from_file_path(&path), std::fs::read_to_string(&path))
+            else {
+                continue;
+            };
+            self.project.set_document(url, text);
+        }
+    }
+
+    fn handle_notification(
+        &mut self,
+        notification: Notification,
+        connection: &Connection,
+    ) -> Result<(), ServerError> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let Some(params) = extract::<DidOpenTextDocument>(notification) else {
+                    return Ok(());
+                };
+                let document = params.text_document;
+                self.project.set_document(document.uri, document.text);
+            }
+            DidChangeTextDocument::METHOD => {
+                let Some(mut params) = extract::<DidChangeTextDocument>(notification) else {
+                    return Ok(());
+                };
+                // We only ask for full syncs, so the last change contains the whole document
+                let Some(change) = params.content_changes.pop() else {
+                    return Ok(());
+                };
+                self.project
+                    .set_document(params.text_document.uri, change.text);
+            }
+            DidCloseTextDocument::METHOD => {
+                let Some(params) = extract::<DidCloseTextDocument>(notification) else {
+                    return Ok(());
+                };
+                let url = params.text_document.uri;
+                // Files in the workspace are still part of the project, but their unsaved changes are discarded
+                let text = url
+                    .to_file_path()
+                    .ok()
+                    .filter(|path| {
+                        self.workspace_folders
+                            .iter()
+                            .any(|folder| path.starts_with(folder))
+                    })
+                    .and_then(|path| std::fs::read_to_string(path).ok());
+                match text {
+                    Some(text) => self.project.set_document(url, text),
+                    None => {
+                        self.project.remove_document(&url);
+                        let params = PublishDiagnosticsParams::new(url, Vec::new(), None);
+                        send_notification::<PublishDiagnostics>(connection, params)?;
+                    }
+                }
+            }
+            _ => return Ok(()),
+        }
+        self.project.compile();
+        self.publish_diagnostics(connection)
+    }
+
+    fn handle_request(&mut self, request: Request) -> Response {
+        match request.method.as_str() {
+            GotoDefinition::METHOD => respond::<GotoDefinition>(request, |params| {
+                let position = params.text_document_position_params;
+                let locations = self
+                    .project
+                    .definition(&position.text_document.uri, position.position);
+                (!locations.is_empty()).then_some(GotoDefinitionResponse::Array(locations))
+            }),
+            HoverRequest::METHOD => respond::<HoverRequest>(request, |params| {
+                let position = params.text_document_position_params;
+                self.project
+                    .hover(&position.text_document.uri, position.position)
+            }),
+            Completion::METHOD => respond::<Completion>(request, |params| {
+                let position = params.text_document_position;
+                let items = self
+                    .project
+                    .completion(&position.text_document.uri, position.position);
+                Some(CompletionResponse::Array(items))
+            }),
+            DocumentSymbolRequest::METHOD => respond::<DocumentSymbolRequest>(request, |params| {
+                let symbols = self.project.document_symbols(&params.text_document.uri);
+                Some(DocumentSymbolResponse::Nested(symbols))
+            }),
+            method => Response::new_err(
+                request.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("Unsupported request: {method}"),
+            ),
+        }
+    }
+
+    fn publish_diagnostics(&self, connection: &Connection) -> Result<(), ServerError> {
+        for (url, diagnostics) in self.project.diagnostics() {
+            let params = PublishDiagnosticsParams::new(url.clone(), diagnostics.clone(), None);
+            send_notification::<PublishDiagnostics>(connection, params)?;
+        }
+        Ok(())
+    }
+}
+
+fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["$".to_owned(), " ".to_owned(), "{".to_owned()]),
+            ..Default::default()
+        }),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    }
+}
+
+fn extract<N: lsp_types::notification::Notification>(
+    notification: Notification,
+) -> Option<N::Params> {
+    notification.extract(N::METHOD).ok()
+}
+
+fn send_notification<N: lsp_types::notification::Notification>(
+    connection: &Connection,
+    params: N::Params,
+) -> Result<(), ServerError> {
+    let notification = Notification::new(N::METHOD.to_owned(), params);
+    connection.sender.send(notification.into())?;
+    Ok(())
+}
+
+fn respond<R: LspRequest>(
+    request: Request,
+    handler: impl FnOnce(R::Params) -> R::Result,
+) -> Response {
+    let id = request.id.clone();
+    match request.extract::<R::Params>(R::METHOD) {
+        Ok((_, params)) => Response::new_ok(id, handler(params)),
+        Err(error) => Response::new_err(id, ErrorCode::InvalidParams as i32, error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_server::RequestId;
+    use lsp_types::notification::{Exit, Initialized};
+    use lsp_types::request::{Initialize, Shutdown};
+    use lsp_types::{
+        DidOpenTextDocumentParams, DocumentSymbolParams, Location, Position, Range,
+        TextDocumentIdentifier, TextDocumentItem, TextDocumentPositionParams,
+    };
+    use serde_json::Value;
+    use std::thread;
+
+    fn request<R: LspRequest>(client: &Connection, id: i32, params: R::Params) -> R::Result {
+        let request = Request::new(RequestId::from(id), R::METHOD.to_owned(), params);
+        client.sender.send(request.into()).unwrap();
+        loop {
+            if let Message::Response(response) = client.receiver.recv().unwrap() {
+                assert_eq!(RequestId::from(id), response.id);
+                return serde_json::from_value(response.result.unwrap_or(Value::Null)).unwrap();
+            }
+        }
+    }
+
+    fn notify<N: lsp_types::notification::Notification>(client: &Connection, params: N::Params) {
+        send_notification::<N>(client, params).unwrap();
+    }
+
+    #[test]
+    fn serves_editor_requests() {
+        let (server, client) = Connection::memory();
+        let server = thread::spawn(move || {
+            LanguageServer::new(Library::standard_library())
+                .run(&server)
+                .unwrap()
+        });
+
+        let initialize_result = request::<Initialize>(&client, 1, InitializeParams::default());
+        assert_eq!(
+            Some(OneOf::Left(true)),
+            initialize_result.capabilities.definition_provider
+        );
+        notify::<Initialized>(&client, lsp_types::InitializedParams {});
+
+        let url = Url::parse("file:///story.yarn").unwrap();
+        notify::<DidOpenTextDocument>(
+            &client,
+            DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    url.clone(),
+                    "yarnspinner".to_owned(),
+                    1,
+                    "title: Start\n---\n<<jump Ending>>\n===\ntitle: Ending\n---\n<<set $x to 1 + \"one\">>\n===\n"
+                        .to_owned(),
+                ),
+            },
+        );
+        let diagnostics = loop {
+            let Message::Notification(notification) = client.receiver.recv().unwrap() else {
+                continue;
+            };
+            let params = notification
+                .extract::<PublishDiagnosticsParams>(PublishDiagnostics::METHOD)
+                .unwrap();
+            if params.uri == url {
+                break params.diagnostics;
+            }
+        };
+        assert!(!diagnostics.is_empty());
+        assert_eq!(6, diagnostics[0].range.start.line);
+
+        let position = TextDocumentPositionParams::new(
+            TextDocumentIdentifier::new(url.clone()),
+            Position::new(2, 10),
+        );
+        let definition = request::<GotoDefinition>(
+            &client,
+            2,
+            lsp_types::GotoDefinitionParams {
+                text_document_position_params: position,
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            },
+        );
+        assert_eq!(
+            Some(GotoDefinitionResponse::Array(vec![Location::new(
+                url.clone(),
+                Range::new(Position::new(4, 7), Position::new(4, 13))
+            )])),
+            definition
+        );
+
+        let symbols = request::<DocumentSymbolRequest>(
+            &client,
+            3,
+            DocumentSymbolParams {
+                text_document: TextDocumentIdentifier::new(url),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            },
+        );
+        let Some(DocumentSymbolResponse::Nested(symbols)) = symbols else {
+            panic!("Unexpected symbols: {symbols:?}");
+        };
+        let names: Vec<_> = symbols.into_iter().map(|symbol| symbol.name).collect();
+        assert_eq!(vec!["Start", "Ending"], names);
+
+        request::<Shutdown>(&client, 4, ());
+        notify::<Exit>(&client, ());
+        server.join().unwrap();
+    }
+}
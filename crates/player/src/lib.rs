@@ -0,0 +1,24 @@
+//! A terminal player for Yarn files, so writers can playtest their dialogue without launching the game.
+//!
+//! It compiles a folder of `.yarn` files, prints the compiler's warnings and plays the dialogue from a chosen node:
+//! - lines are printed with the name of their character, and with `[b]`, `[i]` and `[u]` markup shown as bold, italic and underlined text,
+//! - options are numbered and selected by entering their number,
+//! - commands are printed instead of being run.
+//!
+//! Whenever options are presented, the prompt also accepts the following commands:
+//! - `/set $variable value` changes a variable, e.g. `/set $gold 100`.
+//! - `/vars` lists all variables.
+//! - `/lang` lists the available languages, `/lang de-CH` switches to one and `/lang base` switches back
+//!   to the language the Yarn files are written in. Translations are read from `<language>.strings.csv` files
+//!   next to the Yarn files, as written by the Bevy plugin.
+//! - `/help` lists these commands, `/quit` stops playing.
+//!
+//! The `yarnspinner_player` binary runs the player with the functions every [`Dialogue`](yarnspinner::runtime::Dialogue) offers.
+//! If your game registers functions of its own, load a [`Player`] with your [`Library`](yarnspinner::core::Library) instead.
+#![warn(missing_docs, missing_debug_implementations)]
+
+mod player;
+mod render;
+mod strings_file;
+
+pub use crate::player::{Player, PlayerError};
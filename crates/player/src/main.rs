@@ -0,0 +1,64 @@
+use clap::Parser;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use yarnspinner::runtime::{Dialogue, MemoryVariableStorage, StringTableTextProvider};
+use yarnspinner_player::{Player, PlayerError};
+
+/// Plays Yarn dialogue in the terminal.
+#[derive(Debug, Parser)]
+#[command(name = "yarnspinner_player", version)]
+struct Args {
+    /// The `.yarn` files to play, or directories to search for them and for `<language>.strings.csv` translations.
+    #[arg(required = true, value_name = "PATH")]
+    paths: Vec<PathBuf>,
+
+    /// The node to start the dialogue at.
+    #[arg(short, long, default_value = "Start", value_name = "NODE")]
+    start: String,
+
+    /// The language to start in, e.g. `de-CH`. Defaults to the language the Yarn files are written in.
+    #[arg(short, long, value_name = "LANGUAGE")]
+    language: Option<String>,
+
+    /// Prints lines without colors and styles. This is the default if the output is not a terminal or `NO_COLOR` is set.
+    #[arg(long)]
+    plain: bool,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {error}");
+            let mut source = error.source();
+            while let Some(cause) = source {
+                eprintln!("  caused by: {cause}");
+                source = cause.source();
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Args) -> Result<(), PlayerError> {
+    let dialogue = Dialogue::new(
+        Box::new(MemoryVariableStorage::new()),
+        Box::new(StringTableTextProvider::new()),
+    );
+    let styled =
+        !args.plain && std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal();
+    let mut player =
+        Player::load(&args.paths, dialogue.library().clone())?.with_ansi_styles(styled);
+    for warning in player.warnings() {
+        eprintln!("{warning}");
+    }
+    player.set_language(args.language.as_deref())?;
+    player.play(
+        &args.start,
+        std::io::stdin().lock(),
+        std::io::stdout().lock(),
+    )?;
+    Ok(())
+}
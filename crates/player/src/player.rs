@@ -0,0 +1,426 @@
+use crate::{render, strings_file};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::io::{BufRead, Write};
+use std::ops::ControlFlow;
+use std::path::Path;
+use yarnspinner::compiler::{Compiler, Diagnostic};
+use yarnspinner::core::{Library, YarnValue};
+use yarnspinner::runtime::{
+    Dialogue, DialogueEvent, DialogueOption, Language, MemoryVariableStorage, OptionId,
+    StringTable, StringTableTextProvider,
+};
+
+/// The error type returned when loading or playing fails.
+pub type PlayerError = Box<dyn Error + Send + Sync>;
+
+const HELP: &str = "\
+Enter the number of an option to select it, or one of these commands:
+  /set $variable value  Changes a variable
+  /vars                 Lists all variables
+  /lang [language]      Lists the languages or switches to one, use `base` for the language of the Yarn files
+  /help                 Shows this help
+  /quit                 Stops playing";
+
+/// Plays compiled Yarn files in the terminal. See the [crate documentation](crate) for what it offers.
+///
+/// ## Example
+///
+/// ```no_run
+/// use yarnspinner::prelude::*;
+/// use yarnspinner_player::Player;
+///
+/// let mut library = YarnLibrary::standard_library();
+/// library.add_function("is_daytime", || true);
+/// let mut player = Player::load(&["assets/dialogue"], library).unwrap();
+/// for warning in player.warnings() {
+///     eprintln!("{warning}");
+/// }
+/// player
+///     .play("Start", std::io::stdin().lock(), std::io::stdout().lock())
+///     .unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Player {
+    dialogue: Dialogue,
+    warnings: Vec<Diagnostic>,
+    translations: HashMap<String, StringTable>,
+    styled: bool,
+}
+
+impl Player {
+    /// Compiles all `.yarn` files given directly or found in the given directories, which are searched recursively.
+    /// `.strings.csv` files found along the way are loaded as translations.
+    ///
+    /// The [`Library`] must contain all functions used by the Yarn files, including the ones every [`Dialogue`] offers.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if a file cannot be read or the Yarn files contain errors.
+    pub fn load(paths: &[impl AsRef<Path>], library: Library) -> Result<Self, PlayerError> {
+        let mut yarn_files = Vec::new();
+        let mut strings_files = Vec::new();
+        for path in paths {
+            let path = path.as_ref();
+            if path.is_dir() {
+                let search_error =
+                    |error| format!("Failed to search \"{}\": {error}", path.display());
+                yarn_files.extend(Compiler::find_yarn_files(path).map_err(search_error)?);
+                strings_files
+                    .extend(Compiler::find_files(path, is_strings_file).map_err(search_error)?);
+            } else if is_strings_file(path) {
+                strings_files.push(path.to_owned());
+            } else {
+                yarn_files.push(path.to_owned());
+            }
+        }
+        yarn_files.sort();
+        yarn_files.dedup();
+
+        let mut compiler = Compiler::new();
+        for path in &yarn_files {
+            compiler
+                .try_read_file(path)
+                .map_err(|error| format!("Failed to read \"{}\": {error}", path.display()))?;
+        }
+        let compilation = compiler.extend_library(library.clone()).compile()?;
+
+        let mut translations = HashMap::new();
+        for path in &strings_files {
+            strings_file::read(path, &mut translations)?;
+        }
+
+        let mut text_provider = StringTableTextProvider::new();
+        text_provider.extend_base_language(
+            compilation
+                .string_table
+                .into_iter()
+                .map(|(id, info)| (id, info.text))
+                .collect(),
+        );
+        let mut dialogue = Dialogue::new(
+            Box::new(MemoryVariableStorage::new()),
+            Box::new(text_provider),
+        );
+        dialogue.library_mut().import(library);
+        dialogue.add_program(
+            compilation
+                .program
+                .expect("A full compilation always produces a program"),
+        );
+        Ok(Self {
+            dialogue,
+            warnings: compilation.warnings,
+            translations,
+            styled: false,
+        })
+    }
+
+    /// Sets whether lines are styled with ANSI escape codes, which most terminals understand. Defaults to `false`.
+    pub fn with_ansi_styles(mut self, styled: bool) -> Self {
+        self.styled = styled;
+        self
+    }
+
+    /// The warnings the compiler found in the Yarn files.
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+
+    /// The languages translations were loaded for, sorted alphabetically.
+    pub fn languages(&self) -> Vec<&str> {
+        let mut languages: Vec<_> = self.translations.keys().map(String::as_str).collect();
+        languages.sort_unstable();
+        languages
+    }
+
+    /// Switches to the translation of the given language, or back to the language of the Yarn files if `None` is given.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if no translation was loaded for the language.
+    pub fn set_language(&mut self, language: Option<&str>) -> Result<(), PlayerError> {
+        let Some(language) = language else {
+            self.dialogue.set_language_code(None);
+            return Ok(());
+        };
+        let Some(string_table) = self.translations.get(language) else {
+            return Err(format!(
+                "No translation was loaded for \"{language}\". Available languages: {}",
+                self.languages().join(", ")
+            )
+            .into());
+        };
+        let language = Language::new(language);
+        self.dialogue
+            .text_provider_mut()
+            .as_any_mut()
+            .downcast_mut::<StringTableTextProvider>()
+            .expect("The player always uses a StringTableTextProvider")
+            .extend_translation(language.clone(), string_table.clone());
+        self.dialogue.set_language_code(language);
+        Ok(())
+    }
+
+    /// The [`Dialogue`] being played, e.g. for setting variables before playing.
+    pub fn dialogue(&self) -> &Dialogue {
+        &self.dialogue
+    }
+
+    /// The [`Dialogue`] being played, e.g. for setting variables before playing.
+    pub fn dialogue_mut(&mut self) -> &mut Dialogue {
+        &mut self.dialogue
+    }
+
+    /// Plays the dialogue from the given node until it completes, writing lines, options and commands to `output`
+    /// and reading selected options and commands from `input`.
+    ///
+    /// Stops early if `input` ends or `/quit` is entered while waiting for an option to be selected.
+    pub fn play(
+        &mut self,
+        start_node: &str,
+        mut input: impl BufRead,
+        mut output: impl Write,
+    ) -> Result<(), PlayerError> {
+        if !self.dialogue.node_exists(start_node) {
+            return Err(format!("There is no node named \"{start_node}\"").into());
+        }
+        self.dialogue.set_node(start_node)?;
+        loop {
+            for event in self.dialogue.continue_()? {
+                match event {
+                    DialogueEvent::Line(line) => {
+                        writeln!(output, "{}", render::line(&line, self.styled))?
+                    }
+                    DialogueEvent::Command(command) => {
+                        writeln!(output, "{}", render::command(&command, self.styled))?
+                    }
+                    DialogueEvent::Options(options) => {
+                        for (index, option) in options.iter().enumerate() {
+                            writeln!(output, "{}", render::option(index + 1, option, self.styled))?;
+                        }
+                        let Some(option) = self.prompt(&options, &mut input, &mut output)? else {
+                            return Ok(());
+                        };
+                        self.dialogue.set_selected_option(option)?;
+                    }
+                    DialogueEvent::DialogueComplete => return Ok(()),
+                    DialogueEvent::NodeStart(_)
+                    | DialogueEvent::NodeComplete(_)
+                    | DialogueEvent::LineHints(_) => {}
+                }
+            }
+        }
+    }
+
+    /// Reads input until an available option is selected. Returns `None` if playing should stop.
+    fn prompt(
+        &mut self,
+        options: &[DialogueOption],
+        input: &mut impl BufRead,
+        output: &mut impl Write,
+    ) -> Result<Option<OptionId>, PlayerError> {
+        loop {
+            write!(output, "> ")?;
+            output.flush()?;
+            let mut answer = String::new();
+            if input.read_line(&mut answer)? == 0 {
+                return Ok(None);
+            }
+            let answer = answer.trim();
+            if let Some(command) = answer.strip_prefix('/') {
+                match self.run_command(command) {
+                    Ok(ControlFlow::Continue(message)) => writeln!(output, "{message}")?,
+                    Ok(ControlFlow::Break(())) => return Ok(None),
+                    Err(message) => writeln!(output, "{message}")?,
+                }
+                continue;
+            }
+            let option = answer
+                .parse::<usize>()
+                .ok()
+                .and_then(|number| options.get(number.checked_sub(1)?))
+                .filter(|option| option.is_available);
+            match option {
+                Some(option) => return Ok(Some(option.id)),
+                None => writeln!(
+                    output,
+                    "Please enter the number of an available option (1-{}), or /help for commands",
+                    options.len()
+                )?,
+            }
+        }
+    }
+
+    /// Runs a prompt command. Returns the message to show, or [`ControlFlow::Break`] if playing should stop.
+    fn run_command(&mut self, command: &str) -> Result<ControlFlow<(), String>, String> {
+        let (name, arguments) = command.split_once(' ').unwrap_or((command, ""));
+        let arguments = arguments.trim();
+        match name {
+            "set" => {
+                let (variable, value) = arguments
+                    .split_once(' ')
+                    .ok_or("Usage: /set $variable value")?;
+                let value = self.parse_value(variable, value.trim())?;
+                self.dialogue
+                    .variable_storage_mut()
+                    .set(variable.to_owned(), value.clone())
+                    .map_err(|error| error.to_string())?;
+                Ok(ControlFlow::Continue(format!("{variable} = {value}")))
+            }
+            "vars" => {
+                let variables: BTreeMap<_, _> = self
+                    .dialogue
+                    .variable_storage()
+                    .variables()
+                    .into_iter()
+                    .filter(|(name, _)| !name.starts_with("$Yarn.Internal."))
+                    .collect();
+                let lines: Vec<_> = variables
+                    .iter()
+                    .map(|(name, value)| format!("{name} = {value}"))
+                    .collect();
+                Ok(ControlFlow::Continue(lines.join("\n")))
+            }
+            "lang" => match arguments {
+                "" => {
+                    let current = self
+                        .dialogue
+                        .language_code()
+                        .map_or_else(|| "base".to_owned(), ToString::to_string);
+                    Ok(ControlFlow::Continue(format!(
+                        "Current language: {current}. Available languages: base, {}",
+                        self.languages().join(", ")
+                    )))
+                }
+                "base" => {
+                    self.set_language(None).map_err(|error| error.to_string())?;
+                    Ok(ControlFlow::Continue(
+                        "Switched to the base language, starting with the next line".to_owned(),
+                    ))
+                }
+                language => {
+                    self.set_language(Some(language))
+                        .map_err(|error| error.to_string())?;
+                    Ok(ControlFlow::Continue(format!(
+                        "Switched to {language}, starting with the next line"
+                    )))
+                }
+            },
+            "help" => Ok(ControlFlow::Continue(HELP.to_owned())),
+            "quit" => Ok(ControlFlow::Break(())),
+            _ => Err(format!(
+                "Unknown command /{name}, enter /help for a list of commands"
+            )),
+        }
+    }
+
+    /// Parses a value for a variable, keeping the type the variable already has.
+    fn parse_value(&self, variable: &str, value: &str) -> Result<YarnValue, String> {
+        let current = self
+            .dialogue
+            .variable_storage()
+            .get(variable)
+            .map_err(|error| error.to_string())?;
+        match current {
+            YarnValue::Number(_) => value
+                .parse()
+                .map(YarnValue::Number)
+                .map_err(|_| format!("{variable} is a number, but \"{value}\" is not")),
+            YarnValue::Boolean(_) => value
+                .parse()
+                .map(YarnValue::Boolean)
+                .map_err(|_| format!("{variable} is a boolean, please enter true or false")),
+            YarnValue::String(_) => Ok(YarnValue::String(
+                value
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+                    .unwrap_or(value)
+                    .to_owned(),
+            )),
+        }
+    }
+}
+
+fn is_strings_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().ends_with(".strings.csv"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "\
+title: Start
+---
+<<declare $gold = 5>>
+Alice: Welcome! #line:welcome
+<<open_shop>>
+-> Buy a sword <<if $gold >= 10>> #line:buy
+    You bought a sword. #line:bought
+-> Leave #line:leave
+    Goodbye! #line:goodbye
+===
+";
+
+    fn player(name: &str) -> Player {
+        let directory =
+            std::env::temp_dir().join(format!("yarnspinner-player-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("shop.yarn"), SOURCE).unwrap();
+        std::fs::write(
+            directory.join("de-CH.strings.csv"),
+            "language,id,text,file,node,line_number,lock,comment\n\
+             de-CH,line:welcome,Alice: Willkommen!,shop.yarn,Start,4,,\n\
+             de-CH,line:buy,Kauf ein Schwert,shop.yarn,Start,6,,\n\
+             de-CH,line:bought,Du hast ein Schwert gekauft.,shop.yarn,Start,7,,\n\
+             de-CH,line:leave,Geh,shop.yarn,Start,8,,\n\
+             de-CH,line:goodbye,Tschüss!,shop.yarn,Start,9,,\n",
+        )
+        .unwrap();
+        let dialogue = Dialogue::new(
+            Box::new(MemoryVariableStorage::new()),
+            Box::new(StringTableTextProvider::new()),
+        );
+        Player::load(&[directory], dialogue.library().clone()).unwrap()
+    }
+
+    fn play(player: &mut Player, input: &str) -> String {
+        let mut output = Vec::new();
+        player.play("Start", input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn plays_selected_options() {
+        let mut player = player("options");
+        assert_eq!(
+            "Alice: Welcome!\n<<open_shop>>\n  1) Buy a sword (unavailable)\n  2) Leave\n> Goodbye!\n",
+            play(&mut player, "2\n")
+        );
+    }
+
+    #[test]
+    fn sets_variables_from_the_prompt() {
+        let mut player = player("variables");
+        let output = play(&mut player, "/set $gold lots\n/set $gold 10\n/vars\n2\n");
+        assert!(output.contains("> $gold is a number, but \"lots\" is not\n"));
+        assert!(output.ends_with("> $gold = 10\n> $gold = 10\n> Goodbye!\n"));
+
+        // Options were already evaluated when they were shown, so the new value applies from the next ones on.
+        assert!(play(&mut player, "1\n").ends_with("> You bought a sword.\n"));
+    }
+
+    #[test]
+    fn switches_languages_from_the_prompt() {
+        let mut player = player("languages");
+        assert_eq!(vec!["de-CH"], player.languages());
+        let output = play(&mut player, "/lang fr\n/lang de-CH\n2\n");
+        assert!(output.contains("No translation was loaded for \"fr\""));
+        assert!(output.ends_with("> Tschüss!\n"));
+
+        player.set_language(None).unwrap();
+        assert!(play(&mut player, "/quit\n").starts_with("Alice: Welcome!\n"));
+    }
+}
@@ -0,0 +1,127 @@
+//! Turning dialogue content into text for the terminal, optionally styled with ANSI escape codes.
+
+use yarnspinner::runtime::{Command, DialogueOption, Line, CHARACTER_ATTRIBUTE};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const ITALIC: &str = "\x1b[3m";
+const UNDERLINE: &str = "\x1b[4m";
+const CYAN: &str = "\x1b[36m";
+
+/// The character name followed by the text. When styled, markup like `[b]` is shown as the matching style.
+pub(crate) fn line(line: &Line, styled: bool) -> String {
+    let mut text = String::new();
+    if let Some(name) = line.character_name() {
+        text += &paint(&format!("{BOLD}{CYAN}"), name, styled);
+        text += ": ";
+    }
+    let character_range = line
+        .attribute(CHARACTER_ATTRIBUTE)
+        .map(|attribute| attribute.position..attribute.position + attribute.length)
+        .unwrap_or_default();
+    let mut current_style = String::new();
+    for (index, character) in line.text.chars().enumerate() {
+        if character_range.contains(&index) {
+            continue;
+        }
+        if styled {
+            let style = style_at(line, index);
+            if style != current_style {
+                if !current_style.is_empty() {
+                    text += RESET;
+                }
+                text += &style;
+                current_style = style;
+            }
+        }
+        text.push(character);
+    }
+    if !current_style.is_empty() {
+        text += RESET;
+    }
+    text
+}
+
+pub(crate) fn option(number: usize, option: &DialogueOption, styled: bool) -> String {
+    let text = format!("  {number}) {}", line(&option.line, styled));
+    if option.is_available {
+        text
+    } else if styled {
+        paint(DIM, &format!("{text} (unavailable)"), true)
+    } else {
+        format!("{text} (unavailable)")
+    }
+}
+
+pub(crate) fn command(command: &Command, styled: bool) -> String {
+    paint(DIM, &format!("<<{}>>", command.raw), styled)
+}
+
+fn paint(style: &str, text: &str, styled: bool) -> String {
+    if styled {
+        format!("{style}{text}{RESET}")
+    } else {
+        text.to_owned()
+    }
+}
+
+/// The escape codes for the markup attributes covering the character at the given index.
+fn style_at(line: &Line, index: usize) -> String {
+    let is_covered = |names: &[&str]| {
+        line.attributes.iter().any(|attribute| {
+            names.contains(&attribute.name.as_str())
+                && (attribute.position..attribute.position + attribute.length).contains(&index)
+        })
+    };
+    [
+        (["b", "bold"], BOLD),
+        (["i", "italic"], ITALIC),
+        (["u", "underline"], UNDERLINE),
+    ]
+    .into_iter()
+    .filter(|(names, _)| is_covered(names))
+    .map(|(_, code)| code)
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yarnspinner::runtime::{MarkupAttribute, CHARACTER_ATTRIBUTE_NAME_PROPERTY};
+
+    fn attribute(name: &str, position: usize, length: usize) -> MarkupAttribute {
+        MarkupAttribute {
+            name: name.to_owned(),
+            position,
+            length,
+            properties: Default::default(),
+            source_position: 0,
+        }
+    }
+
+    fn alice_line() -> Line {
+        let mut character = attribute(CHARACTER_ATTRIBUTE, 0, 7);
+        character
+            .properties
+            .insert(CHARACTER_ATTRIBUTE_NAME_PROPERTY.to_owned(), "Alice".into());
+        Line {
+            id: "line:1".into(),
+            text: "Alice: Hello there!".to_owned(),
+            attributes: vec![character, attribute("b", 7, 5)],
+        }
+    }
+
+    #[test]
+    fn renders_plain_lines() {
+        assert_eq!("Alice: Hello there!", line(&alice_line(), false));
+    }
+
+    #[test]
+    fn styles_character_names_and_markup() {
+        assert_eq!(
+            "\x1b[1m\x1b[36mAlice\x1b[0m: \x1b[1mHello\x1b[0m there!",
+            line(&alice_line(), true)
+        );
+    }
+}
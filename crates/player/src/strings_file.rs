@@ -0,0 +1,64 @@
+//! Reading translations from the `<language>.strings.csv` files used by the Bevy plugin.
+
+use std::collections::HashMap;
+use std::path::Path;
+use yarnspinner::runtime::StringTable;
+
+/// Reads the lines of a strings file into the string tables of their languages.
+pub(crate) fn read(
+    path: &Path,
+    translations: &mut HashMap<String, StringTable>,
+) -> csv::Result<()> {
+    let mut reader = csv::Reader::from_path(path)?;
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|header| header == name);
+    let (Some(language), Some(id), Some(text)) = (column("language"), column("id"), column("text"))
+    else {
+        return Err(csv::Error::from(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "\"{}\" needs the columns \"language\", \"id\" and \"text\"",
+                path.display()
+            ),
+        )));
+    };
+    for record in reader.records() {
+        let record = record?;
+        let (Some(language), Some(id), Some(text)) =
+            (record.get(language), record.get(id), record.get(text))
+        else {
+            continue;
+        };
+        translations
+            .entry(language.to_owned())
+            .or_default()
+            .insert(id.into(), text.to_owned());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_lines_by_language() {
+        let directory =
+            std::env::temp_dir().join(format!("yarnspinner-player-strings-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("de-CH.strings.csv");
+        std::fs::write(
+            &path,
+            "language,id,text,file,node,line_number,lock,comment\n\
+             de-CH,line:1,\"Hallo, Welt\",test.yarn,Start,3,23beac47,\n\
+             de-CH,line:2,Tschüss,test.yarn,Start,4,ccf66591,\n",
+        )
+        .unwrap();
+
+        let mut translations = HashMap::new();
+        read(&path, &mut translations).unwrap();
+        let lines = &translations["de-CH"];
+        assert_eq!(2, lines.len());
+        assert_eq!("Hallo, Welt", lines[&"line:1".into()]);
+    }
+}
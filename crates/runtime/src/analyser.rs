@@ -1,13 +1,14 @@
 //! Adapted from <https://github.com/YarnSpinnerTool/YarnSpinner/blob/da39c7195107d8211f21c263e4084f773b84eaff/YarnSpinner/Analyser.cs>
 
 pub(crate) use self::default_analysers::*;
-pub use self::{context::*, diagnosis::*};
+pub use self::{context::*, diagnosis::*, node_graph_analyser::*};
 use std::fmt::Debug;
 use yarnspinner_core::prelude::*;
 
 mod context;
 pub(crate) mod default_analysers;
 mod diagnosis;
+mod node_graph_analyser;
 
 /// A trait for analysing a compiled Yarn program. Can be used by adding them to a [`Context`] with [`Context::add_analyser`] and then applied to a
 /// compiled Yarn program with [`Dialogue::analyse`](crate::prelude::Dialogue).
@@ -23,9 +23,9 @@ impl CompiledProgramAnalyser for UnusedVariableChecker {
         let new_variables = program.nodes.values().flat_map(|node| {
             node.instructions
                 .iter()
-                .filter_map(|instruction| match instruction.opcode() {
-                    OpCode::PushVariable | OpCode::StoreVariable => {
-                        Some((instruction.opcode(), instruction.operands[0].clone()))
+                .filter_map(|instruction| match OpCode::try_from(instruction.opcode) {
+                    Ok(opcode @ (OpCode::PushVariable | OpCode::StoreVariable)) => {
+                        Some((opcode, instruction.operands[0].clone()))
                     }
                     _ => None,
                 })
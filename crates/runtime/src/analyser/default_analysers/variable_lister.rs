@@ -22,8 +22,8 @@ impl CompiledProgramAnalyser for VariableLister {
         let new_variables = program.nodes.values().flat_map(|node| {
             node.instructions
                 .iter()
-                .filter_map(|instruction| match instruction.opcode() {
-                    OpCode::PushVariable | OpCode::StoreVariable => {
+                .filter_map(|instruction| match OpCode::try_from(instruction.opcode) {
+                    Ok(OpCode::PushVariable | OpCode::StoreVariable) => {
                         Some(instruction.operands[0].clone())
                     }
                     _ => None,
@@ -0,0 +1,486 @@
+//! Checks how the nodes of a program are connected to each other.
+
+use crate::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use yarnspinner_core::prelude::*;
+
+/// A [`CompiledProgramAnalyser`] that follows the jumps between nodes, i.e. `<<jump>>`, `<<detour>>` and node groups, and reports:
+/// - [`DiagnosisSeverity::Warning`]: Nodes that cannot be reached from any of the entry nodes.
+/// - [`DiagnosisSeverity::Error`]: Jumps and detours to nodes that do not exist.
+/// - [`DiagnosisSeverity::Warning`]: Jumps and detours whose destination is an expression, since their destination is only known at runtime.
+///   The nodes they may reach are not taken into account when looking for unreachable nodes.
+/// - Only if configured with [`NodeGraphAnalyser::with_dead_end_severity`]: Nodes that can end without showing options or jumping to another node,
+///   which ends the dialogue. Explicit `<<stop>>` and `<<return>>` commands are considered intentional, and nodes that are only ever entered through
+///   `<<detour>>` are never dead ends, since the dialogue returns to where the detour started.
+///
+/// Diagnoses only have line numbers if the positions of the instructions are passed with [`NodeGraphAnalyser::with_instruction_positions`].
+///
+/// ## Example
+///
+/// ```
+/// # use yarnspinner_runtime::prelude::*;
+/// let mut context = Context::empty().add_analyser(Box::new(
+///     NodeGraphAnalyser::new()
+///         .with_entry_nodes(["Start", "Epilogue"])
+///         .with_dead_end_severity(DiagnosisSeverity::Error),
+/// ));
+/// ```
+#[derive(Debug, Clone)]
+pub struct NodeGraphAnalyser {
+    entry_nodes: BTreeSet<String>,
+    dead_end_severity: Option<DiagnosisSeverity>,
+    positions: HashMap<String, HashMap<usize, Option<Position>>>,
+    nodes: BTreeMap<String, NodeSummary>,
+}
+
+impl Default for NodeGraphAnalyser {
+    fn default() -> Self {
+        Self {
+            entry_nodes: BTreeSet::from(["Start".to_owned()]),
+            dead_end_severity: None,
+            positions: HashMap::new(),
+            nodes: BTreeMap::new(),
+        }
+    }
+}
+
+impl NodeGraphAnalyser {
+    /// Creates a new analyser that uses the node `Start` as its only entry node and does not report dead ends.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the nodes that the dialogue can be started at, replacing the default `Start`.
+    /// If there are none, unreachable nodes are not reported.
+    #[must_use]
+    pub fn with_entry_nodes(
+        mut self,
+        entry_nodes: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.entry_nodes = entry_nodes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Reports nodes that can end without showing options or jumping to another node with the given severity.
+    /// By default, these are not reported, since ending a node like that is a valid way to end the dialogue.
+    #[must_use]
+    pub fn with_dead_end_severity(mut self, severity: DiagnosisSeverity) -> Self {
+        self.dead_end_severity = Some(severity);
+        self
+    }
+
+    /// Sets the source positions of the instructions of a node, which are used for the line numbers of its diagnoses.
+    /// When compiling with `yarnspinner_compiler`, these are the `line_positions` of the node's `DebugInfo`.
+    #[must_use]
+    pub fn with_instruction_positions(
+        mut self,
+        node_name: impl Into<String>,
+        positions: HashMap<usize, Option<Position>>,
+    ) -> Self {
+        self.positions.insert(node_name.into(), positions);
+        self
+    }
+
+    fn diagnosis(
+        &self,
+        severity: DiagnosisSeverity,
+        message: String,
+        node_name: &str,
+        instruction: usize,
+    ) -> Diagnosis {
+        let diagnosis = Diagnosis::new(severity, message).with_node_name(node_name);
+        let position = self
+            .positions
+            .get(node_name)
+            .and_then(|positions| positions.get(&instruction))
+            .and_then(Option::as_ref);
+        match position {
+            Some(position) => diagnosis.with_line(position.line + 1),
+            None => diagnosis,
+        }
+    }
+
+    /// The nodes that the given node can go to. Jumps to a node group go to the node that selects one of its members,
+    /// which in turn is treated as going to all of them.
+    fn successors<'a>(&'a self, node_name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.nodes[node_name]
+            .edges
+            .iter()
+            .filter_map(|edge| edge.target.as_deref())
+            .chain(self.group_members(node_name))
+    }
+
+    fn group_members<'a>(&'a self, node_name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.nodes
+            .iter()
+            .filter(move |(_, member)| member.node_group.as_deref() == Some(node_name))
+            .map(|(name, _)| name.as_str())
+    }
+
+    fn is_node_group(&self, node_name: &str) -> bool {
+        self.group_members(node_name).next().is_some()
+    }
+
+    fn reachable_nodes(&self) -> HashSet<&str> {
+        let mut reachable = HashSet::new();
+        let mut pending: Vec<_> = self
+            .entry_nodes
+            .iter()
+            .map(String::as_str)
+            .filter(|name| self.nodes.contains_key(*name))
+            .collect();
+        while let Some(node_name) = pending.pop() {
+            if reachable.insert(node_name) {
+                pending.extend(
+                    self.successors(node_name)
+                        .filter(|name| self.nodes.contains_key(*name)),
+                );
+            }
+        }
+        reachable
+    }
+
+    /// Nodes that are entered through a plain jump or are entry nodes, as opposed to only through detours.
+    fn nodes_entered_without_detour(&self) -> HashSet<&str> {
+        let jump_targets = self.nodes.iter().flat_map(|(name, node)| {
+            node.edges
+                .iter()
+                .filter(|edge| edge.kind == EdgeKind::Jump)
+                .filter_map(|edge| edge.target.as_deref())
+                .chain(self.group_members(name))
+        });
+        self.entry_nodes
+            .iter()
+            .map(String::as_str)
+            .chain(jump_targets)
+            .collect()
+    }
+}
+
+impl CompiledProgramAnalyser for NodeGraphAnalyser {
+    fn diagnose(&mut self, program: &Program) {
+        for (name, node) in &program.nodes {
+            self.nodes
+                .insert(name.clone(), NodeSummary::from_node(node));
+        }
+    }
+
+    fn collect_diagnoses(&self) -> Vec<Diagnosis> {
+        let mut diagnoses = Vec::new();
+
+        for (node_name, node) in &self.nodes {
+            for edge in &node.edges {
+                let action = match edge.kind {
+                    EdgeKind::Jump => "jumps",
+                    EdgeKind::Detour => "detours",
+                };
+                match edge.target.as_deref() {
+                    Some(target) if !self.nodes.contains_key(target) => {
+                        diagnoses.push(self.diagnosis(
+                            DiagnosisSeverity::Error,
+                            format!("Node \"{node_name}\" {action} to node \"{target}\", which does not exist"),
+                            node_name,
+                            edge.instruction,
+                        ));
+                    }
+                    Some(_) => {}
+                    // Node groups select their destination at runtime, but only ever go to their members
+                    None if self.is_node_group(node_name) => {}
+                    None => {
+                        diagnoses.push(self.diagnosis(
+                            DiagnosisSeverity::Warning,
+                            format!("Node \"{node_name}\" {action} to a node that is only known at runtime, so the destination cannot be checked"),
+                            node_name,
+                            edge.instruction,
+                        ));
+                    }
+                }
+            }
+        }
+
+        if !self.entry_nodes.is_empty() {
+            for entry_node in &self.entry_nodes {
+                if !self.nodes.contains_key(entry_node) {
+                    diagnoses.push(Diagnosis::new(
+                        DiagnosisSeverity::Error,
+                        format!("Entry node \"{entry_node}\" does not exist"),
+                    ));
+                }
+            }
+            let reachable = self.reachable_nodes();
+            let entry_nodes = self
+                .entry_nodes
+                .iter()
+                .map(|name| format!("\"{name}\""))
+                .collect::<Vec<_>>()
+                .join(", ");
+            for (node_name, node) in &self.nodes {
+                if !node.is_smart_variable && !reachable.contains(node_name.as_str()) {
+                    diagnoses.push(self.diagnosis(
+                        DiagnosisSeverity::Warning,
+                        format!("Node \"{node_name}\" cannot be reached from the entry nodes {entry_nodes}"),
+                        node_name,
+                        0,
+                    ));
+                }
+            }
+        }
+
+        if let Some(severity) = &self.dead_end_severity {
+            let entered_without_detour = self.nodes_entered_without_detour();
+            for (node_name, node) in &self.nodes {
+                let Some(instruction) = node.dead_end else {
+                    continue;
+                };
+                if node.is_smart_variable
+                    || self.is_node_group(node_name)
+                    || !entered_without_detour.contains(node_name.as_str())
+                {
+                    continue;
+                }
+                diagnoses.push(self.diagnosis(
+                    severity.clone(),
+                    format!("Node \"{node_name}\" can end without showing options or jumping to another node, which ends the dialogue"),
+                    node_name,
+                    instruction,
+                ));
+            }
+        }
+
+        diagnoses
+    }
+}
+
+/// What the analyser needs to know about a node, so that programs don't have to be kept around until the diagnoses are collected.
+#[derive(Debug, Clone, Default)]
+struct NodeSummary {
+    edges: Vec<Edge>,
+    /// The instruction at which the node can end without having shown options or jumped to another node, if any.
+    dead_end: Option<usize>,
+    is_smart_variable: bool,
+    node_group: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Edge {
+    kind: EdgeKind,
+    /// The name of the destination node, or [`None`] if it is computed at runtime.
+    target: Option<String>,
+    instruction: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EdgeKind {
+    /// [`OpCode::RunNode`], i.e. `<<jump>>`
+    Jump,
+    /// [`OpCode::DetourToNode`], i.e. `<<detour>>`
+    Detour,
+}
+
+impl NodeSummary {
+    fn from_node(node: &Node) -> Self {
+        let opcodes: Vec<_> = node
+            .instructions
+            .iter()
+            .map(|instruction| OpCode::try_from(instruction.opcode).ok())
+            .collect();
+        let edges = opcodes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, opcode)| {
+                let kind = match opcode {
+                    Some(OpCode::RunNode) => EdgeKind::Jump,
+                    Some(OpCode::DetourToNode) => EdgeKind::Detour,
+                    _ => return None,
+                };
+                // Node names are pushed right before jumping, while expressions are evaluated with multiple instructions
+                let target = index
+                    .checked_sub(1)
+                    .map(|previous| &node.instructions[previous])
+                    .filter(|previous| previous.opcode == OpCode::PushString as i32)
+                    .and_then(|previous| String::try_from(previous.operands.first()?.clone()).ok());
+                Some(Edge {
+                    kind,
+                    target,
+                    instruction: index,
+                })
+            })
+            .collect();
+        Self {
+            edges,
+            dead_end: find_dead_end(node, &opcodes),
+            is_smart_variable: node.is_smart_variable(),
+            node_group: node.node_group().map(ToOwned::to_owned),
+        }
+    }
+}
+
+/// Follows all paths through the node and returns the instruction at which one of them completes the node
+/// without having shown options or jumped to another node.
+///
+/// Only the implicit [`OpCode::Stop`] at the end of the node counts, since explicit `<<return>>` and `<<stop>>` commands are intentional.
+fn find_dead_end(node: &Node, opcodes: &[Option<OpCode>]) -> Option<usize> {
+    let label = |instruction: &Instruction, operand: usize| {
+        let name = String::try_from(instruction.operands.get(operand)?.clone()).ok()?;
+        usize::try_from(*node.labels.get(&name)?).ok()
+    };
+    let option_destinations: Vec<_> = node
+        .instructions
+        .iter()
+        .zip(opcodes)
+        .filter_map(|(instruction, opcode)| match opcode {
+            Some(OpCode::AddOption) => label(instruction, 1),
+            Some(OpCode::AddSaliencyCandidate) => label(instruction, 2),
+            _ => None,
+        })
+        .collect();
+
+    // Each path is identified by its position and whether it has shown options yet
+    let mut visited = HashSet::new();
+    let mut pending = vec![(0, false)];
+    while let Some((index, has_shown_options)) = pending.pop() {
+        if !visited.insert((index, has_shown_options)) {
+            continue;
+        }
+        let Some(instruction) = node.instructions.get(index) else {
+            continue;
+        };
+        let next = index + 1;
+        let successors = match opcodes[index] {
+            Some(OpCode::JumpTo) => label(instruction, 0).into_iter().collect(),
+            Some(OpCode::JumpIfFalse) => label(instruction, 0).into_iter().chain([next]).collect(),
+            Some(OpCode::Jump) => option_destinations.clone(),
+            Some(OpCode::ShowOptions) => {
+                pending.push((next, true));
+                continue;
+            }
+            Some(OpCode::Stop) if next == node.instructions.len() => {
+                if !has_shown_options {
+                    return Some(index);
+                }
+                vec![]
+            }
+            Some(OpCode::Return | OpCode::Stop | OpCode::RunNode) | None => vec![],
+            Some(_) => vec![next],
+        };
+        pending.extend(
+            successors
+                .into_iter()
+                .map(|index| (index, has_shown_options)),
+        );
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnose(listing: &str, analyser: NodeGraphAnalyser) -> Vec<String> {
+        let mut context = Context::empty().add_analyser(Box::new(analyser));
+        context.diagnose_program(&Program::assemble(listing).unwrap());
+        let mut diagnoses: Vec<_> = context
+            .finish_analysis()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        diagnoses.sort();
+        diagnoses
+    }
+
+    #[test]
+    fn reports_unreachable_nodes_and_missing_destinations() {
+        let listing = r#"
+            .node "Start"
+                PUSH_STRING "Shop"
+                DETOUR_TO_NODE
+                PUSH_STRING "Missing"
+                RUN_NODE
+            .node "Shop"
+                STOP
+            .node "Secret"
+                PUSH_VARIABLE "$destination"
+                RUN_NODE
+        "#;
+        let positions = HashMap::from([(
+            3,
+            Some(Position {
+                line: 6,
+                character: 0,
+            }),
+        )]);
+        let analyser = NodeGraphAnalyser::new().with_instruction_positions("Start", positions);
+        assert_eq!(
+            vec![
+                "ERROR: Start: 7: Node \"Start\" jumps to node \"Missing\", which does not exist",
+                "WARNING: Secret: Node \"Secret\" cannot be reached from the entry nodes \"Start\"",
+                "WARNING: Secret: Node \"Secret\" jumps to a node that is only known at runtime, so the destination cannot be checked",
+            ],
+            diagnose(listing, analyser)
+        );
+    }
+
+    #[test]
+    fn follows_node_groups() {
+        let listing = r#"
+            .node "Start"
+                PUSH_STRING "Greeting"
+                RUN_NODE
+            .node "Greeting"
+                SELECT_SALIENCY_CANDIDATE
+                JUMP_IF_FALSE "empty"
+                POP
+                RUN_NODE
+            empty:
+                POP
+                STOP
+            .node "Greeting.1"
+            .header "$Yarn.Internal.NodeGroup" "Greeting"
+                RETURN
+        "#;
+        assert_eq!(
+            Vec::<String>::new(),
+            diagnose(
+                listing,
+                NodeGraphAnalyser::new().with_dead_end_severity(DiagnosisSeverity::Error)
+            )
+        );
+    }
+
+    #[test]
+    fn reports_dead_ends_when_configured() {
+        let listing = r#"
+            .node "Start"
+                PUSH_STRING "Helper"
+                DETOUR_TO_NODE
+                ADD_OPTION "line:a" "choice" 0 false
+                SHOW_OPTIONS
+                JUMP
+            choice:
+                PUSH_STRING "End"
+                RUN_NODE
+            .node "Helper"
+                STOP
+            .node "End"
+                RUN_LINE "line:b" 0
+                PUSH_BOOL true
+                JUMP_IF_FALSE "skip"
+                STOP
+            skip:
+                STOP
+        "#;
+        assert_eq!(
+            Vec::<String>::new(),
+            diagnose(listing, NodeGraphAnalyser::new())
+        );
+        assert_eq!(
+            vec![
+                "WARNING: End: Node \"End\" can end without showing options or jumping to another node, which ends the dialogue",
+            ],
+            diagnose(
+                listing,
+                NodeGraphAnalyser::new().with_dead_end_severity(DiagnosisSeverity::Warning)
+            )
+        );
+    }
+}
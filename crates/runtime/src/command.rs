@@ -4,7 +4,6 @@
 //! The original delegates command parsing to the Unity plugin, but we think it's foundational enough to do it directly in the runtime.
 
 use crate::markup::normalize;
-#[cfg(any(feature = "bevy", feature = "serde"))]
 use crate::prelude::*;
 use yarnspinner_core::prelude::YarnValue;
 
@@ -39,25 +38,23 @@ pub struct Command {
 }
 
 impl Command {
-    pub(crate) fn parse(input: String) -> Self {
-        assert!(!input.trim().is_empty(), "Failed to parse the command \"{input}\" because it is composed entirely of whitespace. \
-            Help: You might have passed an expression that evaluates to whitespace, e.g. `{{0}} {{\"  \"}}`. \
-            If you think this is a bug, please report it at https://github.com/YarnSpinnerTool/YarnSpinner-Rust/issues/new");
-
+    /// Parses the command text into its name and parameters.
+    ///
+    /// ## Errors
+    ///
+    /// Returns [`DialogueError::MalformedCommand`] if the command text is composed entirely of whitespace.
+    pub(crate) fn parse(input: String) -> crate::Result<Self> {
         let mut components = split_command_text(&input);
-        assert!(
-            !components.is_empty(),
-            "Parsing the command \"{}\" resulted in an empty list of components. \
-            This is a bug. Please report it at https://github.com/YarnSpinnerTool/YarnSpinner-Rust/issues/new",
-            input
-        );
+        if components.is_empty() {
+            return Err(DialogueError::MalformedCommand { command: input });
+        }
         let name = components.remove(0);
         let parameters = components.into_iter().map(YarnValue::from).collect();
-        Self {
+        Ok(Self {
             name,
             parameters,
             raw: input,
-        }
+        })
     }
 }
 
@@ -233,9 +230,21 @@ mod tests {
                 },
             ),
         ] {
-            let parsed_command = Command::parse(input.to_string());
+            let parsed_command = Command::parse(input.to_string()).unwrap();
 
             assert_eq!(expected_command, parsed_command);
         }
     }
+
+    #[test]
+    fn rejects_whitespace_command() {
+        for input in ["", " ", "  \t  "] {
+            let result = Command::parse(input.to_string());
+
+            assert!(matches!(
+                result,
+                Err(DialogueError::MalformedCommand { command }) if command == input
+            ));
+        }
+    }
 }
@@ -0,0 +1,322 @@
+//! Records which content of a dialogue was seen, so that play tests can reveal content nobody has seen yet.
+
+use crate::prelude::*;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Which lines, options and nodes a [`Dialogue`] delivered, and how often. Enable recording with [`Dialogue::set_coverage_enabled`].
+///
+/// Coverage from multiple sessions, e.g. of different testers, can be combined with [`Coverage::merge`].
+/// With the `serde` feature, it can be saved and loaded in any format supported by serde, e.g. as JSON with `serde_json`.
+/// [`Coverage::to_lcov`] exports it in the format of `lcov` tracefiles, which many editors and CI services can display.
+///
+/// ## Example
+///
+/// ```
+/// # use yarnspinner_runtime::prelude::*;
+/// # let mut dialogue = Dialogue::new(Box::new(MemoryVariableStorage::new()), Box::new(StringTableTextProvider::new()));
+/// # let coverage_of_last_session = Coverage::default();
+/// dialogue.set_coverage_enabled(true);
+/// // Run the dialogue...
+/// let mut coverage = dialogue.coverage().cloned().unwrap_or_default();
+/// coverage.merge(&coverage_of_last_session);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Coverage {
+    /// How often each line was delivered via [`DialogueEvent::Line`].
+    pub lines: BTreeMap<LineId, usize>,
+
+    /// How often each option was shown via [`DialogueEvent::Options`], keyed by the ID of the option's line.
+    /// Only available options are counted, since most games don't show the others.
+    pub options_shown: BTreeMap<LineId, usize>,
+
+    /// How often each option was selected via [`Dialogue::set_selected_option`], keyed by the ID of the option's line.
+    pub options_selected: BTreeMap<LineId, usize>,
+
+    /// How often each node was entered. Returning to a node after a `<<detour>>` does not count as entering it again.
+    pub nodes: BTreeMap<String, usize>,
+}
+
+impl Coverage {
+    /// Adds the counts of another [`Coverage`] to this one.
+    pub fn merge(&mut self, other: &Coverage) {
+        merge_counts(&mut self.lines, &other.lines);
+        merge_counts(&mut self.options_shown, &other.options_shown);
+        merge_counts(&mut self.options_selected, &other.options_selected);
+        merge_counts(&mut self.nodes, &other.nodes);
+    }
+
+    /// Returns `true` if the line was delivered, or shown as an option, at least once.
+    pub fn is_line_seen(&self, line_id: &LineId) -> bool {
+        self.line_count(line_id) > 0
+    }
+
+    /// How often the line was delivered, plus how often it was shown as an option.
+    pub fn line_count(&self, line_id: &LineId) -> usize {
+        self.lines.get(line_id).copied().unwrap_or_default()
+            + self.options_shown.get(line_id).copied().unwrap_or_default()
+    }
+
+    /// Exports the coverage as an `lcov` tracefile. Since the runtime does not know where lines come from,
+    /// their locations must be passed in, usually from the string table the compiler produced:
+    ///
+    /// ```
+    /// # use yarnspinner_runtime::prelude::*;
+    /// # use yarnspinner_core::prelude::*;
+    /// # use std::collections::HashMap;
+    /// # struct StringInfo { file_name: String, node_name: String, line_number: usize }
+    /// # struct Compilation { string_table: HashMap<LineId, StringInfo> }
+    /// # let compilation = Compilation { string_table: HashMap::new() };
+    /// # let coverage = Coverage::default();
+    /// let lcov = coverage.to_lcov(compilation.string_table.iter().map(|(id, info)| {
+    ///     (id.clone(), LineLocation::new(&info.file_name, &info.node_name, info.line_number))
+    /// }));
+    /// ```
+    ///
+    /// Every line is reported with its [`Coverage::line_count`], and every node as a function starting at its first line.
+    /// Lines without a location are left out, while lines that were never seen are reported with a count of `0`.
+    pub fn to_lcov(&self, locations: impl IntoIterator<Item = (LineId, LineLocation)>) -> String {
+        let mut files: BTreeMap<String, Vec<(LineId, LineLocation)>> = BTreeMap::new();
+        for (line_id, location) in locations {
+            files
+                .entry(location.file_name.clone())
+                .or_default()
+                .push((line_id, location));
+        }
+
+        let mut lcov = String::new();
+        for (file_name, mut lines) in files {
+            lines.sort_by_key(|(_, location)| location.line_number);
+            let mut nodes: BTreeMap<&str, usize> = BTreeMap::new();
+            for (_, location) in &lines {
+                nodes
+                    .entry(&location.node_name)
+                    .or_insert(location.line_number);
+            }
+
+            // Writing to a `String` never fails
+            let _ = writeln!(lcov, "TN:\nSF:{file_name}");
+            for (node_name, line_number) in &nodes {
+                let _ = writeln!(lcov, "FN:{line_number},{node_name}");
+            }
+            for node_name in nodes.keys() {
+                let count = self.nodes.get(*node_name).copied().unwrap_or_default();
+                let _ = writeln!(lcov, "FNDA:{count},{node_name}");
+            }
+            let nodes_hit = nodes
+                .keys()
+                .filter(|node_name| self.nodes.get(**node_name).is_some_and(|count| *count > 0))
+                .count();
+            let _ = writeln!(lcov, "FNF:{}\nFNH:{nodes_hit}", nodes.len());
+            for (line_id, location) in &lines {
+                let _ = writeln!(
+                    lcov,
+                    "DA:{},{}",
+                    location.line_number,
+                    self.line_count(line_id)
+                );
+            }
+            let lines_hit = lines
+                .iter()
+                .filter(|(line_id, _)| self.is_line_seen(line_id))
+                .count();
+            let _ = writeln!(lcov, "LF:{}\nLH:{lines_hit}\nend_of_record", lines.len());
+        }
+        lcov
+    }
+
+    pub(crate) fn record_line(&mut self, line_id: &LineId) {
+        *self.lines.entry(line_id.clone()).or_default() += 1;
+    }
+
+    pub(crate) fn record_options_shown(&mut self, options: &[DialogueOption]) {
+        for option in options.iter().filter(|option| option.is_available) {
+            *self
+                .options_shown
+                .entry(option.line.id.clone())
+                .or_default() += 1;
+        }
+    }
+
+    pub(crate) fn record_option_selected(&mut self, option: &DialogueOption) {
+        *self
+            .options_selected
+            .entry(option.line.id.clone())
+            .or_default() += 1;
+    }
+
+    pub(crate) fn record_node(&mut self, node_name: &str) {
+        *self.nodes.entry(node_name.to_owned()).or_default() += 1;
+    }
+}
+
+fn merge_counts<K: Ord + Clone>(counts: &mut BTreeMap<K, usize>, other: &BTreeMap<K, usize>) {
+    for (key, count) in other {
+        *counts.entry(key.clone()).or_default() += count;
+    }
+}
+
+/// Where a line is written in the Yarn files, as needed by [`Coverage::to_lcov`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LineLocation {
+    /// The name of the file containing the line.
+    pub file_name: String,
+    /// The name of the node containing the line.
+    pub node_name: String,
+    /// The 1-based line number within the file.
+    pub line_number: usize,
+}
+
+impl LineLocation {
+    /// Creates a new [`LineLocation`].
+    pub fn new(
+        file_name: impl Into<String>,
+        node_name: impl Into<String>,
+        line_number: usize,
+    ) -> Self {
+        Self {
+            file_name: file_name.into(),
+            node_name: node_name.into(),
+            line_number,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dialogue() -> Dialogue {
+        let program = Program::assemble(
+            r#"
+            .node "Start"
+                RUN_LINE "line:hello" 0
+                ADD_OPTION "line:yes" "yes" 0 false
+                PUSH_BOOL false
+                ADD_OPTION "line:no" "no" 0 true
+                SHOW_OPTIONS
+                JUMP
+            yes:
+                PUSH_STRING "Yes"
+                RUN_NODE
+            no:
+                RUN_LINE "line:never" 0
+                STOP
+            .node "Yes"
+                RUN_LINE "line:great" 0
+                STOP
+            "#,
+        )
+        .unwrap();
+        let mut text_provider = StringTableTextProvider::new();
+        text_provider.extend_base_language(
+            [
+                "line:hello",
+                "line:yes",
+                "line:no",
+                "line:never",
+                "line:great",
+            ]
+            .into_iter()
+            .map(|id| (id.into(), String::new()))
+            .collect(),
+        );
+        let mut dialogue = Dialogue::new(
+            Box::new(MemoryVariableStorage::new()),
+            Box::new(text_provider),
+        );
+        dialogue.add_program(program);
+        dialogue
+    }
+
+    fn play(dialogue: &mut Dialogue) {
+        dialogue.set_node("Start").unwrap();
+        loop {
+            for event in dialogue.continue_().unwrap() {
+                match event {
+                    DialogueEvent::Options(_) => {
+                        dialogue.set_selected_option(OptionId(0)).unwrap();
+                    }
+                    DialogueEvent::DialogueComplete => return,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn counts<K: Ord>(entries: impl IntoIterator<Item = (K, usize)>) -> BTreeMap<K, usize> {
+        entries.into_iter().collect()
+    }
+
+    #[test]
+    fn records_nothing_by_default() {
+        let mut dialogue = dialogue();
+        play(&mut dialogue);
+        assert_eq!(None, dialogue.coverage());
+    }
+
+    #[test]
+    fn records_delivered_content() {
+        let mut dialogue = dialogue();
+        dialogue.set_coverage_enabled(true);
+        play(&mut dialogue);
+        play(&mut dialogue);
+
+        let coverage = dialogue.coverage().unwrap();
+        assert_eq!(
+            counts([("line:great".into(), 2), ("line:hello".into(), 2)]),
+            coverage.lines
+        );
+        assert_eq!(counts([("line:yes".into(), 2)]), coverage.options_shown);
+        assert_eq!(counts([("line:yes".into(), 2)]), coverage.options_selected);
+        assert_eq!(
+            counts([("Start".to_owned(), 2), ("Yes".to_owned(), 2)]),
+            coverage.nodes
+        );
+
+        let mut merged = coverage.clone();
+        merged.merge(coverage);
+        assert_eq!(Some(&4), merged.nodes.get("Yes"));
+        assert!(!merged.is_line_seen(&"line:no".into()));
+    }
+
+    #[test]
+    fn exports_lcov() {
+        let mut dialogue = dialogue();
+        dialogue.set_coverage_enabled(true);
+        play(&mut dialogue);
+        let locations = [
+            ("line:hello", "Start", 3),
+            ("line:yes", "Start", 4),
+            ("line:no", "Start", 6),
+            ("line:never", "Start", 7),
+            ("line:great", "Yes", 11),
+        ]
+        .map(|(id, node_name, line_number)| {
+            (
+                id.into(),
+                LineLocation::new("test.yarn", node_name, line_number),
+            )
+        });
+        assert_eq!(
+            "TN:\n\
+             SF:test.yarn\n\
+             FN:3,Start\n\
+             FN:11,Yes\n\
+             FNDA:1,Start\n\
+             FNDA:1,Yes\n\
+             FNF:2\n\
+             FNH:2\n\
+             DA:3,1\n\
+             DA:4,1\n\
+             DA:6,0\n\
+             DA:7,0\n\
+             DA:11,1\n\
+             LF:5\n\
+             LH:3\n\
+             end_of_record\n",
+            dialogue.coverage().unwrap().to_lcov(locations)
+        );
+    }
+}
@@ -0,0 +1,524 @@
+//! Allows pausing a dialogue at breakpoints and stepping through it, e.g. from an editor.
+
+use crate::prelude::*;
+use std::collections::HashMap;
+use yarnspinner_core::prelude::*;
+
+/// Runs a [`Dialogue`] under the control of a debugger: execution pauses at [`Breakpoint`]s, can advance by single source lines
+/// or single instructions, and the value stack, current node and variables can be inspected whenever it is paused.
+///
+/// Unlike [`Dialogue::continue_`], running the debugger does not stop after each line or command. Instead, all [`DialogueEvent`]s
+/// that occurred until the next pause are returned together in a [`DebugPause`]. Execution always pauses when options are presented,
+/// so that one can be selected with [`Debugger::select_option`].
+///
+/// Breakpoints on lines need to know where each instruction comes from, which is passed with [`Debugger::add_node_source`].
+///
+/// ## Example
+///
+/// ```no_run
+/// # use yarnspinner_runtime::prelude::*;
+/// # use yarnspinner_core::prelude::*;
+/// # let dialogue = Dialogue::new(Box::new(MemoryVariableStorage::new()), Box::new(StringTableTextProvider::new()));
+/// let mut debugger = Debugger::new(dialogue);
+/// debugger.add_breakpoint(Breakpoint::NodeEntry { node_name: "Shop".to_owned() });
+/// debugger.set_node("Start")?;
+/// let pause = debugger.resume()?;
+/// if let PauseReason::Breakpoint(_) = pause.reason {
+///     println!("Entered the shop with {:?} gold", debugger.variables().get("$gold"));
+/// }
+/// # Ok::<(), DialogueError>(())
+/// ```
+#[derive(Debug)]
+pub struct Debugger {
+    dialogue: Dialogue,
+    sources: HashMap<String, NodeSource>,
+    breakpoints: Vec<Breakpoint>,
+    /// Where execution paused the last time, so that resuming does not immediately hit the same breakpoint again.
+    paused_at: Option<(String, usize)>,
+}
+
+#[derive(Debug, Clone)]
+struct NodeSource {
+    file_name: String,
+    positions: HashMap<usize, Option<Position>>,
+}
+
+/// A location at which a [`Debugger`] pauses execution.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Breakpoint {
+    /// Pauses before running the first instruction that comes from the given line, unless the previous instruction came from the same line.
+    Line {
+        /// The name of the file, as passed to [`Debugger::add_node_source`].
+        file_name: String,
+        /// The zero-indexed line in the file, like in [`Position::line`].
+        line: usize,
+    },
+    /// Pauses before running the first instruction of a node, whenever the node is entered.
+    NodeEntry {
+        /// The name of the node.
+        node_name: String,
+    },
+}
+
+/// Where a [`Debugger`] is currently paused, or a node that is waiting for a `<<detour>>` to return.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DebugLocation {
+    /// The name of the node.
+    pub node_name: String,
+    /// The index of the instruction in the node that runs next.
+    pub instruction: usize,
+    /// The file containing the node, if it was passed to [`Debugger::add_node_source`].
+    pub file_name: Option<String>,
+    /// The position of the instruction in the file, if known.
+    pub position: Option<Position>,
+}
+
+/// The result of running a [`Debugger`] until it pauses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugPause {
+    /// Why execution paused.
+    pub reason: PauseReason,
+    /// All events that occurred since execution was resumed, in order.
+    pub events: Vec<DialogueEvent>,
+}
+
+/// Why a [`Debugger`] paused execution.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PauseReason {
+    /// Execution reached the given breakpoint.
+    Breakpoint(Breakpoint),
+    /// A step requested with [`Debugger::step_line`] or [`Debugger::step_instruction`] completed.
+    Step,
+    /// Options were presented. Select one with [`Debugger::select_option`] before resuming.
+    WaitingForOptionSelection,
+    /// The dialogue completed. Start it again with [`Debugger::set_node`].
+    DialogueComplete,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StepMode {
+    Resume,
+    Line,
+    Instruction,
+}
+
+impl Debugger {
+    /// Creates a debugger for the given [`Dialogue`] without any breakpoints.
+    pub fn new(dialogue: Dialogue) -> Self {
+        Self {
+            dialogue,
+            sources: HashMap::new(),
+            breakpoints: Vec::new(),
+            paused_at: None,
+        }
+    }
+
+    /// Sets the file a node comes from and the source positions of its instructions, which are needed for [`Breakpoint::Line`] and [`Debugger::step_line`].
+    /// When compiling with `yarnspinner_compiler`, these are the `file_name` and `line_positions` of the node's `DebugInfo`.
+    pub fn add_node_source(
+        &mut self,
+        node_name: impl Into<String>,
+        file_name: impl Into<String>,
+        positions: HashMap<usize, Option<Position>>,
+    ) -> &mut Self {
+        let source = NodeSource {
+            file_name: file_name.into(),
+            positions,
+        };
+        self.sources.insert(node_name.into(), source);
+        self
+    }
+
+    /// Gets the [`Dialogue`] being debugged.
+    pub fn dialogue(&self) -> &Dialogue {
+        &self.dialogue
+    }
+
+    /// Mutably gets the [`Dialogue`] being debugged. Running it directly bypasses all breakpoints.
+    pub fn dialogue_mut(&mut self) -> &mut Dialogue {
+        &mut self.dialogue
+    }
+
+    /// Stops debugging and returns the [`Dialogue`].
+    pub fn into_dialogue(self) -> Dialogue {
+        self.dialogue
+    }
+
+    /// Starts running the given node, like [`Dialogue::set_node`]. A [`Breakpoint::NodeEntry`] for the node is hit on the next resume or step.
+    pub fn set_node(&mut self, node_name: impl Into<String>) -> crate::Result<&mut Self> {
+        self.dialogue.set_node(node_name)?;
+        self.paused_at = None;
+        Ok(self)
+    }
+
+    /// Selects an option after execution paused with [`PauseReason::WaitingForOptionSelection`], like [`Dialogue::set_selected_option`].
+    pub fn select_option(&mut self, option: OptionId) -> crate::Result<&mut Self> {
+        self.dialogue.set_selected_option(option)?;
+        Ok(self)
+    }
+
+    /// Adds a breakpoint and returns it as it was added, or `None` if it can never be hit, in which case it is not added.
+    ///
+    /// A [`Breakpoint::Line`] on a line without any instructions, e.g. an empty line or a comment, is moved to the next line of the same node that has instructions.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> Option<Breakpoint> {
+        let breakpoint = self.resolve_breakpoint(breakpoint)?;
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint.clone());
+        }
+        Some(breakpoint)
+    }
+
+    /// Removes a breakpoint. Returns `false` if there was no such breakpoint.
+    pub fn remove_breakpoint(&mut self, breakpoint: &Breakpoint) -> bool {
+        let count = self.breakpoints.len();
+        self.breakpoints.retain(|existing| existing != breakpoint);
+        self.breakpoints.len() != count
+    }
+
+    /// Removes all breakpoints.
+    pub fn clear_breakpoints(&mut self) -> &mut Self {
+        self.breakpoints.clear();
+        self
+    }
+
+    /// Gets all breakpoints, in the order they were added.
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// Runs until a breakpoint is hit, options are presented or the dialogue completes.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if no node is running or the [`Dialogue`] is waiting for an option to be selected,
+    /// or if the [`Dialogue`] returns an error while running.
+    pub fn resume(&mut self) -> crate::Result<DebugPause> {
+        self.run(StepMode::Resume)
+    }
+
+    /// Runs until the next instruction comes from a different source line than the current one.
+    /// Pauses early if a breakpoint is hit, options are presented or the dialogue completes.
+    ///
+    /// ## Errors
+    ///
+    /// See [`Debugger::resume`].
+    pub fn step_line(&mut self) -> crate::Result<DebugPause> {
+        self.run(StepMode::Line)
+    }
+
+    /// Runs a single instruction.
+    ///
+    /// ## Errors
+    ///
+    /// See [`Debugger::resume`].
+    pub fn step_instruction(&mut self) -> crate::Result<DebugPause> {
+        self.run(StepMode::Instruction)
+    }
+
+    /// Where execution is currently paused, or `None` if no node is running.
+    pub fn location(&self) -> Option<DebugLocation> {
+        let node_name = self.dialogue.current_node()?;
+        Some(self.debug_location(node_name, self.dialogue.vm().program_counter()))
+    }
+
+    /// The current location followed by the nodes waiting for a `<<detour>>` to return, innermost first.
+    pub fn call_stack(&self) -> Vec<DebugLocation> {
+        let detours = self
+            .dialogue
+            .vm()
+            .detour_frames()
+            .map(|(node_name, instruction)| self.debug_location(node_name.to_owned(), instruction))
+            .rev();
+        self.location().into_iter().chain(detours).collect()
+    }
+
+    /// The instruction that runs next, if any.
+    pub fn current_instruction(&self) -> Option<&Instruction> {
+        self.dialogue.vm().current_instruction()
+    }
+
+    /// The values on the stack of the virtual machine, from bottom to top.
+    pub fn stack(&self) -> Vec<YarnValue> {
+        self.dialogue.vm().stack_values()
+    }
+
+    /// All variables in the [`VariableStorage`], including the ones used internally by Yarn Spinner.
+    pub fn variables(&self) -> HashMap<String, YarnValue> {
+        self.dialogue.variable_storage().variables()
+    }
+
+    /// The values of the `<<local>>` variables of the current node.
+    pub fn locals(&self) -> &HashMap<String, YarnValue> {
+        self.dialogue.vm().locals()
+    }
+
+    fn run(&mut self, mode: StepMode) -> crate::Result<DebugPause> {
+        let start_line = self.location().and_then(|location| source_line(&location));
+        let mut previous_line = None;
+        let mut events = Vec::new();
+        let mut is_first_instruction = true;
+        let reason = loop {
+            if !is_first_instruction {
+                if self.dialogue.is_waiting_for_option_selection() {
+                    break PauseReason::WaitingForOptionSelection;
+                }
+                if !self.dialogue.is_active() {
+                    break PauseReason::DialogueComplete;
+                }
+            }
+            let location = self.location();
+            let line = location.as_ref().and_then(source_line);
+            let was_paused_here = is_first_instruction
+                && location
+                    .as_ref()
+                    .is_some_and(|location| self.is_paused_at(location));
+            if let Some(location) = location.as_ref().filter(|_| !was_paused_here) {
+                if let Some(breakpoint) = self.breakpoint_at(location, previous_line.as_ref()) {
+                    break PauseReason::Breakpoint(breakpoint.clone());
+                }
+            }
+            if !is_first_instruction {
+                match mode {
+                    StepMode::Instruction => break PauseReason::Step,
+                    StepMode::Line if line.is_some() && line != start_line => {
+                        break PauseReason::Step
+                    }
+                    StepMode::Line | StepMode::Resume => {}
+                }
+            }
+
+            events.extend(self.dialogue.vm_mut().step_instruction()?);
+            if line.is_some() {
+                previous_line = line;
+            }
+            is_first_instruction = false;
+        };
+        self.paused_at = self
+            .location()
+            .map(|location| (location.node_name, location.instruction));
+        Ok(DebugPause { reason, events })
+    }
+
+    fn is_paused_at(&self, location: &DebugLocation) -> bool {
+        self.paused_at
+            .as_ref()
+            .is_some_and(|(node_name, instruction)| {
+                *node_name == location.node_name && *instruction == location.instruction
+            })
+    }
+
+    fn breakpoint_at(
+        &self,
+        location: &DebugLocation,
+        previous_line: Option<&(String, usize)>,
+    ) -> Option<&Breakpoint> {
+        let line = source_line(location);
+        self.breakpoints.iter().find(|breakpoint| match breakpoint {
+            Breakpoint::NodeEntry { node_name } => {
+                *node_name == location.node_name && location.instruction == 0
+            }
+            Breakpoint::Line {
+                file_name,
+                line: breakpoint_line,
+            } => line.as_ref().is_some_and(|line| {
+                line.0 == *file_name && line.1 == *breakpoint_line && previous_line != Some(line)
+            }),
+        })
+    }
+
+    fn resolve_breakpoint(&self, breakpoint: Breakpoint) -> Option<Breakpoint> {
+        match breakpoint {
+            Breakpoint::NodeEntry { node_name } => self
+                .dialogue
+                .node_exists(&node_name)
+                .then_some(Breakpoint::NodeEntry { node_name }),
+            Breakpoint::Line { file_name, line } => {
+                let resolved_line = self
+                    .sources
+                    .values()
+                    .filter(|source| source.file_name == file_name)
+                    .map(|source| source.lines().collect::<Vec<_>>())
+                    .filter(|lines| lines.first().is_some_and(|first| *first <= line))
+                    .filter_map(|lines| lines.into_iter().find(|node_line| *node_line >= line))
+                    .min()?;
+                Some(Breakpoint::Line {
+                    file_name,
+                    line: resolved_line,
+                })
+            }
+        }
+    }
+
+    fn debug_location(&self, node_name: String, instruction: usize) -> DebugLocation {
+        let source = self.sources.get(&node_name);
+        DebugLocation {
+            file_name: source.map(|source| source.file_name.clone()),
+            position: source
+                .and_then(|source| source.positions.get(&instruction).cloned().flatten()),
+            node_name,
+            instruction,
+        }
+    }
+}
+
+impl NodeSource {
+    /// The lines that have instructions, sorted and without duplicates.
+    fn lines(&self) -> impl Iterator<Item = usize> {
+        let mut lines: Vec<_> = self
+            .positions
+            .values()
+            .flatten()
+            .map(|position| position.line)
+            .collect();
+        lines.sort_unstable();
+        lines.dedup();
+        lines.into_iter()
+    }
+}
+
+fn source_line(location: &DebugLocation) -> Option<(String, usize)> {
+    Some((
+        location.file_name.clone()?,
+        location.position.as_ref()?.line,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Equivalent to
+    /// ```text
+    /// 0 title: Start
+    /// 1 ---
+    /// 2 <<set $gold to 10>>
+    /// 3 Hello
+    /// 4
+    /// 5 -> Shop
+    /// 6     <<jump Shop>>
+    /// 7 -> Leave
+    /// 8 ===
+    /// ```
+    fn debugger() -> Debugger {
+        let program = Program::assemble(
+            r#"
+            .node "Start"
+                PUSH_FLOAT 10
+                STORE_VARIABLE "$gold"
+                POP
+                RUN_LINE "line:hello" 0
+                ADD_OPTION "line:shop" "shop" 0 false
+                ADD_OPTION "line:leave" "leave" 0 false
+                SHOW_OPTIONS
+                JUMP
+            shop:
+                PUSH_STRING "Shop"
+                RUN_NODE
+            leave:
+                STOP
+            .node "Shop"
+                RUN_LINE "line:welcome" 0
+                STOP
+            "#,
+        )
+        .unwrap();
+        let mut text_provider = StringTableTextProvider::new();
+        text_provider.extend_base_language(
+            ["line:hello", "line:shop", "line:leave", "line:welcome"]
+                .into_iter()
+                .map(|id| (id.into(), String::new()))
+                .collect(),
+        );
+        let mut dialogue = Dialogue::new(
+            Box::new(MemoryVariableStorage::new()),
+            Box::new(text_provider),
+        );
+        dialogue.add_program(program);
+
+        let line_of_instruction = [2, 2, 2, 3, 5, 7, 7, 7, 6, 6, 8];
+        let positions = line_of_instruction
+            .into_iter()
+            .enumerate()
+            .map(|(instruction, line)| (instruction, Some(Position { line, character: 0 })))
+            .collect();
+        let mut debugger = Debugger::new(dialogue);
+        debugger.add_node_source("Start", "start.yarn", positions);
+        debugger.set_node("Start").unwrap();
+        debugger
+    }
+
+    fn line_breakpoint(line: usize) -> Breakpoint {
+        Breakpoint::Line {
+            file_name: "start.yarn".to_owned(),
+            line,
+        }
+    }
+
+    #[test]
+    fn pauses_at_breakpoints() {
+        let mut debugger = debugger();
+        assert_eq!(
+            Some(line_breakpoint(5)),
+            debugger.add_breakpoint(line_breakpoint(4))
+        );
+        assert_eq!(None, debugger.add_breakpoint(line_breakpoint(9)));
+        let shop = Breakpoint::NodeEntry {
+            node_name: "Shop".to_owned(),
+        };
+        assert_eq!(Some(shop.clone()), debugger.add_breakpoint(shop.clone()));
+
+        let pause = debugger.resume().unwrap();
+        assert_eq!(PauseReason::Breakpoint(line_breakpoint(5)), pause.reason);
+        assert_eq!(
+            vec![
+                DialogueEvent::NodeStart("Start".to_owned()),
+                DialogueEvent::Line(hello_line())
+            ],
+            pause.events
+        );
+        assert_eq!(
+            Some(&YarnValue::from(10.0)),
+            debugger.variables().get("$gold")
+        );
+
+        let pause = debugger.resume().unwrap();
+        assert_eq!(PauseReason::WaitingForOptionSelection, pause.reason);
+        debugger.select_option(OptionId(0)).unwrap();
+        let pause = debugger.resume().unwrap();
+        assert_eq!(PauseReason::Breakpoint(shop), pause.reason);
+        assert_eq!("Shop", debugger.location().unwrap().node_name);
+
+        let pause = debugger.resume().unwrap();
+        assert_eq!(PauseReason::DialogueComplete, pause.reason);
+    }
+
+    #[test]
+    fn steps_through_lines_and_instructions() {
+        let mut debugger = debugger();
+        let pause = debugger.step_instruction().unwrap();
+        assert_eq!(PauseReason::Step, pause.reason);
+        assert_eq!(vec![YarnValue::from(10.0)], debugger.stack());
+        assert_eq!(1, debugger.location().unwrap().instruction);
+
+        debugger.step_line().unwrap();
+        let location = debugger.location().unwrap();
+        assert_eq!(3, location.instruction);
+        assert_eq!(3, location.position.unwrap().line);
+        assert!(debugger.stack().is_empty());
+
+        let pause = debugger.step_line().unwrap();
+        assert_eq!(vec![DialogueEvent::Line(hello_line())], pause.events);
+        assert_eq!(
+            Some(5),
+            debugger.location().unwrap().position.map(|p| p.line)
+        );
+    }
+
+    fn hello_line() -> Line {
+        Line {
+            id: "line:hello".into(),
+            text: String::new(),
+            attributes: Vec::new(),
+        }
+    }
+}
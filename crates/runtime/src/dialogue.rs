@@ -1,6 +1,8 @@
 //! Adapted from <https://github.com/YarnSpinnerTool/YarnSpinner/blob/da39c7195107d8211f21c263e4084f773b84eaff/YarnSpinner/Dialogue.cs>
 
-use crate::markup::{DialogueTextProcessor, LineParser, MarkupParseError};
+use crate::markup::{
+    AttributeMarkerProcessor, DialogueTextProcessor, LineParser, MarkupParseError,
+};
 use crate::prelude::*;
 use log::error;
 use std::collections::HashMap;
@@ -44,6 +46,25 @@ pub enum DialogueError {
         function_name: String,
         library: Library,
     },
+    FunctionError {
+        function_name: String,
+        source: YarnFnError,
+    },
+    FunctionArityMismatch {
+        function_name: String,
+        expected: usize,
+        actual: usize,
+    },
+    MissingInitialValue {
+        variable_name: String,
+    },
+    StackUnderflow,
+    MalformedCommand {
+        command: String,
+    },
+    InvalidProgram {
+        reason: String,
+    },
 }
 
 impl Error for DialogueError {
@@ -52,6 +73,7 @@ impl Error for DialogueError {
         match self {
             MarkupParseError(e) => e.source(),
             VariableStorageError(e) => e.source(),
+            FunctionError { source, .. } => Some(source.as_ref()),
             _ => None,
         }
     }
@@ -71,6 +93,13 @@ impl Display for DialogueError {
             InvalidNode { node_name } => write!(f, "No node named \"{node_name}\" has been loaded."),
             VariableStorageError(e) => Display::fmt(e, f),
             FunctionNotFound { function_name, library } => write!(f, "Function \"{function_name}\" not found in library: {library}"),
+            FunctionError { function_name, .. } => write!(f, "Function \"{function_name}\" returned an error"),
+            FunctionArityMismatch { function_name, expected, actual } => write!(f, "Function \"{function_name}\" expected {expected} parameters, but received {actual}"),
+            MissingInitialValue { variable_name } => write!(f, "The loaded program does not contain an initial value for the variable {variable_name}"),
+            StackUnderflow => f.write_str("Tried to read a value from the stack, but the stack was empty. The program is most likely corrupted."),
+            MalformedCommand { command } => write!(f, "Failed to parse the command \"{command}\" because it is composed entirely of whitespace. \
+                Help: You might have passed an expression that evaluates to whitespace, e.g. `{{0}} {{\"  \"}}`."),
+            InvalidProgram { reason } => write!(f, "The loaded program is invalid: {reason}. To fix this error, re-compile the original source code with the latest compiler."),
         }
     }
 }
@@ -206,6 +235,66 @@ impl Dialogue {
         self
     }
 
+    /// Gets whether the [`Dialogue`] records which lines, options and nodes it delivers in its [`Coverage`].
+    /// The default is `false`.
+    #[must_use]
+    pub fn coverage_enabled(&self) -> bool {
+        self.vm.coverage.is_some()
+    }
+
+    /// Sets whether the [`Dialogue`] records which lines, options and nodes it delivers in its [`Coverage`].
+    /// Enabling it while it is already enabled keeps the recorded coverage, while disabling it discards the recorded coverage.
+    pub fn set_coverage_enabled(&mut self, enabled: bool) -> &mut Self {
+        match (enabled, &self.vm.coverage) {
+            (true, None) => self.vm.coverage = Some(Coverage::default()),
+            (false, _) => self.vm.coverage = None,
+            (true, Some(_)) => {}
+        }
+        self
+    }
+
+    /// Gets the [`Coverage`] recorded since it was enabled with [`Dialogue::set_coverage_enabled`], or `None` if it is disabled.
+    #[must_use]
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.vm.coverage.as_ref()
+    }
+
+    /// Mutably gets the [`Coverage`] recorded since it was enabled with [`Dialogue::set_coverage_enabled`], or `None` if it is disabled.
+    /// Use this to e.g. [`Coverage::merge`] the coverage of a previous session into the current one.
+    pub fn coverage_mut(&mut self) -> Option<&mut Coverage> {
+        self.vm.coverage.as_mut()
+    }
+
+    /// Registers an [`AttributeMarkerProcessor`] that produces replacement text for markers with the name `attribute_name`,
+    /// e.g. `playername` for `[playername/]`. Registering a processor for a name that already has one replaces the old processor,
+    /// including the built-in ones for `select`, `plural`, `ordinal` and `nomarkup`.
+    ///
+    /// The processor is immediately informed of the current language code and will be informed of any change done via [`Dialogue::set_language_code`].
+    pub fn add_marker_processor(
+        &mut self,
+        attribute_name: impl Into<String>,
+        processor: Box<dyn AttributeMarkerProcessor>,
+    ) -> &mut Self {
+        self.vm
+            .add_marker_processor(attribute_name.into(), processor);
+        self
+    }
+
+    /// Gets the [`SaliencyStrategy`] that picks the content of line groups and node groups.
+    pub fn saliency_strategy(&self) -> &dyn SaliencyStrategy {
+        self.vm.saliency_strategy()
+    }
+
+    /// Sets the [`SaliencyStrategy`] that picks the content of line groups and node groups.
+    /// The default is [`BestLeastRecentlySeenSaliencyStrategy`].
+    pub fn set_saliency_strategy(
+        &mut self,
+        saliency_strategy: Box<dyn SaliencyStrategy>,
+    ) -> &mut Self {
+        self.vm.set_saliency_strategy(saliency_strategy);
+        self
+    }
+
     /// Gets the currently registered [`TextProvider`].
     pub fn text_provider(&self) -> &dyn TextProvider {
         self.vm.text_provider()
@@ -268,14 +357,19 @@ impl Dialogue {
     }
 
     /// Sets or replaces the [`Dialogue`]'s current [`Program`]. The program is replaced, all current state is reset.
+    ///
+    /// In debug builds, the program is checked with [`Program::verify_without_library`] and any problems are logged as errors.
     pub fn replace_program(&mut self, program: Program) -> &mut Self {
         self.vm.program.replace(program.clone());
         self.vm.reset_state();
         self.extend_variable_storage_from(&program);
+        self.verify_program();
         self
     }
 
     /// Merges the currently set [`Program`] with the given one. If there is no program set, the given one is set.
+    ///
+    /// In debug builds, the merged program is checked with [`Program::verify_without_library`] like in [`Dialogue::replace_program`].
     pub fn add_program(&mut self, program: Program) -> &mut Self {
         if let Some(existing_program) = self.vm.program.as_mut() {
             *existing_program =
@@ -285,10 +379,24 @@ impl Dialogue {
             self.vm.reset_state();
         }
         self.extend_variable_storage_from(&program);
+        self.verify_program();
 
         self
     }
 
+    fn verify_program(&self) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        let Some(program) = self.vm.program.as_ref() else {
+            return;
+        };
+        // Functions may still be added to the library after the program
+        if let Err(e) = program.verify_without_library() {
+            error!("{e}");
+        }
+    }
+
     /// Prepares the [`Dialogue`] that the user intends to start running a node.
     ///
     /// After this method is called, you call [`Dialogue::next`] to start executing it.
@@ -296,6 +404,8 @@ impl Dialogue {
     /// If [`Dialogue::line_hints_enabled`] has been set, the next [`Dialogue::next`] call will return a [`DialogueEvent::LineHints`],
     /// as the Dialogue determines which lines may be delivered during the `node_name` node's execution.
     ///
+    /// Any `<<detour>>` that is in progress is abandoned, so the new node will not return to the node that started it.
+    ///
     /// ## Errors
     ///
     /// Returns an error if no node with the value of `node_name` has been loaded.
@@ -327,13 +437,31 @@ impl Dialogue {
         self.vm.unload_programs()
     }
 
+    #[cfg(feature = "qa")]
+    pub(crate) fn program(&self) -> Option<&Program> {
+        self.vm.program.as_ref()
+    }
+
+    pub(crate) fn vm(&self) -> &VirtualMachine {
+        &self.vm
+    }
+
+    pub(crate) fn vm_mut(&mut self) -> &mut VirtualMachine {
+        &mut self.vm
+    }
+
     /// Gets the names of the nodes in the currently loaded Program, if there is one.
+    ///
+    /// The nodes the compiler generates to compute smart variables are not included.
     #[must_use]
     pub fn node_names(&self) -> Option<impl Iterator<Item = &str>> {
-        self.vm
-            .program
-            .as_ref()
-            .map(|program| program.nodes.keys().map(|s| s.as_str()))
+        self.vm.program.as_ref().map(|program| {
+            program
+                .nodes
+                .iter()
+                .filter(|(_, node)| !node.is_smart_variable())
+                .map(|(name, _)| name.as_str())
+        })
     }
 
     /// Returns the line ID that contains the original, uncompiled source
@@ -385,7 +513,10 @@ impl Dialogue {
     pub fn node_exists(&self, node_name: &str) -> bool {
         // Not calling `get_node_logging_errors` because this method does not write errors when there are no nodes.
         if let Some(program) = self.vm.program.as_ref() {
-            program.nodes.contains_key(node_name)
+            program
+                .nodes
+                .get(node_name)
+                .is_some_and(|node| !node.is_smart_variable())
         } else {
             error!("Tried to call NodeExists, but no program has been loaded");
             false
@@ -446,6 +577,30 @@ impl Dialogue {
         Ok(self)
     }
 
+    /// Captures the current execution state of the [`Dialogue`], including the current node, the position within it,
+    /// pending options and the language code. Pass the returned [`DialogueSnapshot`] to [`Dialogue::restore`] to resume from this point later.
+    ///
+    /// Variables are not part of the snapshot, since they live in the [`VariableStorage`]. Save them separately if needed.
+    #[must_use]
+    pub fn snapshot(&self) -> DialogueSnapshot {
+        self.vm.snapshot()
+    }
+
+    /// Restores a [`DialogueSnapshot`] created by [`Dialogue::snapshot`]. Any events that were not yet returned by [`Dialogue::continue_`] are discarded.
+    ///
+    /// If the snapshot was taken while waiting for an option selection, the dialogue will be waiting for one again, so the options
+    /// last received via [`DialogueEvent::Options`] are valid for [`Dialogue::set_selected_option`].
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if no program is loaded or if the snapshot's current node does not exist in the loaded program.
+    pub fn restore(&mut self, snapshot: DialogueSnapshot) -> Result<&mut Self> {
+        let language_code = snapshot.language_code().cloned();
+        self.vm.restore(snapshot)?;
+        self.set_language_code(language_code);
+        Ok(self)
+    }
+
     /// Gets a value indicating whether the Dialogue is currently executing Yarn instructions.
     #[must_use]
     pub fn is_active(&self) -> bool {
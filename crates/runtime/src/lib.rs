@@ -8,13 +8,20 @@
 #![warn(missing_docs, missing_debug_implementations)]
 mod analyser;
 mod command;
+mod coverage;
+mod debugger;
 mod dialogue;
 mod dialogue_option;
 mod events;
 mod language;
 mod line;
 pub mod markup;
+#[cfg(feature = "qa")]
+mod path_explorer;
 mod pluralization;
+mod saliency;
+#[cfg(feature = "qa")]
+mod simulation;
 mod text_provider;
 mod variable_storage;
 mod virtual_machine;
@@ -26,15 +33,21 @@ pub mod prelude {
     pub use crate::{
         analyser::*,
         command::*,
+        coverage::*,
+        debugger::*,
         dialogue::{Dialogue, DialogueError},
         dialogue_option::*,
         events::*,
         language::*,
         line::*,
         markup::MarkupParseError,
+        saliency::*,
         text_provider::*,
         variable_storage::*,
+        virtual_machine::DialogueSnapshot,
     };
+    #[cfg(feature = "qa")]
+    pub use crate::{path_explorer::*, simulation::*};
     pub(crate) use crate::{pluralization::*, virtual_machine::*};
     pub(crate) use yarnspinner_core::prelude::*;
 }
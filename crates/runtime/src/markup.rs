@@ -10,8 +10,10 @@ mod line_parser;
 mod markup_parse_error;
 mod parsed_markup;
 
+pub use self::attribute_marker_processor::AttributeMarkerProcessor;
 pub use self::line_parser::{
-    CHARACTER_ATTRIBUTE, CHARACTER_ATTRIBUTE_NAME_PROPERTY, TRIM_WHITESPACE_PROPERTY,
+    CHARACTER_ATTRIBUTE, CHARACTER_ATTRIBUTE_NAME_PROPERTY, REPLACEMENT_MARKER_CONTENTS,
+    TRIM_WHITESPACE_PROPERTY,
 };
 pub(crate) use self::{attribute_marker_processor::*, line_parser::*};
 pub use self::{markup_parse_error::*, parsed_markup::*};
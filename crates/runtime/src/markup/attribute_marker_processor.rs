@@ -9,15 +9,46 @@ mod dialogue_text_processor;
 mod no_markup_text_processor;
 
 /// Provides a mechanism for producing replacement text for a marker.
-pub(crate) trait AttributeMarkerProcessor: Debug + Send + Sync {
+///
+/// Register an implementation with [`Dialogue::add_marker_processor`](crate::prelude::Dialogue::add_marker_processor)
+/// to replace markers such as `[playername/]` with text of your choosing. This is also how the built-in `select`, `plural` and `ordinal` markers are implemented.
+///
+/// ## Example
+///
+/// ```
+/// # use yarnspinner_runtime::markup::*;
+/// # use yarnspinner_runtime::prelude::*;
+/// #[derive(Debug, Clone)]
+/// struct PlayerNameProcessor {
+///     name: String,
+/// }
+///
+/// impl AttributeMarkerProcessor for PlayerNameProcessor {
+///     fn replacement_text_for_marker(&self, _marker: &MarkupAttributeMarker) -> String {
+///         self.name.clone()
+///     }
+///
+///     fn set_language_code(&mut self, _language_code: Option<Language>) {}
+///
+///     fn clone_box(&self) -> Box<dyn AttributeMarkerProcessor> {
+///         Box::new(self.clone())
+///     }
+/// }
+/// ```
+pub trait AttributeMarkerProcessor: Debug + Send + Sync {
     /// Produces the replacement text that should be inserted into a parse
     /// result for a given attribute.
     ///
     /// If the marker is an `open` marker, the text from the marker's
     /// position to its corresponding closing marker is provided as a string
-    /// property called `contents`.
+    /// property called `contents`, see [`REPLACEMENT_MARKER_CONTENTS`](crate::markup::REPLACEMENT_MARKER_CONTENTS).
     fn replacement_text_for_marker(&self, marker: &MarkupAttributeMarker) -> String;
+
+    /// Called whenever the [`Dialogue`](crate::prelude::Dialogue)'s language changes and once upon registration.
+    /// A value of `None` means that the base language is used.
     fn set_language_code(&mut self, language_code: Option<Language>);
+
+    /// Clones the processor into a new [`Box`]. Usually implemented as `Box::new(self.clone())`.
     fn clone_box(&self) -> Box<dyn AttributeMarkerProcessor>;
 }
 
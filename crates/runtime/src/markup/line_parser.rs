@@ -81,6 +81,16 @@ impl LineParser {
         self
     }
 
+    /// Registers a marker processor after construction, replacing any processor previously registered for the same marker name.
+    pub(crate) fn add_marker_processor(
+        &mut self,
+        attribute_name: impl Into<String>,
+        processor: Box<dyn AttributeMarkerProcessor>,
+    ) {
+        self.marker_processors
+            .insert(attribute_name.into(), processor);
+    }
+
     /// Parses a line of text, and produces a [`ParsedMarkup`] containing the processed text
     ///
     /// ## Implementation notes
@@ -726,7 +736,7 @@ pub(crate) fn normalize(string: &str) -> String {
 }
 
 /// The name of the property in replacement attributes that contains the text of the attribute.
-pub(crate) const REPLACEMENT_MARKER_CONTENTS: &str = "contents";
+pub const REPLACEMENT_MARKER_CONTENTS: &str = "contents";
 
 /// The name of the implicitly-generated `character` attribute.
 pub const CHARACTER_ATTRIBUTE: &str = "character";
@@ -9,16 +9,24 @@ use std::collections::HashMap;
 /// You do not create instances of this struct yourself. It is created
 /// by objects that can parse markup, such as [`Dialogue`].
 #[derive(Debug, Clone, PartialEq)]
-pub(crate) struct MarkupAttributeMarker {
+pub struct MarkupAttributeMarker {
     /// The name of the marker.
     /// For example, the marker `[wave]` has the name `wave`.
-    pub(crate) name: Option<String>,
+    pub name: Option<String>,
     /// The position of the marker in the plain text.
-    pub(crate) position: usize,
+    pub position: usize,
     /// The list of properties associated with this marker.
-    pub(crate) properties: HashMap<String, MarkupValue>,
+    pub properties: HashMap<String, MarkupValue>,
     /// The type of marker that this is.
-    pub(crate) tag_type: TagType,
+    pub tag_type: TagType,
     /// The position of this marker in the original source text.
-    pub(crate) source_position: usize,
+    pub source_position: usize,
+}
+
+impl MarkupAttributeMarker {
+    /// Returns the value of the property with the given name, if it exists.
+    /// For example, the marker `[item id=3/]` has a property named `id` with the value `3`.
+    pub fn property(&self, name: &str) -> Option<&MarkupValue> {
+        self.properties.get(name)
+    }
 }
@@ -0,0 +1,641 @@
+//! Plays through every branch of a dialogue to find content that can never be seen.
+
+use crate::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt::{self, Display, Formatter};
+use yarnspinner_core::prelude::*;
+
+/// Drives a [`Dialogue`] through every combination of options, starting at a given node, and reports what it found in an [`ExplorationReport`].
+///
+/// Whenever the dialogue presents options, the explorer takes a [`DialogueSnapshot`] and a copy of all variables,
+/// and later restores both for each available option in turn. Paths that lead to a state that was already explored are not followed again.
+/// The exploration is repeated once for every variable seed added with [`PathExplorer::with_variable_seed`], which allows exploring
+/// content that depends on variables that are set by the game.
+///
+/// Commands are not executed, so content that depends on their side effects, e.g. variables set by the game in response to a command,
+/// is only explored if the effects are part of a variable seed. Functions are called as usual, so they must be in the [`Dialogue::library`].
+///
+/// ## Example
+///
+/// ```no_run
+/// # use yarnspinner_runtime::prelude::*;
+/// # use yarnspinner_core::prelude::*;
+/// # let mut dialogue = Dialogue::new(Box::new(MemoryVariableStorage::new()), Box::new(StringTableTextProvider::new()));
+/// let report = PathExplorer::new("Start")
+///     .with_variable_seed([("$has_sword".to_owned(), YarnValue::from(true))])
+///     .with_end_nodes(["Ending"])
+///     .explore(&mut dialogue)?;
+/// println!("{report}");
+/// # Ok::<(), DialogueError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct PathExplorer {
+    start_node: String,
+    variable_seeds: Vec<HashMap<String, YarnValue>>,
+    end_nodes: BTreeSet<String>,
+    max_depth: usize,
+    max_visits: usize,
+    max_steps: usize,
+}
+
+impl PathExplorer {
+    /// The default for [`PathExplorer::with_max_depth`].
+    pub const DEFAULT_MAX_DEPTH: usize = 100;
+    /// The default for [`PathExplorer::with_max_visits`].
+    pub const DEFAULT_MAX_VISITS: usize = 10_000;
+    /// The default for [`PathExplorer::with_max_steps`].
+    pub const DEFAULT_MAX_STEPS: usize = 10_000;
+
+    /// Creates an explorer that starts every path at the given node.
+    pub fn new(start_node: impl Into<String>) -> Self {
+        Self {
+            start_node: start_node.into(),
+            variable_seeds: Vec::new(),
+            end_nodes: BTreeSet::new(),
+            max_depth: Self::DEFAULT_MAX_DEPTH,
+            max_visits: Self::DEFAULT_MAX_VISITS,
+            max_steps: Self::DEFAULT_MAX_STEPS,
+        }
+    }
+
+    /// Adds a set of variable values that are set before exploring, on top of the variables the [`Dialogue`] already has.
+    /// Every seed results in a separate exploration from the start node.
+    /// Without any seeds, the dialogue is explored once with its variables as they are.
+    #[must_use]
+    pub fn with_variable_seed(
+        mut self,
+        variables: impl IntoIterator<Item = (String, YarnValue)>,
+    ) -> Self {
+        self.variable_seeds.push(variables.into_iter().collect());
+        self
+    }
+
+    /// Sets the nodes in which the dialogue is expected to complete. Completing in any other node is reported in [`ExplorationReport::unexpected_completions`].
+    /// By default, there are no end nodes and completions are never reported.
+    #[must_use]
+    pub fn with_end_nodes(
+        mut self,
+        end_nodes: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.end_nodes = end_nodes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the maximum number of options that are selected on a single path. Paths that would be longer are not followed.
+    /// Default is [`PathExplorer::DEFAULT_MAX_DEPTH`].
+    #[must_use]
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets the maximum number of times options are presented over the course of the whole exploration, across all variable seeds.
+    /// Default is [`PathExplorer::DEFAULT_MAX_VISITS`].
+    #[must_use]
+    pub fn with_max_visits(mut self, max_visits: usize) -> Self {
+        self.max_visits = max_visits;
+        self
+    }
+
+    /// Sets the maximum number of calls to [`Dialogue::continue_`] between two option selections.
+    /// Dialogue that runs longer than this without presenting options or completing is reported as a possible infinite loop.
+    /// Default is [`PathExplorer::DEFAULT_MAX_STEPS`].
+    ///
+    /// Note that loops that contain no lines, commands or options at all cannot be detected, since [`Dialogue::continue_`] never returns for them.
+    #[must_use]
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Explores all paths through the dialogue. The variables of the [`Dialogue`] are reset to their original values afterwards,
+    /// but its execution state is not, so call [`Dialogue::set_node`] before running it again.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if the start node does not exist or the [`Dialogue`] returns an error while running, e.g. because a function is missing.
+    pub fn explore(&self, dialogue: &mut Dialogue) -> crate::Result<ExplorationReport> {
+        let original_variables = dialogue.variable_storage().variables();
+        let seeds = if self.variable_seeds.is_empty() {
+            vec![HashMap::new()]
+        } else {
+            self.variable_seeds.clone()
+        };
+
+        let mut exploration = Exploration {
+            explorer: self,
+            content: ContentIndex::from_dialogue(dialogue),
+            reached_lines: HashSet::new(),
+            available_options: HashSet::new(),
+            seen_states: HashMap::new(),
+            visits: 0,
+            report: ExplorationReport {
+                is_exhaustive: true,
+                ..Default::default()
+            },
+        };
+        let result = seeds
+            .into_iter()
+            .enumerate()
+            .try_for_each(|(seed_index, seed)| {
+                let mut variables = original_variables.clone();
+                variables.extend(seed);
+                exploration.explore_seed(dialogue, seed_index, variables)
+            });
+        set_variables(dialogue, original_variables)?;
+        result?;
+        Ok(exploration.finish())
+    }
+}
+
+/// What a [`PathExplorer`] found out about a dialogue.
+///
+/// Every finding contains the [`ExplorationPath`] that leads to it, which is the shortest one that was found.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct ExplorationReport {
+    /// Lines that were never delivered on any path, sorted by node.
+    pub lines_never_reached: Vec<ContentLocation>,
+
+    /// Options that were never available on any path, either because their condition was always false or because they were never presented. Sorted by node.
+    pub options_never_available: Vec<ContentLocation>,
+
+    /// Paths that completed the dialogue in a node that is not one of the [`PathExplorer::with_end_nodes`], one per node.
+    pub unexpected_completions: Vec<ExplorationPath>,
+
+    /// Paths that never presented options or completed the dialogue, either because the dialogue reached the exact same state twice
+    /// or because it exceeded [`PathExplorer::with_max_steps`]. One per node.
+    pub infinite_loops: Vec<ExplorationPath>,
+
+    /// The number of times options were presented, i.e. the number of branching points that were explored.
+    pub visits: usize,
+
+    /// `false` if paths were not followed to their end because of [`PathExplorer::with_max_depth`] or [`PathExplorer::with_max_visits`].
+    /// In that case, some of the content in [`ExplorationReport::lines_never_reached`] and [`ExplorationReport::options_never_available`] might actually be reachable.
+    pub is_exhaustive: bool,
+}
+
+/// A line or option in the loaded [`Program`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ContentLocation {
+    /// The node containing the line or option.
+    pub node_name: String,
+    /// The ID of the line or option.
+    pub line_id: LineId,
+}
+
+/// The options to select to get from the start node to a finding of a [`PathExplorer`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ExplorationPath {
+    /// The index of the variable seed that was used, in the order they were added with [`PathExplorer::with_variable_seed`].
+    /// Always `0` if there are no seeds.
+    pub seed_index: usize,
+    /// The IDs of the lines of the selected options, in order.
+    pub selected_options: Vec<LineId>,
+    /// The node that was running when the finding occurred.
+    pub node_name: String,
+}
+
+impl Display for ExplorationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let exhaustive = if self.is_exhaustive {
+            ""
+        } else {
+            ", stopped early because of the exploration budget"
+        };
+        writeln!(f, "Explored {} branching points{exhaustive}", self.visits)?;
+        for line in &self.lines_never_reached {
+            writeln!(f, "Line never reached: {line}")?;
+        }
+        for option in &self.options_never_available {
+            writeln!(f, "Option never available: {option}")?;
+        }
+        for path in &self.unexpected_completions {
+            writeln!(f, "Dialogue completes unexpectedly in {path}")?;
+        }
+        for path in &self.infinite_loops {
+            writeln!(f, "Possible infinite loop in {path}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for ContentLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in node \"{}\"", self.line_id, self.node_name)
+    }
+}
+
+impl Display for ExplorationPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "node \"{}\" (seed {}", self.node_name, self.seed_index)?;
+        if !self.selected_options.is_empty() {
+            let options: Vec<_> = self
+                .selected_options
+                .iter()
+                .map(|id| id.0.as_str())
+                .collect();
+            write!(f, ", options {}", options.join(" -> "))?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// The lines and options of the loaded program.
+#[derive(Debug, Default)]
+struct ContentIndex {
+    lines: BTreeSet<ContentLocation>,
+    options: BTreeSet<ContentLocation>,
+}
+
+impl ContentIndex {
+    fn from_dialogue(dialogue: &Dialogue) -> Self {
+        dialogue
+            .program()
+            .map(Self::from_program)
+            .unwrap_or_default()
+    }
+
+    fn from_program(program: &Program) -> Self {
+        let mut index = Self::default();
+        for (node_name, node) in &program.nodes {
+            for instruction in &node.instructions {
+                let content = match OpCode::try_from(instruction.opcode) {
+                    Ok(OpCode::RunLine) => &mut index.lines,
+                    Ok(OpCode::AddOption) => &mut index.options,
+                    _ => continue,
+                };
+                let Some(Ok(line_id)) = instruction.operands.first().cloned().map(String::try_from)
+                else {
+                    continue;
+                };
+                content.insert(ContentLocation {
+                    node_name: node_name.clone(),
+                    line_id: line_id.into(),
+                });
+            }
+        }
+        index
+    }
+}
+
+/// A point at which options were presented, together with one option to select there.
+#[derive(Debug, Clone)]
+struct Branch {
+    snapshot: DialogueSnapshot,
+    variables: HashMap<String, YarnValue>,
+    option: OptionId,
+    selected_options: Vec<LineId>,
+}
+
+/// Everything the dialogue can remember between two option selections.
+type ExecutionState = (DialogueSnapshot, HashMap<String, YarnValue>);
+
+struct Exploration<'a> {
+    explorer: &'a PathExplorer,
+    content: ContentIndex,
+    reached_lines: HashSet<LineId>,
+    available_options: HashSet<LineId>,
+    /// The states in which options were presented, bucketed by node and variables so that the snapshots don't need to be hashable.
+    seen_states: HashMap<String, Vec<DialogueSnapshot>>,
+    visits: usize,
+    report: ExplorationReport,
+}
+
+impl Exploration<'_> {
+    fn explore_seed(
+        &mut self,
+        dialogue: &mut Dialogue,
+        seed_index: usize,
+        variables: HashMap<String, YarnValue>,
+    ) -> crate::Result<()> {
+        set_variables(dialogue, variables)?;
+        dialogue.set_node(self.explorer.start_node.clone())?;
+
+        // Breadth-first, so that findings are reported with the shortest path leading to them
+        let mut pending = VecDeque::new();
+        self.run_until_branch(dialogue, seed_index, Vec::new(), &mut pending)?;
+        while let Some(branch) = pending.pop_front() {
+            dialogue.restore(branch.snapshot)?;
+            set_variables(dialogue, branch.variables)?;
+            dialogue.set_selected_option(branch.option)?;
+            self.run_until_branch(dialogue, seed_index, branch.selected_options, &mut pending)?;
+        }
+        Ok(())
+    }
+
+    /// Continues the dialogue until it presents options or completes. For options, adds a [`Branch`] for each of the available ones to `pending`.
+    fn run_until_branch(
+        &mut self,
+        dialogue: &mut Dialogue,
+        seed_index: usize,
+        selected_options: Vec<LineId>,
+        pending: &mut VecDeque<Branch>,
+    ) -> crate::Result<()> {
+        let mut states: Vec<ExecutionState> = Vec::new();
+        // The dialogue forgets its current node when it stops, so it is tracked here instead
+        let mut node_name = dialogue.current_node().unwrap_or_default();
+        for _ in 0..self.explorer.max_steps {
+            for event in dialogue.continue_()? {
+                match event {
+                    DialogueEvent::Line(line) => {
+                        self.reached_lines.insert(line.id);
+                    }
+                    DialogueEvent::Options(options) => {
+                        self.visit_options(dialogue, options, selected_options, pending);
+                        return Ok(());
+                    }
+                    DialogueEvent::NodeStart(name) => node_name = name,
+                    DialogueEvent::DialogueComplete => {
+                        if !self.explorer.end_nodes.is_empty()
+                            && !self.explorer.end_nodes.contains(&node_name)
+                        {
+                            let path = ExplorationPath {
+                                seed_index,
+                                selected_options,
+                                node_name,
+                            };
+                            add_finding(&mut self.report.unexpected_completions, path);
+                        }
+                        return Ok(());
+                    }
+                    DialogueEvent::Command(_)
+                    | DialogueEvent::NodeComplete(_)
+                    | DialogueEvent::LineHints(_) => {}
+                }
+            }
+            let state = (dialogue.snapshot(), dialogue.variable_storage().variables());
+            if states.contains(&state) {
+                break;
+            }
+            states.push(state);
+        }
+        let path = ExplorationPath {
+            seed_index,
+            selected_options,
+            node_name,
+        };
+        add_finding(&mut self.report.infinite_loops, path);
+        Ok(())
+    }
+
+    fn visit_options(
+        &mut self,
+        dialogue: &Dialogue,
+        options: Vec<DialogueOption>,
+        selected_options: Vec<LineId>,
+        pending: &mut VecDeque<Branch>,
+    ) {
+        self.available_options.extend(
+            options
+                .iter()
+                .filter(|option| option.is_available)
+                .map(|option| option.line.id.clone()),
+        );
+        if self.visits >= self.explorer.max_visits
+            || selected_options.len() >= self.explorer.max_depth
+        {
+            self.report.is_exhaustive = false;
+            return;
+        }
+        let snapshot = dialogue.snapshot();
+        let variables = dialogue.variable_storage().variables();
+        if !self.mark_as_seen(&snapshot, &variables) {
+            return;
+        }
+        self.visits += 1;
+        for option in options.into_iter().filter(|option| option.is_available) {
+            let mut selected_options = selected_options.clone();
+            selected_options.push(option.line.id);
+            pending.push_back(Branch {
+                snapshot: snapshot.clone(),
+                variables: variables.clone(),
+                option: option.id,
+                selected_options,
+            });
+        }
+    }
+
+    /// Returns `false` if the state was already seen before.
+    fn mark_as_seen(
+        &mut self,
+        snapshot: &DialogueSnapshot,
+        variables: &HashMap<String, YarnValue>,
+    ) -> bool {
+        let sorted_variables: BTreeMap<_, _> = variables.iter().collect();
+        let key = format!("{:?} {sorted_variables:?}", snapshot.current_node());
+        let snapshots = self.seen_states.entry(key).or_default();
+        if snapshots.contains(snapshot) {
+            return false;
+        }
+        snapshots.push(snapshot.clone());
+        true
+    }
+
+    fn finish(mut self) -> ExplorationReport {
+        self.report.visits = self.visits;
+        self.report.lines_never_reached = self
+            .content
+            .lines
+            .into_iter()
+            .filter(|line| !self.reached_lines.contains(&line.line_id))
+            .collect();
+        self.report.options_never_available = self
+            .content
+            .options
+            .into_iter()
+            .filter(|option| !self.available_options.contains(&option.line_id))
+            .collect();
+        self.report
+    }
+}
+
+/// Adds the path unless there already is one for the same node, which is at most as long because of the breadth-first exploration.
+fn add_finding(findings: &mut Vec<ExplorationPath>, path: ExplorationPath) {
+    if !findings
+        .iter()
+        .any(|finding| finding.node_name == path.node_name)
+    {
+        findings.push(path);
+    }
+}
+
+fn set_variables(
+    dialogue: &mut Dialogue,
+    variables: HashMap<String, YarnValue>,
+) -> crate::Result<()> {
+    let variable_storage = dialogue.variable_storage_mut();
+    variable_storage.clear();
+    variable_storage.extend(variables)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dialogue(listing: &str) -> Dialogue {
+        let program = Program::assemble(listing).unwrap();
+        let mut text_provider = StringTableTextProvider::new();
+        text_provider.extend_base_language(
+            ContentIndex::from_program(&program)
+                .lines
+                .into_iter()
+                .chain(ContentIndex::from_program(&program).options)
+                .map(|content| (content.line_id, String::new()))
+                .collect(),
+        );
+        let mut dialogue = Dialogue::new(
+            Box::new(MemoryVariableStorage::new()),
+            Box::new(text_provider),
+        );
+        dialogue.add_program(program);
+        dialogue
+    }
+
+    fn location(node_name: &str, line_id: &str) -> ContentLocation {
+        ContentLocation {
+            node_name: node_name.to_owned(),
+            line_id: line_id.into(),
+        }
+    }
+
+    #[test]
+    fn explores_every_option() {
+        let mut dialogue = dialogue(
+            r#"
+            .initial "$has_key" false
+            .node "Start"
+                ADD_OPTION "line:left" "left" 0 false
+                PUSH_VARIABLE "$has_key"
+                ADD_OPTION "line:door" "door" 0 true
+                ADD_OPTION "line:right" "right" 0 false
+                SHOW_OPTIONS
+                JUMP
+            left:
+                RUN_LINE "line:wall" 0
+                STOP
+            door:
+                RUN_LINE "line:treasure" 0
+                STOP
+            right:
+                PUSH_BOOL false
+                JUMP_IF_FALSE "end"
+                RUN_LINE "line:never" 0
+            end:
+                STOP
+            "#,
+        );
+        let report = PathExplorer::new("Start").explore(&mut dialogue).unwrap();
+        assert_eq!(
+            vec![
+                location("Start", "line:never"),
+                location("Start", "line:treasure")
+            ],
+            report.lines_never_reached
+        );
+        assert_eq!(
+            vec![location("Start", "line:door")],
+            report.options_never_available
+        );
+        assert_eq!(1, report.visits);
+        assert!(report.is_exhaustive);
+
+        let report = PathExplorer::new("Start")
+            .with_variable_seed([("$has_key".to_owned(), YarnValue::from(true))])
+            .explore(&mut dialogue)
+            .unwrap();
+        assert_eq!(
+            vec![location("Start", "line:never")],
+            report.lines_never_reached
+        );
+        assert!(report.options_never_available.is_empty());
+        assert_eq!(
+            Ok(YarnValue::from(false)),
+            dialogue
+                .variable_storage()
+                .get("$has_key")
+                .map_err(|e| e.to_string()),
+            "Variables are reset after exploring"
+        );
+    }
+
+    #[test]
+    fn reports_unexpected_completions_and_infinite_loops() {
+        let mut dialogue = dialogue(
+            r#"
+            .node "Start"
+                ADD_OPTION "line:loop" "loop" 0 false
+                ADD_OPTION "line:quit" "quit" 0 false
+                ADD_OPTION "line:end" "end" 0 false
+                SHOW_OPTIONS
+                JUMP
+            loop:
+                PUSH_STRING "Loop"
+                RUN_NODE
+            quit:
+                STOP
+            end:
+                PUSH_STRING "End"
+                RUN_NODE
+            .node "Loop"
+                RUN_LINE "line:again" 0
+                PUSH_STRING "Loop"
+                RUN_NODE
+            .node "End"
+                STOP
+            "#,
+        );
+        let report = PathExplorer::new("Start")
+            .with_end_nodes(["End"])
+            .explore(&mut dialogue)
+            .unwrap();
+        assert_eq!(
+            vec![ExplorationPath {
+                seed_index: 0,
+                selected_options: vec!["line:quit".into()],
+                node_name: "Start".to_owned(),
+            }],
+            report.unexpected_completions
+        );
+        assert_eq!(
+            vec![ExplorationPath {
+                seed_index: 0,
+                selected_options: vec!["line:loop".into()],
+                node_name: "Loop".to_owned(),
+            }],
+            report.infinite_loops
+        );
+    }
+
+    #[test]
+    fn stops_at_the_budget() {
+        let mut dialogue = dialogue(
+            r#"
+            .node "Start"
+                PUSH_VARIABLE "$count"
+                PUSH_FLOAT 1
+                PUSH_FLOAT 2
+                CALL_FUNC "Number.Add"
+                STORE_VARIABLE "$count"
+                POP
+                ADD_OPTION "line:again" "again" 0 false
+                SHOW_OPTIONS
+                JUMP
+            again:
+                PUSH_STRING "Start"
+                RUN_NODE
+            "#,
+        );
+        dialogue
+            .variable_storage_mut()
+            .set("$count".to_owned(), YarnValue::from(0))
+            .unwrap();
+        let report = PathExplorer::new("Start")
+            .with_max_depth(5)
+            .explore(&mut dialogue)
+            .unwrap();
+        assert_eq!(5, report.visits);
+        assert!(!report.is_exhaustive);
+    }
+}
@@ -0,0 +1,202 @@
+//! Not part of the original, which only gained saliency in Yarn Spinner 3.
+//!
+//! Line groups (`=> Line <<if $condition>>`) and node groups (nodes that share a title and have `when:` headers)
+//! are compiled into a list of candidates, one of which is picked by a [`SaliencyStrategy`] when the group is reached.
+
+use crate::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::fmt::Debug;
+
+/// A piece of content that can be selected by a [`SaliencyStrategy`], i.e. a line of a line group or a node of a node group.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "bevy", derive(Reflect))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "bevy", reflect(Debug, PartialEq, Hash))]
+#[cfg_attr(
+    all(feature = "bevy", feature = "serde"),
+    reflect(Serialize, Deserialize)
+)]
+pub struct SaliencyCandidate {
+    /// The ID of the content, i.e. the [`LineId`] of a line in a line group or the name of a node in a node group.
+    pub content_id: String,
+
+    /// Whether all conditions of this candidate passed.
+    /// Candidates whose conditions failed are still passed to the [`SaliencyStrategy`], which will usually ignore them.
+    pub is_available: bool,
+
+    /// The number of conditions of this candidate, e.g. `2` for `<<if $a and $b>>` and `0` for a candidate without any condition.
+    /// Candidates with a higher score are considered more specific.
+    pub complexity_score: usize,
+
+    /// Where the virtual machine continues if this candidate is selected.
+    pub(crate) destination: String,
+}
+
+/// A strategy for selecting one of multiple [`SaliencyCandidate`]s when a line group or node group is reached.
+/// Set it with [`Dialogue::set_saliency_strategy`]. The default is [`BestLeastRecentlySeenSaliencyStrategy`].
+///
+/// ## Example
+///
+/// ```
+/// # use yarnspinner_runtime::prelude::*;
+/// #[derive(Debug, Clone)]
+/// struct LastAvailableSaliencyStrategy;
+///
+/// impl SaliencyStrategy for LastAvailableSaliencyStrategy {
+///     fn select(
+///         &mut self,
+///         candidates: &[SaliencyCandidate],
+///         _variable_storage: &mut dyn VariableStorage,
+///     ) -> yarnspinner_runtime::Result<Option<usize>> {
+///         Ok(candidates.iter().rposition(|candidate| candidate.is_available))
+///     }
+///
+///     fn clone_box(&self) -> Box<dyn SaliencyStrategy> {
+///         Box::new(self.clone())
+///     }
+/// }
+/// ```
+pub trait SaliencyStrategy: Debug + Send + Sync {
+    /// Returns the index of the candidate that should be run, or `None` if no candidate should be run.
+    /// In the latter case, a line group is skipped and a node group completes without running any of its nodes.
+    ///
+    /// The variable storage is passed along so that strategies can persist information about which content was already seen.
+    fn select(
+        &mut self,
+        candidates: &[SaliencyCandidate],
+        variable_storage: &mut dyn VariableStorage,
+    ) -> crate::Result<Option<usize>>;
+
+    /// Clones the strategy into a new trait object.
+    fn clone_box(&self) -> Box<dyn SaliencyStrategy>;
+}
+
+impl Clone for Box<dyn SaliencyStrategy> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// A [`SaliencyStrategy`] that always selects the first available candidate, i.e. the one that comes first in the Yarn file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FirstAvailableSaliencyStrategy;
+
+impl SaliencyStrategy for FirstAvailableSaliencyStrategy {
+    fn select(
+        &mut self,
+        candidates: &[SaliencyCandidate],
+        _variable_storage: &mut dyn VariableStorage,
+    ) -> crate::Result<Option<usize>> {
+        Ok(candidates
+            .iter()
+            .position(|candidate| candidate.is_available))
+    }
+
+    fn clone_box(&self) -> Box<dyn SaliencyStrategy> {
+        Box::new(*self)
+    }
+}
+
+/// A [`SaliencyStrategy`] that selects a random available candidate.
+#[derive(Debug, Clone)]
+pub struct RandomSaliencyStrategy(ChaCha8Rng);
+
+impl Default for RandomSaliencyStrategy {
+    fn default() -> Self {
+        Self(ChaCha8Rng::from_entropy())
+    }
+}
+
+impl RandomSaliencyStrategy {
+    /// Creates a new [`RandomSaliencyStrategy`] seeded from the system's entropy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new [`RandomSaliencyStrategy`] with a fixed seed, which is useful for reproducible tests.
+    /// The same seed selects the same candidates on every platform.
+    pub fn from_seed(seed: u64) -> Self {
+        Self(ChaCha8Rng::seed_from_u64(seed))
+    }
+}
+
+impl SaliencyStrategy for RandomSaliencyStrategy {
+    fn select(
+        &mut self,
+        candidates: &[SaliencyCandidate],
+        _variable_storage: &mut dyn VariableStorage,
+    ) -> crate::Result<Option<usize>> {
+        let available: Vec<_> = candidates
+            .iter()
+            .enumerate()
+            .filter_map(|(index, candidate)| candidate.is_available.then_some(index))
+            .collect();
+        if available.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(available[self.0.gen_range(0..available.len())]))
+    }
+
+    fn clone_box(&self) -> Box<dyn SaliencyStrategy> {
+        Box::new(self.clone())
+    }
+}
+
+/// A [`SaliencyStrategy`] that selects the most specific available candidate, i.e. the one with the highest [`SaliencyCandidate::complexity_score`].
+/// If there are multiple, the one that was selected the fewest times so far wins. Remaining ties are broken by the order in the Yarn file.
+///
+/// The number of times a candidate was selected is stored in the [`VariableStorage`] under the name returned by [`BestLeastRecentlySeenSaliencyStrategy::view_count_variable_name`],
+/// so it is saved and loaded together with the rest of the variables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BestLeastRecentlySeenSaliencyStrategy;
+
+impl BestLeastRecentlySeenSaliencyStrategy {
+    /// The name of the variable that stores how often the content with the given ID was selected.
+    pub fn view_count_variable_name(content_id: &str) -> String {
+        format!("$Yarn.Internal.Saliency.ViewCount.{content_id}")
+    }
+
+    fn view_count(variable_storage: &dyn VariableStorage, content_id: &str) -> usize {
+        variable_storage
+            .get(&Self::view_count_variable_name(content_id))
+            .ok()
+            .and_then(|value| f32::try_from(value).ok())
+            .map_or(0, |count| count as usize)
+    }
+}
+
+impl SaliencyStrategy for BestLeastRecentlySeenSaliencyStrategy {
+    fn select(
+        &mut self,
+        candidates: &[SaliencyCandidate],
+        variable_storage: &mut dyn VariableStorage,
+    ) -> crate::Result<Option<usize>> {
+        let Some(index) = candidates
+            .iter()
+            .enumerate()
+            .filter(|(_, candidate)| candidate.is_available)
+            // `min_by_key` returns the first of several equal elements, which keeps the order of the Yarn file for ties
+            .min_by_key(|(_, candidate)| {
+                (
+                    std::cmp::Reverse(candidate.complexity_score),
+                    Self::view_count(variable_storage, &candidate.content_id),
+                )
+            })
+            .map(|(index, _)| index)
+        else {
+            return Ok(None);
+        };
+        let content_id = &candidates[index].content_id;
+        let view_count = Self::view_count(variable_storage, content_id) + 1;
+        variable_storage.set(
+            Self::view_count_variable_name(content_id),
+            (view_count as f32).into(),
+        )?;
+        Ok(Some(index))
+    }
+
+    fn clone_box(&self) -> Box<dyn SaliencyStrategy> {
+        Box::new(*self)
+    }
+}
@@ -0,0 +1,658 @@
+//! Plays through a dialogue many times with randomly selected options to gather statistics for balancing.
+
+use crate::prelude::*;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Debug, Display, Formatter};
+
+/// Runs a [`Dialogue`] from a start node to its end many times, selecting options with an [`OptionSelectionPolicy`],
+/// and aggregates the final variable values, node visits and path lengths of all playthroughs into a [`SimulationReport`].
+///
+/// All randomness of the policy comes from a random number generator seeded with [`PlaythroughSimulator::with_seed`],
+/// so a simulation with the same seed, policy and dialogue always has the same outcome, on every platform.
+/// Note that this does not apply to the [`SaliencyStrategy`] of the [`Dialogue`] and functions like `random()`,
+/// so use e.g. [`RandomSaliencyStrategy::from_seed`] if they need to be reproducible as well.
+///
+/// Commands are not executed, so variables the game sets in response to them keep their values.
+/// Every playthrough starts with the variables the [`Dialogue`] had when the simulation was started, and they are reset to these values afterwards.
+///
+/// ## Example
+///
+/// ```no_run
+/// # use yarnspinner_runtime::prelude::*;
+/// # let mut dialogue = Dialogue::new(Box::new(MemoryVariableStorage::new()), Box::new(StringTableTextProvider::new()));
+/// let report = PlaythroughSimulator::new("Start")
+///     .with_playthroughs(10_000)
+///     .with_seed(42)
+///     .run(&mut dialogue)?;
+/// if let Some(gold) = report.variables.get("$gold") {
+///     println!("Players end with {:?} gold on average", gold.mean());
+/// }
+/// # Ok::<(), DialogueError>(())
+/// ```
+#[derive(Debug)]
+pub struct PlaythroughSimulator {
+    start_node: String,
+    playthroughs: usize,
+    seed: u64,
+    max_steps: usize,
+    policy: Box<dyn OptionSelectionPolicy>,
+}
+
+impl PlaythroughSimulator {
+    /// The default for [`PlaythroughSimulator::with_playthroughs`].
+    pub const DEFAULT_PLAYTHROUGHS: usize = 1000;
+    /// The default for [`PlaythroughSimulator::with_max_steps`].
+    pub const DEFAULT_MAX_STEPS: usize = 10_000;
+
+    /// Creates a simulator that starts every playthrough at the given node and selects options with the [`UniformOptionPolicy`].
+    pub fn new(start_node: impl Into<String>) -> Self {
+        Self {
+            start_node: start_node.into(),
+            playthroughs: Self::DEFAULT_PLAYTHROUGHS,
+            seed: 0,
+            max_steps: Self::DEFAULT_MAX_STEPS,
+            policy: Box::new(UniformOptionPolicy),
+        }
+    }
+
+    /// Sets the number of playthroughs to simulate. Default is [`PlaythroughSimulator::DEFAULT_PLAYTHROUGHS`].
+    #[must_use]
+    pub fn with_playthroughs(mut self, playthroughs: usize) -> Self {
+        self.playthroughs = playthroughs;
+        self
+    }
+
+    /// Sets the seed of the random number generator passed to the [`OptionSelectionPolicy`]. Default is `0`.
+    #[must_use]
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Sets the maximum number of calls to [`Dialogue::continue_`] in a single playthrough.
+    /// Playthroughs that take longer, e.g. because the dialogue loops forever, are stopped and counted in [`SimulationReport::truncated`].
+    /// Default is [`PlaythroughSimulator::DEFAULT_MAX_STEPS`].
+    #[must_use]
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Sets the [`OptionSelectionPolicy`] that decides which option is selected whenever options are presented.
+    #[must_use]
+    pub fn with_policy(mut self, policy: impl OptionSelectionPolicy + 'static) -> Self {
+        self.policy = Box::new(policy);
+        self
+    }
+
+    /// Runs all playthroughs. The variables of the [`Dialogue`] are reset to their original values afterwards,
+    /// but its execution state is not, so call [`Dialogue::set_node`] before running it again.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if the start node does not exist, the policy selects an invalid option,
+    /// or the [`Dialogue`] returns an error while running, e.g. because a function is missing.
+    pub fn run(&mut self, dialogue: &mut Dialogue) -> crate::Result<SimulationReport> {
+        let original_variables = dialogue.variable_storage().variables();
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        let mut report = SimulationReport::default();
+        let result = (0..self.playthroughs).try_for_each(|_| {
+            set_variables(dialogue, original_variables.clone())?;
+            self.run_playthrough(dialogue, &mut rng, &mut report)
+        });
+        set_variables(dialogue, original_variables)?;
+        result?;
+        Ok(report)
+    }
+
+    fn run_playthrough(
+        &mut self,
+        dialogue: &mut Dialogue,
+        rng: &mut ChaCha8Rng,
+        report: &mut SimulationReport,
+    ) -> crate::Result<()> {
+        dialogue.set_node(self.start_node.clone())?;
+        self.policy.start_playthrough();
+        let mut node_visits: HashMap<String, usize> = HashMap::new();
+        let mut path_length = 0;
+        let outcome = 'playthrough: {
+            for _ in 0..self.max_steps {
+                for event in dialogue.continue_()? {
+                    match event {
+                        DialogueEvent::NodeStart(node_name) => {
+                            *node_visits.entry(node_name).or_default() += 1;
+                        }
+                        DialogueEvent::Options(options) => {
+                            let Some(option) = self.policy.select(&options, rng) else {
+                                break 'playthrough PlaythroughOutcome::Stuck;
+                            };
+                            dialogue.set_selected_option(option)?;
+                            path_length += 1;
+                        }
+                        DialogueEvent::DialogueComplete => {
+                            break 'playthrough PlaythroughOutcome::Completed;
+                        }
+                        DialogueEvent::Line(_)
+                        | DialogueEvent::Command(_)
+                        | DialogueEvent::NodeComplete(_)
+                        | DialogueEvent::LineHints(_) => {}
+                    }
+                }
+            }
+            PlaythroughOutcome::Truncated
+        };
+
+        report.playthroughs += 1;
+        match outcome {
+            PlaythroughOutcome::Completed => report.completed += 1,
+            PlaythroughOutcome::Stuck => report.stuck += 1,
+            PlaythroughOutcome::Truncated => report.truncated += 1,
+        }
+        *report.path_lengths.entry(path_length).or_default() += 1;
+        for (node_name, visits) in node_visits {
+            let statistics = report.node_visits.entry(node_name).or_default();
+            statistics.total_visits += visits;
+            statistics.playthroughs += 1;
+        }
+        for (name, value) in dialogue.variable_storage().variables() {
+            if !name.starts_with("$Yarn.Internal.") {
+                report.variables.entry(name).or_default().add(value);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlaythroughOutcome {
+    Completed,
+    Stuck,
+    Truncated,
+}
+
+/// Decides which option a [`PlaythroughSimulator`] selects whenever the dialogue presents options.
+///
+/// ## Example
+///
+/// ```
+/// # use yarnspinner_runtime::prelude::*;
+/// # use rand::RngCore;
+/// /// Always selects the last available option.
+/// #[derive(Debug)]
+/// struct LastOptionPolicy;
+///
+/// impl OptionSelectionPolicy for LastOptionPolicy {
+///     fn select(&mut self, options: &[DialogueOption], _rng: &mut dyn RngCore) -> Option<OptionId> {
+///         options.iter().rev().find(|option| option.is_available).map(|option| option.id)
+///     }
+/// }
+/// ```
+pub trait OptionSelectionPolicy: Debug {
+    /// Returns the ID of the option to select, or `None` if no option can be selected, which ends the playthrough.
+    /// Unavailable options are passed as well, but selecting one is an error.
+    fn select(&mut self, options: &[DialogueOption], rng: &mut dyn RngCore) -> Option<OptionId>;
+
+    /// Called before every playthrough, so that policies can reset any state they keep during a playthrough.
+    fn start_playthrough(&mut self) {}
+}
+
+/// An [`OptionSelectionPolicy`] that selects one of the available options with equal probability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UniformOptionPolicy;
+
+impl OptionSelectionPolicy for UniformOptionPolicy {
+    fn select(&mut self, options: &[DialogueOption], rng: &mut dyn RngCore) -> Option<OptionId> {
+        select_weighted(options, rng, |_| 1.0)
+    }
+}
+
+/// An [`OptionSelectionPolicy`] that selects available options with a probability proportional to a weight read from their tags,
+/// e.g. `-> Buy the sword #weight:3` is selected three times as often as an option without a weight.
+///
+/// The runtime does not know the tags of lines, so they need to be passed in, e.g. from the `metadata` of the compiler's string table.
+/// Options without a weight tag have a weight of `1`.
+///
+/// ## Example
+///
+/// ```
+/// # use yarnspinner_runtime::prelude::*;
+/// # use yarnspinner_core::prelude::*;
+/// let policy = WeightedOptionPolicy::new([(LineId::from("line:buy"), vec!["weight:3".to_owned()])])
+///     .with_tag_prefix("chance:");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeightedOptionPolicy {
+    tags: HashMap<LineId, Vec<String>>,
+    tag_prefix: String,
+}
+
+impl WeightedOptionPolicy {
+    /// The default for [`WeightedOptionPolicy::with_tag_prefix`].
+    pub const DEFAULT_TAG_PREFIX: &'static str = "weight:";
+
+    /// Creates a policy that reads weights from the given tags of each line.
+    pub fn new(tags: impl IntoIterator<Item = (LineId, Vec<String>)>) -> Self {
+        Self {
+            tags: tags.into_iter().collect(),
+            tag_prefix: Self::DEFAULT_TAG_PREFIX.to_owned(),
+        }
+    }
+
+    /// Sets the prefix of the tags that contain the weight. Default is [`WeightedOptionPolicy::DEFAULT_TAG_PREFIX`].
+    #[must_use]
+    pub fn with_tag_prefix(mut self, tag_prefix: impl Into<String>) -> Self {
+        self.tag_prefix = tag_prefix.into();
+        self
+    }
+
+    /// The weight of the option with the given line, i.e. the number in its first weight tag that is a valid, non-negative number, or `1`.
+    pub fn weight(&self, line_id: &LineId) -> f32 {
+        self.tags
+            .get(line_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|tag| tag.strip_prefix(&self.tag_prefix)?.parse::<f32>().ok())
+            .find(|weight| weight.is_finite() && *weight >= 0.0)
+            .unwrap_or(1.0)
+    }
+}
+
+impl OptionSelectionPolicy for WeightedOptionPolicy {
+    fn select(&mut self, options: &[DialogueOption], rng: &mut dyn RngCore) -> Option<OptionId> {
+        select_weighted(options, rng, |option| self.weight(&option.line.id))
+    }
+}
+
+/// An [`OptionSelectionPolicy`] that follows a script of options, e.g. to simulate a player who always visits the shop.
+///
+/// Whenever options are presented, the policy selects the next option of the script if it is available and moves on to the one after.
+/// Otherwise, or once the script is done, it selects one of the available options with equal probability.
+/// The script starts over in every playthrough.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptedOptionPolicy {
+    script: Vec<LineId>,
+    position: usize,
+}
+
+impl ScriptedOptionPolicy {
+    /// Creates a policy that selects the options with the given lines, in order.
+    pub fn new(script: impl IntoIterator<Item = impl Into<LineId>>) -> Self {
+        Self {
+            script: script.into_iter().map(Into::into).collect(),
+            position: 0,
+        }
+    }
+}
+
+impl OptionSelectionPolicy for ScriptedOptionPolicy {
+    fn select(&mut self, options: &[DialogueOption], rng: &mut dyn RngCore) -> Option<OptionId> {
+        let scripted_option = self.script.get(self.position).and_then(|line_id| {
+            options
+                .iter()
+                .find(|option| option.is_available && &option.line.id == line_id)
+        });
+        match scripted_option {
+            Some(option) => {
+                self.position += 1;
+                Some(option.id)
+            }
+            None => UniformOptionPolicy.select(options, rng),
+        }
+    }
+
+    fn start_playthrough(&mut self) {
+        self.position = 0;
+    }
+}
+
+/// Selects one of the available options with a probability proportional to its weight. Options with a weight of `0` are never selected.
+fn select_weighted(
+    options: &[DialogueOption],
+    rng: &mut dyn RngCore,
+    weight: impl Fn(&DialogueOption) -> f32,
+) -> Option<OptionId> {
+    let weighted: Vec<_> = options
+        .iter()
+        .filter(|option| option.is_available)
+        .map(|option| (option.id, weight(option)))
+        .filter(|(_, weight)| *weight > 0.0)
+        .collect();
+    let total: f32 = weighted.iter().map(|(_, weight)| weight).sum();
+    if weighted.is_empty() {
+        return None;
+    }
+    let mut remaining = rng.gen_range(0.0..total);
+    for (id, weight) in &weighted {
+        if remaining < *weight {
+            return Some(*id);
+        }
+        remaining -= weight;
+    }
+    // Only reachable through floating point rounding
+    weighted.last().map(|(id, _)| *id)
+}
+
+/// The aggregated results of all playthroughs of a [`PlaythroughSimulator`].
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct SimulationReport {
+    /// The number of simulated playthroughs.
+    pub playthroughs: usize,
+
+    /// The number of playthroughs that ended with [`DialogueEvent::DialogueComplete`].
+    pub completed: usize,
+
+    /// The number of playthroughs that ended because the [`OptionSelectionPolicy`] could not select any option,
+    /// which usually means that none of the presented options were available.
+    pub stuck: usize,
+
+    /// The number of playthroughs that were stopped after [`PlaythroughSimulator::with_max_steps`].
+    pub truncated: usize,
+
+    /// The values of each variable at the end of the playthroughs, without the variables used internally by Yarn Spinner.
+    pub variables: BTreeMap<String, ValueDistribution>,
+
+    /// How often each node was entered.
+    pub node_visits: BTreeMap<String, NodeVisitStatistics>,
+
+    /// How many playthroughs selected how many options, i.e. a histogram of path lengths.
+    pub path_lengths: BTreeMap<usize, usize>,
+}
+
+impl SimulationReport {
+    /// The average number of options selected per playthrough, or `None` if there were no playthroughs.
+    pub fn mean_path_length(&self) -> Option<f32> {
+        let total: usize = self
+            .path_lengths
+            .iter()
+            .map(|(length, count)| length * count)
+            .sum();
+        (self.playthroughs > 0).then(|| total as f32 / self.playthroughs as f32)
+    }
+
+    /// The fraction of playthroughs that entered the given node at least once.
+    pub fn visit_rate(&self, node_name: &str) -> f32 {
+        let playthroughs = self
+            .node_visits
+            .get(node_name)
+            .map_or(0, |statistics| statistics.playthroughs);
+        if self.playthroughs == 0 {
+            0.0
+        } else {
+            playthroughs as f32 / self.playthroughs as f32
+        }
+    }
+}
+
+/// How often a node was entered over the course of a simulation. See [`SimulationReport::node_visits`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NodeVisitStatistics {
+    /// The number of times the node was entered, over all playthroughs.
+    pub total_visits: usize,
+    /// The number of playthroughs that entered the node at least once.
+    pub playthroughs: usize,
+}
+
+/// The distinct values a variable had at the end of the playthroughs of a simulation, together with how often each occurred.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValueDistribution {
+    counts: Vec<(YarnValue, usize)>,
+}
+
+impl ValueDistribution {
+    /// Counts another occurrence of the value.
+    pub fn add(&mut self, value: YarnValue) {
+        // `YarnValue` is neither `Hash` nor `Ord`, but variables rarely end up with many distinct values
+        match self
+            .counts
+            .iter_mut()
+            .find(|(existing, _)| *existing == value)
+        {
+            Some((_, count)) => *count += 1,
+            None => self.counts.push((value, 1)),
+        }
+    }
+
+    /// The distinct values and how often each occurred, in the order they first occurred.
+    pub fn counts(&self) -> &[(YarnValue, usize)] {
+        &self.counts
+    }
+
+    /// The number of values that were counted.
+    pub fn total(&self) -> usize {
+        self.counts.iter().map(|(_, count)| count).sum()
+    }
+
+    /// The value that occurred most often. Ties are broken in favor of the value that occurred first.
+    pub fn most_common(&self) -> Option<&YarnValue> {
+        self.counts
+            .iter()
+            .rev()
+            .max_by_key(|(_, count)| count)
+            .map(|(value, _)| value)
+    }
+
+    /// The average of all values, or `None` if there are no values or any of them is not a number.
+    pub fn mean(&self) -> Option<f32> {
+        let numbers = self.numbers()?;
+        let total = self.total();
+        (total > 0).then(|| {
+            numbers
+                .iter()
+                .map(|(number, count)| number * *count as f32)
+                .sum::<f32>()
+                / total as f32
+        })
+    }
+
+    /// The smallest value, or `None` if there are no values or any of them is not a number.
+    pub fn min(&self) -> Option<f32> {
+        self.numbers()?
+            .into_iter()
+            .map(|(number, _)| number)
+            .reduce(f32::min)
+    }
+
+    /// The largest value, or `None` if there are no values or any of them is not a number.
+    pub fn max(&self) -> Option<f32> {
+        self.numbers()?
+            .into_iter()
+            .map(|(number, _)| number)
+            .reduce(f32::max)
+    }
+
+    fn numbers(&self) -> Option<Vec<(f32, usize)>> {
+        self.counts
+            .iter()
+            .map(|(value, count)| match value {
+                YarnValue::Number(number) => Some((*number, *count)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl Display for SimulationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} playthroughs: {} completed, {} stuck, {} truncated",
+            self.playthroughs, self.completed, self.stuck, self.truncated
+        )?;
+        if let Some(mean) = self.mean_path_length() {
+            writeln!(f, "Options selected per playthrough: {mean:.2} on average")?;
+        }
+        writeln!(f, "Nodes:")?;
+        for (node_name, statistics) in &self.node_visits {
+            writeln!(
+                f,
+                "    {node_name}: entered in {:.1}% of playthroughs, {} times in total",
+                self.visit_rate(node_name) * 100.0,
+                statistics.total_visits
+            )?;
+        }
+        writeln!(f, "Variables:")?;
+        for (name, distribution) in &self.variables {
+            write!(f, "    {name}: ")?;
+            match (distribution.mean(), distribution.min(), distribution.max()) {
+                (Some(mean), Some(min), Some(max)) => {
+                    writeln!(f, "mean {mean:.2}, min {min}, max {max}")?
+                }
+                _ => {
+                    let counts: Vec<_> = distribution
+                        .counts()
+                        .iter()
+                        .map(|(value, count)| format!("{value} ({count})"))
+                        .collect();
+                    writeln!(f, "{}", counts.join(", "))?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn set_variables(
+    dialogue: &mut Dialogue,
+    variables: HashMap<String, YarnValue>,
+) -> crate::Result<()> {
+    let variable_storage = dialogue.variable_storage_mut();
+    variable_storage.clear();
+    variable_storage.extend(variables)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHOP: &str = r#"
+        .node "Start"
+            ADD_OPTION "line:buy" "buy" 0 false
+            ADD_OPTION "line:leave" "leave" 0 false
+            ADD_OPTION "line:steal" "steal" 0 false
+            SHOW_OPTIONS
+            JUMP
+        buy:
+            PUSH_VARIABLE "$gold"
+            PUSH_FLOAT 10
+            PUSH_FLOAT 2
+            CALL_FUNC "Number.Subtract"
+            STORE_VARIABLE "$gold"
+            POP
+            PUSH_STRING "Start"
+            RUN_NODE
+        leave:
+            STOP
+        steal:
+            PUSH_STRING "Prison"
+            RUN_NODE
+        .node "Prison"
+            RUN_LINE "line:caught" 0
+            STOP
+        "#;
+
+    fn dialogue(listing: &str) -> Dialogue {
+        let mut text_provider = StringTableTextProvider::new();
+        text_provider.extend_base_language(
+            ["line:buy", "line:leave", "line:steal", "line:caught"]
+                .into_iter()
+                .map(|id| (id.into(), String::new()))
+                .collect(),
+        );
+        let mut dialogue = Dialogue::new(
+            Box::new(MemoryVariableStorage::new()),
+            Box::new(text_provider),
+        );
+        dialogue.add_program(Program::assemble(listing).unwrap());
+        dialogue
+            .variable_storage_mut()
+            .set("$gold".to_owned(), 100.0.into())
+            .unwrap();
+        dialogue
+    }
+
+    #[test]
+    fn aggregates_playthroughs() {
+        let mut dialogue = dialogue(SHOP);
+        let report = PlaythroughSimulator::new("Start")
+            .with_playthroughs(300)
+            .with_seed(1)
+            .run(&mut dialogue)
+            .unwrap();
+        assert_eq!(300, report.playthroughs);
+        assert_eq!(300, report.completed);
+        assert_eq!(300, report.node_visits["Start"].playthroughs);
+        assert!(report.node_visits["Start"].total_visits > 300);
+        let prison_rate = report.visit_rate("Prison");
+        assert!(prison_rate > 0.3 && prison_rate < 0.7, "{prison_rate}");
+
+        let gold = &report.variables["$gold"];
+        assert_eq!(300, gold.total());
+        assert_eq!(Some(100.0), gold.max());
+        assert!(gold.mean().unwrap() < 100.0);
+        assert_eq!(Some(&YarnValue::from(100.0)), gold.most_common());
+        assert_eq!(300, report.path_lengths.values().sum::<usize>());
+
+        assert_eq!(
+            Ok(YarnValue::from(100.0)),
+            dialogue
+                .variable_storage()
+                .get("$gold")
+                .map_err(|e| e.to_string()),
+            "Variables are reset after the simulation"
+        );
+        let same_seed = PlaythroughSimulator::new("Start")
+            .with_playthroughs(300)
+            .with_seed(1)
+            .run(&mut dialogue)
+            .unwrap();
+        assert_eq!(report, same_seed);
+    }
+
+    #[test]
+    fn follows_policies() {
+        let mut dialogue = dialogue(SHOP);
+        let report = PlaythroughSimulator::new("Start")
+            .with_playthroughs(50)
+            .with_policy(ScriptedOptionPolicy::new([
+                "line:buy",
+                "line:buy",
+                "line:leave",
+            ]))
+            .run(&mut dialogue)
+            .unwrap();
+        assert_eq!(
+            &[(YarnValue::from(80.0), 50)],
+            report.variables["$gold"].counts()
+        );
+        assert_eq!(Some(&50), report.path_lengths.get(&3));
+
+        let policy = WeightedOptionPolicy::new([
+            ("line:buy".into(), vec!["weight:0".to_owned()]),
+            ("line:steal".into(), vec!["weight:0".to_owned()]),
+        ]);
+        let report = PlaythroughSimulator::new("Start")
+            .with_playthroughs(50)
+            .with_policy(policy)
+            .run(&mut dialogue)
+            .unwrap();
+        assert_eq!(Some(&50), report.path_lengths.get(&1));
+        assert!(!report.node_visits.contains_key("Prison"));
+    }
+
+    #[test]
+    fn truncates_endless_playthroughs() {
+        let mut dialogue = dialogue(SHOP);
+        let report = PlaythroughSimulator::new("Start")
+            .with_playthroughs(5)
+            .with_max_steps(20)
+            .with_policy(ScriptedOptionPolicy::new(["line:buy"; 100]))
+            .run(&mut dialogue)
+            .unwrap();
+        assert_eq!(5, report.truncated);
+        assert_eq!(0, report.completed);
+    }
+}
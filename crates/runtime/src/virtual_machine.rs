@@ -4,15 +4,18 @@
 //! The `Operand` extensions and the `Operator` enum were moved into upstream crates to make them not depend on the runtime.
 
 pub(crate) use self::{execution_state::*, state::*};
-use crate::markup::{LineParser, ParsedMarkup};
+use crate::markup::{AttributeMarkerProcessor, LineParser, ParsedMarkup};
 use crate::prelude::*;
 use crate::Result;
 use log::*;
+pub use snapshot::DialogueSnapshot;
+use std::collections::HashMap;
 use std::fmt::Debug;
 use yarnspinner_core::prelude::OpCode;
 use yarnspinner_core::prelude::*;
 
 mod execution_state;
+mod snapshot;
 mod state;
 
 #[derive(Debug, Clone)]
@@ -21,6 +24,7 @@ pub(crate) struct VirtualMachine {
     pub(crate) program: Option<Program>,
     pub(crate) variable_storage: Box<dyn VariableStorage>,
     pub(crate) line_hints_enabled: bool,
+    pub(crate) coverage: Option<Coverage>,
     current_node_name: Option<String>,
     state: State,
     execution_state: ExecutionState,
@@ -29,6 +33,7 @@ pub(crate) struct VirtualMachine {
     line_parser: LineParser,
     text_provider: Box<dyn TextProvider>,
     language_code: Option<Language>,
+    saliency_strategy: Box<dyn SaliencyStrategy>,
 }
 
 impl Iterator for VirtualMachine {
@@ -61,6 +66,8 @@ impl VirtualMachine {
             current_node: Default::default(),
             batched_events: Default::default(),
             line_hints_enabled: Default::default(),
+            coverage: Default::default(),
+            saliency_strategy: Box::new(BestLeastRecentlySeenSaliencyStrategy),
         }
     }
 
@@ -80,6 +87,14 @@ impl VirtualMachine {
         self.variable_storage.as_mut()
     }
 
+    pub(crate) fn saliency_strategy(&self) -> &dyn SaliencyStrategy {
+        self.saliency_strategy.as_ref()
+    }
+
+    pub(crate) fn set_saliency_strategy(&mut self, saliency_strategy: Box<dyn SaliencyStrategy>) {
+        self.saliency_strategy = saliency_strategy;
+    }
+
     pub(crate) fn set_language_code(&mut self, language_code: impl Into<Option<Language>>) {
         let language_code = language_code.into();
         self.language_code.clone_from(&language_code);
@@ -87,6 +102,16 @@ impl VirtualMachine {
         self.text_provider.set_language(language_code);
     }
 
+    pub(crate) fn add_marker_processor(
+        &mut self,
+        attribute_name: String,
+        mut processor: Box<dyn AttributeMarkerProcessor>,
+    ) {
+        processor.set_language_code(self.language_code.clone());
+        self.line_parser
+            .add_marker_processor(attribute_name, processor);
+    }
+
     pub(crate) fn reset_state(&mut self) {
         self.state = State::default();
         self.current_node_name = None;
@@ -117,6 +142,9 @@ impl VirtualMachine {
         self.reset_state();
 
         self.current_node_name = Some(node_name.clone());
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record_node(&node_name);
+        }
 
         self.batched_events
             .push(DialogueEvent::NodeStart(node_name));
@@ -143,16 +171,16 @@ impl VirtualMachine {
             // line or add an option; these are the two instructions
             // that will signal a line can appear to the player
             .filter_map(|instruction| {
-                let opcode: OpCode = instruction.opcode.try_into().unwrap();
-                [OpCode::RunLine, OpCode::AddOption]
-                    .contains(&opcode)
-                    .then(|| {
-                        // Both RunLine and AddOption have the string ID
-                        // they want to show as their first operand, so
-                        // store that
-                        let id: String = instruction.operands[0].clone().try_into().unwrap();
-                        LineId(id)
-                    })
+                let opcode: OpCode = instruction.opcode.try_into().ok()?;
+                if ![OpCode::RunLine, OpCode::AddOption].contains(&opcode) {
+                    return None;
+                }
+                // Both RunLine and AddOption have the string ID
+                // they want to show as their first operand, so
+                // store that.
+                // Invalid instructions are skipped here and reported once they are run.
+                let id: String = instruction.operands.first()?.clone().try_into().ok()?;
+                Some(LineId(id))
             })
             .collect();
         self.text_provider.accept_line_hints(&string_ids);
@@ -176,19 +204,49 @@ impl VirtualMachine {
             .program
             .as_ref()
             .ok_or_else(|| DialogueError::NoProgramLoaded)?;
-        assert!(
-            !program.nodes.is_empty(),
-            "Cannot load node \"{node_name}\": No nodes have been loaded.",
-        );
-
+        // Smart variables are stored as nodes, but they cannot be run as dialogue
         program
             .nodes
             .get(node_name)
+            .filter(|node| !node.is_smart_variable())
             .ok_or_else(|| DialogueError::InvalidNode {
                 node_name: node_name.to_owned(),
             })
     }
 
+    /// Returns the node that computes the value of the smart variable `variable_name`, if it is one.
+    fn get_smart_variable_node(&self, variable_name: &str) -> Option<Node> {
+        self.program
+            .as_ref()?
+            .nodes
+            .get(variable_name)
+            .filter(|node| node.is_smart_variable())
+            .cloned()
+    }
+
+    /// Runs the instructions of a smart variable's node, which leave the variable's current value on the stack.
+    /// The program counter and the stack of the current node are left untouched.
+    fn evaluate_smart_variable(&mut self, node: &Node) -> Result<InternalValue> {
+        let program_counter = self.state.program_counter;
+        let stack = std::mem::take(&mut self.state.stack);
+        let mut result = node
+            .instructions
+            .iter()
+            .try_for_each(|instruction| self.run_instruction(instruction))
+            .and_then(|_| self.state.pop_value());
+        if result.is_ok() && !self.state.stack.is_empty() {
+            result = Err(DialogueError::InvalidProgram {
+                reason: format!(
+                    "The smart variable \"{}\" left more than one value on the stack",
+                    node.name
+                ),
+            });
+        }
+        self.state.program_counter = program_counter;
+        self.state.stack = stack;
+        result
+    }
+
     /// Resumes execution.
     ///
     /// ## Implementation note
@@ -199,24 +257,102 @@ impl VirtualMachine {
         self.set_execution_state(ExecutionState::Running);
 
         while self.execution_state == ExecutionState::Running {
-            let current_node = self.current_node.clone().unwrap();
-            let current_instruction = &current_node.instructions[self.state.program_counter];
-            self.run_instruction(current_instruction)?;
-            // ## Implementation note
-            // The original increments the program counter here, but that leads to intentional underflow on [`OpCode::RunNode`],
-            // so we do the incrementation in [`VirtualMachine::run_instruction`] instead.
+            self.run_next_instruction()?;
+        }
+        Ok(std::mem::take(&mut self.batched_events))
+    }
+
+    /// Runs only the next instruction, unlike [`VirtualMachine::continue_`], which runs until there is content to deliver.
+    /// Used by the [`Debugger`].
+    pub(crate) fn step_instruction(&mut self) -> crate::Result<Vec<DialogueEvent>> {
+        self.assert_can_continue()?;
+        self.set_execution_state(ExecutionState::Running);
+        self.run_next_instruction()?;
+        Ok(std::mem::take(&mut self.batched_events))
+    }
 
+    fn run_next_instruction(&mut self) -> crate::Result<()> {
+        let current_node = self.running_node()?.clone();
+        let current_instruction = current_node
+            .instructions
+            .get(self.state.program_counter)
+            .ok_or_else(|| DialogueError::InvalidProgram {
+                reason: format!(
+                    "Instruction {} does not exist in node \"{}\"",
+                    self.state.program_counter, current_node.name
+                ),
+            })?;
+        self.run_instruction(current_instruction)?;
+        // ## Implementation note
+        // The original increments the program counter here, but that leads to intentional underflow on [`OpCode::RunNode`],
+        // so we do the incrementation in [`VirtualMachine::run_instruction`] instead.
+
+        // The instruction may have switched to another node, e.g. when returning from a detour
+        loop {
+            let current_node = self.running_node()?;
             if self.state.program_counter < current_node.instructions.len() {
-                continue;
+                return Ok(());
             }
 
             self.batched_events
                 .push(DialogueEvent::NodeComplete(current_node.name.clone()));
-            self.set_execution_state(ExecutionState::Stopped);
-            self.batched_events.push(DialogueEvent::DialogueComplete);
-            debug!("Run complete.");
+            if !self.return_from_detour()? {
+                break;
+            }
         }
-        Ok(std::mem::take(&mut self.batched_events))
+        self.set_execution_state(ExecutionState::Stopped);
+        self.batched_events.push(DialogueEvent::DialogueComplete);
+        debug!("Run complete.");
+        Ok(())
+    }
+
+    /// Continues the node that started the innermost `<<detour>>` where it left off.
+    /// The node is not started again, so neither [`DialogueEvent::NodeStart`] nor line hints are sent.
+    ///
+    /// Returns `false` if the current node was not entered via `<<detour>>`.
+    fn return_from_detour(&mut self) -> Result<bool> {
+        let Some(frame) = self.state.call_stack.pop() else {
+            return Ok(false);
+        };
+        debug!("Returning to node \"{}\"", frame.node_name);
+        self.current_node = Some(self.get_node_from_name(&frame.node_name)?.clone());
+        self.current_node_name = Some(frame.node_name);
+        self.state.program_counter = frame.program_counter;
+        self.state.stack = frame.stack;
+        self.state.locals = frame.locals;
+        self.state.saliency_candidates.clear();
+        Ok(true)
+    }
+
+    /// The index of the instruction that runs next in the current node.
+    pub(crate) fn program_counter(&self) -> usize {
+        self.state.program_counter
+    }
+
+    /// The instruction that runs next, if any.
+    pub(crate) fn current_instruction(&self) -> Option<&Instruction> {
+        self.current_node
+            .as_ref()?
+            .instructions
+            .get(self.state.program_counter)
+    }
+
+    /// The values on the stack, from bottom to top.
+    pub(crate) fn stack_values(&self) -> Vec<YarnValue> {
+        self.state.stack.iter().cloned().map(Into::into).collect()
+    }
+
+    /// The values of the `<<local>>` variables of the current node.
+    pub(crate) fn locals(&self) -> &HashMap<String, YarnValue> {
+        &self.state.locals
+    }
+
+    /// The nodes waiting for a `<<detour>>` to return, together with the instruction they continue at, innermost last.
+    pub(crate) fn detour_frames(&self) -> impl DoubleEndedIterator<Item = (&str, usize)> {
+        self.state
+            .call_stack
+            .iter()
+            .map(|frame| (frame.node_name.as_str(), frame.program_counter))
     }
 
     pub(crate) fn parse_markup(&mut self, line: &str) -> crate::markup::Result<ParsedMarkup> {
@@ -251,6 +387,10 @@ impl VirtualMachine {
             });
         }
 
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record_option_selected(&self.state.current_options[selected_option_id.0]);
+        }
+
         // We now know what number option was selected; push the
         // corresponding node name to the stack.
         let destination_node = self.state.current_options[selected_option_id.0]
@@ -283,23 +423,29 @@ impl VirtualMachine {
     ///
     /// Increments the program counter here instead of in `continue_` for cleaner code
     fn run_instruction(&mut self, instruction: &Instruction) -> crate::Result<()> {
-        let opcode: OpCode = instruction.opcode.try_into().unwrap();
+        let opcode: OpCode =
+            instruction
+                .opcode
+                .try_into()
+                .map_err(|_| DialogueError::InvalidProgram {
+                    reason: InvalidOpCodeError(instruction.opcode).to_string(),
+                })?;
         match opcode {
             OpCode::JumpTo => {
                 // Jumps to a named label
-                let label_name: String = instruction.read_operand(0);
-                self.state.program_counter = self.find_instruction_point_for_label(&label_name);
+                let label_name: String = self.read_operand(instruction, 0)?;
+                self.state.program_counter = self.find_instruction_point_for_label(&label_name)?;
             }
             OpCode::Jump => {
                 // Jumps to a label whose name is on the stack.
-                let jump_destination: String = self.state.peek();
+                let jump_destination: String = self.state.peek()?;
                 self.state.program_counter =
-                    self.find_instruction_point_for_label(&jump_destination);
+                    self.find_instruction_point_for_label(&jump_destination)?;
             }
             OpCode::RunLine => {
                 // Looks up a string from the string table and passes it to the client as a line
 
-                let string_id: String = instruction.read_operand(0);
+                let string_id: String = self.read_operand(instruction, 0)?;
                 let string_id: LineId = string_id.into();
 
                 // The second operand, if provided (compilers prior
@@ -307,10 +453,13 @@ impl VirtualMachine {
                 // of expressions in the line. We need to pop these
                 // values off the stack and deliver them to the
                 // line handler.
-                assert_up_to_date_compiler(instruction.operands.len() >= 2);
+                assert_up_to_date_compiler(instruction, 2)?;
 
-                let substitutions = self.pop_substitutions_with_count_at_operand(instruction, 1);
+                let substitutions = self.pop_substitutions_with_count_at_operand(instruction, 1)?;
                 let line = self.prepare_line(string_id, &substitutions)?;
+                if let Some(coverage) = &mut self.coverage {
+                    coverage.record_line(&line.id);
+                }
 
                 self.batched_events.push(DialogueEvent::Line(line));
 
@@ -324,16 +473,16 @@ impl VirtualMachine {
             }
             OpCode::RunCommand => {
                 // Passes a string to the client as a custom command
-                let command_text: String = instruction.read_operand(0);
-                assert_up_to_date_compiler(instruction.operands.len() >= 2);
+                let command_text: String = self.read_operand(instruction, 0)?;
+                assert_up_to_date_compiler(instruction, 2)?;
                 let command_text = self
-                    .pop_substitutions_with_count_at_operand(instruction, 1)
+                    .pop_substitutions_with_count_at_operand(instruction, 1)?
                     .into_iter()
                     .enumerate()
                     .fold(command_text, |command_text, (i, substitution)| {
                         command_text.replace(&format!("{{{i}}}"), &substitution)
                     });
-                let command = Command::parse(command_text);
+                let command = Command::parse(command_text)?;
 
                 self.batched_events.push(DialogueEvent::Command(command));
 
@@ -347,29 +496,29 @@ impl VirtualMachine {
             }
             OpCode::AddOption => {
                 // Add an option to the current state
-                let string_id: String = instruction.read_operand(0);
+                let string_id: String = self.read_operand(instruction, 0)?;
                 let string_id: LineId = string_id.into();
-                assert_up_to_date_compiler(instruction.operands.len() >= 4);
-                let substitutions = self.pop_substitutions_with_count_at_operand(instruction, 2);
+                assert_up_to_date_compiler(instruction, 4)?;
+                let substitutions = self.pop_substitutions_with_count_at_operand(instruction, 2)?;
                 let line = self.prepare_line(string_id, &substitutions)?;
 
                 // Indicates whether the VM believes that the
                 // option should be shown to the user, based on any
                 // conditions that were attached to the option.
-                let line_condition_passed = if instruction.read_operand(3) {
+                let line_condition_passed = if self.read_operand(instruction, 3)? {
                     // The fourth operand is a bool that indicates
                     // whether this option had a condition or not.
                     // If it does, then a bool value will exist on
                     // the stack indicating whether the condition
                     // passed or not. We pass that information to
                     // the game.
-                    self.state.pop()
+                    self.state.pop()?
                 } else {
                     true
                 };
 
                 let index = self.state.current_options.len();
-                let node_name = instruction.read_operand(1);
+                let node_name = self.read_operand(instruction, 1)?;
                 // ## Implementation note:
                 // The original calculates the ID in the `ShowOptions` opcode,
                 // but this way is cleaner because it allows us to store a `DialogueOption` instead of a bunch of values in a big tuple.
@@ -397,6 +546,9 @@ impl VirtualMachine {
                 // delegate for them to call when the user has made
                 // a selection
                 let current_options = self.state.current_options.clone();
+                if let Some(coverage) = &mut self.coverage {
+                    coverage.record_options_shown(&current_options);
+                }
                 self.batched_events
                     .push(DialogueEvent::Options(current_options));
 
@@ -406,32 +558,34 @@ impl VirtualMachine {
             }
             OpCode::PushString => {
                 // Pushes a string value onto the stack. The operand is an index into the string table, so that's looked up first.
-                let string_table_index: String = instruction.read_operand(0);
+                let string_table_index: String = self.read_operand(instruction, 0)?;
                 self.state.push(string_table_index);
                 self.state.program_counter += 1;
             }
             OpCode::PushFloat => {
                 // Pushes a floating point onto the stack.
-                let float: f32 = instruction.read_operand(0);
+                let float: f32 = self.read_operand(instruction, 0)?;
                 self.state.push(float);
                 self.state.program_counter += 1;
             }
             OpCode::PushBool => {
                 // Pushes a boolean value onto the stack.
-                let boolean: bool = instruction.read_operand(0);
+                let boolean: bool = self.read_operand(instruction, 0)?;
                 self.state.push(boolean);
                 self.state.program_counter += 1;
             }
 
             OpCode::PushNull => {
-                panic!("PushNull is no longer valid op code, because null is no longer a valid value from Yarn Spinner 2.0 onwards. To fix this error, re-compile the original source code.");
+                return Err(DialogueError::InvalidProgram {
+                    reason: "PushNull is no longer valid op code, because null is no longer a valid value from Yarn Spinner 2.0 onwards".to_owned(),
+                });
             }
             OpCode::JumpIfFalse => {
                 // Jumps to a named label if the value on the top of the stack evaluates to the boolean value 'false'.
-                let is_top_value_true: bool = self.state.peek();
+                let is_top_value_true: bool = self.state.peek()?;
                 if !is_top_value_true {
-                    let label_name: String = instruction.read_operand(0);
-                    let instruction_point = self.find_instruction_point_for_label(&label_name);
+                    let label_name: String = self.read_operand(instruction, 0)?;
+                    let instruction_point = self.find_instruction_point_for_label(&label_name)?;
                     self.state.program_counter = instruction_point;
                 } else {
                     self.state.program_counter += 1;
@@ -439,23 +593,23 @@ impl VirtualMachine {
             }
             OpCode::Pop => {
                 // Pops a value from the stack.
-                self.state.pop_value();
+                self.state.pop_value()?;
                 self.state.program_counter += 1;
             }
             OpCode::CallFunc => {
-                let actual_parameter_count: usize = self.state.pop();
+                let actual_parameter_count: usize = self.state.pop()?;
                 // Get the parameters, which were pushed in reverse
                 let parameters = {
-                    let mut parameters: Vec<_> = (0..actual_parameter_count)
+                    let mut parameters = (0..actual_parameter_count)
                         .rev()
-                        .map(|_| self.state.pop_value().raw_value)
-                        .collect();
+                        .map(|_| self.state.pop_value().map(|value| value.raw_value))
+                        .collect::<Result<Vec<_>>>()?;
                     parameters.reverse();
                     parameters
                 };
 
                 // Call a function, whose parameters are expected to be on the stack. Pushes the function's return value, if it returns one.
-                let function_name: String = instruction.read_operand(0);
+                let function_name: String = self.read_operand(instruction, 0)?;
                 let function =
                     self.library
                         .get(&function_name)
@@ -468,13 +622,22 @@ impl VirtualMachine {
                 // actually passed at the top of the stack.
                 let expected_parameter_count = function.parameter_types().len();
 
-                assert_eq!(
-                    expected_parameter_count, actual_parameter_count,
-                    "Function {function_name} expected {expected_parameter_count} parameters, but received {actual_parameter_count}",
-                );
+                if expected_parameter_count != actual_parameter_count {
+                    return Err(DialogueError::FunctionArityMismatch {
+                        function_name,
+                        expected: expected_parameter_count,
+                        actual: actual_parameter_count,
+                    });
+                }
 
                 // Invoke the function
-                let return_value = function.call(parameters);
+                let return_value =
+                    function
+                        .call(parameters)
+                        .map_err(|source| DialogueError::FunctionError {
+                            function_name: function_name.clone(),
+                            source,
+                        })?;
                 let return_type = function
                     .return_type()
                     .try_into()
@@ -491,12 +654,22 @@ impl VirtualMachine {
             }
             OpCode::PushVariable => {
                 // Get the contents of a variable, push that onto the stack.
-                let variable_name: String = instruction.read_operand(0);
-                let loaded_value = self
-                    .variable_storage
-                    .get(&variable_name)
-                    .or_else(|e| {
-                        if let VariableStorageError::VariableNotFound { .. } = e {
+                let variable_name: String = self.read_operand(instruction, 0)?;
+                let loaded_value = if Library::is_local_variable_name(&variable_name) {
+                    // Locals are initialized at the start of their node, so they always have a value
+                    self.state
+                        .locals
+                        .get(&variable_name)
+                        .cloned()
+                        .ok_or_else(|| DialogueError::MissingInitialValue {
+                            variable_name: variable_name.clone(),
+                        })?
+                } else if let Some(smart_variable) = self.get_smart_variable_node(&variable_name) {
+                    self.evaluate_smart_variable(&smart_variable)?.into()
+                } else {
+                    match self.variable_storage.get(&variable_name) {
+                        Ok(value) => value,
+                        Err(VariableStorageError::VariableNotFound { .. }) => {
                             // We don't have a value for this. The initial
                             // value may be found in the program. (If it's
                             // not, then the variable's value is undefined,
@@ -504,52 +677,142 @@ impl VirtualMachine {
                             let initial_value = self
                                 .program
                                 .as_ref()
-                                .unwrap()
-                                .initial_values
-                                .get(&variable_name)
-                                .unwrap_or_else(|| panic!("The loaded program does not contain an initial value for the variable {variable_name}"))
+                                .and_then(|program| program.initial_values.get(&variable_name))
+                                .ok_or_else(|| DialogueError::MissingInitialValue {
+                                    variable_name: variable_name.clone(),
+                                })?
                                 .clone();
 
                             // Store the initial value in the variable_storage
-                            self.variable_storage.set(variable_name.clone(), initial_value.clone().into())?;
+                            self.variable_storage
+                                .set(variable_name.clone(), initial_value.clone().into())?;
 
-                            Ok(initial_value.into())
-                        } else {
-                            Err(e)
+                            initial_value.into()
                         }
-                    })?;
+                        Err(e) => return Err(e.into()),
+                    }
+                };
                 self.state.push(loaded_value);
                 self.state.program_counter += 1;
             }
             OpCode::StoreVariable => {
                 // Store the top value on the stack in a variable.
-                let top_value = self.state.peek_value().clone();
-                let variable_name: String = instruction.read_operand(0);
-                self.variable_storage.set(variable_name, top_value.into())?;
+                let top_value = self.state.peek_value()?.clone();
+                let variable_name: String = self.read_operand(instruction, 0)?;
+                if Library::is_local_variable_name(&variable_name) {
+                    self.state.locals.insert(variable_name, top_value.into());
+                } else {
+                    self.variable_storage.set(variable_name, top_value.into())?;
+                }
                 self.state.program_counter += 1;
             }
             OpCode::Stop => {
-                // Immediately stop execution, and report that fact.
-                let current_node_name = self.current_node_name.clone().unwrap();
+                // The stop at the end of a node is where the node ends by itself,
+                // so inside a detour the node that started it continues.
+                // Any other stop ends the whole dialogue, including every node waiting for a detour.
+                let current_node = self.running_node()?;
+                let is_end_of_node =
+                    self.state.program_counter + 1 == current_node.instructions.len();
+                let current_node_name = current_node.name.clone();
                 self.batched_events
                     .push(DialogueEvent::NodeComplete(current_node_name));
+                if is_end_of_node && self.return_from_detour()? {
+                    return Ok(());
+                }
+                while let Some(frame) = self.state.call_stack.pop() {
+                    self.batched_events
+                        .push(DialogueEvent::NodeComplete(frame.node_name));
+                }
                 self.batched_events.push(DialogueEvent::DialogueComplete);
                 self.set_execution_state(ExecutionState::Stopped);
 
                 self.state.program_counter += 1;
             }
+            OpCode::Return => {
+                // Complete the current node. Inside a detour, the node that started it continues.
+                let current_node_name = self.running_node()?.name.clone();
+                self.batched_events
+                    .push(DialogueEvent::NodeComplete(current_node_name));
+                if !self.return_from_detour()? {
+                    self.batched_events.push(DialogueEvent::DialogueComplete);
+                    self.set_execution_state(ExecutionState::Stopped);
+
+                    self.state.program_counter += 1;
+                }
+            }
             OpCode::RunNode => {
                 // Run a node
 
                 // Pop a string from the stack, and jump to a node
                 // with that name.
-                let node_name: String = self.state.pop();
+                let node_name: String = self.state.pop()?;
                 self.batched_events
                     .push(DialogueEvent::NodeComplete(node_name.clone()));
+                // A jump inside a detour replaces the detoured node,
+                // so the end of the new node returns from the detour instead.
+                let call_stack = std::mem::take(&mut self.state.call_stack);
                 self.set_node(&node_name)?;
+                self.state.call_stack = call_stack;
 
                 // No need to increment the program counter, since otherwise we'd skip the first instruction
             }
+            OpCode::DetourToNode => {
+                // Pop a string from the stack, and run the node with that name.
+                // Remember where we are, so that we can continue here once it completes.
+                let node_name: String = self.state.pop()?;
+                let caller_name = self.running_node()?.name.clone();
+                let mut call_stack = std::mem::take(&mut self.state.call_stack);
+                call_stack.push(ReturnFrame {
+                    node_name: caller_name,
+                    program_counter: self.state.program_counter + 1,
+                    stack: std::mem::take(&mut self.state.stack),
+                    locals: std::mem::take(&mut self.state.locals),
+                });
+                self.set_node(&node_name)?;
+                self.state.call_stack = call_stack;
+
+                // No need to increment the program counter, since otherwise we'd skip the first instruction
+            }
+            OpCode::AddSaliencyCandidate => {
+                // Add a candidate for the next saliency selection.
+                // The result of its condition is on the stack.
+                let content_id = self.read_operand(instruction, 0)?;
+                let complexity_score: f32 = self.read_operand(instruction, 1)?;
+                let destination = self.read_operand(instruction, 2)?;
+                let is_available = self.state.pop()?;
+                self.state.saliency_candidates.push(SaliencyCandidate {
+                    content_id,
+                    is_available,
+                    complexity_score: complexity_score as usize,
+                    destination,
+                });
+                self.state.program_counter += 1;
+            }
+            OpCode::SelectSaliencyCandidate => {
+                // Let the saliency strategy pick one of the candidates.
+                // If it did, push its destination and `true`, otherwise push `false`.
+                let mut candidates = std::mem::take(&mut self.state.saliency_candidates);
+                let selected = self
+                    .saliency_strategy
+                    .select(&candidates, self.variable_storage.as_mut())?;
+                match selected {
+                    Some(index) if index >= candidates.len() => {
+                        return Err(DialogueError::InvalidProgram {
+                            reason: format!(
+                                "The saliency strategy selected candidate {index}, but there are only {} candidates",
+                                candidates.len()
+                            ),
+                        });
+                    }
+                    Some(index) => {
+                        let candidate = candidates.swap_remove(index);
+                        self.state.push(candidate.destination);
+                        self.state.push(true);
+                    }
+                    None => self.state.push(false),
+                }
+                self.state.program_counter += 1;
+            }
         }
         Ok(())
     }
@@ -575,50 +838,91 @@ impl VirtualMachine {
 
     /// Looks up the instruction number for a named label in the current node.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// Panics in the following cases:
-    /// - The label is not found in the current node
-    /// - The current node is unset
-    /// - The found instruction point is negative
-    fn find_instruction_point_for_label(&self, label_name: &str) -> usize {
-        self.current_node
-            .as_ref()
-            .unwrap()
+    /// Returns [`DialogueError::InvalidProgram`] if the label is not found in the current node or points to a negative instruction.
+    fn find_instruction_point_for_label(&self, label_name: &str) -> Result<usize> {
+        let current_node = self.running_node()?;
+        current_node
             .labels
             .get(label_name)
-            .copied()
-            .unwrap_or_else(|| {
-                panic!(
-                    "Unknown label {label_name} in node {}",
-                    self.current_node_name.as_ref().unwrap()
-                )
+            .and_then(|&instruction_point| instruction_point.try_into().ok())
+            .ok_or_else(|| DialogueError::InvalidProgram {
+                reason: format!(
+                    "Unknown label {label_name} in node \"{}\"",
+                    current_node.name
+                ),
+            })
+    }
+
+    /// The node whose instructions are being run.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DialogueError::InvalidProgram`] if no node is running.
+    fn running_node(&self) -> Result<&Node> {
+        self.current_node
+            .as_ref()
+            .ok_or_else(|| DialogueError::InvalidProgram {
+                reason: "No node is running".to_owned(),
+            })
+    }
+
+    /// Reads the operand at the given index of the instruction and converts it to the specified type.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DialogueError::InvalidProgram`] if the operand does not exist or cannot be converted.
+    fn read_operand<T>(&self, instruction: &Instruction, index: usize) -> Result<T>
+    where
+        T: TryFrom<Operand>,
+        <T as TryFrom<Operand>>::Error: Debug,
+    {
+        instruction
+            .operands
+            .get(index)
+            .cloned()
+            .ok_or_else(|| format!("missing operand {index}"))
+            .and_then(|operand| {
+                operand
+                    .try_into()
+                    .map_err(|e| format!("failed to convert operand {index}: {e:?}"))
+            })
+            .map_err(|e| DialogueError::InvalidProgram {
+                reason: format!(
+                    "Instruction {} in node \"{}\" has an invalid operand: {e}",
+                    self.state.program_counter,
+                    self.current_node_name.as_deref().unwrap_or_default(),
+                ),
             })
-            .try_into()
-            .unwrap()
     }
 
     fn pop_substitutions_with_count_at_operand(
         &mut self,
         instruction: &Instruction,
         index: usize,
-    ) -> Vec<String> {
-        let expression_count: usize = instruction.operands[index].clone().try_into().unwrap();
-        let mut values: Vec<_> = (0..expression_count)
+    ) -> Result<Vec<String>> {
+        let expression_count: usize = self.read_operand(instruction, index)?;
+        let mut values = (0..expression_count)
             .rev()
             .map(|_| self.state.pop())
-            .collect();
+            .collect::<Result<Vec<_>>>()?;
         values.reverse();
-        values
+        Ok(values)
     }
 }
 
-fn assert_up_to_date_compiler(predicate: bool) {
-    assert!(
-        predicate,
-        "The Yarn script provided was compiled using an older compiler. \
-        Please recompile it using the latest version of either Yarn Spinner or Yarn Spinner."
-    )
+fn assert_up_to_date_compiler(
+    instruction: &Instruction,
+    expected_operand_count: usize,
+) -> Result<()> {
+    if instruction.operands.len() >= expected_operand_count {
+        Ok(())
+    } else {
+        Err(DialogueError::InvalidProgram {
+            reason: "The Yarn script provided was compiled using an older compiler".to_owned(),
+        })
+    }
 }
 
 /// Replaces all substitution markers in a text with the given substitution list.
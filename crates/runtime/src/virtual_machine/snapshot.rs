@@ -0,0 +1,65 @@
+//! Allows saving and restoring the execution state of a [`VirtualMachine`] in the middle of a node.
+
+#[cfg(feature = "serde")]
+use crate::prelude::*;
+use crate::prelude::{Language, VirtualMachine};
+use crate::virtual_machine::{ExecutionState, State};
+
+/// A snapshot of the execution state of a [`Dialogue`](crate::prelude::Dialogue), created by [`Dialogue::snapshot`](crate::prelude::Dialogue::snapshot).
+///
+/// Restoring it via [`Dialogue::restore`](crate::prelude::Dialogue::restore) resumes the dialogue exactly where it was when the snapshot was taken,
+/// e.g. in the middle of a node or while waiting for an option to be selected.
+/// This includes the nodes that are waiting for a `<<detour>>` to return.
+///
+/// The snapshot only contains the execution state. Variables are not included, as they are owned by the [`VariableStorage`](crate::prelude::VariableStorage),
+/// which you should save alongside the snapshot. The exception are variables declared via `<<local>>`, which are part of the execution state of their node. The snapshot also does not contain the [`Program`](yarnspinner_core::prelude::Program) itself,
+/// so it must be restored into a [`Dialogue`](crate::prelude::Dialogue) that has the same program loaded as when the snapshot was taken.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DialogueSnapshot {
+    state: State,
+    execution_state: ExecutionState,
+    current_node_name: Option<String>,
+    language_code: Option<Language>,
+}
+
+impl DialogueSnapshot {
+    /// The name of the node that was being executed when the snapshot was taken, if any.
+    #[must_use]
+    pub fn current_node(&self) -> Option<&str> {
+        self.current_node_name.as_deref()
+    }
+
+    /// The language code that was set when the snapshot was taken.
+    #[must_use]
+    pub fn language_code(&self) -> Option<&Language> {
+        self.language_code.as_ref()
+    }
+}
+
+impl VirtualMachine {
+    pub(crate) fn snapshot(&self) -> DialogueSnapshot {
+        DialogueSnapshot {
+            state: self.state.clone(),
+            execution_state: self.execution_state,
+            current_node_name: self.current_node_name.clone(),
+            language_code: self.language_code.clone(),
+        }
+    }
+
+    /// Restores everything in the snapshot except for the language code, which is handled by the [`Dialogue`](crate::prelude::Dialogue).
+    pub(crate) fn restore(&mut self, snapshot: DialogueSnapshot) -> crate::Result<()> {
+        let current_node = snapshot
+            .current_node_name
+            .as_deref()
+            .map(|node_name| self.get_node_from_name(node_name).cloned())
+            .transpose()?;
+
+        self.current_node = current_node;
+        self.current_node_name = snapshot.current_node_name;
+        self.state = snapshot.state;
+        self.execution_state = snapshot.execution_state;
+        self.batched_events.clear();
+        Ok(())
+    }
+}
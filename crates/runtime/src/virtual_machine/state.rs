@@ -1,6 +1,7 @@
 //! Adapted from <https://github.com/YarnSpinnerTool/YarnSpinner/blob/da39c7195107d8211f21c263e4084f773b84eaff/YarnSpinner/VirtualMachine.cs>, which we split into multiple files
 
 use crate::prelude::*;
+use std::collections::HashMap;
 use std::fmt::Debug;
 use yarnspinner_core::prelude::*;
 
@@ -22,6 +23,39 @@ pub(crate) struct State {
 
     /// The value stack.
     pub(crate) stack: Vec<InternalValue>,
+
+    /// The values of the variables declared via `<<local>>` in the current node.
+    /// They are discarded whenever a new node starts and are never written to the [`VariableStorage`].
+    pub(crate) locals: HashMap<String, YarnValue>,
+
+    /// The candidates that will be passed to the [`SaliencyStrategy`]
+    /// when the next SelectSaliencyCandidate instruction is encountered.
+    pub(crate) saliency_candidates: Vec<SaliencyCandidate>,
+
+    /// Where to continue when the current node completes, if it was entered via `<<detour>>`.
+    /// The last frame belongs to the innermost detour.
+    pub(crate) call_stack: Vec<ReturnFrame>,
+}
+
+/// The part of the [`State`] of a node that is restored when returning to it from a detour.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(Reflect))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "bevy", reflect(Debug, PartialEq))]
+#[cfg_attr(
+    all(feature = "bevy", feature = "serde"),
+    reflect(Serialize, Deserialize)
+)]
+pub(crate) struct ReturnFrame {
+    /// The node that started the detour.
+    pub(crate) node_name: String,
+
+    /// The instruction after the one that started the detour.
+    pub(crate) program_counter: usize,
+
+    pub(crate) stack: Vec<InternalValue>,
+
+    pub(crate) locals: HashMap<String, YarnValue>,
 }
 
 impl State {
@@ -31,46 +65,47 @@ impl State {
 
     /// Pops a value from the stack and tries to convert it to the specified type.
     ///
-    /// ## Panics
-    /// - Panics on an empty stack to mirror C# behavior.
-    /// - Panics if the value cannot be converted to the specified type.
-    pub(crate) fn pop<T>(&mut self) -> T
+    /// ## Errors
+    /// - Returns [`DialogueError::StackUnderflow`] on an empty stack.
+    /// - Returns [`DialogueError::InvalidProgram`] if the value cannot be converted to the specified type.
+    pub(crate) fn pop<T>(&mut self) -> crate::Result<T>
     where
         T: TryFrom<InternalValue>,
         <T as TryFrom<InternalValue>>::Error: Debug,
     {
-        self.pop_value()
-            .try_into()
-            .unwrap_or_else(|e| panic!("Failed to convert popped value: {e:?}",))
+        convert_stack_value(self.pop_value()?)
     }
 
-    /// Pops a value from the stack. Panics on an empty stack to mirror C# behavior.
-    pub(crate) fn pop_value(&mut self) -> InternalValue {
-        self.stack
-            .pop()
-            .unwrap_or_else(|| panic!("Tried to pop value, but the stack was empty."))
+    /// Pops a value from the stack. Returns [`DialogueError::StackUnderflow`] on an empty stack.
+    pub(crate) fn pop_value(&mut self) -> crate::Result<InternalValue> {
+        self.stack.pop().ok_or(DialogueError::StackUnderflow)
     }
 
-    /// Peeks the top value of the stack. Panics on an empty stack to mirror C# behavior.
-    pub(crate) fn peek<T>(&self) -> T
+    /// Copies the top value of the stack and tries to convert it to the specified type.
+    ///
+    /// ## Errors
+    /// - Returns [`DialogueError::StackUnderflow`] on an empty stack.
+    /// - Returns [`DialogueError::InvalidProgram`] if the value cannot be converted to the specified type.
+    pub(crate) fn peek<T>(&self) -> crate::Result<T>
     where
         T: TryFrom<InternalValue>,
         <T as TryFrom<InternalValue>>::Error: Debug,
     {
-        self.peek_value()
-            .clone()
-            .try_into()
-            .unwrap_or_else(|e| panic!("Failed to convert popped value: {e:?}",))
+        convert_stack_value(self.peek_value()?.clone())
     }
 
-    /// Copies the top value of the stack and tries to convert it to the specified type.
-    ///
-    /// ## Panics
-    /// - Panics on an empty stack to mirror C# behavior.
-    /// - Panics if the value cannot be converted to the specified type.
-    pub(crate) fn peek_value(&self) -> &InternalValue {
-        self.stack
-            .last()
-            .unwrap_or_else(|| panic!("Tried to peek value, but the stack was empty."))
+    /// Peeks the top value of the stack. Returns [`DialogueError::StackUnderflow`] on an empty stack.
+    pub(crate) fn peek_value(&self) -> crate::Result<&InternalValue> {
+        self.stack.last().ok_or(DialogueError::StackUnderflow)
     }
 }
+
+fn convert_stack_value<T>(value: InternalValue) -> crate::Result<T>
+where
+    T: TryFrom<InternalValue>,
+    <T as TryFrom<InternalValue>>::Error: Debug,
+{
+    value.try_into().map_err(|e| DialogueError::InvalidProgram {
+        reason: format!("Failed to convert value on the stack: {e:?}"),
+    })
+}
@@ -10,29 +10,33 @@ pub use log;
 pub mod prelude {
     //! Everything you need to get started using Yarn Spinner.
     pub use crate::compiler::{
-        Compilation, CompilationType, Compiler as YarnCompiler, CompilerError, File as YarnFile,
-        LineInfo, Result as YarnCompilerResult, StringInfo,
+        Compilation, CompilationType, CompiledProject, Compiler as YarnCompiler, CompilerError,
+        File as YarnFile, LineInfo, Result as YarnCompilerResult, StringInfo,
     };
     pub use crate::core::{
         yarn_library, IntoYarnValueFromNonYarnValue, Library as YarnLibrary, LineId,
         Program as YarnProgram, YarnFn, YarnValue,
     };
     pub use crate::runtime::{
-        Command as YarnCommand, CompiledProgramAnalyser as YarnAnalyser,
+        AttributeMarkerProcessor, Command as YarnCommand, CompiledProgramAnalyser as YarnAnalyser,
         Context as YarnAnalysisContext, Dialogue, DialogueError, DialogueEvent, DialogueOption,
-        Language, Line as YarnLine, MarkupAttribute, MarkupValue, OptionId,
-        Result as YarnRuntimeResult, StringTable, TextProvider, VariableStorage,
+        DialogueSnapshot, Language, Line as YarnLine, MarkupAttribute, MarkupAttributeMarker,
+        MarkupValue, OptionId, Result as YarnRuntimeResult, SaliencyStrategy, StringTable,
+        TextProvider, VariableStorage,
     };
 }
 
 pub mod core {
     //! Core types and traits that are used by both the compiler and runtime.
     pub use yarnspinner_core::prelude::{
-        optionality, yarn_fn_type, yarn_library, Header, Instruction,
-        IntoYarnValueFromNonYarnValue, InvalidOpCodeError, Library, LineId, Node, Position,
-        Program, Type, UntypedYarnFn, YarnFn, YarnFnParam, YarnFnParamItem, YarnValue,
-        YarnValueCastError, YarnValueWrapper, YarnValueWrapperIter,
+        optionality, yarn_fn_type, yarn_library, CommandDeclaration, CommandLibrary,
+        CommandParameter, CommandParameterKind, Header, Instruction, IntoYarnValueFromNonYarnValue,
+        InvalidOpCodeError, Library, LineId, Node, OpCode, Operand, OperandValue, Position,
+        Program, ProgramDecodeError, Type, UntypedYarnFn, YarnFn, YarnFnError, YarnFnOut,
+        YarnFnParam, YarnFnParamItem, YarnValue, YarnValueCastError, YarnValueWrapper,
+        YarnValueWrapperIter,
     };
+    pub use yarnspinner_core::types::EnumType;
 }
 pub mod compiler {
     //! Types and traits used by the compiler, in particular the [`Compiler`] struct.
@@ -43,8 +47,9 @@ pub mod compiler {
 pub mod runtime {
     //! Types and traits used by the runtime, in particular the [`Dialogue`] struct.
     pub use yarnspinner_runtime::markup::{
-        MarkupAttribute, MarkupParseError, MarkupValue, CHARACTER_ATTRIBUTE,
-        CHARACTER_ATTRIBUTE_NAME_PROPERTY, TRIM_WHITESPACE_PROPERTY,
+        AttributeMarkerProcessor, MarkupAttribute, MarkupAttributeMarker, MarkupParseError,
+        MarkupValue, TagType, CHARACTER_ATTRIBUTE, CHARACTER_ATTRIBUTE_NAME_PROPERTY,
+        REPLACEMENT_MARKER_CONTENTS, TRIM_WHITESPACE_PROPERTY,
     };
     pub use yarnspinner_runtime::prelude::*;
     pub use yarnspinner_runtime::Result;
@@ -6,6 +6,7 @@
 use std::collections::HashMap;
 use test_base::prelude::*;
 use yarnspinner::compiler::*;
+use yarnspinner::core::*;
 use yarnspinner::runtime::*;
 
 mod test_base;
@@ -327,3 +328,1147 @@ fn test_selecting_option_from_inside_option_callback() {
         }
     }
 }
+
+#[test]
+fn test_restoring_snapshot_resumes_mid_node() {
+    let result = Compiler::from_test_source(
+        "first line\nsecond line\n-> option 1\n    picked 1\n-> option 2\n    picked 2\nfinal line\n",
+    )
+    .compile()
+    .unwrap();
+
+    let mut test_base = TestBase::new().with_compilation(result);
+    let dialogue = &mut test_base.dialogue;
+    dialogue.set_node("Start").unwrap();
+
+    assert_eq!("first line", next_line(dialogue));
+    let mid_node_snapshot = dialogue.snapshot();
+    assert_eq!(Some("Start"), mid_node_snapshot.current_node());
+
+    assert_eq!("second line", next_line(dialogue));
+    let events = dialogue.continue_().unwrap();
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, DialogueEvent::Options(options) if options.len() == 2)));
+    let options_snapshot = dialogue.snapshot();
+    dialogue.set_selected_option(OptionId(0)).unwrap();
+    assert_eq!("picked 1", next_line(dialogue));
+
+    dialogue.restore(options_snapshot).unwrap();
+    assert!(dialogue.is_waiting_for_option_selection());
+    dialogue.set_selected_option(OptionId(1)).unwrap();
+    assert_eq!("picked 2", next_line(dialogue));
+    assert_eq!("final line", next_line(dialogue));
+
+    dialogue.restore(mid_node_snapshot).unwrap();
+    assert_eq!(Some("Start".to_owned()), dialogue.current_node());
+    assert_eq!("second line", next_line(dialogue));
+}
+
+#[test]
+fn test_restoring_snapshot_fails_for_unknown_node() {
+    let result = Compiler::from_test_source("a line\n").compile().unwrap();
+    let mut source = TestBase::new().with_compilation(result);
+    source.dialogue.set_node("Start").unwrap();
+    let snapshot = source.dialogue.snapshot();
+
+    let result = Compiler::new()
+        .add_file(File {
+            file_name: "other.yarn".to_owned(),
+            source: "title: Other\n---\nanother line\n===\n".to_owned(),
+        })
+        .compile()
+        .unwrap();
+    let mut target = TestBase::new().with_compilation(result);
+    assert!(matches!(
+        target.dialogue.restore(snapshot),
+        Err(DialogueError::InvalidNode { node_name }) if node_name == "Start"
+    ));
+}
+
+#[test]
+fn test_custom_marker_processors() {
+    #[derive(Debug, Clone, Default)]
+    struct GreetingProcessor {
+        language_code: Option<Language>,
+    }
+
+    impl AttributeMarkerProcessor for GreetingProcessor {
+        fn replacement_text_for_marker(&self, marker: &MarkupAttributeMarker) -> String {
+            let name = marker.property("name").unwrap().to_string();
+            match self
+                .language_code
+                .as_ref()
+                .map(ToString::to_string)
+                .as_deref()
+            {
+                Some("de-CH") => format!("Grüezi {name}"),
+                _ => format!("Hello {name}"),
+            }
+        }
+
+        fn set_language_code(&mut self, language_code: Option<Language>) {
+            self.language_code = language_code;
+        }
+
+        fn clone_box(&self) -> Box<dyn AttributeMarkerProcessor> {
+            Box::new(self.clone())
+        }
+    }
+
+    let result = Compiler::from_test_source("[greet name=\"Mae\"/]!\n[greet name=\"Greg\"/]!\n")
+        .compile()
+        .unwrap();
+
+    let mut test_base = TestBase::new().with_compilation(result);
+    let dialogue = &mut test_base.dialogue;
+    dialogue
+        .add_marker_processor("greet", Box::new(GreetingProcessor::default()))
+        .set_node("Start")
+        .unwrap();
+
+    assert_eq!("Hello Mae!", next_line(dialogue));
+
+    dialogue.set_language_code(Language::new("de-CH"));
+    assert_eq!("Grüezi Greg!", next_line(dialogue));
+}
+
+#[test]
+fn test_line_groups() {
+    let result = Compiler::from_test_source(
+        "<<declare $gold = 10>>
+=> Hello.
+=> Hello, rich one. <<if $gold > 5>>
+    Nice coins.
+=> Hello, poor one. <<if $gold < 5>>
+Bye.
+",
+    )
+    .compile()
+    .unwrap();
+
+    // The most specific available line wins
+    let mut test_base = TestBase::new().with_compilation(result).with_test_plan(
+        TestPlan::new()
+            .expect_line("Hello, rich one.")
+            .expect_line("Nice coins.")
+            .expect_line("Bye."),
+    );
+    test_base.run_standard_testcase();
+}
+
+#[test]
+fn test_saliency_strategies() {
+    let source = "<<set $runs += 1>>
+=> A
+=> B
+=> C <<if false>>
+<<if $runs < 4>>
+    <<jump Start>>
+<<endif>>
+";
+    let run = |strategy: Box<dyn SaliencyStrategy>| {
+        let result = Compiler::from_test_source(source).compile().unwrap();
+        let mut test_base = TestBase::new().with_compilation(result);
+        let dialogue = &mut test_base.dialogue;
+        dialogue
+            .set_saliency_strategy(strategy)
+            .set_node("Start")
+            .unwrap();
+        (0..4).map(|_| next_line(dialogue)).collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        vec!["A", "B", "A", "B"],
+        run(Box::new(BestLeastRecentlySeenSaliencyStrategy))
+    );
+    assert_eq!(
+        vec!["A", "A", "A", "A"],
+        run(Box::new(FirstAvailableSaliencyStrategy))
+    );
+    let random_lines = run(Box::new(RandomSaliencyStrategy::from_seed(42)));
+    assert!(random_lines.iter().all(|line| line == "A" || line == "B"));
+}
+
+#[test]
+fn test_node_groups() {
+    let result = Compiler::new()
+        .add_file(File {
+            file_name: "input.yarn".to_owned(),
+            source: "title: Start
+---
+<<declare $gold = 10>>
+<<jump Greeting>>
+===
+title: Greeting
+when: $gold > 5
+---
+Hello, rich one.
+===
+title: Greeting
+when: always
+---
+Hello.
+===
+title: Greeting
+when: $gold > 5
+when: $gold < 100
+---
+Hello, moderately rich one.
+<<set $gold to 0>>
+<<jump Greeting>>
+===
+"
+            .to_owned(),
+        })
+        .compile()
+        .unwrap();
+
+    let program = result.program.as_ref().unwrap();
+    let group_members: Vec<_> = program
+        .nodes
+        .values()
+        .filter(|node| node.node_group() == Some("Greeting"))
+        .collect();
+    assert_eq!(3, group_members.len());
+    assert!(program.nodes.contains_key("Greeting"));
+
+    let mut test_base = TestBase::new().with_compilation(result).with_test_plan(
+        TestPlan::new()
+            .expect_line("Hello, moderately rich one.")
+            .expect_line("Hello."),
+    );
+    test_base.run_standard_testcase();
+}
+
+#[test]
+fn test_node_group_without_available_node_stops() {
+    let result = Compiler::new()
+        .add_file(File {
+            file_name: "input.yarn".to_owned(),
+            source: "title: Start
+---
+Before.
+<<jump Greeting>>
+===
+title: Greeting
+when: false
+---
+Hello.
+===
+"
+            .to_owned(),
+        })
+        .compile()
+        .unwrap();
+
+    let mut test_base = TestBase::new()
+        .with_compilation(result)
+        .with_test_plan(TestPlan::new().expect_line("Before.").expect_stop());
+    test_base.run_standard_testcase();
+}
+
+#[test]
+fn test_saliency_diagnostics() {
+    for (source, expected_error) in [
+        (
+            "title: Start\nwhen: 1\n---\nA\n===\ntitle: Start\nwhen: always\n---\nB\n===\n",
+            "when header",
+        ),
+        (
+            "title: Start\nwhen: always\n---\nA\n===\ntitle: Start\n---\nB\n===\n",
+            "All nodes named Start must have a when: header",
+        ),
+        ("title: Start\n---\n=> A\n-> B\n===\n", "can't be mixed"),
+    ] {
+        let result = Compiler::new()
+            .add_file(File {
+                file_name: "input.yarn".to_owned(),
+                source: source.to_owned(),
+            })
+            .compile()
+            .unwrap_err();
+        assert!(
+            result.0.iter().any(|d| d.message.contains(expected_error)),
+            "Expected an error containing \"{expected_error}\" for source {source:?}, got {result}"
+        );
+    }
+}
+
+#[test]
+fn test_mixed_line_group_diagnostic_marks_the_option() {
+    let result = Compiler::new()
+        .add_file(File {
+            file_name: "input.yarn".to_owned(),
+            source: "title: Start\n---\n=> a\n-> b\n    c\n===\n".to_owned(),
+        })
+        .compile()
+        .unwrap_err();
+    let diagnostic = result
+        .0
+        .iter()
+        .find(|d| d.message.contains("can't be mixed"))
+        .unwrap();
+    assert_eq!(
+        Some(
+            Position {
+                line: 3,
+                character: 0
+            }..Position {
+                line: 3,
+                character: 4
+            }
+        ),
+        diagnostic.range
+    );
+    let formatted = diagnostic.to_string();
+    assert!(formatted.contains("=> a\n"), "{formatted}");
+    assert!(formatted.contains("-> b\n"), "{formatted}");
+}
+
+#[test]
+fn test_detour_returns_to_caller() {
+    let result = Compiler::new()
+        .add_file(File {
+            file_name: "input.yarn".to_owned(),
+            source: "title: Start
+---
+<<declare $destination = \"Tavern\">>
+Before.
+-> Visit the shop
+    <<detour Shop>>
+    Back from the shop.
+-> Stay
+Between.
+<<detour {$destination}>>
+After.
+===
+title: Shop
+---
+In the shop.
+<<if true>>
+    <<return>>
+<<endif>>
+Never reached.
+===
+title: Tavern
+---
+In the tavern.
+===
+"
+            .to_owned(),
+        })
+        .compile()
+        .unwrap();
+
+    let mut test_base = TestBase::new().with_compilation(result).with_test_plan(
+        TestPlan::new()
+            .expect_line("Before.")
+            .expect_option("Visit the shop")
+            .expect_option("Stay")
+            .then_select(1)
+            .expect_line("In the shop.")
+            .expect_line("Back from the shop.")
+            .expect_line("Between.")
+            .expect_line("In the tavern.")
+            .expect_line("After.")
+            .expect_stop(),
+    );
+    test_base.run_standard_testcase();
+}
+
+#[test]
+fn test_detour_emits_node_events() {
+    let result = Compiler::new()
+        .add_file(File {
+            file_name: "input.yarn".to_owned(),
+            source: "title: Start
+---
+<<detour Shop>>
+===
+title: Shop
+---
+===
+"
+            .to_owned(),
+        })
+        .compile()
+        .unwrap();
+
+    let mut test_base = TestBase::new().with_compilation(result);
+    let dialogue = &mut test_base.dialogue;
+    dialogue.set_node("Start").unwrap();
+
+    let mut node_events = Vec::new();
+    for event in dialogue.continue_().unwrap() {
+        match event {
+            DialogueEvent::NodeStart(name) => node_events.push(format!("start {name}")),
+            DialogueEvent::NodeComplete(name) => node_events.push(format!("complete {name}")),
+            _ => {}
+        }
+    }
+    assert_eq!(
+        vec![
+            "start Start",
+            "start Shop",
+            "complete Shop",
+            "complete Start",
+        ],
+        node_events
+    );
+}
+
+#[test]
+fn test_return_without_detour_stops() {
+    let result = Compiler::from_test_source("Before.\n<<return>>\nAfter.\n")
+        .compile()
+        .unwrap();
+
+    let mut test_base = TestBase::new()
+        .with_compilation(result)
+        .with_test_plan(TestPlan::new().expect_line("Before.").expect_stop());
+    test_base.run_standard_testcase();
+}
+
+#[test]
+fn test_stop_inside_detour_ends_dialogue() {
+    for optimize in [false, true] {
+        let result = Compiler::new()
+            .add_file(File {
+                file_name: "input.yarn".to_owned(),
+                source: "title: Start
+---
+<<detour Shop>>
+Back from the shop.
+===
+title: Shop
+---
+In the shop.
+<<stop>>
+===
+"
+                .to_owned(),
+            })
+            .with_optimization(optimize)
+            .compile()
+            .unwrap();
+
+        let mut test_base = TestBase::new()
+            .with_compilation(result)
+            .with_test_plan(TestPlan::new().expect_line("In the shop.").expect_stop());
+        test_base.run_standard_testcase();
+    }
+}
+
+#[test]
+fn test_stop_inside_detour_completes_waiting_nodes() {
+    let result = Compiler::new()
+        .add_file(File {
+            file_name: "input.yarn".to_owned(),
+            source: "title: Start
+---
+<<detour Shop>>
+===
+title: Shop
+---
+<<stop>>
+===
+"
+            .to_owned(),
+        })
+        .compile()
+        .unwrap();
+
+    let mut test_base = TestBase::new().with_compilation(result);
+    let dialogue = &mut test_base.dialogue;
+    dialogue.set_node("Start").unwrap();
+
+    let mut node_events = Vec::new();
+    for event in dialogue.continue_().unwrap() {
+        match event {
+            DialogueEvent::NodeStart(name) => node_events.push(format!("start {name}")),
+            DialogueEvent::NodeComplete(name) => node_events.push(format!("complete {name}")),
+            DialogueEvent::DialogueComplete => node_events.push("dialogue complete".to_owned()),
+            _ => {}
+        }
+    }
+    assert_eq!(
+        vec![
+            "start Start",
+            "start Shop",
+            "complete Shop",
+            "complete Start",
+            "dialogue complete",
+        ],
+        node_events
+    );
+    assert!(!dialogue.is_active());
+}
+
+#[test]
+fn test_jump_inside_detour_returns_to_caller_after_destination() {
+    // The jump replaces the detoured node, so the caller continues once the destination ends
+    let result = Compiler::new()
+        .add_file(File {
+            file_name: "input.yarn".to_owned(),
+            source: "title: Start
+---
+<<detour Shop>>
+Back from the shop.
+===
+title: Shop
+---
+In the shop.
+<<jump Storage>>
+Never reached.
+===
+title: Storage
+---
+In the storage.
+===
+"
+            .to_owned(),
+        })
+        .compile()
+        .unwrap();
+
+    let mut test_base = TestBase::new().with_compilation(result).with_test_plan(
+        TestPlan::new()
+            .expect_line("In the shop.")
+            .expect_line("In the storage.")
+            .expect_line("Back from the shop.")
+            .expect_stop(),
+    );
+    test_base.run_standard_testcase();
+}
+
+#[test]
+fn test_invalid_detour_is_rejected() {
+    let result = Compiler::from_test_source("<<detour>>\n")
+        .compile()
+        .unwrap_err();
+    assert!(result
+        .0
+        .iter()
+        .any(|d| d.message.contains("<<detour>> expects the name of a node")));
+}
+
+#[test]
+fn test_once_blocks_and_lines() {
+    let result = Compiler::new()
+        .add_file(File {
+            file_name: "input.yarn".to_owned(),
+            source: "title: Start
+---
+<<declare $visits = 0>>
+<<set $visits += 1>>
+<<once>>
+    First visit.
+<<else>>
+    Welcome back.
+<<endonce>>
+<<once if $visits > 1>>
+    Second visit or later, but only once.
+<<endonce>>
+Hello, stranger. #once
+-> Ask about the weather #once
+    Sunny.
+-> Leave
+    Bye.
+<<if $visits < 3>>
+    <<jump Start>>
+<<endif>>
+===
+"
+            .to_owned(),
+        })
+        .compile()
+        .unwrap();
+
+    let once_declarations: Vec<_> = result
+        .declarations
+        .iter()
+        .filter(|declaration| declaration.name.starts_with("$Yarn.Internal.Once."))
+        .collect();
+    assert_eq!(4, once_declarations.len());
+    assert!(once_declarations
+        .iter()
+        .all(|declaration| declaration.is_implicit
+            && declaration.r#type == yarnspinner::core::Type::Boolean
+            && declaration.default_value == Some(false.into())));
+
+    // The standard test case always selects the first option, even if it is unavailable
+    let mut test_base = TestBase::new().with_compilation(result);
+    let dialogue = &mut test_base.dialogue;
+    dialogue.set_node("Start").unwrap();
+    let mut transcript = Vec::new();
+    let mut is_complete = false;
+    while !is_complete {
+        for event in dialogue.continue_().unwrap() {
+            match event {
+                DialogueEvent::DialogueComplete => is_complete = true,
+                DialogueEvent::Line(line) => transcript.push(line.text),
+                DialogueEvent::Options(options) => {
+                    for option in &options {
+                        transcript.push(format!(
+                            "-> {} (available: {})",
+                            option.line.text, option.is_available
+                        ));
+                    }
+                    let selected = options.iter().find(|option| option.is_available).unwrap();
+                    dialogue.set_selected_option(selected.id).unwrap();
+                }
+                _ => {}
+            }
+        }
+    }
+    assert_eq!(
+        vec![
+            "First visit.",
+            "Hello, stranger.",
+            "-> Ask about the weather (available: true)",
+            "-> Leave (available: true)",
+            "Sunny.",
+            "Welcome back.",
+            "Second visit or later, but only once.",
+            "-> Ask about the weather (available: false)",
+            "-> Leave (available: true)",
+            "Bye.",
+            "Welcome back.",
+            "-> Ask about the weather (available: false)",
+            "-> Leave (available: true)",
+            "Bye.",
+        ],
+        transcript
+    );
+}
+
+#[test]
+fn test_once_variables_survive_edits_elsewhere_in_the_node() {
+    let once_variables = |file_name: &str, body: &str| {
+        let mut names: Vec<_> = Compiler::new()
+            .add_file(File {
+                file_name: file_name.to_owned(),
+                source: format!("title: Start\n---\n{body}===\n"),
+            })
+            .compile()
+            .unwrap()
+            .declarations
+            .into_iter()
+            .map(|declaration| declaration.name)
+            .filter(|name| name.starts_with("$Yarn.Internal.Once."))
+            .collect();
+        names.sort();
+        names
+    };
+    let original = "\
+<<once>>
+    First visit.
+<<endonce>>
+Hello, stranger. #once #line:stranger
+Nice weather. #once
+";
+    let edited = "\
+A new line.
+<<once if true>>
+    A new block.
+<<endonce>>
+-> A new option #once
+<<once>>
+    First visit.
+<<endonce>>
+Another new line.
+Hello, stranger. #once #line:stranger
+Nice  weather. #once
+";
+    let original_variables = once_variables("input.yarn", original);
+    assert_eq!(3, original_variables.len());
+    assert!(original_variables.contains(&"$Yarn.Internal.Once.Start.line:stranger".to_owned()));
+    assert!(original_variables
+        .iter()
+        .all(|name| !name.contains("input.yarn")));
+
+    let edited_variables = once_variables("moved/elsewhere.yarn", edited);
+    assert_eq!(5, edited_variables.len());
+    assert!(original_variables
+        .iter()
+        .all(|name| edited_variables.contains(name)));
+}
+
+#[test]
+fn test_once_blocks_with_the_same_content_are_told_apart() {
+    let result = Compiler::from_test_source(
+        "<<once>>\n    Hi.\n<<endonce>>\n<<once>>\n    Hi.\n<<endonce>>\n",
+    )
+    .compile()
+    .unwrap();
+    let once_variables = result
+        .declarations
+        .iter()
+        .filter(|declaration| declaration.name.starts_with("$Yarn.Internal.Once."))
+        .count();
+    assert_eq!(2, once_variables);
+
+    let mut test_base = TestBase::new()
+        .with_compilation(result)
+        .with_test_plan(TestPlan::new().expect_line("Hi.").expect_line("Hi.").expect_stop());
+    test_base.run_standard_testcase();
+}
+
+#[test]
+fn test_once_diagnostics() {
+    for (source, expected_error) in [
+        ("<<once>>\nA\n<<endif>>\n", "Expected an <<endonce>>"),
+        ("<<if true>>\nA\n<<endonce>>\n", "Unexpected <<endonce>>"),
+        (
+            "<<once>>\nA\n<<elseif true>>\nB\n<<endonce>>\n",
+            "can't have <<elseif>> clauses",
+        ),
+        ("<<once if>>\nA\n<<endonce>>\n", "must contain a condition"),
+        ("<<once if 1>>\nA\n<<endonce>>\n", "once statement"),
+    ] {
+        let result = Compiler::from_test_source(source).compile().unwrap_err();
+        assert!(
+            result.0.iter().any(|d| d.message.contains(expected_error)),
+            "Expected an error containing \"{expected_error}\" for source {source:?}, got {result}"
+        );
+    }
+}
+
+#[test]
+fn test_smart_variables_are_evaluated_when_read() {
+    let result = Compiler::new()
+        .add_file(File {
+            file_name: "input.yarn".to_owned(),
+            source: "title: Start
+---
+<<declare $gold = 5>>
+<<declare $can_afford = $gold >= 10>>
+<<declare $price_text = \"Price: \" + string($gold * 2) as string>>
+Can afford: {$can_afford}, {$price_text}
+<<set $gold to 12>>
+Can afford: {$can_afford}, {$price_text}
+===
+"
+            .to_owned(),
+        })
+        .compile()
+        .unwrap();
+
+    let smart_variable = result
+        .declarations
+        .iter()
+        .find(|declaration| declaration.name == "$can_afford")
+        .unwrap();
+    assert!(smart_variable.is_smart_variable);
+    assert_eq!(yarnspinner::core::Type::Boolean, smart_variable.r#type);
+    assert_eq!(None, smart_variable.default_value);
+    let program = result.program.as_ref().unwrap();
+    assert!(!program.initial_values.contains_key("$can_afford"));
+    assert!(program.nodes["$can_afford"].is_smart_variable());
+
+    let mut test_base = TestBase::new().with_compilation(result);
+    let dialogue = &mut test_base.dialogue;
+    assert_eq!(
+        vec!["Start"],
+        dialogue.node_names().unwrap().collect::<Vec<_>>()
+    );
+    assert!(!dialogue.node_exists("$can_afford"));
+    assert!(matches!(
+        dialogue.set_node("$can_afford"),
+        Err(DialogueError::InvalidNode { .. })
+    ));
+    dialogue.set_node("Start").unwrap();
+    assert_eq!("Can afford: false, Price: 10", next_line(dialogue));
+    assert_eq!("Can afford: true, Price: 24", next_line(dialogue));
+    assert!(!dialogue.variable_storage().contains("$can_afford"));
+}
+
+#[test]
+fn test_constant_declarations_are_not_smart_variables() {
+    let result = Compiler::new()
+        .add_file(File {
+            file_name: "input.yarn".to_owned(),
+            source: "title: Start
+---
+<<declare $neg = -1>>
+<<declare $parens = (5)>>
+<<declare $nested = -(-(2.5)) as number>>
+<<declare $tired = !true>>
+{$neg}, {$parens}, {$nested}, {$tired}
+<<set $neg to $neg - 1>>
+<<set $parens to $parens * 2>>
+{$neg}, {$parens}
+===
+"
+            .to_owned(),
+        })
+        .compile()
+        .unwrap();
+
+    let program = result.program.as_ref().unwrap();
+    for (name, value) in [
+        ("$neg", YarnValue::from(-1.0)),
+        ("$parens", YarnValue::from(5.0)),
+        ("$nested", YarnValue::from(2.5)),
+        ("$tired", YarnValue::from(false)),
+    ] {
+        let declaration = result
+            .declarations
+            .iter()
+            .find(|declaration| declaration.name == name)
+            .unwrap();
+        assert!(!declaration.is_smart_variable, "{name} is a smart variable");
+        assert_eq!(Some(value), declaration.default_value, "{name}");
+        assert!(program.initial_values.contains_key(name));
+        assert!(!program.nodes.contains_key(name));
+    }
+
+    let mut test_base = TestBase::new().with_compilation(result);
+    let dialogue = &mut test_base.dialogue;
+    dialogue.set_node("Start").unwrap();
+    assert_eq!("-1, 5, 2.5, false", next_line(dialogue));
+    assert_eq!("-2, 10", next_line(dialogue));
+}
+
+#[test]
+fn test_smart_variable_diagnostics() {
+    for (source, expected_error) in [
+        (
+            "<<declare $a = not $b as bool>>\n<<declare $b = $a and true as bool>>\n",
+            "Smart variable $a has a cyclic definition: $a -> $b -> $a",
+        ),
+        (
+            "<<declare $a = $a + 1 as number>>\n",
+            "Smart variable $a has a cyclic definition: $a -> $a",
+        ),
+        (
+            "<<declare $gold = 5>>\n<<declare $rich = $gold > 100>>\n<<set $rich to true>>\n",
+            "$rich is a smart variable, so it can't be assigned to",
+        ),
+        (
+            "<<declare $gold = 5>>\n<<declare $rich = $gold + 1 as bool>>\n",
+            "$rich (Bool) cannot be assigned a Number",
+        ),
+        (
+            "<<declare $copy = $gold>>\n<<declare $gold = 5>>\n",
+            "Can't figure out the type of smart variable $copy",
+        ),
+        (
+            "<<declare $count = -1 as string>>\n",
+            "Type string does not match value -1 (Number)",
+        ),
+    ] {
+        let result = Compiler::from_test_source(source).compile().unwrap_err();
+        assert!(
+            result.0.iter().any(|d| d.message.contains(expected_error)),
+            "Expected an error containing \"{expected_error}\" for source {source:?}, got {:?}",
+            result.0.iter().map(|d| &d.message).collect::<Vec<_>>()
+        );
+    }
+}
+
+#[test]
+fn test_disassembled_programs_can_be_assembled_again() {
+    let result = Compiler::new()
+        .add_file(File {
+            file_name: "input.yarn".to_owned(),
+            source: "title: Start
+---
+<<declare $gold = 5>>
+Hello! #line:hello
+<<if $gold > 3>>
+    You're rich. #line:rich
+<<endif>>
+===
+"
+            .to_owned(),
+        })
+        .compile()
+        .unwrap();
+
+    let listing = result.disassemble().unwrap();
+    assert!(listing.contains(".initial \"$gold\" 5\n"), "{listing}");
+    let run_line = listing
+        .lines()
+        .find(|line| line.contains("RUN_LINE \"line:hello\""))
+        .unwrap();
+    assert!(run_line.ends_with("; input.yarn:4:1"), "{run_line}");
+    assert_eq!(
+        result.program.unwrap(),
+        Program::assemble(&listing).unwrap()
+    );
+}
+
+#[test]
+fn test_assembled_programs_can_be_run() {
+    let program = Program::assemble(
+        r#"
+.initial "$count" 0
+.node "Start"
+loop:
+    PUSH_VARIABLE "$count"
+    PUSH_FLOAT 1
+    PUSH_FLOAT 2
+    CALL_FUNC "Number.Add"
+    STORE_VARIABLE "$count"
+    POP
+    RUN_LINE "line:count" 0
+    PUSH_VARIABLE "$count"
+    PUSH_FLOAT 2
+    PUSH_FLOAT 2
+    CALL_FUNC "Number.GreaterThanOrEqualTo"
+    JUMP_IF_FALSE "loop"
+    POP
+    STOP
+"#,
+    )
+    .unwrap();
+    let string_table = HashMap::from([(
+        LineId::from("line:count"),
+        StringInfo {
+            text: "Counting".to_owned(),
+            ..Default::default()
+        },
+    )]);
+
+    let mut test_base = TestBase::new()
+        .with_program(program)
+        .with_string_table(string_table);
+    let dialogue = &mut test_base.dialogue;
+    dialogue.set_node("Start").unwrap();
+    assert_eq!("Counting", next_line(dialogue));
+    assert_eq!("Counting", next_line(dialogue));
+    assert_eq!(
+        vec![
+            DialogueEvent::NodeComplete("Start".to_owned()),
+            DialogueEvent::DialogueComplete
+        ],
+        dialogue.continue_().unwrap()
+    );
+    assert_eq!(
+        YarnValue::from(2.0),
+        dialogue.variable_storage().get("$count").unwrap()
+    );
+}
+
+#[test]
+fn test_compiled_programs_pass_verification() {
+    let result = Compiler::new()
+        .add_file(File {
+            file_name: "input.yarn".to_owned(),
+            source: "title: Start
+---
+<<declare $gold = 10>>
+<<declare $rich = $gold > 5>>
+<<declare $destination = \"Shop\">>
+<<local $count = 1>>
+Gold: {$gold}, {$count} #line:gold
+<<once if $rich>>
+    Rich!
+<<else>>
+    Poor.
+<<endonce>>
+<<if $gold > 100>>
+    Very rich.
+<<elseif $rich>>
+    Rich.
+<<else>>
+    Poor.
+<<endif>>
+=> Hello.
+=> Hello, rich one. <<if $gold > 5>>
+
+-> Buy {$gold} <<if $rich>> #once
+    <<detour Shop>>
+-> Leave
+    <<detour {$destination}>>
+<<command {$gold}>>
+<<jump Greeting>>
+===
+title: Shop
+---
+Welcome.
+<<return>>
+===
+title: Greeting
+when: $gold > 5
+---
+Hello, rich one.
+===
+title: Greeting
+when: always
+---
+Hello.
+===
+"
+            .to_owned(),
+        })
+        .compile()
+        .unwrap();
+
+    let program = result.program.unwrap();
+    let library = TestBase::new().dialogue.library().clone();
+    if let Err(error) = program.verify(&library) {
+        panic!("{error}\n{}", program.disassemble());
+    }
+
+    let mut broken_program = program;
+    let start = broken_program.nodes.get_mut("Start").unwrap();
+    start.labels.clear();
+    let error = broken_program.verify(&library).unwrap_err();
+    assert!(error.0.iter().all(
+        |problem| problem.node_name == "Start" && problem.message.starts_with("Unknown label")
+    ));
+}
+
+#[test]
+fn test_optimizer_folds_constants_and_removes_dead_code() {
+    let compile = |optimize: bool| {
+        Compiler::new()
+            .add_file(File {
+                file_name: "input.yarn".to_owned(),
+                source: "title: Start
+---
+The answer is {40 + 2}. #line:answer
+<<if 1 > 2>>
+    Never. #line:never
+<<endif>>
+Done. #line:done
+===
+"
+                .to_owned(),
+            })
+            .with_optimization(optimize)
+            .compile()
+            .unwrap()
+    };
+    let unoptimized = compile(false);
+    let optimized = compile(true);
+
+    let unoptimized_node = &unoptimized.program.as_ref().unwrap().nodes["Start"];
+    let optimized_node = &optimized.program.as_ref().unwrap().nodes["Start"];
+    assert!(optimized_node.instructions.len() < unoptimized_node.instructions.len());
+    let listing = optimized.disassemble().unwrap();
+    assert!(listing.contains("PUSH_FLOAT 42"), "{listing}");
+    assert!(!listing.contains("CALL_FUNC"), "{listing}");
+    assert!(!listing.contains("line:never"), "{listing}");
+
+    // Debug info still points to the right lines
+    let run_line = listing
+        .lines()
+        .find(|line| line.contains("RUN_LINE \"line:done\""))
+        .unwrap();
+    assert!(run_line.ends_with("; input.yarn:7:1"), "{run_line}");
+    assert_eq!(
+        optimized_node.instructions.len(),
+        optimized.debug_info["Start"].line_positions.len()
+    );
+}
+
+#[test]
+fn test_optimized_programs_behave_the_same() {
+    let source = "title: Start
+---
+<<declare $gold = 10>>
+<<declare $rich = $gold > 5>>
+<<local $count = 2>>
+Count: {$count}, {2 * 3 + 1}
+<<if $gold > 100>>
+    Very rich.
+<<elseif true>>
+    Rich enough.
+<<else>>
+    Poor.
+<<endif>>
+<<if false>>
+    Never.
+<<endif>>
+<<once>>
+    First time.
+<<endonce>>
+=> Hello.
+=> Hello, rich one. <<if $rich>>
+
+-> Buy {1 + 1} <<if $rich>> #once
+    <<detour Shop>>
+-> Leave <<if 1 > 2>>
+    Bye.
+<<set $gold to $gold + 1>>
+<<if $gold < 12>>
+    <<jump Start>>
+<<endif>>
+===
+title: Shop
+---
+Welcome, you have {$gold} gold.
+<<return>>
+===
+";
+    let run = |optimize: bool| {
+        let compilation = Compiler::new()
+            .add_file(File {
+                file_name: "input.yarn".to_owned(),
+                source: source.to_owned(),
+            })
+            .with_optimization(optimize)
+            .compile()
+            .unwrap();
+        let program = compilation.program.clone().unwrap();
+        let mut test_base = TestBase::new().with_compilation(compilation);
+        program
+            .verify(test_base.dialogue.library())
+            .unwrap_or_else(|error| panic!("{error}\n{}", program.disassemble()));
+        let dialogue = &mut test_base.dialogue;
+        dialogue.set_node("Start").unwrap();
+        let mut lines = Vec::new();
+        loop {
+            let events = dialogue.continue_().unwrap();
+            for event in &events {
+                match event {
+                    DialogueEvent::Line(line) => lines.push(line.text.clone()),
+                    DialogueEvent::Options(options) => {
+                        lines.extend(options.iter().map(|option| {
+                            format!("{} ({})", option.line.text, option.is_available)
+                        }));
+                        let option = options
+                            .iter()
+                            .find(|option| option.is_available)
+                            .unwrap_or(&options[0]);
+                        dialogue.set_selected_option(option.id).unwrap();
+                    }
+                    _ => {}
+                }
+            }
+            if events.contains(&DialogueEvent::DialogueComplete) {
+                break;
+            }
+        }
+        (program, lines)
+    };
+    let (unoptimized_program, unoptimized_lines) = run(false);
+    let (optimized_program, optimized_lines) = run(true);
+
+    assert_eq!(unoptimized_lines, optimized_lines);
+    assert!(optimized_lines.contains(&"Count: 2, 7".to_owned()));
+    let instruction_count = |program: &Program| -> usize {
+        program
+            .nodes
+            .values()
+            .map(|node| node.instructions.len())
+            .sum()
+    };
+    assert!(instruction_count(&optimized_program) < instruction_count(&unoptimized_program));
+}
+
+fn next_line(dialogue: &mut Dialogue) -> String {
+    dialogue
+        .continue_()
+        .unwrap()
+        .into_iter()
+        .find_map(|event| match event {
+            DialogueEvent::Line(line) => Some(line.text),
+            _ => None,
+        })
+        .unwrap()
+}
@@ -3,6 +3,8 @@
 use crate::test_base::*;
 use test_base::prelude::*;
 use yarnspinner::compiler::*;
+use yarnspinner::core::*;
+use yarnspinner::runtime::*;
 
 mod test_base;
 
@@ -101,3 +103,86 @@ fn test_compiling_same_file_twice_fails() {
         .iter()
         .any(|d| d.message.contains("Duplicate line ID line:794945")));
 }
+
+#[test]
+fn test_calling_function_with_wrong_parameter_count_errors() {
+    let mut dialogue = dialogue_running(vec![
+        instruction(OpCode::PushFloat, vec![1.0.into()]),
+        instruction(OpCode::PushFloat, vec![1.0.into()]),
+        instruction(OpCode::PushFloat, vec![2.0.into()]),
+        instruction(OpCode::CallFunc, vec!["string".to_owned().into()]),
+    ]);
+
+    let error = dialogue.continue_().unwrap_err();
+    assert!(matches!(
+        error,
+        DialogueError::FunctionArityMismatch { function_name, expected: 1, actual: 2 }
+            if function_name == "string"
+    ));
+}
+
+#[test]
+fn test_reading_variable_without_initial_value_errors() {
+    let mut dialogue = dialogue_running(vec![instruction(
+        OpCode::PushVariable,
+        vec!["$undeclared".to_owned().into()],
+    )]);
+
+    let error = dialogue.continue_().unwrap_err();
+    assert!(matches!(
+        error,
+        DialogueError::MissingInitialValue { variable_name } if variable_name == "$undeclared"
+    ));
+}
+
+#[test]
+fn test_popping_empty_stack_errors() {
+    let mut dialogue = dialogue_running(vec![instruction(OpCode::Pop, vec![])]);
+
+    let error = dialogue.continue_().unwrap_err();
+    assert!(matches!(error, DialogueError::StackUnderflow));
+}
+
+#[test]
+fn test_jumping_to_unknown_label_errors() {
+    let mut dialogue = dialogue_running(vec![instruction(
+        OpCode::JumpTo,
+        vec!["L999".to_owned().into()],
+    )]);
+
+    let error = dialogue.continue_().unwrap_err();
+    assert!(matches!(error, DialogueError::InvalidProgram { .. }));
+}
+
+#[test]
+fn test_running_invalid_opcode_errors() {
+    let mut dialogue = dialogue_running(vec![Instruction {
+        opcode: 999,
+        operands: vec![],
+    }]);
+
+    let error = dialogue.continue_().unwrap_err();
+    assert!(matches!(error, DialogueError::InvalidProgram { .. }));
+}
+
+fn dialogue_running(instructions: Vec<Instruction>) -> Dialogue {
+    let node = Node {
+        name: "Start".to_owned(),
+        instructions,
+        ..Default::default()
+    };
+    let program = Program {
+        nodes: [(node.name.clone(), node)].into(),
+        ..Default::default()
+    };
+    let mut dialogue = TestBase::new().dialogue;
+    dialogue.replace_program(program).set_node("Start").unwrap();
+    dialogue
+}
+
+fn instruction(opcode: OpCode, operands: Vec<Operand>) -> Instruction {
+    Instruction {
+        opcode: opcode.into(),
+        operands,
+    }
+}
@@ -0,0 +1,247 @@
+//! Not part of the original, which has no formatter.
+
+use std::collections::HashMap;
+use yarnspinner::compiler::*;
+
+const UNFORMATTED: &str = "title:Start
+tags:
+// Where the node is in the editor
+position:  3,4
+---
+
+
+// Only the indentation decides what belongs to an option
+-> Gold {$gold*2} // the first option
+    <<set $gold  =  -$gold>>
+    -> Nested #once
+            Deeply nested
+-> Escapes \\{ are kept <<if !visited( \"Other\" )>>
+// This comment ends the option
+    Not part of the option
+<<if $gold>=1 && ( $silver+2 )*3==9>>
+\t<<custom   command  \"a  b\" {  $gold  }>>
+
+
+<<else>>
+Tagged line #line:abc123 #mood:happy   // with a comment
+<<endif>>
+=> Group  member
+    <<jump {\"Oth\" + \"er\"}>>
+
+
+===
+title: Other
+---
+<<once if $gold > 1>>
+Once.
+<<endonce>>
+===
+";
+
+const FORMATTED: &str = "title: Start
+// Where the node is in the editor
+position: 3,4
+tags:
+---
+// Only the indentation decides what belongs to an option
+-> Gold {$gold * 2} // the first option
+    <<set $gold = -$gold>>
+    -> Nested #once
+        Deeply nested
+-> Escapes \\{ are kept <<if !visited(\"Other\")>>
+// This comment ends the option
+Not part of the option
+<<if $gold >= 1 && ($silver + 2) * 3 == 9>>
+    <<custom   command  \"a  b\" {$gold}>>
+
+<<else>>
+    Tagged line #mood:happy #line:abc123 // with a comment
+<<endif>>
+=> Group  member
+    <<jump {\"Oth\" + \"er\"}>>
+===
+
+title: Other
+---
+<<once if $gold > 1>>
+    Once.
+<<endonce>>
+===
+";
+
+fn compile(source: &str) -> Compilation {
+    Compiler::new()
+        .add_file(File {
+            file_name: "test.yarn".to_owned(),
+            source: source.to_owned(),
+        })
+        .compile()
+        .unwrap()
+}
+
+/// Formats the source, checks that formatting it again changes nothing, and that it compiles to the same program and lines.
+fn assert_formatting_does_not_change_compilation(source: &str) -> String {
+    let formatted = Compiler::format(source).unwrap();
+    assert_eq!(formatted, Compiler::format(formatted.clone()).unwrap());
+
+    let original = compile(source);
+    let reformatted = compile(&formatted);
+    assert_eq!(original.program, reformatted.program);
+    let texts = |compilation: &Compilation| {
+        compilation
+            .string_table
+            .iter()
+            .map(|(id, info)| (id.clone(), info.text.clone()))
+            .collect::<HashMap<_, _>>()
+    };
+    assert_eq!(texts(&original), texts(&reformatted));
+    formatted
+}
+
+#[test]
+fn test_formats_source_in_canonical_style() {
+    let formatted = Compiler::format(UNFORMATTED).unwrap();
+    assert_eq!(FORMATTED, formatted);
+}
+
+#[test]
+fn test_formatting_is_idempotent() {
+    let formatted = Compiler::format(FORMATTED).unwrap();
+    assert_eq!(FORMATTED, formatted);
+}
+
+#[test]
+fn test_formatting_does_not_change_compiled_program() {
+    // Blank lines are part of the indentation that decides what belongs to an option
+    assert_formatting_does_not_change_compilation(
+        "title: Start
+---
+<<declare $gold = 0>>
+-> Option 1
+    Nice.
+-> Option 2
+    Nicer
+    
+    Still part of the second option
+
+  Not part of an option anymore
+    
+Neither is this
+<<if $gold  >  1>>
+-> Option 3
+        Part of the third option
+  
+        Also part of the third option
+<<endif>>
+===
+",
+    );
+}
+
+#[test]
+fn test_formatting_node_groups_and_detours() {
+    let formatted = assert_formatting_does_not_change_compilation(
+        "title: Start
+---
+<<declare $gold = 0>>
+<<detour   Greeting>>
+<<detour {\"Gree\"+\"ting\"}>>
+=> Hello  there
+=> Hi
+    <<set $gold  +=  1>>
+===
+title: Greeting
+when:  $gold>1
+---
+Welcome back.
+<<return>>
+===
+title: Greeting
+when: always
+---
+Welcome.
+===
+",
+    );
+    assert_eq!(
+        "title: Start
+---
+<<declare $gold = 0>>
+<<detour Greeting>>
+<<detour {\"Gree\" + \"ting\"}>>
+=> Hello  there
+=> Hi
+    <<set $gold += 1>>
+===
+
+title: Greeting
+when: $gold>1
+---
+Welcome back.
+<<return>>
+===
+
+title: Greeting
+when: always
+---
+Welcome.
+===
+",
+        formatted
+    );
+}
+
+#[test]
+fn test_formatting_enums_and_local_variables() {
+    let formatted = assert_formatting_does_not_change_compilation(
+        "title: Start
+---
+<<enum Mood>>
+<<case Happy>>
+<<case   Sad>>
+<<endenum>>
+<<declare $mood = Mood.Happy>>
+<<local $count = 1>>
+<<set $count  =  $count+1>>
+<<if $mood==Mood.Sad>>
+Cheer up! {$count}
+<<endif>>
+===
+",
+    );
+    assert_eq!(
+        "title: Start
+---
+<<enum Mood>>
+    <<case Happy>>
+    <<case Sad>>
+<<endenum>>
+<<declare $mood = Mood.Happy>>
+<<local $count = 1>>
+<<set $count = $count + 1>>
+<<if $mood == Mood.Sad>>
+    Cheer up! {$count}
+<<endif>>
+===
+",
+        formatted
+    );
+}
+
+#[test]
+fn test_formatting_keeps_raw_text_nodes_as_they_are() {
+    let source = "title: Raw
+tags: rawText
+---
+  This   is
+<<kept>>   as is
+===
+";
+    assert_eq!(source, Compiler::format(source).unwrap());
+}
+
+#[test]
+fn test_formatting_rejects_source_with_syntax_errors() {
+    let result = Compiler::format("title: Start\n---\n<<if $gold>>\n===\n");
+    assert!(result.is_err());
+}
@@ -211,12 +211,71 @@ fn test_sources() {
 }
 
 #[test]
-#[should_panic]
-fn crashes_on_command_expression_evaluating_whitespace() {
+fn errors_on_command_expression_evaluating_whitespace() {
     let result = Compiler::from_test_source("<<{0} {\"   \"}>>")
         .compile()
         .unwrap();
-    TestBase::new()
-        .with_compilation(result)
-        .run_standard_testcase();
+    let mut dialogue = TestBase::new().with_compilation(result).dialogue;
+    dialogue.set_node("Start").unwrap();
+
+    let error = dialogue.continue_().unwrap_err();
+    assert!(matches!(error, DialogueError::MalformedCommand { .. }));
+}
+
+#[test]
+fn test_line_conditions() {
+    let result = Compiler::from_test_source(
+        "<<declare $visited = false>>\n\
+        shown <<if !$visited>>\n\
+        hidden <<if $visited>>\n\
+        <<set $visited to true>>\n\
+        hidden again <<if !$visited>>\n\
+        shown again <<if $visited>>\n\
+        -> available <<if $visited>>\n\
+        -> unavailable <<if !$visited>>\n",
+    )
+    .compile()
+    .unwrap();
+
+    let mut test_base = TestBase::new().with_compilation(result);
+    let dialogue = &mut test_base.dialogue;
+    dialogue.set_node("Start").unwrap();
+
+    let mut lines = Vec::new();
+    let options = loop {
+        let events = dialogue.continue_().unwrap();
+        lines.extend(events.iter().filter_map(|event| match event {
+            DialogueEvent::Line(line) => Some(line.text.clone()),
+            _ => None,
+        }));
+        if let Some(options) = events.into_iter().find_map(|event| match event {
+            DialogueEvent::Options(options) => Some(options),
+            _ => None,
+        }) {
+            break options;
+        }
+    };
+
+    assert_eq!(vec!["shown", "shown again"], lines);
+    let availability: Vec<_> = options
+        .iter()
+        .map(|option| (option.line.text.as_str(), option.is_available))
+        .collect();
+    assert_eq!(
+        vec![("available", true), ("unavailable", false)],
+        availability
+    );
+}
+
+#[test]
+fn test_line_conditions_must_be_boolean() {
+    let result = Compiler::from_test_source("a line <<if 1>>")
+        .compile()
+        .unwrap_err();
+
+    println!("{}", result);
+    assert!(result
+        .0
+        .iter()
+        .any(|d| d.message.contains("line condition")));
 }
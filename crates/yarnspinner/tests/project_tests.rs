@@ -7,6 +7,7 @@ use regex::Regex;
 use std::collections::HashSet;
 use test_base::prelude::*;
 use yarnspinner::compiler::*;
+use yarnspinner::runtime::DialogueEvent;
 use yarnspinner_core::prelude::*;
 
 mod test_base;
@@ -292,3 +293,54 @@ fn test_debug_output_is_produced() {
     assert_eq!(2, first_line_info.position.unwrap().line);
     assert_eq!(0, first_line_info.position.unwrap().character);
 }
+
+#[test]
+fn test_compiled_projects_can_be_saved_and_loaded() {
+    let file = File {
+        file_name: "input".to_owned(),
+        source: "title: Start
+---
+Hello there. #line:hello #greeting
+<<jump End>>
+===
+title: End
+---
+Bye.
+===
+"
+        .to_owned(),
+    };
+    let compilation = Compiler::new().add_file(file).compile().unwrap();
+    let project = compilation.to_compiled_project().unwrap();
+
+    let bytes = project.to_bytes();
+    assert_eq!(bytes, project.clone().to_bytes());
+    let loaded = CompiledProject::from_bytes(&bytes).unwrap();
+    assert_eq!(project, loaded);
+    assert_eq!(
+        vec!["line:hello".to_owned(), "greeting".to_owned()],
+        loaded.string_table[&LineId::from("line:hello")].metadata
+    );
+
+    // The program is embedded as-is, so it can also be loaded on its own
+    let program_bytes = compilation.program.as_ref().unwrap().to_bytes();
+    assert_eq!(
+        compilation.program.unwrap(),
+        Program::from_bytes(&program_bytes).unwrap()
+    );
+    assert!(Program::from_bytes(&program_bytes[..program_bytes.len() - 1]).is_err());
+
+    let mut dialogue = TestBase::default()
+        .with_compilation(Compilation::from(loaded))
+        .dialogue;
+    dialogue.set_node("Start").unwrap();
+    let lines: Vec<_> = std::iter::from_fn(|| dialogue.continue_().ok())
+        .take_while(|events| !events.contains(&DialogueEvent::DialogueComplete))
+        .flatten()
+        .filter_map(|event| match event {
+            DialogueEvent::Line(line) => Some(line.text),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(vec!["Hello there.", "Bye."], lines);
+}
@@ -595,29 +595,95 @@ fn test_type_conversion() {
 }
 
 #[test]
-#[should_panic = "Failed to convert a Yarn value to a number: ParseFloatError(ParseFloatError { kind: Invalid })"]
 fn test_type_conversion_failure_to_number() {
     let source = "{number(\"hello\")}";
-    let test_base =
-        TestBase::new().with_test_plan(TestPlan::new().expect_line("test failure if seen"));
+    let test_base = TestBase::new();
     let result = Compiler::from_test_source(source)
         .extend_library(test_base.dialogue.library().clone())
         .compile()
         .unwrap();
-    test_base.with_compilation(result).run_standard_testcase();
+    let mut dialogue = test_base.with_compilation(result).dialogue;
+    dialogue.set_node("Start").unwrap();
+
+    let error = dialogue.continue_().unwrap_err();
+    assert!(matches!(
+        &error,
+        DialogueError::FunctionError { function_name, source }
+            if function_name == "number" && source.is::<YarnValueCastError>()
+    ));
+    assert_eq!("Function \"number\" returned an error", error.to_string());
+    assert_eq!(
+        "invalid float literal",
+        std::error::Error::source(&error).unwrap().to_string()
+    );
 }
 
 #[test]
-#[should_panic = "Failed to convert a Yarn value to a bool: ParseBoolError(ParseBoolError"]
 fn test_type_conversion_failure_to_bool() {
     let source = "{bool(\"hello\")}";
-    let test_base =
-        TestBase::new().with_test_plan(TestPlan::new().expect_line("test failure if seen"));
+    let test_base = TestBase::new();
     let result = Compiler::from_test_source(source)
         .extend_library(test_base.dialogue.library().clone())
         .compile()
         .unwrap();
-    test_base.with_compilation(result).run_standard_testcase();
+    let mut dialogue = test_base.with_compilation(result).dialogue;
+    dialogue.set_node("Start").unwrap();
+
+    let error = dialogue.continue_().unwrap_err();
+    assert!(matches!(
+        &error,
+        DialogueError::FunctionError { function_name, source }
+            if function_name == "bool" && source.is::<YarnValueCastError>()
+    ));
+}
+
+#[test]
+fn test_fallible_function_errors_are_returned() {
+    #[derive(Debug)]
+    struct NotFound(String);
+
+    impl std::fmt::Display for NotFound {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "no entry named {}", self.0)
+        }
+    }
+
+    impl std::error::Error for NotFound {}
+
+    let test_base = TestBase::new().extend_library(|library| {
+        library.extend(yarn_library! {
+            "lookup" => |key: &str| -> std::result::Result<f32, NotFound> {
+                match key {
+                    "gold" => Ok(10.0),
+                    _ => Err(NotFound(key.to_owned())),
+                }
+            },
+        });
+    });
+    let result =
+        Compiler::from_test_source("You have {lookup(\"gold\")} gold\n{lookup(\"silver\")}")
+            .extend_library(test_base.dialogue.library().clone())
+            .compile()
+            .unwrap();
+    let mut test_base = test_base.with_compilation(result);
+    let dialogue = &mut test_base.dialogue;
+    dialogue.set_node("Start").unwrap();
+
+    let events = dialogue.continue_().unwrap();
+    assert!(events.iter().any(
+        |event| matches!(event, DialogueEvent::Line(line) if line.text == "You have 10 gold")
+    ));
+
+    let error = dialogue.continue_().unwrap_err();
+    let DialogueError::FunctionError {
+        function_name,
+        source,
+    } = error
+    else {
+        panic!("Expected a function error, got {error:?}");
+    };
+    assert_eq!("lookup", function_name);
+    assert_eq!("no entry named silver", source.to_string());
 }
 
 #[test]
@@ -774,3 +840,274 @@ fn test_if_statement_expressions_must_be_boolean() {
         .message
         .contains("Terms of 'if statement' must be Bool, not String")));
 }
+
+#[test]
+fn test_enums() {
+    let source = "
+        <<enum Mood>>
+            <<case Happy>>
+            <<case Sad>>
+        <<endenum>>
+
+        <<declare $mood = Mood.Happy>>
+        <<declare $other_mood = Mood.Sad as Mood>>
+
+        {$mood}
+        <<if $mood == Mood.Happy>>
+        happy
+        <<endif>>
+
+        <<set $mood to $other_mood>>
+        <<set $implicit_mood to Mood.Happy>>
+        {$mood != $implicit_mood}
+        ";
+    let result = Compiler::from_test_source(source).compile().unwrap();
+
+    let mood = result
+        .declarations
+        .iter()
+        .find(|d| d.name == "$mood")
+        .unwrap();
+    let Type::Enum(mood_type) = &mood.r#type else {
+        panic!("Expected $mood to be an enum, but it is {}", mood.r#type);
+    };
+    assert_eq!("Mood", mood_type.name);
+    assert_eq!(vec!["Happy", "Sad"], mood_type.cases);
+    assert!(result
+        .declarations
+        .iter()
+        .any(|d| d.name == "$implicit_mood" && d.r#type == mood.r#type));
+
+    let mut test_base = TestBase::new().with_compilation(result).with_test_plan(
+        TestPlan::new()
+            .expect_line("Happy")
+            .expect_line("happy")
+            .expect_line("true"),
+    );
+    test_base.run_standard_testcase();
+
+    // Enums are stored by the name of their case
+    assert_eq!(
+        YarnValue::from("Sad"),
+        test_base.dialogue.variable_storage().get("$mood").unwrap()
+    );
+}
+
+#[test]
+fn test_enum_comparisons_are_type_checked() {
+    for (expression, expected_error) in [
+        (
+            "$mood == \"Happy\"",
+            "All terms of == must be the same, not Mood, String",
+        ),
+        (
+            "$mood == Color.Red",
+            "All terms of == must be the same, not Mood, Color",
+        ),
+        ("$mood == Mood.Angry", "Enum Mood has no case named Angry"),
+        ("$mood == Feeling.Happy", "Unknown enum Feeling"),
+        ("$mood > Mood.Sad", "Mood has no implementation defined for"),
+    ] {
+        let source = format!(
+            "
+            <<enum Mood>>
+                <<case Happy>>
+                <<case Sad>>
+            <<endenum>>
+            <<enum Color>>
+                <<case Red>>
+            <<endenum>>
+            <<declare $mood = Mood.Happy>>
+            <<if {expression}>>
+            Hello
+            <<endif>>
+            "
+        );
+        let result = Compiler::from_test_source(&source).compile().unwrap_err();
+
+        println!("{}", result);
+        assert!(
+            result.0.iter().any(|d| d.message.contains(expected_error)),
+            "Expected error \"{expected_error}\" for expression {expression}"
+        );
+    }
+}
+
+#[test]
+fn test_unknown_enum_cases_are_reported_only_once() {
+    for statements in [
+        "<<declare $mood = Mood.Angry>>\n<<set $mood = Mood.Sad>>\n",
+        "<<declare $mood = Mood.Happy>>\n<<set $mood = Mood.Angry>>\n",
+        "<<declare $mood = Mood.Happy>>\n<<set $mood to Mood.Angry>>\n<<if $mood == Mood.Sad>>\nHello\n<<endif>>\n",
+    ] {
+        let source = format!(
+            "<<enum Mood>>\n<<case Happy>>\n<<case Sad>>\n<<endenum>>\n{statements}"
+        );
+        let result = Compiler::from_test_source(&source).compile().unwrap_err();
+        let messages: Vec<_> = result.0.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            vec!["Enum Mood has no case named Angry"],
+            messages,
+            "for statements {statements:?}"
+        );
+    }
+}
+
+#[test]
+fn test_malformed_enum_declarations() {
+    for (source, expected_error) in [
+        (
+            "<<enum Mood>>\n<<case Happy>>\n",
+            "Expected an <<endenum>> to match the <<enum Mood>> statement",
+        ),
+        (
+            "<<case Happy>>\n",
+            "<<case Happy>> must be inside an <<enum>> statement",
+        ),
+        (
+            "<<enum Mood>>\n<<endenum>>\n",
+            "Enum Mood must have at least one case",
+        ),
+        (
+            "<<enum Mood>>\n<<case Happy>>\n<<case Happy>>\n<<endenum>>\n",
+            "Enum Mood already has a case named Happy",
+        ),
+        (
+            "<<enum Mood>>\n<<case Happy = 1>>\n<<endenum>>\n",
+            "The cases of enum Mood can't have explicit values",
+        ),
+        (
+            "<<enum Mood>>\nHello\n<<endenum>>\n",
+            "Only <<case>> statements are allowed inside <<enum Mood>>",
+        ),
+    ] {
+        let result = Compiler::from_test_source(source).compile().unwrap_err();
+
+        println!("{}", result);
+        assert!(result.0.iter().any(|d| d.message.contains(expected_error)));
+    }
+}
+
+#[test]
+fn test_dotted_function_calls_are_not_enum_cases() {
+    let source = "
+        <<enum Mood>>
+            <<case Happy>>
+        <<endenum>>
+        {Math.max(1, 2)} {Mood.Happy}
+        {Number.Add(1, Math.max(2, 3))}
+        ";
+    let test_base = TestBase::new().extend_library(|library| {
+        library.add_function("Math.max", |a: f32, b: f32| a.max(b));
+    });
+    let result = Compiler::from_test_source(source)
+        .extend_library(test_base.dialogue.library().clone())
+        .compile()
+        .unwrap();
+
+    let mut test_base = test_base
+        .with_compilation(result)
+        .with_test_plan(TestPlan::new().expect_line("2 Happy").expect_line("4"));
+    test_base.run_standard_testcase();
+}
+
+#[test]
+fn test_local_variables() {
+    let source = "title: Start
+---
+{$greeting}
+<<local $greeting = \"Hello\">>
+<<local $count = 1>>
+<<set $count += 1>>
+{$count}
+<<set $runs += 1>>
+<<if $runs < 2>>
+    <<jump Start>>
+<<endif>>
+<<jump Other>>
+===
+title: Other
+---
+<<local $count = \"other\" as string>>
+{$count}
+===";
+    let result = Compiler::new()
+        .add_file(File {
+            file_name: "input".to_owned(),
+            source: source.to_owned(),
+        })
+        .compile()
+        .unwrap();
+    let program = result.program.as_ref().unwrap();
+    assert_eq!(
+        vec!["$runs"],
+        program.initial_values.keys().collect::<Vec<_>>()
+    );
+
+    // Locals are reported under their own name and node instead of as regular declarations
+    assert!(result
+        .declarations
+        .iter()
+        .all(|declaration| !declaration.name.starts_with("$Yarn.Internal.Local")));
+    let mut locals: Vec<_> = result
+        .local_declarations
+        .iter()
+        .map(|declaration| {
+            (
+                declaration.name.as_str(),
+                declaration.source_node_name.as_deref().unwrap(),
+                declaration.r#type.clone(),
+            )
+        })
+        .collect();
+    locals.sort_by_key(|&(name, node, _)| (node, name));
+    assert_eq!(
+        vec![
+            ("$count", "Other", Type::String),
+            ("$count", "Start", Type::Number),
+            ("$greeting", "Start", Type::String),
+        ],
+        locals
+    );
+
+    // Every visit of a node starts with fresh locals
+    let mut test_base = TestBase::new().with_compilation(result).with_test_plan(
+        TestPlan::new()
+            .expect_line("Hello")
+            .expect_line("2")
+            .expect_line("Hello")
+            .expect_line("2")
+            .expect_line("other"),
+    );
+    test_base.run_standard_testcase();
+
+    let variable_storage = test_base.dialogue.variable_storage();
+    assert_eq!(YarnValue::from(2), variable_storage.get("$runs").unwrap());
+    let variables = variable_storage.variables();
+    assert_eq!(vec!["$runs"], variables.keys().collect::<Vec<_>>());
+}
+
+#[test]
+fn test_local_variables_are_type_checked() {
+    for (source, expected_error) in [
+        (
+            "<<local $count = 0>>\n<<set $count to \"text\">>",
+            "$count (Number) cannot be assigned a String",
+        ),
+        (
+            "<<local $count = 0>>\n<<local $count = 1>>",
+            "$count has already been declared",
+        ),
+        (
+            "<<local $count = 0 as bool>>",
+            "Type bool does not match value 0 (Number)",
+        ),
+    ] {
+        let result = Compiler::from_test_source(source).compile().unwrap_err();
+        println!("{result}");
+        assert!(
+            result.0.iter().any(|d| d.message.contains(expected_error)),
+            "Expected an error containing \"{expected_error}\" for source {source:?}"
+        );
+    }
+}